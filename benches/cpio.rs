@@ -0,0 +1,68 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use rpm_utils::payload::{self, CpioWriter, FileEntry};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, Read};
+
+const ENTRIES: u32 = 50_000;
+const LARGE_FILES: u32 = 3;
+const LARGE_FILE_SIZE: u32 = 100 * 1024 * 1024;
+
+fn archive() -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..ENTRIES {
+        let entry = FileEntry {
+            name: format!("./usr/share/bench/file-{}", i),
+            ino: i,
+            mode: 0o100_644,
+            nlink: 1,
+            ..Default::default()
+        };
+        data.write_cpio_entry(entry).unwrap();
+    }
+    data.cpio_close().unwrap();
+    data
+}
+
+fn large_archive(path: &std::path::Path) {
+    let mut writer = BufWriter::new(File::create(path).unwrap());
+    for i in 0..LARGE_FILES {
+        let entry = FileEntry {
+            name: format!("./usr/lib/bench/large-{}", i),
+            ino: i,
+            mode: 0o100_644,
+            nlink: 1,
+            file_size: LARGE_FILE_SIZE,
+            ..Default::default()
+        };
+        let mut data = io::repeat(i as u8).take(LARGE_FILE_SIZE.into());
+        writer.write_cpio_record(entry, &mut data).unwrap();
+    }
+    writer.cpio_close().unwrap();
+}
+
+fn list_entries(c: &mut Criterion) {
+    let data = archive();
+    c.bench_function("list 50k cpio entries", |b| {
+        b.iter(|| payload::read_entries(&mut Cursor::new(&data)).unwrap())
+    });
+}
+
+fn extract_large_files(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large.cpio");
+    large_archive(&path);
+
+    let mut group = c.benchmark_group("extract");
+    group.sample_size(10);
+    group.bench_function("extract 3 files of 100 MB", |b| {
+        b.iter(|| {
+            let mut reader = BufReader::new(File::open(&path).unwrap());
+            let target = dir.path().join("target");
+            payload::extract_entries_with(&mut reader, &target, &Default::default()).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, list_entries, extract_large_files);
+criterion_main!(benches);
@@ -0,0 +1,101 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use rpm_utils::header::{
+    header_bytes, HeaderLead, Index, IndexArray, RType, ReadOptions, Tag, Tags,
+};
+use rpm_utils::{file_names, RPMFile, RPMInfo, INFO_TAGS};
+use std::convert::TryFrom;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const FILES: u32 = 50_000;
+const DIRS: u32 = 500;
+
+fn tags() -> Tags<Tag> {
+    let mut tags = Tags::<Tag>::new();
+    tags.insert_name("bench".to_owned())
+        .insert(
+            Tag::BaseNames,
+            RType::StringArray((0..FILES).map(|i| format!("file-{}", i)).collect()),
+        )
+        .insert(
+            Tag::DirNames,
+            RType::StringArray(
+                (0..DIRS)
+                    .map(|i| format!("/usr/share/bench/{}/", i))
+                    .collect(),
+            ),
+        )
+        .insert(
+            Tag::DirIndexes,
+            RType::Int32Array((0..FILES).map(|i| i % DIRS).collect()),
+        )
+        .insert(
+            Tag::FileUserName,
+            RType::StringArray(vec!["root".to_owned(); FILES as usize]),
+        )
+        .insert(Tag::FileSizes, RType::Int32Array((0..FILES).collect()));
+    tags
+}
+
+fn read(bytes: &[u8]) -> Tags<Tag> {
+    let mut reader = Cursor::new(bytes);
+    let lead = HeaderLead::read(&mut reader).unwrap();
+    let indexes: Vec<Index<u32>> = IndexArray::read(&mut reader, lead.nindex).unwrap();
+    Tags::read(&mut reader, &indexes, lead.hsize as usize).unwrap()
+}
+
+fn read_header(c: &mut Criterion) {
+    let bytes = header_bytes(&tags()).unwrap();
+    c.bench_function("read a header of 50k files", |b| b.iter(|| read(&bytes)));
+}
+
+fn lookups(c: &mut Criterion) {
+    let tags = tags();
+    let mut group = c.benchmark_group("lookup");
+    group.bench_function("get_value of 50k base names", |b| {
+        b.iter(|| tags.get_value(Tag::BaseNames).is_some())
+    });
+    group.bench_function("get_ref of 50k base names", |b| {
+        b.iter(|| tags.get_ref(Tag::BaseNames).is_some())
+    });
+    group.bench_function("file names of 50k files", |b| b.iter(|| file_names(&tags)));
+    group.finish();
+}
+
+fn fixture_packages() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+    let mut packages: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "rpm"))
+        .collect();
+    packages.sort();
+    packages
+}
+
+fn scan_packages(c: &mut Criterion) {
+    let packages = fixture_packages();
+    let scan = |options: &ReadOptions| {
+        for path in &packages {
+            let rpm = RPMFile::open_with(path, options).unwrap();
+            RPMInfo::try_from(&rpm).unwrap();
+        }
+    };
+
+    let mut group = c.benchmark_group("scan");
+    group.bench_function("info of fixture packages", |b| {
+        b.iter(|| scan(&ReadOptions::default()))
+    });
+    let options = ReadOptions {
+        skip_signature: true,
+        ..Default::default()
+    }
+    .with_tags(INFO_TAGS.iter().copied());
+    group.bench_function("info of fixture packages without files", |b| {
+        b.iter(|| scan(&options))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, read_header, lookups, scan_packages);
+criterion_main!(benches);
@@ -1,11 +1,12 @@
-use rpm_utils::payload::CpioBuilder;
+use rpm_utils::exit::{self, ExitCode, EXIT_CODES_HELP};
+use rpm_utils::payload::{CpioBuilder, Owner};
+use std::fs::File;
 use std::io;
 use std::path::PathBuf;
-use std::process::exit;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "cpio-create")]
+#[structopt(name = "cpio-create", after_help = EXIT_CODES_HELP)]
 struct Args {
     /// Path to cpio file
     #[structopt(name = "file", long = "file", short = "f", parse(from_os_str))]
@@ -14,21 +15,75 @@ struct Args {
     /// Target directory to extract
     #[structopt(name = "path", parse(from_os_str))]
     files: Vec<PathBuf>,
+
+    /// Save user attributes and IMA signatures of the files into <file>.xattrs
+    #[structopt(long = "xattrs")]
+    xattrs: bool,
+
+    /// Owner of all entries, e.g. root:root or 0:0
+    #[structopt(long = "owner")]
+    owner: Option<Owner>,
+
+    /// Group of all entries, e.g. root or 0
+    #[structopt(long = "group", parse(try_from_str = parse_group))]
+    group: Option<Owner>,
+
+    /// Octal mask of permission bits kept on files and directories, e.g. 755
+    #[structopt(long = "mode", parse(try_from_str = parse_mode))]
+    mode: Option<u32>,
+
+    /// Modification time of all entries in seconds since the epoch
+    #[structopt(long = "mtime")]
+    mtime: Option<u32>,
+
+    /// Set modification times later than the epoch to it, e.g. SOURCE_DATE_EPOCH
+    #[structopt(long = "clamp-mtime")]
+    clamp_mtime: Option<u32>,
+
+    /// Produce the same archive from the same files: sorted entries,
+    /// renumbered inodes and zeroed device ids
+    #[structopt(long = "deterministic")]
+    deterministic: bool,
 }
 
-fn run(args: Args) -> io::Result<()> {
-    let mut builder = CpioBuilder::open(&args.file)?;
+fn parse_group(s: &str) -> io::Result<Owner> {
+    format!(":{}", s).parse()
+}
+
+fn parse_mode(s: &str) -> Result<u32, std::num::ParseIntError> {
+    u32::from_str_radix(s, 8)
+}
+
+fn run(args: Args) -> io::Result<ExitCode> {
+    let mut builder = CpioBuilder::open(&args.file)?
+        .xattrs(args.xattrs)
+        .deterministic(args.deterministic);
+    for owner in args.owner.into_iter().chain(args.group) {
+        builder = builder.owner(owner);
+    }
+    if let Some(mask) = args.mode {
+        builder = builder.mode_mask(mask);
+    }
+    if let Some(mtime) = args.mtime {
+        builder = builder.mtime(mtime);
+    }
+    if let Some(epoch) = args.clamp_mtime {
+        builder = builder.clamp_mtime(epoch);
+    }
     for path in args.files.into_iter() {
         builder = builder.add_raw_file(&path)?;
     }
-    builder.build()
+
+    if let Some(manifest) = builder.xattr_manifest() {
+        let mut path = args.file.into_os_string();
+        path.push(".xattrs");
+        manifest.write(&mut File::create(path)?)?;
+    }
+    builder.build()?;
+    Ok(ExitCode::Ok)
 }
 
 fn main() {
-    let args = Args::from_args();
-
-    if let Err(err) = run(args) {
-        eprintln!("{}", err);
-        exit(1);
-    }
+    let args = exit::from_args();
+    exit::exit_with(run(args));
 }
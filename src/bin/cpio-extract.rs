@@ -1,12 +1,13 @@
-use rpm_utils::payload;
+use rpm_utils::exit::{self, ExitCode, EXIT_CODES_HELP};
+use rpm_utils::payload::{self, ExtractOptions, XattrManifest};
 use std::fs::File;
-use std::io;
+use std::io::{self, BufReader};
 use std::path::PathBuf;
-use std::process::exit;
+use std::sync::Arc;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "cpio-extract")]
+#[structopt(name = "cpio-extract", after_help = EXIT_CODES_HELP)]
 struct Args {
     /// Path to data file
     #[structopt(name = "path", parse(from_os_str))]
@@ -16,32 +17,67 @@ struct Args {
     #[structopt(long = "debug", short = "d")]
     debug: bool,
 
+    /// With --debug, print the entries as JSON
+    #[cfg(feature = "serde")]
+    #[structopt(long = "json", requires = "debug")]
+    json: bool,
+
+    /// Do not print extracted entries and the summary
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
     /// Target directory to extract
     #[structopt(short = "e", parse(from_os_str))]
     target_dir: PathBuf,
+
+    /// Restore extended attributes from the manifest written by cpio-create --xattrs
+    #[structopt(long = "xattrs", parse(from_os_str))]
+    xattrs: Option<PathBuf>,
 }
 
-fn run(args: Args) -> io::Result<()> {
+fn run(args: Args) -> io::Result<ExitCode> {
     let mut file = File::open(&args.path)?;
     if args.debug {
         let entries = payload::read_entries(&mut file)?;
+        #[cfg(feature = "serde")]
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+            return Ok(ExitCode::Ok);
+        }
         for entry in &entries {
             println!("{:#?}", entry);
         }
-    } else {
-        let entries = payload::extract_entries(&mut file, &args.target_dir, true, false)?;
-        for entry in &entries {
+        return Ok(ExitCode::Ok);
+    }
+
+    let mut options = ExtractOptions::default();
+    if let Some(path) = &args.xattrs {
+        let manifest = XattrManifest::read(BufReader::new(File::open(path)?))?;
+        options.xattrs = Some(Arc::new(manifest));
+    }
+    let report = payload::extract_entries_with(&mut file, &args.target_dir, &options)?;
+    if !args.quiet {
+        for entry in &report.entries {
             println!("Extracting {}", &entry.name);
         }
+        println!("{}", report);
     }
-    Ok(())
-}
 
-fn main() {
-    let args = Args::from_args();
+    for (name, reason) in &report.skipped {
+        eprintln!("{}: {}", name, reason);
+    }
+    for warning in &report.warnings {
+        eprintln!("{}", warning);
+    }
 
-    if let Err(err) = run(args) {
-        eprintln!("{}", err);
-        exit(1);
+    if report.is_partial() {
+        Ok(ExitCode::Partial)
+    } else {
+        Ok(ExitCode::Ok)
     }
 }
+
+fn main() {
+    let args = exit::from_args();
+    exit::exit_with(run(args));
+}
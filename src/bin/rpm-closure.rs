@@ -0,0 +1,55 @@
+use rpm_utils::depsolve;
+use rpm_utils::exit::{self, ExitCode, EXIT_CODES_HELP};
+use rpm_utils::{RPMFile, RPMInfo};
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "rpm-closure", after_help = EXIT_CODES_HELP)]
+struct Args {
+    /// Directory with packages to be installed together
+    #[structopt(name = "dir", parse(from_os_str))]
+    dir: PathBuf,
+
+    /// Do not print unsatisfied dependencies, only set the exit code
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+}
+
+fn run(args: Args) -> io::Result<ExitCode> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(&args.dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "rpm") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut packages = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let file = RPMFile::open(path)?;
+        packages.push(RPMInfo::try_from(&file)?);
+    }
+
+    let unsatisfied = depsolve::check_closure(&packages);
+    if !args.quiet {
+        for dependency in &unsatisfied {
+            println!("{}", dependency);
+        }
+    }
+
+    if unsatisfied.is_empty() {
+        Ok(ExitCode::Ok)
+    } else {
+        Ok(ExitCode::Verification)
+    }
+}
+
+fn main() {
+    let args = exit::from_args();
+    exit::exit_with(run(args));
+}
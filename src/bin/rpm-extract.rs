@@ -0,0 +1,69 @@
+use rpm_utils::exit::{self, ExitCode, EXIT_CODES_HELP};
+use rpm_utils::payload::ExtractOptions;
+use rpm_utils::RPMFile;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "rpm-extract", after_help = EXIT_CODES_HELP)]
+struct Args {
+    /// Path to rpm file
+    #[structopt(name = "rpm", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Print debug information
+    #[structopt(long = "debug", short = "d")]
+    debug: bool,
+
+    /// Do not print extracted entries and the summary
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Target directory to extract
+    #[structopt(short = "e", parse(from_os_str))]
+    target_dir: PathBuf,
+}
+
+fn run(args: Args) -> io::Result<ExitCode> {
+    let rpm = RPMFile::open(&args.path)?;
+    if args.debug {
+        for entry in &rpm.list_payload()? {
+            println!("{:#?}", entry);
+        }
+        return Ok(ExitCode::Ok);
+    }
+
+    // extended attributes, e.g. IMA signatures, are stored in the header
+    let mut options = ExtractOptions::default();
+    let manifest = rpm.xattr_manifest();
+    if !manifest.is_empty() {
+        options.xattrs = Some(Arc::new(manifest));
+    }
+    let report = rpm.extract(&args.target_dir, &options)?;
+    if !args.quiet {
+        for entry in &report.entries {
+            println!("Extracting {}", &entry.name);
+        }
+        println!("{}", report);
+    }
+
+    for (name, reason) in &report.skipped {
+        eprintln!("{}: {}", name, reason);
+    }
+    for warning in &report.warnings {
+        eprintln!("{}", warning);
+    }
+
+    if report.is_partial() {
+        Ok(ExitCode::Partial)
+    } else {
+        Ok(ExitCode::Ok)
+    }
+}
+
+fn main() {
+    let args = exit::from_args();
+    exit::exit_with(run(args));
+}
@@ -1,39 +1,421 @@
-use rpm_utils::{RPMFile, RPMInfo};
-use std::io;
-use std::path::PathBuf;
-use std::process::exit;
+use rpm_utils::exit::{self, ExitCode, EXIT_CODES_HELP};
+use rpm_utils::header::Tags;
+use rpm_utils::payload::{format_file_listing, ListingFormat};
+use rpm_utils::signature::SignatureMeta;
+use rpm_utils::{format_query, HeaderTags, PackageLeads, RPMFile, RPMInfo};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "rpm-info")]
+#[structopt(name = "rpm-info", after_help = EXIT_CODES_HELP)]
 struct Args {
-    /// Path to data file
+    /// Path to data file, `-` reads the package from stdin
     #[structopt(name = "path", parse(from_os_str))]
     path: PathBuf,
 
     /// Show internal debug information
-    #[structopt(long = "debug", short = "d")]
+    #[structopt(long = "debug")]
     debug: bool,
+
+    /// List files
+    #[structopt(long = "list", short = "l", alias = "files")]
+    list: bool,
+
+    /// List files with mode, owner, group, size and mtime as rpm -qlv does
+    #[structopt(long = "files-verbose")]
+    files_verbose: bool,
+
+    /// With --list, show mode, size and mtime of the files
+    #[structopt(long = "verbose", short = "v")]
+    verbose: bool,
+
+    /// With --list or --dump, show the file class after the path
+    #[structopt(long = "classify")]
+    classify: bool,
+
+    /// Show file metadata as rpm -q --dump does
+    #[structopt(long = "dump")]
+    dump: bool,
+
+    /// List only configuration files
+    #[structopt(long = "configfiles", short = "c")]
+    config_files: bool,
+
+    /// List only documentation files
+    #[structopt(long = "docfiles", short = "d")]
+    doc_files: bool,
+
+    /// List only license files
+    #[structopt(long = "license-files")]
+    license_files: bool,
+
+    /// Show install and transaction scripts
+    #[structopt(long = "scripts")]
+    scripts: bool,
+
+    /// Print tags with a format like rpm -q --queryformat, e.g. '%{NAME}-%{VERSION}\n'
+    #[structopt(long = "queryformat", alias = "qf")]
+    queryformat: Option<String>,
+
+    /// Show the package lead and the leads of both headers, the headers are not parsed
+    #[structopt(long = "lead")]
+    lead: bool,
+
+    /// Read a standalone header without the lead and the signature header, e.g. from rpmdb
+    #[structopt(long = "header-only")]
+    header_only: bool,
+
+    /// Show the changelog, the newest entries first
+    #[structopt(long = "changelog")]
+    changelog: bool,
+
+    /// Check the package for inconsistencies
+    #[structopt(long = "lint")]
+    lint: bool,
+
+    /// With --lint, also compare the payload with the header file list
+    #[structopt(long = "payload", requires = "lint")]
+    payload: bool,
+
+    /// Show the largest header tags by size
+    #[structopt(long = "stats-header")]
+    stats_header: bool,
+
+    /// Number of tags shown by --stats-header
+    #[structopt(long = "top", default_value = "10")]
+    top: usize,
+
+    /// Do not print anything, only set the exit code
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Verify the header signature
+    #[structopt(long = "checksig", short = "K")]
+    checksig: bool,
+
+    /// Verify the digest of the compressed payload, PayloadDigest
+    #[structopt(long = "check-payload")]
+    check_payload: bool,
+
+    /// Print the package information with the file list as JSON, with --debug the lead and all tags
+    #[cfg(feature = "serde")]
+    #[structopt(long = "json")]
+    json: bool,
+
+    /// Public key file or directory with key files used by --checksig
+    #[cfg(feature = "signing")]
+    #[structopt(long = "keyring", parse(from_os_str))]
+    keyring: Option<PathBuf>,
+}
+
+/// Package file or stdin, which is read into memory as the checks read parts of the package again
+enum Input {
+    File(File),
+    Stdin(Cursor<Vec<u8>>),
 }
 
-fn run(args: Args) -> io::Result<()> {
-    let file = RPMFile::open(args.path)?;
-    let info: RPMInfo = (&file).into();
+impl Input {
+    fn open(path: &Path) -> io::Result<Self> {
+        if path == Path::new("-") {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data)?;
+            Ok(Input::Stdin(Cursor::new(data)))
+        } else {
+            Ok(Input::File(File::open(path)?))
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::File(file) => file.read(buf),
+            Input::Stdin(data) => data.read(buf),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Input::File(file) => file.seek(pos),
+            Input::Stdin(data) => data.seek(pos),
+        }
+    }
+}
+
+fn open(path: &Path) -> io::Result<RPMFile<Input>> {
+    Ok(RPMFile::read(Input::open(path)?)?)
+}
+
+fn run(args: Args) -> io::Result<ExitCode> {
+    if args.checksig {
+        return checksig(&args);
+    }
+    if args.lead {
+        return print_leads(&args);
+    }
+    if args.header_only {
+        return print_header(&args);
+    }
+
+    let mut file = open(&args.path)?;
+    if args.check_payload {
+        return check_payload(&args, &mut file);
+    }
+    if args.lint {
+        return lint(&args, &mut file);
+    }
+    let info = RPMInfo::try_from(&file)?;
+
+    if args.quiet {
+        return Ok(ExitCode::Ok);
+    }
+
+    #[cfg(feature = "serde")]
+    if args.json {
+        return print_json(&args, &mut file, info);
+    }
+
+    if let Some(template) = &args.queryformat {
+        print!("{}", format_query(&file.header_tags, template)?);
+        return Ok(ExitCode::Ok);
+    }
 
-    if args.debug {
-        println!("{:#?}", file.signature_tags);
-        println!("{:#?}", file.header_tags);
+    if args.config_files || args.doc_files || args.license_files {
+        let payload = &info.payload;
+        let mut files = Vec::new();
+        if args.config_files {
+            files.extend(payload.config_files());
+        }
+        if args.doc_files {
+            files.extend(payload.doc_files());
+        }
+        if args.license_files {
+            files.extend(payload.license_files());
+        }
+        for file in files {
+            println!("{}", file.name);
+        }
+    } else if args.files_verbose {
+        print!("{}", format_file_listing(&info.payload));
+    } else if args.list || args.dump {
+        let format = ListingFormat {
+            human_readable: false,
+            classify: args.classify,
+            ..Default::default()
+        };
+        for file in &info.payload.files {
+            if args.dump {
+                println!("{}", format.dump(file));
+            } else if args.verbose {
+                println!("{}", format.line(file));
+            } else {
+                println!("{}", file.name);
+            }
+        }
+    } else if args.scripts {
+        print!("{}", file.scriptlets());
+    } else if args.changelog {
+        for entry in file.changelog()? {
+            println!("{}", entry);
+        }
+    } else if args.stats_header {
+        print_header_stats(&file, args.top);
+    } else if args.debug {
+        println!("Signature header:\n{}", file.signature_tags);
+        println!("Header:\n{}", file.header_tags);
         println!("{:#?}", info);
     } else {
+        let info = RPMInfo {
+            header_digests: Some(file.verify_header_digests()?),
+            ..info
+        };
         println!("{}", info);
     }
-    Ok(())
+    Ok(ExitCode::Ok)
 }
 
-fn main() {
-    let args = Args::from_args();
-    if let Err(err) = run(args) {
-        eprintln!("{}", err);
-        exit(1);
+#[cfg(feature = "serde")]
+fn print_json(args: &Args, file: &mut RPMFile<Input>, info: RPMInfo) -> io::Result<ExitCode> {
+    let info = RPMInfo {
+        header_digests: Some(file.verify_header_digests()?),
+        ..info
+    };
+    let value = if args.debug {
+        serde_json::json!({
+            "lead": file.lead,
+            "signature_tags": file.signature_tags.dump(),
+            "header_tags": file.header_tags.dump(),
+            "info": info,
+        })
+    } else {
+        serde_json::to_value(&info)?
+    };
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(ExitCode::Ok)
+}
+
+fn print_leads(args: &Args) -> io::Result<ExitCode> {
+    let leads = PackageLeads::read(&mut Input::open(&args.path)?)?;
+    if !args.quiet {
+        println!("Lead:\n{}", leads.lead);
+        println!("Signature header:\n{}", leads.signature);
+        print!("Header:\n{}", leads.header);
+    }
+    Ok(ExitCode::Ok)
+}
+
+fn print_header(args: &Args) -> io::Result<ExitCode> {
+    let tags = HeaderTags(Tags::read_header_blob(&mut Input::open(&args.path)?)?);
+    let info = RPMInfo::from_header_tags(&tags)?;
+    if args.quiet {
+        return Ok(ExitCode::Ok);
+    }
+
+    if let Some(template) = &args.queryformat {
+        print!("{}", format_query(&tags, template)?);
+    } else if args.files_verbose {
+        print!("{}", format_file_listing(&info.payload));
+    } else if args.list {
+        for file in &info.payload.files {
+            println!("{}", file.name);
+        }
+    } else if args.debug {
+        println!("Header:\n{}", tags);
+        println!("{:#?}", info);
+    } else {
+        println!("{}", info);
+    }
+    Ok(ExitCode::Ok)
+}
+
+fn print_header_stats<T>(file: &RPMFile<T>, top: usize) {
+    let report = file.header_tags.size_report();
+    println!(
+        "Header: {} tags, index {} bytes, data {} bytes ({} padding), total {} bytes",
+        report.tags.len(),
+        report.index_size,
+        report.data_size,
+        report.padding,
+        report.total()
+    );
+    for tag in report.tags.iter().take(top) {
+        let name = tag
+            .tag
+            .map_or_else(|| tag.id.to_string(), |tag| tag.to_string());
+        println!(
+            "{:<24} {:>10} {:>6.1}% ({} items)",
+            name,
+            tag.size,
+            report.percent(tag.size),
+            tag.count
+        );
+    }
+}
+
+fn lint(args: &Args, file: &mut RPMFile<Input>) -> io::Result<ExitCode> {
+    let mut discrepancies: Vec<String> = file
+        .check_lead_consistency()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    if args.payload {
+        let report = file.check_payload_manifest()?;
+        for name in report.payload_only {
+            discrepancies.push(format!("{} is in the payload, but not in the header", name));
+        }
+        for name in report.header_only {
+            discrepancies.push(format!("{} is missing from the payload", name));
+        }
+        for mismatch in report.size_mismatches {
+            discrepancies.push(mismatch.to_string());
+        }
+    }
+
+    if !args.quiet {
+        for discrepancy in &discrepancies {
+            println!("{}: {}", args.path.display(), discrepancy);
+        }
+    }
+
+    if discrepancies.is_empty() {
+        Ok(ExitCode::Ok)
+    } else {
+        Ok(ExitCode::Format)
+    }
+}
+
+fn check_payload(args: &Args, file: &mut RPMFile<Input>) -> io::Result<ExitCode> {
+    let status = file.verify_payload_digest()?;
+    if !args.quiet {
+        println!("{}: Payload digest {}", args.path.display(), status);
+    }
+
+    if status.is_mismatch() {
+        eprintln!("Payload digest verification failed");
+        Ok(ExitCode::Verification)
+    } else {
+        Ok(ExitCode::Ok)
     }
 }
+
+#[cfg(feature = "signing")]
+fn checksig(args: &Args) -> io::Result<ExitCode> {
+    use rpm_utils::signature::Keyring;
+
+    let keyring = match &args.keyring {
+        Some(path) => Keyring::load(path)?,
+        None => Keyring::new(),
+    };
+    let mut file = open(&args.path)?;
+    let meta = SignatureMeta::from_tags(&file.signature_tags)?;
+    let status = file.verify_signature(&keyring)?;
+    print_signature(args, meta, &status);
+
+    if status.is_valid() {
+        Ok(ExitCode::Ok)
+    } else {
+        eprintln!("Signature verification failed");
+        Ok(ExitCode::Verification)
+    }
+}
+
+#[cfg(not(feature = "signing"))]
+fn checksig(args: &Args) -> io::Result<ExitCode> {
+    let file = open(&args.path)?;
+    let meta = SignatureMeta::from_tags(&file.signature_tags)?;
+    print_signature(args, meta, &"NOT CHECKED");
+
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Signature verification requires the signing feature",
+    ))
+}
+
+fn print_signature(args: &Args, meta: Option<SignatureMeta>, status: &dyn fmt::Display) {
+    if args.quiet {
+        return;
+    }
+    match meta {
+        Some(meta) => println!(
+            "{}: Header V{} {}/{} Signature, {}",
+            args.path.display(),
+            meta.version,
+            meta.pubkey_algorithm,
+            meta.hash_algorithm,
+            status
+        ),
+        None => println!("{}: {}", args.path.display(), status),
+    }
+}
+
+fn main() {
+    let args = exit::from_args();
+    exit::exit_with(run(args));
+}
@@ -0,0 +1,29 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::exit;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "rpm-join")]
+struct Args {
+    /// Directory with parts saved by rpm-split
+    #[structopt(name = "dir", parse(from_os_str))]
+    dir: PathBuf,
+
+    /// Path to save rpm file
+    #[structopt(long = "output", parse(from_os_str))]
+    output: PathBuf,
+}
+
+fn run(args: Args) -> io::Result<()> {
+    rpm_utils::join(&args.dir, &args.output)?;
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_args();
+    if let Err(err) = run(args) {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,88 @@
+use rpm_utils::payload::{self, FileInfo, ListingFormat, SortKey};
+use rpm_utils::{RPMFile, RPMInfo};
+use std::convert::TryFrom;
+use std::io;
+use std::path::PathBuf;
+use std::process::exit;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "rpm-payload-ls")]
+struct Args {
+    /// Path to rpm file
+    #[structopt(name = "rpm", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Sort by name, size or mtime
+    #[structopt(long = "sort", default_value = "name", possible_values = &["name", "size", "mtime"])]
+    sort: SortKey,
+
+    /// Reverse the sort order
+    #[structopt(long = "reverse", short = "r")]
+    reverse: bool,
+
+    /// List only directories
+    #[structopt(long = "dirs-only")]
+    dirs_only: bool,
+
+    /// Print sizes in human readable format, e.g. 1.5K
+    #[structopt(long = "human-readable", short = "h")]
+    human_readable: bool,
+
+    /// Show only N largest files
+    #[structopt(long = "top")]
+    top: Option<usize>,
+
+    /// Show the file class after the path, only the header has it
+    #[structopt(long = "classify")]
+    classify: bool,
+
+    /// Show the file digest with its algorithm, e.g. sha256:abcd..., only the header has it
+    #[structopt(long = "digests")]
+    digests: bool,
+
+    /// Read file list from the payload instead of the header
+    #[structopt(long = "from-payload")]
+    from_payload: bool,
+}
+
+fn run(args: Args) -> io::Result<()> {
+    let file = RPMFile::open(&args.path)?;
+    let info = RPMInfo::try_from(&file)?;
+
+    // header file tags are much faster to read, the payload is only a fallback
+    let mut files = if args.from_payload || info.payload.files.is_empty() {
+        file.read_payload_files()?
+    } else {
+        info.payload.files
+    };
+
+    if args.dirs_only {
+        files.retain(FileInfo::is_dir);
+    }
+
+    let format = ListingFormat {
+        human_readable: args.human_readable,
+        classify: args.classify,
+        digests: args.digests,
+    };
+    if let Some(n) = args.top {
+        for file in payload::largest_files(&files, n) {
+            println!("{}", format.line(file));
+        }
+    } else {
+        payload::sort_files(&mut files, args.sort, args.reverse);
+        for file in &files {
+            println!("{}", format.line(file));
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_args();
+    if let Err(err) = run(args) {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,30 @@
+use rpm_utils::RPMFile;
+use std::io;
+use std::path::PathBuf;
+use std::process::exit;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "rpm-split")]
+struct Args {
+    /// Path to rpm file
+    #[structopt(name = "rpm", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Directory to save lead, headers and payload
+    #[structopt(long = "outdir", parse(from_os_str))]
+    outdir: PathBuf,
+}
+
+fn run(args: Args) -> io::Result<()> {
+    let mut rpm = RPMFile::open(args.path)?;
+    rpm_utils::split(&mut rpm, &args.outdir)
+}
+
+fn main() {
+    let args = Args::from_args();
+    if let Err(err) = run(args) {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,51 @@
+use rpm_utils::exit::{self, ExitCode, EXIT_CODES_HELP};
+use rpm_utils::{verify_files, RPMFile, RPMInfo};
+use std::convert::TryFrom;
+use std::io;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "rpm-verify", after_help = EXIT_CODES_HELP)]
+struct Args {
+    /// Path to rpm file
+    #[structopt(name = "rpm", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Directory where the package is installed or extracted
+    #[structopt(long = "root", default_value = "/", parse(from_os_str))]
+    root: PathBuf,
+
+    /// Print the files which match the header too
+    #[structopt(long = "verbose", short = "v")]
+    verbose: bool,
+
+    /// Do not print anything, only set the exit code
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+}
+
+fn run(args: Args) -> io::Result<ExitCode> {
+    let info = RPMInfo::try_from(&RPMFile::open(&args.path)?)?;
+    let results = verify_files(&info, &args.root);
+
+    if !args.quiet {
+        for result in results
+            .iter()
+            .filter(|result| args.verbose || !result.is_ok())
+        {
+            println!("{}", result);
+        }
+    }
+
+    if results.iter().all(|result| result.is_ok()) {
+        Ok(ExitCode::Ok)
+    } else {
+        Ok(ExitCode::Verification)
+    }
+}
+
+fn main() {
+    let args = exit::from_args();
+    exit::exit_with(run(args));
+}
@@ -1,31 +1,103 @@
+use rpm_utils::exit::{self, ExitCode, EXIT_CODES_HELP};
+use rpm_utils::payload::{CpioStream, FileInfo, ListingFormat};
 use rpm_utils::RPMFile;
-use std::io;
-use std::path::PathBuf;
-use std::process::exit;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "rpm-info")]
+#[structopt(name = "rpm2cpio", after_help = EXIT_CODES_HELP)]
 struct Args {
-    /// Path to rpm file
+    /// Path to rpm file, `-` reads the package from stdin
     #[structopt(name = "rpm", parse(from_os_str))]
     path: PathBuf,
 
-    /// Path to save file
+    /// Path to save file, the payload is written to stdout if it is not set.
+    /// With `--raw` an existing directory receives `payload.<format>.<compressor>`
     #[structopt(long = "output", parse(from_os_str))]
-    output: PathBuf,
+    output: Option<PathBuf>,
+
+    /// List payload members instead of saving the payload, fails if the payload is corrupted
+    #[structopt(long = "list", conflicts_with = "output")]
+    list: bool,
+
+    /// List members with mode, size and mtime
+    #[structopt(long = "verbose", short = "v", requires = "list")]
+    verbose: bool,
+
+    /// Copy the compressed payload as is, without decompressing it
+    #[structopt(long = "raw", conflicts_with = "list")]
+    raw: bool,
 }
 
-fn run(args: Args) -> io::Result<()> {
-    let rpm = RPMFile::open(args.path)?;
-    rpm.copy_payload(&args.output)?;
-    Ok(())
+fn run(args: Args) -> io::Result<ExitCode> {
+    if args.raw {
+        return copy_raw(&args.path, args.output.as_deref());
+    }
+    let mut payload = payload_reader(&args.path)?;
+    if args.list {
+        let format = ListingFormat::default();
+        let mut stream = CpioStream::new(payload);
+        while let Some(member) = stream.next_member()? {
+            let entry = member.skip()?;
+            if args.verbose {
+                println!("{}", format.line(&FileInfo::from(&entry)));
+            } else {
+                println!("{}", entry.name);
+            }
+        }
+    } else if let Some(output) = args.output {
+        io::copy(&mut payload, &mut File::create(output)?)?;
+    } else {
+        let stdout = io::stdout();
+        let mut writer = stdout.lock();
+        io::copy(&mut payload, &mut writer)?;
+        writer.flush()?;
+    }
+    Ok(ExitCode::Ok)
 }
 
-fn main() {
-    let args = Args::from_args();
-    if let Err(err) = run(args) {
-        eprintln!("{}", err);
-        exit(1);
+/// Copies the compressed payload as is, stdin is read as a stream
+fn copy_raw(path: &Path, output: Option<&Path>) -> io::Result<ExitCode> {
+    let raw = if path == Path::new("-") {
+        let rpm = RPMFile::read_stream(io::stdin())?;
+        let mut writer = raw_writer(output, &rpm.payload_extension())?;
+        let raw = rpm.copy_raw_payload_from_stream(&mut writer)?;
+        writer.flush()?;
+        raw
+    } else {
+        let rpm = RPMFile::open(path)?;
+        let mut writer = raw_writer(output, &rpm.payload_extension())?;
+        let raw = rpm.copy_raw_payload(&mut writer)?;
+        writer.flush()?;
+        raw
+    };
+    eprintln!("{} payload, {} bytes", raw.compressor, raw.size);
+    Ok(ExitCode::Ok)
+}
+
+/// Output file, `payload.<extension>` inside a directory or stdout
+fn raw_writer(output: Option<&Path>, extension: &str) -> io::Result<Box<dyn Write>> {
+    match output {
+        Some(dir) if dir.is_dir() => Ok(Box::new(File::create(
+            dir.join(format!("payload.{}", extension)),
+        )?)),
+        Some(output) => Ok(Box::new(File::create(output)?)),
+        None => Ok(Box::new(io::stdout())),
+    }
+}
+
+/// Decompressed payload, stdin is read as a stream
+fn payload_reader(path: &Path) -> io::Result<Box<dyn Read>> {
+    if path == Path::new("-") {
+        Ok(RPMFile::read_stream(io::stdin())?.into_payload_reader()?)
+    } else {
+        Ok(RPMFile::open(path)?.into_payload_reader()?)
     }
 }
+
+fn main() {
+    let args = exit::from_args();
+    exit::exit_with(run(args));
+}
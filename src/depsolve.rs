@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::rpm::{Dependency, RPMInfo};
+
+/// Requirement which is not provided by any package of the set
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsatisfiedDep {
+    /// name-version-release.arch of the requiring package
+    pub package: String,
+    pub requirement: Dependency,
+}
+
+impl fmt::Display for UnsatisfiedDep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} requires {}", self.package, self.requirement)
+    }
+}
+
+/// Checks that packages installed together satisfy requirements of each other.
+/// Provides, implicit self provides and file paths of the packages are honored,
+/// rpmlib() requirements are ignored.
+pub fn check_closure(packages: &[RPMInfo]) -> Vec<UnsatisfiedDep> {
+    let mut provides: HashMap<&str, Vec<Dependency>> = HashMap::new();
+    let mut files = HashSet::new();
+
+    for package in packages {
+        provides
+            .entry(&package.name)
+            .or_default()
+            .push(package.self_provide());
+        for provide in &package.provides {
+            provides
+                .entry(&provide.name)
+                .or_default()
+                .push(provide.clone());
+        }
+        files.extend(package.payload.files.iter().map(|file| file.name.as_str()));
+    }
+
+    let mut unsatisfied = Vec::new();
+    for package in packages {
+        for requirement in &package.requires {
+            if requirement.is_rpmlib() {
+                continue;
+            }
+
+            let provided = provides
+                .get(requirement.name.as_str())
+                .is_some_and(|candidates| {
+                    candidates
+                        .iter()
+                        .any(|provide| requirement.is_satisfied_by(provide))
+                });
+            if !provided && !files.contains(requirement.name.as_str()) {
+                unsatisfied.push(UnsatisfiedDep {
+                    package: format!(
+                        "{}-{}-{}.{}",
+                        package.name, package.version, package.release, package.arch
+                    ),
+                    requirement: requirement.clone(),
+                });
+            }
+        }
+    }
+    unsatisfied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::FileInfo;
+    use crate::rpm::DependencyFlags;
+
+    fn package(name: &str, version: &str, requires: Vec<Dependency>) -> RPMInfo {
+        RPMInfo {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            release: "1".to_owned(),
+            arch: "x86_64".to_owned(),
+            requires,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_closure() {
+        let ge = DependencyFlags::GREATER | DependencyFlags::EQUAL;
+        let rpmlib = DependencyFlags::LESS | DependencyFlags::EQUAL | DependencyFlags::RPMLIB;
+
+        let mut app = package(
+            "app",
+            "2.0",
+            vec![
+                Dependency::new("libfoo", ge, "1.2"),
+                Dependency::new("webserver", DependencyFlags::empty(), ""),
+                Dependency::new("/usr/bin/foo-helper", DependencyFlags::empty(), ""),
+                Dependency::new("rpmlib(CompressedFileNames)", rpmlib, "3.0.4-1"),
+                Dependency::new("libbar", DependencyFlags::empty(), ""),
+            ],
+        );
+        app.provides = vec![Dependency::new("app", DependencyFlags::EQUAL, "2.0-1")];
+
+        let mut libfoo = package("libfoo", "1.3", vec![]);
+        libfoo.payload.files = vec![FileInfo {
+            name: "/usr/bin/foo-helper".to_owned(),
+            ..Default::default()
+        }];
+
+        let mut server = package("server", "1.0", vec![Dependency::new("app", ge, "2.0")]);
+        server.provides = vec![Dependency::new("webserver", DependencyFlags::empty(), "")];
+
+        assert!(libfoo.satisfies(&Dependency::new("libfoo", DependencyFlags::EQUAL, "1.3-1")));
+        assert!(server.satisfies(&Dependency::new("webserver", ge, "2.0")));
+        assert!(!server.satisfies(&Dependency::new("server", ge, "2.0")));
+
+        let unsatisfied = check_closure(&[app, libfoo, server]);
+        assert_eq!(
+            unsatisfied,
+            vec![UnsatisfiedDep {
+                package: "app-2.0-1.x86_64".to_owned(),
+                requirement: Dependency::new("libbar", DependencyFlags::empty(), ""),
+            }]
+        );
+        assert_eq!(
+            unsatisfied[0].to_string(),
+            "app-2.0-1.x86_64 requires libbar"
+        );
+
+        let old = package("libfoo", "1.1", vec![]);
+        let app = package("app", "2.0", vec![Dependency::new("libfoo", ge, "1.2")]);
+        assert_eq!(check_closure(&[app, old]).len(), 1);
+    }
+}
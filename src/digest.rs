@@ -0,0 +1,272 @@
+use md5::Md5;
+use sha1::Sha1;
+use sha2::digest::DynDigest;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+const BUFSIZE: usize = 64 * 1024;
+
+/// Digest algorithms of PayloadDigestAlgo and FileDigestAlgo tags,
+/// numbered as OpenPGP hash algorithms
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DigestAlgo {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha224,
+}
+
+/// Digest algorithm id which is not known or not implemented
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UnsupportedDigestAlgo(pub u32);
+
+impl fmt::Display for UnsupportedDigestAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error: digest algorithm {} is not supported", self.0)
+    }
+}
+
+impl Error for UnsupportedDigestAlgo {}
+
+impl From<UnsupportedDigestAlgo> for io::Error {
+    fn from(e: UnsupportedDigestAlgo) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+impl DigestAlgo {
+    pub fn from_rpm_id(id: u32) -> Result<Self, UnsupportedDigestAlgo> {
+        match id {
+            1 => Ok(DigestAlgo::Md5),
+            2 => Ok(DigestAlgo::Sha1),
+            8 => Ok(DigestAlgo::Sha256),
+            9 => Ok(DigestAlgo::Sha384),
+            10 => Ok(DigestAlgo::Sha512),
+            11 => Ok(DigestAlgo::Sha224),
+            id => Err(UnsupportedDigestAlgo(id)),
+        }
+    }
+
+    pub fn rpm_id(self) -> u32 {
+        match self {
+            DigestAlgo::Md5 => 1,
+            DigestAlgo::Sha1 => 2,
+            DigestAlgo::Sha256 => 8,
+            DigestAlgo::Sha384 => 9,
+            DigestAlgo::Sha512 => 10,
+            DigestAlgo::Sha224 => 11,
+        }
+    }
+
+    /// Length of the digest in bytes, hex digests are twice as long
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(self) -> usize {
+        match self {
+            DigestAlgo::Md5 => 16,
+            DigestAlgo::Sha1 => 20,
+            DigestAlgo::Sha224 => 28,
+            DigestAlgo::Sha256 => 32,
+            DigestAlgo::Sha384 => 48,
+            DigestAlgo::Sha512 => 64,
+        }
+    }
+
+    /// Hex digest of all data from the reader
+    pub fn digest<R: Read>(self, reader: &mut R) -> io::Result<String> {
+        match self {
+            DigestAlgo::Md5 => hex_digest::<Md5, R>(reader),
+            DigestAlgo::Sha1 => hex_digest::<Sha1, R>(reader),
+            DigestAlgo::Sha224 => hex_digest::<Sha224, R>(reader),
+            DigestAlgo::Sha256 => hex_digest::<Sha256, R>(reader),
+            DigestAlgo::Sha384 => hex_digest::<Sha384, R>(reader),
+            DigestAlgo::Sha512 => hex_digest::<Sha512, R>(reader),
+        }
+    }
+
+    /// Wraps the reader to compute the digest of the data read through it
+    pub fn reader<R: Read>(self, reader: R) -> DigestReader<R> {
+        let hasher: Box<dyn DynDigest> = match self {
+            DigestAlgo::Md5 => Box::new(Md5::new()),
+            DigestAlgo::Sha1 => Box::new(Sha1::new()),
+            DigestAlgo::Sha224 => Box::new(Sha224::new()),
+            DigestAlgo::Sha256 => Box::new(Sha256::new()),
+            DigestAlgo::Sha384 => Box::new(Sha384::new()),
+            DigestAlgo::Sha512 => Box::new(Sha512::new()),
+        };
+        DigestReader { reader, hasher }
+    }
+
+    pub fn digest_bytes(self, data: &[u8]) -> String {
+        let mut reader = data;
+        self.digest(&mut reader)
+            .expect("Digest: reading from memory can not fail")
+    }
+}
+
+/// Reader which hashes the data passing through it, e.g. while it is copied
+pub struct DigestReader<R> {
+    reader: R,
+    hasher: Box<dyn DynDigest>,
+}
+
+impl<R> DigestReader<R> {
+    /// Hex digest of the data read so far
+    pub fn finish(self) -> String {
+        hex::encode(self.hasher.finalize())
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Result of comparing a stored digest with the digest of the data
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DigestVerification {
+    Ok,
+    Mismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The package has no digest to compare with
+    NotPresent,
+}
+
+impl DigestVerification {
+    /// Compares hex digests, the case is ignored
+    pub fn compare(expected: &str, actual: String) -> Self {
+        if expected.eq_ignore_ascii_case(&actual) {
+            DigestVerification::Ok
+        } else {
+            DigestVerification::Mismatch {
+                expected: expected.to_owned(),
+                actual,
+            }
+        }
+    }
+
+    pub fn is_mismatch(&self) -> bool {
+        matches!(self, DigestVerification::Mismatch { .. })
+    }
+}
+
+impl fmt::Display for DigestVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DigestVerification::Ok => write!(f, "OK"),
+            DigestVerification::Mismatch { expected, actual } => {
+                write!(f, "BAD (Expected {} != {})", expected, actual)
+            }
+            DigestVerification::NotPresent => write!(f, "NOT PRESENT"),
+        }
+    }
+}
+
+/// Digests of the main header stored in SHA256Header and SHA1Header
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderDigestStatus {
+    pub sha256: DigestVerification,
+    pub sha1: DigestVerification,
+}
+
+impl HeaderDigestStatus {
+    pub fn is_mismatch(&self) -> bool {
+        self.sha256.is_mismatch() || self.sha1.is_mismatch()
+    }
+}
+
+/// Present digests, e.g. `SHA256 digest OK, SHA1 digest OK`, or `MISSING`
+impl fmt::Display for HeaderDigestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digests: Vec<String> = [("SHA256", &self.sha256), ("SHA1", &self.sha1)]
+            .iter()
+            .filter(|(_, status)| **status != DigestVerification::NotPresent)
+            .map(|(name, status)| format!("{} digest {}", name, status))
+            .collect();
+        if digests.is_empty() {
+            write!(f, "MISSING")
+        } else {
+            write!(f, "{}", digests.join(", "))
+        }
+    }
+}
+
+impl fmt::Display for DigestAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DigestAlgo::Md5 => write!(f, "MD5"),
+            DigestAlgo::Sha1 => write!(f, "SHA1"),
+            DigestAlgo::Sha224 => write!(f, "SHA224"),
+            DigestAlgo::Sha256 => write!(f, "SHA256"),
+            DigestAlgo::Sha384 => write!(f, "SHA384"),
+            DigestAlgo::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+fn hex_digest<D: Digest, R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut hasher = D::new();
+    let mut buffer = vec![0_u8; BUFSIZE];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_known_answers() {
+        let cases = [
+            (1, "900150983cd24fb0d6963f7d28e17f72"),
+            (2, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            (
+                8,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                9,
+                "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed\
+                 8086072ba1e7cc2358baeca134c825a7",
+            ),
+            (
+                10,
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                 2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            ),
+            (
+                11,
+                "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7",
+            ),
+        ];
+
+        for (id, expected) in cases.iter() {
+            let algo = DigestAlgo::from_rpm_id(*id).unwrap();
+            assert_eq!(algo.rpm_id(), *id);
+            assert_eq!(algo.digest_bytes(b"abc"), *expected, "{}", algo);
+            assert_eq!(algo.len() * 2, expected.len());
+
+            let mut reader = algo.reader(&b"abc"[..]);
+            io::copy(&mut reader, &mut io::sink()).unwrap();
+            assert_eq!(reader.finish(), *expected);
+        }
+
+        assert_eq!(DigestAlgo::from_rpm_id(3), Err(UnsupportedDigestAlgo(3)));
+    }
+}
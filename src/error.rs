@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::header::Tag;
+
+pub type Result<T> = std::result::Result<T, RpmError>;
+
+/// Failures of reading and writing packages, other errors are kept as `Io`
+#[derive(Debug)]
+pub enum RpmError {
+    /// The lead or a header does not start with the rpm magic
+    NotAnRpm,
+    UnsupportedVersion {
+        major: u8,
+        minor: u8,
+    },
+    /// The file ends inside the named part, e.g. `lead`
+    Truncated(&'static str),
+    MissingTag(Tag),
+    WrongTagType {
+        tag: Tag,
+        expected: &'static str,
+        actual: &'static str,
+    },
+    /// Parallel array tags, e.g. ChangelogTime and ChangelogName, differ in length
+    TagLengthMismatch {
+        tag: Tag,
+        expected: usize,
+        actual: usize,
+    },
+    UnsupportedCompressor(String),
+    /// The codec is known, but its cargo feature is disabled
+    DisabledCompressor(&'static str),
+    /// A cpio entry of the payload can not be read
+    CorruptedPayload(String),
+    /// The header structure is not valid, e.g. its region
+    CorruptedHeader(&'static str),
+    /// A header size is above the limit of `ReadOptions`
+    HeaderLimit {
+        name: &'static str,
+        value: u32,
+        limit: u32,
+    },
+    Io(io::Error),
+}
+
+impl RpmError {
+    /// Kind of the io::Error the error is converted into
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            RpmError::Truncated(_) => io::ErrorKind::UnexpectedEof,
+            RpmError::MissingTag(_) => io::ErrorKind::NotFound,
+            RpmError::Io(e) => e.kind(),
+            _ => io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl fmt::Display for RpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpmError::NotAnRpm => write!(f, "Error: File is not rpm"),
+            RpmError::UnsupportedVersion { major, minor } => write!(
+                f,
+                "Error: rpm format version is not supported {}.{}",
+                major, minor
+            ),
+            RpmError::Truncated(part) => write!(f, "Error: rpm {} is truncated", part),
+            RpmError::MissingTag(tag) => write!(f, "Error: tag {} is not found", tag),
+            RpmError::WrongTagType {
+                tag,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Error: tag {} is {}, {} is expected",
+                tag, actual, expected
+            ),
+            RpmError::TagLengthMismatch {
+                tag,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Error: tag {} has {} entries, {} are expected",
+                tag, actual, expected
+            ),
+            RpmError::UnsupportedCompressor(compressor) => write!(
+                f,
+                "Error: decompressor \"{}\" is not implemented",
+                compressor
+            ),
+            RpmError::DisabledCompressor(codec) => {
+                write!(f, "Error: support for {} is not compiled in", codec)
+            }
+            RpmError::CorruptedPayload(reason) => write!(f, "Error: {}", reason),
+            RpmError::CorruptedHeader(reason) => write!(f, "Error: rpm header has {}", reason),
+            RpmError::HeaderLimit { name, value, limit } => write!(
+                f,
+                "Error: rpm header has {} {}, the limit is {}",
+                value, name, limit
+            ),
+            RpmError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for RpmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RpmError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RpmError {
+    fn from(e: io::Error) -> Self {
+        RpmError::Io(e)
+    }
+}
+
+impl From<RpmError> for io::Error {
+    fn from(e: RpmError) -> Self {
+        match e {
+            RpmError::Io(e) => e,
+            e => io::Error::new(e.kind(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lead::Lead;
+    use crate::RPMFile;
+    use std::io::Cursor;
+    use std::path::Path;
+
+    #[test]
+    fn test_error_variants() -> io::Result<()> {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        let bytes = std::fs::read(data.join("hello-1.0-1.noarch.rpm"))?;
+
+        let err = Lead::read(&mut Cursor::new(&bytes[..50])).unwrap_err();
+        assert!(matches!(err, RpmError::Truncated("lead")));
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::UnexpectedEof);
+        let err = Lead::read(&mut Cursor::new(&bytes[4..])).unwrap_err();
+        assert!(matches!(err, RpmError::NotAnRpm));
+
+        let mut rpm = RPMFile::open(data.join("hello-1.0-1.noarch.rpm"))?;
+        rpm.header_tags.insert_payload_compressor("lz4".to_owned());
+        let err = rpm.read_payload_files().unwrap_err();
+        assert!(matches!(err, RpmError::UnsupportedCompressor(ref c) if c == "lz4"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use std::io;
+use std::process;
+
+/// Exit codes section of the binaries help
+pub const EXIT_CODES_HELP: &str = "EXIT CODES:
+    0    success
+    1    error, e.g. a missing file
+    2    incorrect usage
+    3    not an rpm or cpio file, the file is corrupted or inconsistent
+    4    signature verification or dependency check failed
+    5    some entries were not extracted";
+
+/// Exit status of the binaries, scripts may rely on these values
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExitCode {
+    Ok = 0,
+    Error = 1,
+    Usage = 2,
+    Format = 3,
+    Verification = 4,
+    Partial = 5,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    pub fn exit(self) -> ! {
+        process::exit(self.code())
+    }
+}
+
+impl From<&io::Error> for ExitCode {
+    fn from(e: &io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => ExitCode::Format,
+            _ => ExitCode::Error,
+        }
+    }
+}
+
+/// Parses command line arguments, usage errors exit with code 2,
+/// help and version are printed to stdout
+#[cfg(feature = "cli")]
+pub fn from_args<T: structopt::StructOpt>() -> T {
+    T::from_iter_safe(std::env::args_os()).unwrap_or_else(|e| {
+        if e.use_stderr() {
+            eprintln!("{}", e.message);
+            ExitCode::Usage.exit()
+        } else {
+            println!("{}", e.message);
+            ExitCode::Ok.exit()
+        }
+    })
+}
+
+/// Exits with the returned code, errors are printed to stderr
+pub fn exit_with(result: io::Result<ExitCode>) -> ! {
+    match result {
+        Ok(code) => code.exit(),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(&e).exit()
+        }
+    }
+}
@@ -27,9 +27,12 @@ impl Default for Type {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RType {
     Null,
     Char(char),
+    /// Array of the single byte CHAR type, e.g. FilesStates
+    CharArray(Vec<u8>),
     Int8(u8),
     Int8Array(Vec<u8>),
     Int16(u16),
@@ -45,6 +48,37 @@ pub enum RType {
 }
 
 impl RType {
+    /// Type name used in error messages, e.g. `int32 array`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RType::Null => "null",
+            RType::Char(_) => "char",
+            RType::CharArray(_) => "char array",
+            RType::Int8(_) => "int8",
+            RType::Int8Array(_) => "int8 array",
+            RType::Int16(_) => "int16",
+            RType::Int16Array(_) => "int16 array",
+            RType::Int32(_) => "int32",
+            RType::Int32Array(_) => "int32 array",
+            RType::Int64(_) => "int64",
+            RType::Int64Array(_) => "int64 array",
+            RType::String(_) => "string",
+            RType::Bin(_) => "binary",
+            RType::StringArray(_) => "string array",
+            RType::I18nstring(_) => "i18n string",
+        }
+    }
+
+    /// Alignment of the value in the header data, numbers are aligned to their size
+    pub fn alignment(&self) -> u32 {
+        match self {
+            RType::Int16(_) | RType::Int16Array(_) => 2,
+            RType::Int32(_) | RType::Int32Array(_) => 4,
+            RType::Int64(_) | RType::Int64Array(_) => 8,
+            _ => 1,
+        }
+    }
+
     pub fn as_string(&self) -> Option<String> {
         match self {
             RType::Null => Some(Default::default()),
@@ -77,8 +111,12 @@ impl RType {
         }
     }
 
+    /// Arrays of one element are read as single values, they are returned as arrays too
     pub fn as_u64_array(&self) -> Option<Vec<u64>> {
         match self {
+            RType::Int8(_) | RType::Int16(_) | RType::Int32(_) | RType::Int64(_) => {
+                self.as_u64().map(|n| vec![n])
+            }
             RType::Int8Array(a) => Some(a.iter().map(|x| u64::from(*x)).collect()),
             RType::Int16Array(a) => Some(a.iter().map(|x| u64::from(*x)).collect()),
             RType::Int32Array(a) => Some(a.iter().map(|x| u64::from(*x)).collect()),
@@ -87,11 +125,13 @@ impl RType {
         }
     }
 
+    /// Wider integers are converted only if the value fits
     pub fn as_u32(&self) -> Option<u32> {
         match self {
             RType::Int8(n) => Some(u32::from(*n)),
             RType::Int16(n) => Some(u32::from(*n)),
             RType::Int32(n) => Some(*n),
+            RType::Int64(n) => u32::try_from(*n).ok(),
             _ => None,
         }
     }
@@ -101,12 +141,14 @@ impl RType {
             RType::Int8(n) => Some(i64::from(*n)),
             RType::Int16(n) => Some(i64::from(*n)),
             RType::Int32(n) => Some(i64::from(*n)),
+            RType::Int64(n) => i64::try_from(*n).ok(),
             _ => None,
         }
     }
 
     pub fn as_u32_array(&self) -> Option<Vec<u32>> {
         match self {
+            RType::Int8(_) | RType::Int16(_) | RType::Int32(_) => self.as_u32().map(|n| vec![n]),
             RType::Int8Array(a) => Some(a.iter().map(|x| u32::from(*x)).collect()),
             RType::Int16Array(a) => Some(a.iter().map(|x| u32::from(*x)).collect()),
             RType::Int32Array(a) => Some(a.clone()),
@@ -114,16 +156,20 @@ impl RType {
         }
     }
 
+    /// Wider integers are converted only if the value fits
     pub fn as_u16(&self) -> Option<u16> {
         match self {
             RType::Int8(n) => Some(u16::from(*n)),
             RType::Int16(n) => Some(*n),
+            RType::Int32(n) => u16::try_from(*n).ok(),
+            RType::Int64(n) => u16::try_from(*n).ok(),
             _ => None,
         }
     }
 
     pub fn as_u16_array(&self) -> Option<Vec<u16>> {
         match self {
+            RType::Int8(_) | RType::Int16(_) => self.as_u16().map(|n| vec![n]),
             RType::Int8Array(a) => Some(a.iter().map(|x| u16::from(*x)).collect()),
             RType::Int16Array(a) => Some(a.clone()),
             _ => None,
@@ -139,7 +185,7 @@ impl RType {
 
     pub fn as_u8_array(&self) -> Option<Vec<u8>> {
         match self {
-            RType::Int8Array(a) => Some(a.clone()),
+            RType::Int8Array(a) | RType::CharArray(a) => Some(a.clone()),
             _ => None,
         }
     }
@@ -254,10 +300,12 @@ where
         });
 
         let type_id: u32 = fh.read_be()?;
-        let itype = Type::from_u32(type_id).unwrap_or_else(|| {
-            println!("Unknown type {}", type_id);
-            Type::Null
-        });
+        let itype = Type::from_u32(type_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Error: tag type {} is not known", type_id),
+            )
+        })?;
 
         let offset: u32 = fh.read_be()?;
         let count: u32 = fh.read_be()?;
@@ -301,13 +349,13 @@ impl<T: Copy> Index<T> {
     pub fn from(tag: &T, rtype: &RType, offset: usize, count: usize) -> Self {
         let itype = match rtype {
             RType::Null => Type::Null,
-            RType::Char(_) => Type::Char,
+            RType::Char(_) | RType::CharArray(_) => Type::Char,
             RType::Int8(_) | RType::Int8Array(_) => Type::Int8,
             RType::Int16(_) | RType::Int16Array(_) => Type::Int16,
             RType::Int32(_) | RType::Int32Array(_) => Type::Int32,
             RType::Int64(_) | RType::Int64Array(_) => Type::Int64,
             RType::String(_) => Type::String,
-            RType::Bin(_) => Type::String,
+            RType::Bin(_) => Type::Bin,
             RType::StringArray(_) => Type::StringArray,
             RType::I18nstring(_) => Type::I18nstring,
         };
@@ -362,4 +410,17 @@ mod tests {
 
         assert_eq!(index, index2);
     }
+
+    #[test]
+    fn test_integer_conversions() {
+        assert_eq!(RType::Int64(1_700_000_000).as_i64(), Some(1_700_000_000));
+        assert_eq!(RType::Int64(u64::MAX).as_i64(), None);
+
+        assert_eq!(RType::Int64(42).as_u32(), Some(42));
+        assert_eq!(RType::Int64(u64::from(u32::MAX) + 1).as_u32(), None);
+        assert_eq!(RType::Int32(42).as_u16(), Some(42));
+        assert_eq!(RType::Int32(0x10000).as_u16(), None);
+        assert_eq!(RType::Int64(0x10000).as_u16(), None);
+        assert_eq!(RType::String("42".to_owned()).as_u32(), None);
+    }
 }
@@ -1,8 +1,48 @@
 use omnom::prelude::*;
+use std::collections::HashSet;
+use std::fmt;
 use std::io::{self, Read, Write};
 
+use super::Tag;
+use crate::error::{Result, RpmError};
+
 pub const MAGIC_HEADER: [u8; 4] = [142, 173, 232, 1];
 
+/// Limits of the headers read from packages, larger values come from corrupted headers,
+/// and the parts of the headers which are decoded
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadOptions {
+    /// Index entries of a header, 64 K by default as rpm allows
+    pub max_index_entries: u32,
+    /// Data size of a header in bytes, 256 MB by default as rpm allows
+    pub max_data_size: u32,
+    /// Main header tags which are decoded, the others are skipped. All tags by default.
+    pub tags: Option<HashSet<Tag>>,
+    /// The signature header is skipped and the signature tags are empty
+    pub skip_signature: bool,
+}
+
+impl ReadOptions {
+    /// Decodes only the tags, e.g. `INFO_TAGS` for package indexes
+    pub fn with_tags<I: IntoIterator<Item = Tag>>(self, tags: I) -> Self {
+        ReadOptions {
+            tags: Some(tags.into_iter().collect()),
+            ..self
+        }
+    }
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            max_index_entries: 0xffff,
+            max_data_size: 0x0fff_ffff,
+            tags: None,
+            skip_signature: false,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct HeaderLead {
     pub magic: [u8; 4],
@@ -12,15 +52,17 @@ pub struct HeaderLead {
 }
 
 impl HeaderLead {
-    pub fn read<R: Read>(fh: &mut R) -> io::Result<Self> {
+    pub fn read<R: Read>(fh: &mut R) -> Result<Self> {
+        Self::read_with(fh, &ReadOptions::default())
+    }
+
+    /// Reads the lead, the sizes above the limits are errors
+    pub fn read_with<R: Read>(fh: &mut R, options: &ReadOptions) -> Result<Self> {
         let mut magic = [0_u8; 4];
         fh.read_exact(&mut magic)?;
 
         if magic != MAGIC_HEADER {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Error: File is not rpm",
-            ));
+            return Err(RpmError::NotAnRpm);
         }
 
         let mut reserved = [0_u8; 4];
@@ -28,9 +70,50 @@ impl HeaderLead {
 
         let nindex: u32 = fh.read_be()?;
         let hsize: u32 = fh.read_be()?;
+        Self::with_sizes(reserved, nindex, hsize, options)
+    }
+
+    /// Reads the lead of a standalone header, rpmdb stores headers without the magic
+    /// and the reserved bytes, so both forms are accepted
+    pub fn read_blob_with<R: Read>(fh: &mut R, options: &ReadOptions) -> Result<Self> {
+        let mut start = [0_u8; 4];
+        fh.read_exact(&mut start)?;
+
+        // the magic read as the index count is above any limit
+        let (reserved, nindex) = if start == MAGIC_HEADER {
+            let mut reserved = [0_u8; 4];
+            fh.read_exact(&mut reserved)?;
+            (reserved, fh.read_be()?)
+        } else {
+            ([0_u8; 4], u32::from_be_bytes(start))
+        };
+        let hsize: u32 = fh.read_be()?;
+        Self::with_sizes(reserved, nindex, hsize, options)
+    }
+
+    fn with_sizes(
+        reserved: [u8; 4],
+        nindex: u32,
+        hsize: u32,
+        options: &ReadOptions,
+    ) -> Result<Self> {
+        if nindex > options.max_index_entries {
+            return Err(RpmError::HeaderLimit {
+                name: "index entries",
+                value: nindex,
+                limit: options.max_index_entries,
+            });
+        }
+        if hsize > options.max_data_size {
+            return Err(RpmError::HeaderLimit {
+                name: "bytes of data",
+                value: hsize,
+                limit: options.max_data_size,
+            });
+        }
 
         Ok(HeaderLead {
-            magic,
+            magic: MAGIC_HEADER,
             reserved,
             nindex: nindex as usize,
             hsize,
@@ -55,6 +138,15 @@ impl HeaderLead {
     }
 }
 
+impl fmt::Display for HeaderLead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "magic: {:?}", self.magic)?;
+        writeln!(f, "reserved: {:?}", self.reserved)?;
+        writeln!(f, "nindex: {}", self.nindex)?;
+        writeln!(f, "hsize: {}", self.hsize)
+    }
+}
+
 impl Default for HeaderLead {
     fn default() -> Self {
         HeaderLead {
@@ -83,4 +175,33 @@ mod tests {
 
         assert_eq!(lead, lead2);
     }
+
+    #[test]
+    fn test_headerlead_limits() {
+        let mut data: Vec<u8> = Vec::new();
+        HeaderLead::from(0x10000, 16).write(&mut data).unwrap();
+
+        let err = HeaderLead::read(&mut Cursor::new(&data)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "Error: rpm header has 65536 index entries, the limit is 65535"
+        );
+
+        let options = ReadOptions {
+            max_index_entries: 0x10000,
+            ..Default::default()
+        };
+        let lead = HeaderLead::read_with(&mut Cursor::new(&data), &options).unwrap();
+        assert_eq!(lead.nindex, 0x10000);
+
+        let options = ReadOptions {
+            max_data_size: 8,
+            ..options
+        };
+        assert!(matches!(
+            HeaderLead::read_with(&mut Cursor::new(&data), &options),
+            Err(RpmError::HeaderLimit { value: 16, .. })
+        ));
+    }
 }
@@ -1,25 +1,36 @@
 mod index;
 mod lead;
+mod size;
 mod tags;
 
 pub use index::*;
 pub use lead::*;
+pub use size::*;
 pub use tags::*;
 
+use md5::{Digest, Md5};
 use num_traits::{FromPrimitive, ToPrimitive};
 use omnom::prelude::*;
 use omnom::ReadBytes;
 use std::char;
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use std::hash::Hash;
 use std::io::{self, Read, Seek, Write};
 use std::mem::size_of;
 
+use crate::digest::DigestAlgo;
+use crate::error::{Result, RpmError};
+use crate::payload::FileInfo;
 use crate::utils::{align_n_bytes, parse_string, parse_strings};
 
-#[derive(Debug, Default)]
-pub struct Tags<T>(pub HashMap<T, RType>)
+/// Array elements shown for each tag by `Display`, the rest are counted
+const DISPLAY_ITEMS: usize = 8;
+
+/// Tags known to `T` and the others by their numeric id, e.g. vendor tags
+#[derive(Debug, Default, PartialEq)]
+pub struct Tags<T>(pub HashMap<T, RType>, pub HashMap<u32, RType>)
 where
     T: Eq + Hash;
 
@@ -28,13 +39,26 @@ where
     T: FromPrimitive + Default + Eq + Hash + Copy,
 {
     pub fn new() -> Self {
-        Tags(HashMap::new())
+        Tags(HashMap::new(), HashMap::new())
     }
 
     pub fn get_value(&self, name: T) -> Option<RType> {
         self.0.get(&name).cloned()
     }
 
+    /// Value without cloning, e.g. for large string arrays
+    pub fn get_ref(&self, name: T) -> Option<&RType> {
+        self.0.get(&name)
+    }
+
+    /// Value by the numeric id, tags unknown to `T` are found too
+    pub fn get_by_id(&self, id: u32) -> Option<RType> {
+        match T::from_u32(id) {
+            Some(tag) => self.get_value(tag),
+            None => self.1.get(&id).cloned(),
+        }
+    }
+
     pub fn get<O>(&self, name: T) -> O
     where
         O: Default + From<RType>,
@@ -50,15 +74,31 @@ where
         self
     }
 
+    /// Inserts by the numeric id, tags unknown to `T` are kept and written too
+    pub fn insert_by_id(&mut self, id: u32, value: RType) -> &mut Self {
+        match T::from_u32(id) {
+            Some(tag) => self.insert(tag, value),
+            None => {
+                self.1.insert(id, value);
+                self
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: T) -> Option<RType> {
+        self.0.remove(&name)
+    }
+
+    #[deprecated(note = "panics if the tag is missing, use try_get_as_string")]
     pub fn get_as_string(&self, name: T) -> String {
-        self.get_value(name)
+        self.get_ref(name)
             .expect("Tag: not found")
             .as_string()
             .expect("Tag: is not a string")
     }
 
     pub fn get_as_string_or(&self, name: T) -> String {
-        if let Some(s) = self.get_value(name) {
+        if let Some(s) = self.get_ref(name) {
             s.as_string().expect("Tag: is not a string")
         } else {
             Default::default()
@@ -66,57 +106,62 @@ where
     }
 
     pub fn get_as_string_array_or(&self, name: T) -> Vec<String> {
-        if let Some(s) = self.get_value(name) {
+        if let Some(s) = self.get_ref(name) {
             s.as_string_array().expect("Tag: is not a string array")
         } else {
             Default::default()
         }
     }
 
+    #[deprecated(note = "panics if the tag is missing, use try_get_as_u8")]
     pub fn get_as_u8(&self, name: T) -> u8 {
-        self.get_value(name)
+        self.get_ref(name)
             .expect("Tag: not found")
             .as_u8()
             .expect("Tag: is not a u8")
     }
     pub fn get_as_u8_default(&self, name: T) -> u8 {
-        if let Some(s) = self.get_value(name) {
+        if let Some(s) = self.get_ref(name) {
             s.as_u8().expect("Tag: is not a u8")
         } else {
             Default::default()
         }
     }
 
+    #[deprecated(note = "panics if the tag is missing, use try_get_as_u16")]
     pub fn get_as_u16(&self, name: T) -> u16 {
-        self.get_value(name)
+        self.get_ref(name)
             .expect("Tag: not found")
             .as_u16()
             .expect("Tag: is not a u16")
     }
 
+    #[deprecated(note = "panics if the tag is missing, use try_get_as_u32")]
     pub fn get_as_u32(&self, name: T) -> u32 {
-        self.get_value(name)
+        self.get_ref(name)
             .expect("Tag: not found")
             .as_u32()
             .expect("Tag: is not a integer")
     }
 
+    #[deprecated(note = "panics if the tag is missing, use try_get_as_u64")]
     pub fn get_as_u64(&self, name: T) -> u64 {
-        self.get_value(name)
+        self.get_ref(name)
             .expect("Tag: not found")
             .as_u64()
             .expect("Tag: is not a integer")
     }
 
+    #[deprecated(note = "panics if the tag is missing, use try_get_as_i64")]
     pub fn get_as_i64(&self, name: T) -> i64 {
-        self.get_value(name)
+        self.get_ref(name)
             .expect("Tag: not found")
             .as_i64()
             .expect("Tag: is not a integer")
     }
 
     pub fn get_as_u64_array_or(&self, name: T) -> Vec<u64> {
-        if let Some(s) = self.get_value(name) {
+        if let Some(s) = self.get_ref(name) {
             s.as_u64_array().expect("Tag: is not a u64 array")
         } else {
             Default::default()
@@ -124,7 +169,7 @@ where
     }
 
     pub fn get_as_u32_array_or(&self, name: T) -> Vec<u32> {
-        if let Some(s) = self.get_value(name) {
+        if let Some(s) = self.get_ref(name) {
             s.as_u32_array().expect("Tag: is not a u32 array")
         } else {
             Default::default()
@@ -132,86 +177,395 @@ where
     }
 
     pub fn get_as_u16_array_or(&self, name: T) -> Vec<u16> {
-        if let Some(s) = self.get_value(name) {
+        if let Some(s) = self.get_ref(name) {
             s.as_u16_array().expect("Tag: is not a u16 array")
         } else {
             Default::default()
         }
     }
 
-    pub fn read<R>(fh: &mut R, indexes: &[Index<T>], size: usize) -> io::Result<Self>
-    where
-        R: Read + Seek,
-    {
-        let mut s_data = vec![0_u8; size];
-        fh.read_exact(&mut s_data)?;
-
-        Self::tags_from_raw(&indexes, &s_data)
-    }
-
-    fn tags_from_raw(indexes: &[Index<T>], data: &[u8]) -> io::Result<Self> {
+    fn tags_from_raw(indexes: &[Index<u32>], data: &[u8]) -> io::Result<Self> {
+        // strings end before the next entry, the last one ends with the data
+        let end = |i: usize| indexes.get(i + 1).map_or(data.len(), |next| next.offset);
         let tags = (0..indexes.len())
             .map(|i| {
                 let item = &indexes[i];
                 let ps = item.offset;
+                let slice = |end: Option<usize>| {
+                    end.and_then(|end| data.get(ps..end)).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Error: tag {} is out of the header data", item.tag),
+                        )
+                    })
+                };
 
                 let tag_value = match item.itype {
                     Type::Null => RType::Null,
-                    Type::Char => {
-                        let c_byte = (&data[ps..]).read_be()?;
-                        let c = char::from_u32(c_byte).unwrap_or_default();
-                        RType::Char(c)
-                    }
-                    Type::Int8 => extract(data, ps, item.count, RType::Int8, RType::Int8Array)?,
-                    Type::Int16 => extract(data, ps, item.count, RType::Int16, RType::Int16Array)?,
-                    Type::Int32 => extract(data, ps, item.count, RType::Int32, RType::Int32Array)?,
-                    Type::Int64 => extract(data, ps, item.count, RType::Int64, RType::Int64Array)?,
-
-                    Type::String => {
-                        let ps2 = indexes[i + 1].offset;
-                        let v = parse_string(&data[ps..ps2]);
-                        RType::String(v)
-                    }
-
-                    Type::Bin => {
-                        let ps2 = ps + item.count;
-                        let bytes = &data[ps..ps2];
-                        RType::Bin(bytes.to_vec())
-                    }
+                    Type::Char => extract(
+                        slice(checked_end::<u8>(ps, item.count))?,
+                        item.count,
+                        |c: u8| RType::Char(char::from(c)),
+                        RType::CharArray,
+                    )?,
+                    Type::Int8 => extract(
+                        slice(checked_end::<u8>(ps, item.count))?,
+                        item.count,
+                        RType::Int8,
+                        RType::Int8Array,
+                    )?,
+                    Type::Int16 => extract(
+                        slice(checked_end::<u16>(ps, item.count))?,
+                        item.count,
+                        RType::Int16,
+                        RType::Int16Array,
+                    )?,
+                    Type::Int32 => extract(
+                        slice(checked_end::<u32>(ps, item.count))?,
+                        item.count,
+                        RType::Int32,
+                        RType::Int32Array,
+                    )?,
+                    Type::Int64 => extract(
+                        slice(checked_end::<u64>(ps, item.count))?,
+                        item.count,
+                        RType::Int64,
+                        RType::Int64Array,
+                    )?,
+
+                    Type::String => RType::String(parse_string(slice(Some(end(i)))?)),
+
+                    Type::Bin => RType::Bin(slice(ps.checked_add(item.count))?.to_vec()),
 
                     Type::StringArray => {
-                        let ps2 = indexes[i + 1].offset;
-                        let v = parse_strings(&data[ps..ps2], item.count);
-                        RType::StringArray(v)
+                        RType::StringArray(parse_strings(slice(Some(end(i)))?, item.count))
                     }
 
-                    Type::I18nstring => {
-                        let ps2 = indexes[i + 1].offset;
-                        let v = parse_string(&data[ps..ps2]);
-                        RType::I18nstring(v)
-                    }
+                    Type::I18nstring => RType::I18nstring(parse_string(slice(Some(end(i)))?)),
                 };
 
                 Ok((item.tag, tag_value))
             })
-            .collect::<io::Result<HashMap<_, _>>>()?;
-        Ok(Tags(tags))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut known = HashMap::new();
+        let mut unknown = HashMap::new();
+        for (id, value) in tags {
+            match T::from_u32(id) {
+                Some(tag) => known.insert(tag, value),
+                None => unknown.insert(id, value),
+            };
+        }
+        Ok(Tags(known, unknown))
+    }
+}
+
+impl<T> Tags<T>
+where
+    T: FromPrimitive + ToPrimitive + Default + Eq + Hash + Copy + RegionTag,
+{
+    /// Reads the header data, the region entry is validated and is not kept as a tag
+    pub fn read<R>(fh: &mut R, indexes: &[Index<u32>], size: usize) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        Self::read_filtered(fh, indexes, size, |_| true)
+    }
+
+    /// Reads the header data, only the entries accepted by their id are decoded
+    pub fn read_filtered<R, F>(
+        fh: &mut R,
+        indexes: &[Index<u32>],
+        size: usize,
+        filter: F,
+    ) -> Result<Self>
+    where
+        R: Read + Seek,
+        F: Fn(u32) -> bool,
+    {
+        let mut s_data = vec![0_u8; size];
+        fh.read_exact(&mut s_data)?;
+
+        let (regions, entries): (Vec<_>, Vec<_>) = indexes
+            .iter()
+            .cloned()
+            .partition(|index| T::REGION.to_u32() == Some(index.tag));
+        if let Some(region) = regions.first() {
+            check_region::<T>(region, indexes.len(), &s_data)?;
+        }
+        let entries: Vec<_> = entries
+            .into_iter()
+            .filter(|index| filter(index.tag))
+            .collect();
+        Ok(Self::tags_from_raw(&entries, &s_data)?)
+    }
+
+    /// Reads a standalone header without the package lead and the signature header,
+    /// e.g. an rpmdb entry or a header cut from a package, with or without the magic
+    pub fn read_header_blob<R: Read>(fh: &mut R) -> Result<Self> {
+        let lead = HeaderLead::read_blob_with(fh, &ReadOptions::default())?;
+        Self::read_after_lead(fh, &lead, |_| true)
+    }
+
+    /// Reads the index and the data following the lead into memory, the reader is not seeked
+    pub(crate) fn read_after_lead<R, F>(fh: &mut R, lead: &HeaderLead, filter: F) -> Result<Self>
+    where
+        R: Read,
+        F: Fn(u32) -> bool,
+    {
+        let size = lead.nindex * 16 + lead.hsize as usize;
+        let mut data = Vec::with_capacity(size);
+        fh.take(size as u64).read_to_end(&mut data)?;
+        if data.len() < size {
+            return Err(RpmError::Truncated("header"));
+        }
+
+        let mut data = io::Cursor::new(data);
+        let indexes = IndexArray::read(&mut data, lead.nindex)?;
+        Self::read_filtered(&mut data, &indexes, lead.hsize as usize, filter)
+    }
+}
+
+impl<T> Tags<T>
+where
+    T: ToPrimitive + Eq + Hash,
+{
+    /// Tags known to `T` ordered by id
+    pub fn iter(&self) -> std::vec::IntoIter<(&T, &RType)> {
+        let mut tags: Vec<(&T, &RType)> = self.0.iter().collect();
+        tags.sort_by_key(|(tag, _)| tag.to_u32());
+        tags.into_iter()
+    }
+
+    /// Tags known to `T` ordered by id
+    pub fn keys(&self) -> impl Iterator<Item = &T> {
+        self.iter().map(|(tag, _)| tag)
+    }
+
+    /// Number of tags, including the ones unknown to `T`
+    pub fn len(&self) -> usize {
+        self.0.len() + self.1.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, tag: T) -> bool {
+        self.0.contains_key(&tag)
+    }
+}
+
+impl<T> Tags<T>
+where
+    T: ToPrimitive + Eq + Hash + fmt::Display,
+{
+    /// Tag names with their values ordered by id, tags unknown to `T` are named by their id
+    pub fn dump(&self) -> Vec<(String, RType)> {
+        let mut tags: Vec<(u32, String, RType)> = self
+            .0
+            .iter()
+            .map(|(tag, value)| {
+                (
+                    tag.to_u32().unwrap_or_default(),
+                    tag.to_string(),
+                    value.clone(),
+                )
+            })
+            .chain(
+                self.1
+                    .iter()
+                    .map(|(id, value)| (*id, id.to_string(), value.clone())),
+            )
+            .collect();
+        tags.sort_by_key(|(id, _, _)| *id);
+        tags.into_iter()
+            .map(|(_, name, value)| (name, value))
+            .collect()
+    }
+}
+
+/// One `NAME (id): value` line per tag ordered by id, arrays are truncated
+impl<T> fmt::Display for Tags<T>
+where
+    T: ToPrimitive + Eq + Hash + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tags: Vec<(u32, String, &RType)> = self
+            .0
+            .iter()
+            .map(|(tag, value)| {
+                let id = tag.to_u32().unwrap_or_default();
+                (id, tag.to_string().to_uppercase(), value)
+            })
+            .chain(
+                self.1
+                    .iter()
+                    .map(|(id, value)| (*id, "UNKNOWN".to_owned(), value)),
+            )
+            .collect();
+        tags.sort_by_key(|(id, _, _)| *id);
+
+        for (id, name, value) in tags {
+            writeln!(f, "{} ({}): {}", name, id, display_value(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// Timestamp of an int32 tag such as BuildTime, rpm reads them unsigned up to 2106
+pub(crate) fn timestamp(time: i64) -> io::Result<u32> {
+    u32::try_from(time).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Error: timestamp {} does not fit an unsigned 32-bit value, \
+                 it must be between 1970 and 2106",
+                time
+            ),
+        )
+    })
+}
+
+/// Value on a single line, strings are escaped and arrays show `DISPLAY_ITEMS` elements
+fn display_value(value: &RType) -> String {
+    fn array<I: fmt::Debug>(items: &[I]) -> String {
+        let shown: Vec<String> = items
+            .iter()
+            .take(DISPLAY_ITEMS)
+            .map(|item| format!("{:?}", item))
+            .collect();
+        match items.len().checked_sub(DISPLAY_ITEMS) {
+            Some(rest) if rest > 0 => format!("[{}, ... {} more]", shown.join(", "), rest),
+            _ => format!("[{}]", shown.join(", ")),
+        }
+    }
+
+    match value {
+        RType::Null => "(null)".to_owned(),
+        RType::Char(c) => format!("{:?}", c),
+        RType::CharArray(a) => array(&a.iter().map(|&c| char::from(c)).collect::<Vec<_>>()),
+        RType::Int8(n) => n.to_string(),
+        RType::Int8Array(a) => array(a),
+        RType::Int16(n) => n.to_string(),
+        RType::Int16Array(a) => array(a),
+        RType::Int32(n) => n.to_string(),
+        RType::Int32Array(a) => array(a),
+        RType::Int64(n) => n.to_string(),
+        RType::Int64Array(a) => array(a),
+        RType::String(s) | RType::I18nstring(s) => format!("{:?}", s),
+        RType::Bin(b) if b.len() > DISPLAY_ITEMS * 4 => format!(
+            "{}... {} bytes",
+            hex::encode(&b[..DISPLAY_ITEMS * 4]),
+            b.len()
+        ),
+        RType::Bin(b) => hex::encode(b),
+        RType::StringArray(a) => array(a),
     }
 }
 
+/// Checks the region entry and its trailer at the end of the region as rpm does
+fn check_region<T: ToPrimitive + RegionTag>(
+    region: &Index<u32>,
+    nindex: usize,
+    data: &[u8],
+) -> Result<()> {
+    if region.itype != Type::Bin || region.count != REGION_SIZE {
+        return Err(RpmError::CorruptedHeader("an invalid region tag"));
+    }
+    let trailer =
+        data.get(region.offset..region.offset + REGION_SIZE)
+            .ok_or(RpmError::CorruptedHeader(
+                "a region trailer out of its data",
+            ))?;
+    let field = |i: usize| u32::from_be_bytes(trailer[i..i + 4].try_into().unwrap_or_default());
+
+    let region_id = T::REGION.to_u32();
+    // some old packages have the image tag in the trailer of the signature region
+    let tag = match field(0) {
+        61 if region_id == Some(62) => 62,
+        tag => tag,
+    };
+    if Some(tag) != region_id || field(4) != Type::Bin as u32 || field(12) != 16 {
+        return Err(RpmError::CorruptedHeader("an invalid region trailer"));
+    }
+    let size = (field(8) as i32).unsigned_abs() as usize;
+    if field(8) as i32 > 0 || !size.is_multiple_of(16) || size / 16 > nindex {
+        return Err(RpmError::CorruptedHeader("an invalid region size"));
+    }
+    Ok(())
+}
+
 impl Tags<Tag> {
+    /// Value of a required tag, converted by the function
+    pub fn try_get_as<O, F>(&self, name: Tag, expected: &'static str, convert: F) -> Result<O>
+    where
+        F: FnOnce(&RType) -> Option<O>,
+    {
+        let value = self.0.get(&name).ok_or(RpmError::MissingTag(name))?;
+        convert(value).ok_or_else(|| RpmError::WrongTagType {
+            tag: name,
+            expected,
+            actual: value.type_name(),
+        })
+    }
+
+    pub fn try_get_as_string(&self, name: Tag) -> Result<String> {
+        self.try_get_as(name, "string", RType::as_string)
+    }
+
+    pub fn try_get_as_u64(&self, name: Tag) -> Result<u64> {
+        self.try_get_as(name, "integer", RType::as_u64)
+    }
+
+    pub fn try_get_as_i64(&self, name: Tag) -> Result<i64> {
+        self.try_get_as(name, "integer", RType::as_i64)
+    }
+
+    pub fn try_get_as_u32(&self, name: Tag) -> Result<u32> {
+        self.try_get_as(name, "integer", RType::as_u32)
+    }
+
+    pub fn try_get_as_u16(&self, name: Tag) -> Result<u16> {
+        self.try_get_as(name, "integer", RType::as_u16)
+    }
+
+    pub fn try_get_as_u8(&self, name: Tag) -> Result<u8> {
+        self.try_get_as(name, "int8", RType::as_u8)
+    }
+
+    pub fn try_get_as_string_array(&self, name: Tag) -> Result<Vec<String>> {
+        self.try_get_as(name, "string array", RType::as_string_array)
+    }
+
+    pub fn try_get_as_u64_array(&self, name: Tag) -> Result<Vec<u64>> {
+        self.try_get_as(name, "integer array", RType::as_u64_array)
+    }
+
+    pub fn try_get_as_u32_array(&self, name: Tag) -> Result<Vec<u32>> {
+        self.try_get_as(name, "integer array", RType::as_u32_array)
+    }
+
+    pub fn try_get_as_u16_array(&self, name: Tag) -> Result<Vec<u16>> {
+        self.try_get_as(name, "integer array", RType::as_u16_array)
+    }
+
     pub fn insert_name(&mut self, name: String) -> &mut Self {
         self.insert(Tag::Name, RType::String(name))
     }
 
-    pub fn insert_epoch(&mut self, epoch: u8) -> &mut Self {
-        self.insert(Tag::Epoch, RType::Int8(epoch))
+    pub fn insert_epoch(&mut self, epoch: u32) -> &mut Self {
+        self.insert(Tag::Epoch, RType::Int32(epoch))
     }
 
     pub fn insert_version(&mut self, version: String) -> &mut Self {
         self.insert(Tag::Version, RType::String(version))
     }
 
+    pub fn insert_release(&mut self, release: String) -> &mut Self {
+        self.insert(Tag::Release, RType::String(release))
+    }
+
     pub fn insert_group(&mut self, group: String) -> &mut Self {
         self.insert(Tag::Group, RType::String(group))
     }
@@ -220,8 +574,21 @@ impl Tags<Tag> {
         self.insert(Tag::Arch, RType::String(arch))
     }
 
+    /// Installed size of the files, LongSize is used above 4 GiB,
+    /// a size inserted before in the other tag is removed
     pub fn insert_size(&mut self, size: u64) -> &mut Self {
-        self.insert(Tag::Size, RType::Int64(size))
+        match u32::try_from(size) {
+            Ok(size) => {
+                self.remove(Tag::LongSize);
+                self.insert(Tag::Size, RType::Int32(size))
+            }
+            Err(_) => self.insert_long_size(size),
+        }
+    }
+
+    pub fn insert_long_size(&mut self, size: u64) -> &mut Self {
+        self.remove(Tag::Size);
+        self.insert(Tag::LongSize, RType::Int64(size))
     }
 
     pub fn insert_license(&mut self, license: String) -> &mut Self {
@@ -256,9 +623,112 @@ impl Tags<Tag> {
         self.insert(Tag::SourceRpm, RType::String(source))
     }
 
-    pub fn insert_build_time(&mut self, time: i64) -> &mut Self {
-        let time_u64 = time.try_into().expect("Timestamp is out of u64");
-        self.insert(Tag::BuildTime, RType::Int64(time_u64))
+    /// BuildTime is int32 as rpm defines it, `timestamp` checks an i64 one
+    pub fn insert_build_time(&mut self, time: u32) -> &mut Self {
+        self.insert(Tag::BuildTime, RType::Int32(time))
+    }
+
+    pub fn insert_platform(&mut self, platform: String) -> &mut Self {
+        self.insert(Tag::Platform, RType::String(platform))
+    }
+
+    pub fn insert_os(&mut self, os: String) -> &mut Self {
+        self.insert(Tag::Os, RType::String(os))
+    }
+
+    pub fn insert_vendor(&mut self, vendor: String) -> &mut Self {
+        self.insert(Tag::Vendor, RType::String(vendor))
+    }
+
+    pub fn insert_url(&mut self, url: String) -> &mut Self {
+        self.insert(Tag::Url, RType::String(url))
+    }
+
+    pub fn insert_packager(&mut self, packager: String) -> &mut Self {
+        self.insert(Tag::Packager, RType::String(packager))
+    }
+
+    pub fn insert_distribution(&mut self, distribution: String) -> &mut Self {
+        self.insert(Tag::Distribution, RType::String(distribution))
+    }
+
+    pub fn insert_disturl(&mut self, url: String) -> &mut Self {
+        self.insert(Tag::DistUrl, RType::String(url))
+    }
+
+    /// Version of the tool that built the package
+    pub fn insert_rpm_version(&mut self, version: String) -> &mut Self {
+        self.insert(Tag::RpmVersion, RType::String(version))
+    }
+
+    pub fn insert_opt_flags(&mut self, flags: String) -> &mut Self {
+        self.insert(Tag::OptFlags, RType::String(flags))
+    }
+
+    pub fn insert_build_archs(&mut self, archs: Vec<String>) -> &mut Self {
+        self.insert(Tag::BuildArchs, RType::StringArray(archs))
+    }
+
+    /// Inserts hex encoded IMA signatures, one per file, empty for unsigned files
+    pub fn insert_file_signatures(&mut self, signatures: Vec<String>) -> &mut Self {
+        let length = signatures.iter().map(|s| s.len() as u32 / 2).max();
+        self.insert(Tag::FileSignatures, RType::StringArray(signatures))
+            .insert(
+                Tag::FileSignatureLength,
+                RType::Int32(length.unwrap_or_default()),
+            )
+    }
+
+    /// Inserts file arrays, members of a hardlink group share the device and inode
+    pub fn insert_files(&mut self, files: &[FileInfo]) -> &mut Self {
+        let mut dirs: Vec<String> = Vec::new();
+        let mut dir_positions: HashMap<&str, u32> = HashMap::new();
+        let mut dir_indexes = Vec::with_capacity(files.len());
+        let mut basenames = Vec::with_capacity(files.len());
+
+        for file in files {
+            let split = file.name.rfind('/').map_or(0, |position| position + 1);
+            let (dir, basename) = file.name.split_at(split);
+            let index = *dir_positions.entry(dir).or_insert_with(|| {
+                dirs.push(dir.to_owned());
+                dirs.len() as u32 - 1
+            });
+            dir_indexes.push(index);
+            basenames.push(basename.to_owned());
+        }
+
+        let strings = |f: fn(&FileInfo) -> &String| -> RType {
+            RType::StringArray(files.iter().map(|file| f(file).clone()).collect())
+        };
+        let numbers =
+            |f: fn(&FileInfo) -> u32| -> RType { RType::Int32Array(files.iter().map(f).collect()) };
+
+        // rpm uses LongFileSizes instead of FileSizes if any file is over 4 GiB
+        if files.iter().any(|file| u32::try_from(file.size).is_err()) {
+            self.insert(
+                Tag::LongFileSizes,
+                RType::Int64Array(files.iter().map(|file| file.size).collect()),
+            );
+        } else {
+            self.insert(Tag::FileSizes, numbers(|file| file.size as u32));
+        }
+
+        self.insert(Tag::BaseNames, RType::StringArray(basenames))
+            .insert(Tag::DirNames, RType::StringArray(dirs))
+            .insert(Tag::DirIndexes, RType::Int32Array(dir_indexes))
+            .insert(
+                Tag::FileModes,
+                RType::Int16Array(files.iter().map(|file| file.mode).collect()),
+            )
+            .insert(Tag::FileMTimes, numbers(|file| file.mtime))
+            .insert(Tag::FileFlags, numbers(|file| file.flags))
+            .insert(Tag::FileUserName, strings(|file| &file.user))
+            .insert(Tag::FileGroupName, strings(|file| &file.group))
+            .insert(Tag::FileMD5s, strings(|file| &file.digest))
+            .insert(Tag::FileLinktos, strings(|file| &file.linkname))
+            .insert(Tag::FileDevices, numbers(|file| file.device))
+            .insert(Tag::FileInodes, numbers(|file| file.inode))
+            .insert(Tag::FileNLinks, numbers(|file| file.nlink))
     }
 
     pub fn insert_pre_install(&mut self, script: String) -> &mut Self {
@@ -276,164 +746,676 @@ impl Tags<Tag> {
     pub fn insert_post_uninstall(&mut self, script: String) -> &mut Self {
         self.insert(Tag::PostUn, RType::String(script))
     }
+
+    pub fn insert_pre_trans(&mut self, script: String) -> &mut Self {
+        self.insert(Tag::PreTrans, RType::String(script))
+    }
+
+    pub fn insert_post_trans(&mut self, script: String) -> &mut Self {
+        self.insert(Tag::PostTrans, RType::String(script))
+    }
+
+    /// Interpreter of the pretrans script with its arguments, e.g. `["/bin/bash", "-e"]`
+    pub fn insert_pre_trans_prog(&mut self, prog: Vec<String>) -> &mut Self {
+        self.insert(Tag::PreTransProg, script_prog(prog))
+    }
+
+    pub fn insert_post_trans_prog(&mut self, prog: Vec<String>) -> &mut Self {
+        self.insert(Tag::PostTransProg, script_prog(prog))
+    }
+
+    pub fn insert_verify_script(&mut self, script: String) -> &mut Self {
+        self.insert(Tag::VerifyScript, RType::String(script))
+    }
+
+    pub fn insert_verify_script_prog(&mut self, prog: Vec<String>) -> &mut Self {
+        self.insert(Tag::VerifyScriptProg, script_prog(prog))
+    }
+
+    pub fn insert_verify_script_flags(&mut self, flags: u32) -> &mut Self {
+        self.insert(Tag::VerifyScriptFlags, RType::Int32(flags))
+    }
+}
+
+/// rpmbuild stores an interpreter without arguments as a string
+fn script_prog(mut prog: Vec<String>) -> RType {
+    if prog.len() == 1 {
+        RType::String(prog.remove(0))
+    } else {
+        RType::StringArray(prog)
+    }
 }
 
 impl Tags<SignatureTag> {
+    /// Size of the uncompressed payload, LongArchiveSize is used above 4 GiB
     pub fn insert_payload_size(&mut self, size: u64) -> &mut Self {
-        self.insert(SignatureTag::PayloadSize, RType::Int64(size))
+        match u32::try_from(size) {
+            Ok(size) => {
+                self.remove(SignatureTag::LongArchiveSize);
+                self.insert(SignatureTag::PayloadSize, RType::Int32(size))
+            }
+            Err(_) => {
+                self.remove(SignatureTag::PayloadSize);
+                self.insert(SignatureTag::LongArchiveSize, RType::Int64(size))
+            }
+        }
+    }
+
+    /// Size of the main header and the compressed payload, LongSigSize is used above 4 GiB
+    pub fn insert_size(&mut self, size: u64) -> &mut Self {
+        match u32::try_from(size) {
+            Ok(size) => {
+                self.remove(SignatureTag::LongSigSize);
+                self.insert(SignatureTag::Size, RType::Int32(size))
+            }
+            Err(_) => {
+                self.remove(SignatureTag::Size);
+                self.insert(SignatureTag::LongSigSize, RType::Int64(size))
+            }
+        }
+    }
+
+    /// Digests of the serialized main header and MD5 of the header with the payload
+    pub fn insert_digests(&mut self, header: &[u8], payload: &[u8]) -> &mut Self {
+        let mut md5 = Md5::new();
+        md5.update(header);
+        md5.update(payload);
+        self.insert(
+            SignatureTag::SHA1Header,
+            RType::String(DigestAlgo::Sha1.digest_bytes(header)),
+        )
+        .insert(
+            SignatureTag::SHA256Header,
+            RType::String(DigestAlgo::Sha256.digest_bytes(header)),
+        )
+        .insert(SignatureTag::MD5, RType::Bin(md5.finalize().to_vec()))
     }
 }
 
 pub trait TagsWrite {
-    fn write_header<T: ToPrimitive + Eq + Hash + Copy>(&mut self, tags: &Tags<T>)
-        -> io::Result<()>;
+    /// Writes the header with its immutable region, the header is not padded
+    fn write_header<T: ToPrimitive + Eq + Hash + Copy + RegionTag>(
+        &mut self,
+        tags: &Tags<T>,
+    ) -> io::Result<()>;
 }
 
 impl<W> TagsWrite for W
 where
     W: Write,
 {
-    fn write_header<T: ToPrimitive + Eq + Hash + Copy>(
+    fn write_header<T: ToPrimitive + Eq + Hash + Copy + RegionTag>(
         &mut self,
         tags: &Tags<T>,
     ) -> io::Result<()> {
-        let mut address: Vec<u8> = Vec::new();
-        let mut data: Vec<u8> = Vec::new();
-        let index = tags.0.len();
-
-        for (tag, value) in &tags.0 {
-            let current = data.len();
-            match value {
-                RType::Null => {
-                    let index = Index::from(tag, value, 0, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::Char(c) => {
-                    data.write_be(*c as u32)?;
-                    let index = Index::from(tag, value, current, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::Int8(i) => {
-                    data.write_be(*i)?;
-                    let index = Index::from(tag, value, current, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::Int16(i) => {
-                    data.write_be(*i)?;
-                    let index = Index::from(tag, value, current, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::Int32(i) => {
-                    data.write_be(*i)?;
-                    let index = Index::from(tag, value, current, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::Int64(i) => {
-                    data.write_be(*i)?;
-                    let index = Index::from(tag, value, current, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::String(s) => {
-                    data.write_all(s.as_bytes())?;
-                    data.write_be(0_u8)?;
-                    let index = Index::from(tag, value, current, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::Bin(b) => {
-                    data.write_all(b)?;
-                    let index = Index::from(tag, value, current, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::StringArray(vector) => {
-                    let index = Index::from(tag, value, current, vector.len());
-                    address.write_index(index)?;
-                    for s in vector {
-                        data.write_all(s.as_bytes())?;
-                        data.write_be(0_u8)?;
-                    }
-                }
-
-                RType::I18nstring(s) => {
-                    data.write_all(s.as_bytes())?;
-                    data.write_be(0_u8)?;
-                    let index = Index::from(tag, value, current, 1);
-                    address.write_index(index)?;
-                }
-
-                RType::Int8Array(vector) => {
-                    let index = Index::from(tag, value, current, vector.len());
-                    address.write_index(index)?;
-                    for value in vector {
-                        data.write_be(*value)?;
-                    }
-                }
+        self.write_all(&header_bytes(tags)?)
+    }
+}
 
-                RType::Int16Array(vector) => {
-                    let index = Index::from(tag, value, current, vector.len());
-                    address.write_index(index)?;
-                    for value in vector {
-                        data.write_be(*value)?;
-                    }
-                }
+/// Region tags are generated on writing, values read from a package are dropped
+const REGION_TAGS: [u32; 4] = [61, 62, 63, 64];
+const REGION_SIZE: usize = 16;
+
+/// Header lead, index and data without padding. Entries are sorted by tag
+/// and numbers are aligned to their size, as rpm expects. The region entry
+/// comes first and covers the whole header, its trailer ends the data.
+pub fn header_bytes<T: ToPrimitive + Eq + Hash + Copy + RegionTag>(
+    tags: &Tags<T>,
+) -> io::Result<Vec<u8>> {
+    let tag_id = |tag: &T| {
+        tag.to_u32()
+            .ok_or_else(|| io::Error::other("Error: tag id is not correct"))
+    };
+    let mut entries = Vec::with_capacity(tags.0.len() + tags.1.len());
+    for (tag, value) in &tags.0 {
+        entries.push((tag_id(tag)?, value));
+    }
+    entries.extend(tags.1.iter().map(|(id, value)| (*id, value)));
+    entries.retain(|(id, _)| !REGION_TAGS.contains(id));
+    entries.sort_by_key(|(id, _)| *id);
+
+    let region = tag_id(&T::REGION)?;
+    let nindex = entries.len() + 1;
+    let mut address: Vec<u8> = Vec::new();
+    let mut data: Vec<u8> = Vec::new();
+
+    for (id, value) in &entries {
+        let padding = align_n_bytes(data.len() as u32, value.alignment());
+        data.resize(data.len() + padding as usize, 0);
+        let offset = data.len();
+        let count = write_value(&mut data, value)?;
+        address.write_index(Index::from(id, value, offset, count))?;
+    }
 
-                RType::Int32Array(vector) => {
-                    let index = Index::from(tag, value, current, vector.len());
-                    address.write_index(index)?;
-                    for value in vector {
-                        data.write_be(*value)?;
-                    }
-                }
+    // the trailer points back to the start of the index
+    let offset = data.len();
+    data.write_index(Index {
+        tag: region,
+        itype: Type::Bin,
+        offset: (-((nindex * 16) as i32)) as u32 as usize,
+        count: REGION_SIZE,
+    })?;
+
+    let mut bytes = Vec::new();
+    HeaderLead::from(nindex, data.len() as u32).write(&mut bytes)?;
+    bytes.write_index(Index {
+        tag: region,
+        itype: Type::Bin,
+        offset,
+        count: REGION_SIZE,
+    })?;
+    bytes.write_all(&address)?;
+    bytes.write_all(&data)?;
+    Ok(bytes)
+}
 
-                RType::Int64Array(vector) => {
-                    let index = Index::from(tag, value, current, vector.len());
-                    address.write_index(index)?;
-                    for value in vector {
-                        data.write_be(*value)?;
-                    }
-                }
+/// Appends the value to the header data, returns the count of the index entry
+fn write_value(data: &mut Vec<u8>, value: &RType) -> io::Result<usize> {
+    let count = match value {
+        RType::Null => 1,
+        RType::Char(c) => {
+            let c = u8::try_from(*c as u32).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Error: char {:?} is not a single byte", c),
+                )
+            })?;
+            data.write_be(c)?;
+            1
+        }
+        RType::Int8(i) => {
+            data.write_be(*i)?;
+            1
+        }
+        RType::Int16(i) => {
+            data.write_be(*i)?;
+            1
+        }
+        RType::Int32(i) => {
+            data.write_be(*i)?;
+            1
+        }
+        RType::Int64(i) => {
+            data.write_be(*i)?;
+            1
+        }
+        RType::String(s) | RType::I18nstring(s) => {
+            data.write_all(s.as_bytes())?;
+            data.write_be(0_u8)?;
+            1
+        }
+        RType::Bin(b) => {
+            data.write_all(b)?;
+            b.len()
+        }
+        RType::StringArray(vector) => {
+            for s in vector {
+                data.write_all(s.as_bytes())?;
+                data.write_be(0_u8)?;
             }
+            vector.len()
         }
+        RType::Int8Array(vector) | RType::CharArray(vector) => {
+            for value in vector {
+                data.write_be(*value)?;
+            }
+            vector.len()
+        }
+        RType::Int16Array(vector) => {
+            for value in vector {
+                data.write_be(*value)?;
+            }
+            vector.len()
+        }
+        RType::Int32Array(vector) => {
+            for value in vector {
+                data.write_be(*value)?;
+            }
+            vector.len()
+        }
+        RType::Int64Array(vector) => {
+            for value in vector {
+                data.write_be(*value)?;
+            }
+            vector.len()
+        }
+    };
+    Ok(count)
+}
 
-        let size = data.len() as u32;
-        let lead = HeaderLead::from(index, size);
-
-        lead.write(self)?;
-        self.write_all(&address)?;
-        self.write_all(&data)?;
-
-        // aligning to 8 bytes
-        let number = align_n_bytes(size, 8) as usize;
-        let pad = vec![0_u8; number];
-        self.write_all(&pad)?;
-
-        Ok(())
-    }
+/// End of `count` numbers starting at the position, if it does not overflow
+fn checked_end<T>(position: usize, count: usize) -> Option<usize> {
+    count
+        .max(1)
+        .checked_mul(size_of::<T>())
+        .and_then(|size| position.checked_add(size))
 }
 
+/// Numbers of the entry, the data is already checked to hold all of them
 fn extract<T: ReadBytes>(
     data: &[u8],
-    position: usize,
     count: usize,
     single: fn(T) -> RType,
     multiple: fn(Vec<T>) -> RType,
 ) -> io::Result<RType> {
     if count > 1 {
-        let values = (0..count)
-            .map(|i| {
-                let pos = position + i * size_of::<T>();
-                (&data[pos..]).read_be()
-            })
+        let values = data
+            .chunks_exact(size_of::<T>())
+            .map(|mut chunk| chunk.read_be())
             .collect::<io::Result<Vec<T>>>()?;
         Ok(multiple(values))
     } else {
-        Ok(single((&data[position..]).read_be()?))
+        Ok(single((&data[..]).read_be()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn read_tags<T>(bytes: &[u8]) -> Result<Tags<T>>
+    where
+        T: FromPrimitive + ToPrimitive + Default + Eq + Hash + Copy + RegionTag,
+    {
+        let mut reader = Cursor::new(bytes);
+        let lead = HeaderLead::read(&mut reader)?;
+        let indexes = IndexArray::read(&mut reader, lead.nindex)?;
+        Tags::read(&mut reader, &indexes, lead.hsize as usize)
+    }
+
+    #[test]
+    fn test_try_get() -> Result<()> {
+        let mut tags = Tags::<Tag>::new();
+        tags.insert_name("hello".to_owned())
+            .insert(Tag::Size, RType::Int32(75))
+            .insert(Tag::Summary, RType::Int32Array(vec![1, 2]))
+            .insert(Tag::Epoch, RType::String("1".to_owned()))
+            .insert(Tag::BaseNames, RType::StringArray(vec!["a".to_owned()]))
+            .insert(Tag::DirNames, RType::String("/".to_owned()))
+            .insert(Tag::FileSizes, RType::Int64Array(vec![1 << 40]));
+
+        assert_eq!(tags.try_get_as_string(Tag::Name)?, "hello");
+        assert_eq!(tags.try_get_as_u32(Tag::Size)?, 75);
+        assert_eq!(tags.try_get_as_string_array(Tag::BaseNames)?, vec!["a"]);
+
+        let missing = |err| matches!(err, RpmError::MissingTag(_));
+        assert!(missing(tags.try_get_as_string(Tag::Version).unwrap_err()));
+        assert!(missing(tags.try_get_as_u32(Tag::Release).unwrap_err()));
+        assert!(missing(
+            tags.try_get_as_string_array(Tag::DirIndexes).unwrap_err()
+        ));
+
+        let err = tags.try_get_as_string(Tag::Summary).unwrap_err();
+        assert!(matches!(
+            err,
+            RpmError::WrongTagType {
+                tag: Tag::Summary,
+                expected: "string",
+                actual: "int32 array"
+            }
+        ));
+        let err = tags.try_get_as_u32(Tag::Epoch).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error: tag Epoch is string, integer is expected"
+        );
+        let err = tags.try_get_as_string_array(Tag::DirNames).unwrap_err();
+        assert!(matches!(
+            err,
+            RpmError::WrongTagType {
+                tag: Tag::DirNames,
+                expected: "string array",
+                actual: "string"
+            }
+        ));
+        let err = tags.try_get_as_u32_array(Tag::FileSizes).unwrap_err();
+        assert!(matches!(
+            err,
+            RpmError::WrongTagType {
+                actual: "int64 array",
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_region() -> Result<()> {
+        let mut tags = Tags::<Tag>::new();
+        tags.insert_name("hello".to_owned())
+            .insert_epoch(1)
+            .insert_size(75)
+            .insert(Tag::Immutable, RType::Bin(vec![0; 16]));
+
+        let bytes = header_bytes(&tags)?;
+        // the region entry comes first and its trailer ends the data
+        let field = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+        assert_eq!((field(8), field(16), field(20), field(28)), (4, 63, 7, 16));
+        let trailer = bytes.len() - 16;
+        assert_eq!(field(24) as usize, trailer - 16 - 4 * 16);
+        assert_eq!(field(trailer + 8) as i32, -4 * 16);
+
+        let read: Tags<Tag> = read_tags(&bytes)?;
+        assert_eq!(read.0.len(), 3);
+        assert!(read.get_value(Tag::Immutable).is_none());
+        assert_eq!(read.try_get_as_string(Tag::Name)?, "hello");
+        assert_eq!(read.try_get_as_u64(Tag::Size)?, 75);
+
+        let mut corrupted = bytes.clone();
+        corrupted[trailer + 11] = 0x10;
+        assert!(matches!(
+            read_tags::<Tag>(&corrupted),
+            Err(RpmError::CorruptedHeader(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_iter_display() -> Result<()> {
+        let mut tags = Tags::<Tag>::new();
+        tags.insert(Tag::Description, RType::String("two\nlines".to_owned()))
+            .insert(Tag::FileSizes, RType::Int32Array((1..=10).collect()))
+            .insert_name("hello".to_owned());
+        tags.1.insert(9001, RType::Bin(vec![0xab, 0xcd]));
+
+        assert_eq!(tags.len(), 4);
+        assert!(tags.contains(Tag::Name));
+        assert!(!tags.contains(Tag::Version));
+        assert_eq!(
+            tags.get_ref(Tag::Name),
+            Some(&RType::String("hello".to_owned()))
+        );
+        assert_eq!(tags.get_ref(Tag::Version), None);
+        assert_eq!(
+            tags.keys().copied().collect::<Vec<_>>(),
+            vec![Tag::Name, Tag::Description, Tag::FileSizes]
+        );
+        assert_eq!(
+            tags.iter().next(),
+            Some((&Tag::Name, &RType::String("hello".to_owned())))
+        );
+
+        assert_eq!(
+            tags.to_string(),
+            "NAME (1000): \"hello\"\n\
+             DESCRIPTION (1005): \"two\\nlines\"\n\
+             FILESIZES (1028): [1, 2, 3, 4, 5, 6, 7, 8, ... 2 more]\n\
+             UNKNOWN (9001): abcd\n"
+        );
+
+        let read: Tags<Tag> = read_tags(&header_bytes(&tags)?)?;
+        assert_eq!(read, tags);
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_alignment() -> Result<()> {
+        let mut tags = Tags::<Tag>::new();
+        tags.insert_name("ab".to_owned())
+            .insert(Tag::BuildTime, RType::Int64(1_700_000_000))
+            .insert(Tag::Size, RType::Int32(75))
+            .insert(Tag::Group, RType::String("group".to_owned()))
+            .insert(Tag::FileModes, RType::Int16Array(vec![0o100644, 0o40755]))
+            .insert(Tag::FileSizes, RType::Int32Array(vec![6, 0]));
+
+        let bytes = header_bytes(&tags)?;
+        let mut reader = Cursor::new(&bytes);
+        let lead = HeaderLead::read(&mut reader)?;
+        let indexes: Vec<Index<Tag>> = IndexArray::read(&mut reader, lead.nindex)?;
+        for index in &indexes {
+            let alignment = match index.itype {
+                Type::Int16 => 2,
+                Type::Int32 => 4,
+                Type::Int64 => 8,
+                _ => 1,
+            };
+            assert_eq!(index.offset % alignment, 0, "{:?}", index);
+        }
+        // the padding is a part of the data
+        let data_start = 16 + indexes.len() * 16;
+        assert_eq!(lead.hsize as usize, bytes.len() - data_start);
+
+        let read: Tags<Tag> = read_tags(&bytes)?;
+        assert_eq!(read.try_get_as_string(Tag::Name)?, "ab");
+        assert_eq!(read.try_get_as_u64(Tag::BuildTime)?, 1_700_000_000);
+        assert_eq!(read.try_get_as_u32(Tag::Size)?, 75);
+        assert_eq!(read.try_get_as_string(Tag::Group)?, "group");
+        assert_eq!(
+            read.get_as_u16_array_or(Tag::FileModes),
+            vec![0o100644, 0o40755]
+        );
+        assert_eq!(read.get_as_u32_array_or(Tag::FileSizes), vec![6, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_long_sizes() {
+        // the builder writes a zero size first and the sum of the files later
+        let mut tags = Tags::<Tag>::new();
+        tags.insert_size(0).insert_size(5 << 30);
+        assert!(tags.get_value(Tag::Size).is_none());
+        assert_eq!(tags.get_value(Tag::LongSize), Some(RType::Int64(5 << 30)));
+        tags.insert_size(75);
+        assert!(tags.get_value(Tag::LongSize).is_none());
+        assert_eq!(tags.get_value(Tag::Size), Some(RType::Int32(75)));
+
+        let mut signature = Tags::<SignatureTag>::new();
+        signature
+            .insert_size(0)
+            .insert_payload_size(0)
+            .insert_size(6 << 30)
+            .insert_payload_size(7 << 30);
+        assert!(signature.get_value(SignatureTag::Size).is_none());
+        assert!(signature.get_value(SignatureTag::PayloadSize).is_none());
+        assert_eq!(
+            signature.get_value(SignatureTag::LongSigSize),
+            Some(RType::Int64(6 << 30))
+        );
+        assert_eq!(
+            signature.get_value(SignatureTag::LongArchiveSize),
+            Some(RType::Int64(7 << 30))
+        );
+    }
+
+    #[test]
+    fn test_header_counts() -> Result<()> {
+        let md5 = (0..16).collect::<Vec<u8>>();
+        let mut tags = Tags::<SignatureTag>::new();
+        tags.insert(SignatureTag::MD5, RType::Bin(md5.clone()))
+            .insert(
+                SignatureTag::SHA1Header,
+                RType::String("da39a3ee".to_owned()),
+            )
+            .insert_payload_size(1024);
+
+        let bytes = header_bytes(&tags)?;
+        let mut reader = Cursor::new(&bytes);
+        let lead = HeaderLead::read(&mut reader)?;
+        let indexes: Vec<Index<SignatureTag>> = IndexArray::read(&mut reader, lead.nindex)?;
+        let md5_index = indexes
+            .iter()
+            .find(|index| index.tag == SignatureTag::MD5)
+            .unwrap();
+        assert_eq!((md5_index.itype.clone(), md5_index.count), (Type::Bin, 16));
+
+        let read: Tags<SignatureTag> = read_tags(&bytes)?;
+        assert_eq!(read.get_value(SignatureTag::MD5), Some(RType::Bin(md5)));
+        assert_eq!(read.get_as_string_or(SignatureTag::SHA1Header), "da39a3ee");
+        assert_eq!(
+            read.get_value(SignatureTag::PayloadSize)
+                .and_then(|size| size.as_u64()),
+            Some(1024)
+        );
+
+        let mut tags = Tags::<Tag>::new();
+        tags.insert(Tag::Summary, RType::I18nstring("Greeting".to_owned()))
+            .insert(
+                Tag::BaseNames,
+                RType::StringArray(vec!["hello".to_owned(), "hi".to_owned()]),
+            );
+        let read: Tags<Tag> = read_tags(&header_bytes(&tags)?)?;
+        assert_eq!(
+            read.get_value(Tag::Summary),
+            Some(RType::I18nstring("Greeting".to_owned()))
+        );
+        assert_eq!(
+            read.get_as_string_array_or(Tag::BaseNames),
+            vec!["hello".to_owned(), "hi".to_owned()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_order() -> Result<()> {
+        let entries = vec![
+            (Tag::Version, RType::String("1.0".to_owned())),
+            (Tag::Name, RType::String("hello".to_owned())),
+            (Tag::BuildTime, RType::Int64(1_700_000_000)),
+            (Tag::Arch, RType::String("noarch".to_owned())),
+            (Tag::Size, RType::Int32(75)),
+            (Tag::Release, RType::String("1".to_owned())),
+        ];
+        let mut tags = Tags::<Tag>::new();
+        for (tag, value) in entries.iter().cloned() {
+            tags.insert(tag, value);
+        }
+        let mut reversed = Tags::<Tag>::new();
+        for (tag, value) in entries.into_iter().rev() {
+            reversed.insert(tag, value);
+        }
+        let bytes = header_bytes(&tags)?;
+        assert_eq!(bytes, header_bytes(&tags)?);
+        assert_eq!(bytes, header_bytes(&reversed)?);
+
+        // tag ids ascend and the data follows the same order, the region trailer ends it
+        let field = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+        let nindex = field(8) as usize;
+        let ids: Vec<u32> = (0..nindex).map(|i| field(16 + i * 16)).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", ids);
+        let offsets: Vec<u32> = (1..nindex).map(|i| field(24 + i * 16)).collect();
+        assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(field(24) > offsets[nindex - 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_tags() -> Result<()> {
+        let mut tags = Tags::<Tag>::new();
+        tags.insert_name("hello".to_owned());
+        tags.1.insert(9001, RType::String("vendor".to_owned()));
+        tags.1.insert(9002, RType::Int32Array(vec![1, 2]));
+
+        let bytes = header_bytes(&tags)?;
+        let read: Tags<Tag> = read_tags(&bytes)?;
+        assert_eq!(read.0.len(), 1);
+        assert!(read.get_value(Tag::Other).is_none());
+        assert_eq!(
+            read.get_by_id(9001),
+            Some(RType::String("vendor".to_owned()))
+        );
+        assert_eq!(read.get_by_id(9002), Some(RType::Int32Array(vec![1, 2])));
+        assert_eq!(
+            read.get_by_id(1000),
+            Some(RType::String("hello".to_owned()))
+        );
+        assert_eq!(header_bytes(&read)?, bytes);
+        Ok(())
+    }
+
+    /// Header with the raw index entries: tag, type, offset and count
+    fn raw_header(entries: &[[u32; 4]], data: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC_HEADER.to_vec();
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        for entry in entries {
+            for field in entry {
+                bytes.extend_from_slice(&field.to_be_bytes());
+            }
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn test_malformed_headers() -> Result<()> {
+        let cases: Vec<Vec<u8>> = vec![
+            // string offset beyond the data
+            raw_header(&[[1000, 6, 50, 1]], b"abc\0"),
+            // array longer than the data
+            raw_header(&[[1028, 4, 0, 0x4000_0000]], &[0; 8]),
+            raw_header(&[[1028, 4, 4, 2]], &[0; 8]),
+            // binary value longer than the data
+            raw_header(&[[1004, 7, 0, 100]], &[0; 4]),
+            raw_header(&[[1004, 7, 0xffff_fff0, 0x20]], &[0; 4]),
+            // the next entry starts beyond the data
+            raw_header(&[[1000, 6, 0, 1], [1001, 6, 60, 1]], b"abc\0"),
+            raw_header(&[[1000, 2, 4, 1]], &[0; 4]),
+        ];
+        for bytes in &cases {
+            let err = read_tags::<Tag>(bytes).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", err);
+        }
+
+        // the last string ends with the data
+        let read: Tags<Tag> = read_tags(&raw_header(&[[1000, 6, 0, 1]], b"abc\0"))?;
+        assert_eq!(read.try_get_as_string(Tag::Name)?, "abc");
+
+        let mut huge = raw_header(&[], &[]);
+        huge[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            read_tags::<Tag>(&huge),
+            Err(RpmError::HeaderLimit { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_packages() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/hello-1.0-1.noarch.rpm");
+        let bytes = std::fs::read(path).unwrap();
+        for length in 0..600 {
+            let truncated = bytes[..length].to_vec();
+            assert!(crate::RPMFile::read(Cursor::new(truncated)).is_err());
+        }
+        // offsets and counts of the signature and main header entries
+        let header = 96 + 16 + 16 * 8 + 256;
+        for position in (96 + 16..header).step_by(4) {
+            let mut corrupted = bytes.clone();
+            corrupted[position..position + 4].copy_from_slice(&[0xff, 0xff, 0xff, 0xf0]);
+            let _ = crate::RPMFile::read(Cursor::new(corrupted));
+        }
+    }
+
+    #[test]
+    fn test_char_tags() -> Result<()> {
+        // header of an installed package: the file states are bytes, the name follows them
+        let bytes = raw_header(
+            &[[1000, 6, 0, 1], [1029, 1, 6, 3]],
+            &[b'h', b'e', b'l', b'l', b'o', 0, 0, 1, 2],
+        );
+        let read: Tags<Tag> = read_tags(&bytes)?;
+        assert_eq!(read.try_get_as_string(Tag::Name)?, "hello");
+        assert_eq!(
+            read.get_value(Tag::FilesStates),
+            Some(RType::CharArray(vec![0, 1, 2]))
+        );
+
+        let written: Tags<Tag> = read_tags(&header_bytes(&read)?)?;
+        assert_eq!(written.0, read.0);
+
+        let single: Tags<Tag> = read_tags(&raw_header(&[[1029, 1, 0, 1]], &[3]))?;
+        assert_eq!(
+            single.get_value(Tag::FilesStates),
+            Some(RType::Char('\u{3}'))
+        );
+        assert_eq!(RType::Char('\u{3}').data_size(), 1);
+
+        let mut tags = Tags::<Tag>::new();
+        tags.insert(Tag::FilesStates, RType::Char('\u{100}'));
+        assert!(header_bytes(&tags).is_err());
+        Ok(())
     }
 }
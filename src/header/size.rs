@@ -0,0 +1,167 @@
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::hash::Hash;
+
+use super::{RType, Tags, REGION_SIZE, REGION_TAGS};
+use crate::utils::align_n_bytes;
+
+/// Size of an index entry: tag, type, offset and count
+pub const INDEX_ENTRY_SIZE: usize = 16;
+
+/// Bytes taken by a tag in the data area, without alignment padding,
+/// `tag` is `None` for ids unknown to `T`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSize<T> {
+    pub id: u32,
+    pub tag: Option<T>,
+    pub count: usize,
+    pub size: usize,
+}
+
+/// Composition of a header, tags are ordered from the largest.
+/// Sizes include the region entry, its trailer and the alignment padding,
+/// so the total is the size of the serialized header without its lead
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeReport<T> {
+    pub tags: Vec<TagSize<T>>,
+    pub index_size: usize,
+    pub data_size: usize,
+    pub padding: usize,
+}
+
+impl<T> SizeReport<T> {
+    pub fn total(&self) -> usize {
+        self.index_size + self.data_size
+    }
+
+    /// Share of the tag in the whole header, in percents
+    pub fn percent(&self, size: usize) -> f64 {
+        if self.total() == 0 {
+            return 0.0;
+        }
+        size as f64 * 100.0 / self.total() as f64
+    }
+}
+
+impl RType {
+    /// Number of elements as written to the index
+    pub fn count(&self) -> usize {
+        match self {
+            RType::Int8Array(v) | RType::CharArray(v) => v.len(),
+            RType::Int16Array(v) => v.len(),
+            RType::Int32Array(v) => v.len(),
+            RType::Int64Array(v) => v.len(),
+            RType::StringArray(v) => v.len(),
+            RType::Bin(v) => v.len(),
+            _ => 1,
+        }
+    }
+
+    /// Serialized size of the value, strings are terminated with NUL
+    pub fn data_size(&self) -> usize {
+        match self {
+            RType::Null => 0,
+            RType::Char(_) | RType::Int8(_) => 1,
+            RType::Int16(_) => 2,
+            RType::Int32(_) => 4,
+            RType::Int64(_) => 8,
+            RType::Int8Array(v) | RType::CharArray(v) => v.len(),
+            RType::Int16Array(v) => v.len() * 2,
+            RType::Int32Array(v) => v.len() * 4,
+            RType::Int64Array(v) => v.len() * 8,
+            RType::String(s) | RType::I18nstring(s) => s.len() + 1,
+            RType::Bin(v) => v.len(),
+            RType::StringArray(v) => v.iter().map(|s| s.len() + 1).sum(),
+        }
+    }
+}
+
+impl<T> Tags<T>
+where
+    T: FromPrimitive + ToPrimitive + Default + Eq + Hash + Copy,
+{
+    /// Per tag sizes computed from the parsed values, the header is not serialized.
+    /// Region tags are skipped as `header_bytes` generates them
+    pub fn size_report(&self) -> SizeReport<T> {
+        let mut entries: Vec<(u32, Option<T>, &RType)> = self
+            .0
+            .iter()
+            .filter_map(|(tag, value)| Some((tag.to_u32()?, Some(*tag), value)))
+            .chain(self.1.iter().map(|(id, value)| (*id, None, value)))
+            .filter(|(id, _, _)| !REGION_TAGS.contains(id))
+            .collect();
+        // padding depends on the offset, so values are laid out in the written order
+        entries.sort_by_key(|(id, _, _)| *id);
+
+        let mut offset = 0;
+        let mut padding = 0;
+        let mut tags = Vec::with_capacity(entries.len());
+        for (id, tag, value) in entries {
+            let align = align_n_bytes(offset as u32, value.alignment()) as usize;
+            let size = value.data_size();
+            padding += align;
+            offset += align + size;
+            tags.push(TagSize {
+                id,
+                tag,
+                count: value.count(),
+                size,
+            });
+        }
+        tags.sort_by_key(|t| (std::cmp::Reverse(t.size), t.id));
+
+        SizeReport {
+            index_size: (tags.len() + 1) * INDEX_ENTRY_SIZE,
+            data_size: offset + REGION_SIZE,
+            padding,
+            tags,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{header_bytes, Tag};
+
+    #[test]
+    fn test_size_report() {
+        let mut tags = Tags::<Tag>::new();
+        tags.insert(Tag::Name, RType::String("hello".to_owned()))
+            .insert(Tag::DirIndexes, RType::Int32Array(vec![0, 0, 1]))
+            .insert(
+                Tag::BaseNames,
+                RType::StringArray(vec!["a".to_owned(), "bc".to_owned()]),
+            )
+            .insert(Tag::FileModes, RType::Int16Array(vec![0o644, 0o755]))
+            .insert(Tag::Epoch, RType::Int32(1))
+            .insert_by_id(9001, RType::Int64(7));
+
+        let report = tags.size_report();
+        let sizes: Vec<(u32, Option<Tag>, usize, usize)> = report
+            .tags
+            .iter()
+            .map(|t| (t.id, t.tag, t.count, t.size))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                (1116, Some(Tag::DirIndexes), 3, 12),
+                (9001, None, 1, 8),
+                (1000, Some(Tag::Name), 1, 6),
+                (1117, Some(Tag::BaseNames), 2, 5),
+                (1003, Some(Tag::Epoch), 1, 4),
+                (1030, Some(Tag::FileModes), 2, 4),
+            ]
+        );
+        // the region entry and the trailer, the Epoch and the Int64 are aligned
+        assert_eq!(report.index_size, 112);
+        assert_eq!(report.padding, 9);
+        assert_eq!(report.data_size, 64);
+        assert_eq!(report.total(), 176);
+        assert!((report.percent(12) - 6.82).abs() < 0.01);
+
+        // the lead of the header is not counted
+        let bytes = header_bytes(&tags).unwrap();
+        assert_eq!(report.total(), bytes.len() - 16);
+    }
+}
@@ -1,4 +1,8 @@
 use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
 use strum_macros::Display;
 
 #[derive(Debug, Copy, Clone, FromPrimitive, ToPrimitive, Display, PartialEq, Eq, Hash)]
@@ -166,6 +170,7 @@ pub enum Tag {
     FsContects = 1148,
     ReContexts = 1149,
     Policies = 1150,
+    PreTrans = 1151,
     PostTrans = 1152,
     PreTransProg = 1153,
     PostTransProg = 1154,
@@ -279,6 +284,48 @@ impl Default for Tag {
     }
 }
 
+impl Tag {
+    /// Tag by its name ignoring case, as in rpm query formats, e.g. `NAME` or `RPMTAG_NAME`
+    pub fn from_name(name: &str) -> Option<Self> {
+        name.parse().ok()
+    }
+
+    /// All known tags in the order of their ids, `Other` is not included
+    pub fn all() -> impl Iterator<Item = Tag> {
+        (0..Tag::Other as u32).filter_map(Tag::from_u32)
+    }
+}
+
+/// Canonical rpm names which differ from the enum spelling
+const TAG_ALIASES: &[(&str, Tag)] = &[
+    ("FILESTATES", Tag::FilesStates),
+    ("PATCHESFLAGS", Tag::CatchesFlags),
+    ("PATCHESVERSION", Tag::CatchesVersion),
+    ("FSCONTEXTS", Tag::FsContects),
+    ("HDRID", Tag::SHA1Header),
+    ("PROVIDES", Tag::ProvideName),
+    ("REQUIRES", Tag::RequireName),
+    ("CONFLICTS", Tag::ConflictName),
+    ("OBSOLETES", Tag::ObsoleteName),
+];
+
+impl FromStr for Tag {
+    type Err = UnknownTagName;
+
+    /// Parses the enum spelling or the rpm name ignoring case, e.g. `PayloadCompressor`
+    /// or `RPMTAG_PAYLOADCOMPRESSOR`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let upper = name.to_ascii_uppercase();
+        let short = upper.strip_prefix("RPMTAG_").unwrap_or(&upper);
+        TAG_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == short)
+            .map(|&(_, tag)| tag)
+            .or_else(|| Tag::all().find(|tag| tag.to_string().eq_ignore_ascii_case(short)))
+            .ok_or_else(|| UnknownTagName(name.to_owned()))
+    }
+}
+
 #[derive(Debug, Copy, Clone, FromPrimitive, ToPrimitive, Display, PartialEq, Eq, Hash)]
 pub enum SignatureTag {
     HeaderImage = 61,
@@ -319,3 +366,98 @@ impl Default for SignatureTag {
         SignatureTag::Other
     }
 }
+
+impl SignatureTag {
+    /// All known signature tags in the order of their ids, `Other` is not included
+    pub fn all() -> impl Iterator<Item = SignatureTag> {
+        (0..SignatureTag::Other as u32).filter_map(SignatureTag::from_u32)
+    }
+}
+
+/// rpm names of signature tags which differ from the enum spelling, without `RPMSIGTAG_`
+const SIGNATURE_TAG_ALIASES: &[(&str, SignatureTag)] = &[
+    ("DSA", SignatureTag::DSAHeader),
+    ("RSA", SignatureTag::RSAHeader),
+    ("SHA1", SignatureTag::SHA1Header),
+    ("SHA256", SignatureTag::SHA256Header),
+    ("LONGSIZE", SignatureTag::LongSigSize),
+];
+
+impl FromStr for SignatureTag {
+    type Err = UnknownTagName;
+
+    /// Parses the enum spelling or the rpm name ignoring case, e.g. `RSAHeader` or `RPMSIGTAG_RSA`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let upper = name.to_ascii_uppercase();
+        let short = ["RPMSIGTAG_", "RPMTAG_"]
+            .iter()
+            .find_map(|prefix| upper.strip_prefix(prefix))
+            .unwrap_or(&upper);
+        SIGNATURE_TAG_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == short)
+            .map(|&(_, tag)| tag)
+            .or_else(|| SignatureTag::all().find(|tag| tag.to_string().eq_ignore_ascii_case(short)))
+            .ok_or_else(|| UnknownTagName(name.to_owned()))
+    }
+}
+
+/// The name of a tag is not known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTagName(pub String);
+
+impl fmt::Display for UnknownTagName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error: tag {} is not known", self.0)
+    }
+}
+
+impl Error for UnknownTagName {}
+
+/// Tag of the immutable region which covers the whole header
+pub trait RegionTag: Sized {
+    const REGION: Self;
+}
+
+impl RegionTag for Tag {
+    const REGION: Self = Tag::Immutable;
+}
+
+impl RegionTag for SignatureTag {
+    const REGION: Self = SignatureTag::HeaderSignatures;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_names() {
+        assert_eq!("RPMTAG_NAME".parse(), Ok(Tag::Name));
+        assert_eq!("payloadcompressor".parse(), Ok(Tag::PayloadCompressor));
+        assert_eq!("RPMTAG_FILESTATES".parse(), Ok(Tag::FilesStates));
+        assert_eq!("Requires".parse(), Ok(Tag::RequireName));
+        assert_eq!(
+            "NOSUCHTAG".parse::<Tag>(),
+            Err(UnknownTagName("NOSUCHTAG".to_owned()))
+        );
+        assert!("Other".parse::<Tag>().is_err());
+
+        assert_eq!("RPMSIGTAG_RSA".parse(), Ok(SignatureTag::RSAHeader));
+        assert_eq!("sha256header".parse(), Ok(SignatureTag::SHA256Header));
+        assert_eq!(
+            "RPMSIGTAG_PAYLOADSIZE".parse(),
+            Ok(SignatureTag::PayloadSize)
+        );
+        assert!("NAME".parse::<SignatureTag>().is_err());
+
+        // every tag is found by its own name
+        assert!(Tag::all().all(|tag| tag.to_string().parse() == Ok(tag)));
+        assert!(SignatureTag::all().all(|tag| tag.to_string().parse() == Ok(tag)));
+        assert_eq!(Tag::all().next(), Some(Tag::Image));
+        assert_eq!(
+            SignatureTag::all().last(),
+            Some(SignatureTag::ReservedSpace)
+        );
+    }
+}
@@ -5,23 +5,30 @@ use std::fmt;
 use std::io::{self, Read, Seek, Write};
 use strum_macros::Display;
 
+use crate::error::{Result, RpmError};
 use crate::utils::parse_string;
 
 pub const MAGIC: [u8; 4] = [237, 171, 238, 219];
+pub const LEAD_SIZE: usize = 96;
+/// Architecture number in the lead of packages built for no architecture
+pub const NOARCH: u16 = 0;
 
 #[derive(Debug, PartialEq, FromPrimitive, ToPrimitive, Display, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     Binary = 0,
     Source = 1,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lead {
     pub magic: [u8; 4],
     pub major: u8,
     pub minor: u8,
     pub rpm_type: Type,
     pub archnum: u16,
+    #[cfg_attr(feature = "serde", serde(with = "name_serde"))]
     pub name: [u8; 66],
     pub osnum: u16,
     pub signature_type: u16,
@@ -29,17 +36,25 @@ pub struct Lead {
 }
 
 impl Lead {
-    pub fn read<R: Read + Seek>(fh: &mut R) -> io::Result<Self> {
+    pub fn read<R: Read + Seek>(fh: &mut R) -> Result<Self> {
         fh.seek(io::SeekFrom::Start(0))?;
-        let mut magic = [0_u8; 4];
-        fh.read_exact(&mut magic)?;
+        Self::read_stream(fh)
+    }
 
-        if magic != MAGIC {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Error: File is not rpm",
-            ));
+    /// Reads the lead at the current position, e.g. of stdin
+    pub fn read_stream<R: Read>(fh: &mut R) -> Result<Self> {
+        let mut lead = Vec::with_capacity(LEAD_SIZE);
+        fh.take(LEAD_SIZE as u64).read_to_end(&mut lead)?;
+        if lead.len() >= MAGIC.len() && lead[..MAGIC.len()] != MAGIC {
+            return Err(RpmError::NotAnRpm);
+        }
+        if lead.len() < LEAD_SIZE {
+            return Err(RpmError::Truncated("lead"));
         }
+        let fh = &mut lead.as_slice();
+
+        let mut magic = [0_u8; 4];
+        fh.read_exact(&mut magic)?;
 
         let mut head = [0_u8; 2];
         fh.read_exact(&mut head)?;
@@ -47,20 +62,15 @@ impl Lead {
 
         match (major, minor) {
             (3, 0) | (3, 1) | (4, 0) => {}
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!(
-                        "Error: rpm format version is not supported {}.{}",
-                        major, minor
-                    ),
-                ))
-            }
+            _ => return Err(RpmError::UnsupportedVersion { major, minor }),
         }
 
         let rpm_type_id: u16 = fh.read_be()?;
         let rpm_type = Type::from_u16(rpm_type_id).ok_or_else(|| {
-            io::Error::new(io::ErrorKind::Other, "Error: can not read the rpm type")
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Error: can not read the rpm type",
+            )
         })?;
         let archnum: u16 = fh.read_be()?;
 
@@ -85,7 +95,7 @@ impl Lead {
         })
     }
 
-    pub fn write<R: Write>(&self, fh: &mut R) -> io::Result<()> {
+    pub fn write<R: Write>(&self, fh: &mut R) -> Result<()> {
         fh.write_all(&MAGIC)?;
         fh.write_all(&[self.major, self.minor])?;
 
@@ -105,19 +115,101 @@ impl Lead {
         Ok(())
     }
 
-    pub fn from_str(info: String) -> Self {
-        let mut name = [0_u8; 66];
-        info.as_bytes().read(&mut name).unwrap();
+    /// Lead consistent with the package header as rpm writes it: the name-version-release
+    /// is truncated to 65 bytes and the arch and os are numbered as in rpmrc.
+    /// As rpm does, noarch packages get the number of the build architecture.
+    pub fn new(nvr: &str, arch: &str, os: &str, rpm_type: Type) -> Self {
+        let archnum = arch_number(arch)
+            .or_else(|| arch_number(std::env::consts::ARCH))
+            .unwrap_or_default();
 
+        Self {
+            minor: 0,
+            rpm_type,
+            archnum,
+            name: name_field(nvr),
+            osnum: os_number(os).unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    /// Name field up to the terminating zero
+    pub fn name(&self) -> String {
+        let size = self
+            .name
+            .iter()
+            .position(|&x| x == 0)
+            .unwrap_or(self.name.len());
+        String::from_utf8_lossy(&self.name[..size]).to_string()
+    }
+
+    pub fn from_str(info: String) -> Self {
         Self {
             major: 3,
             minor: 1,
-            name,
+            name: name_field(&info),
             ..Default::default()
         }
     }
 }
 
+/// Name field of the lead, the name is truncated to keep the terminating zero
+fn name_field(name: &str) -> [u8; 66] {
+    let mut field = [0_u8; 66];
+    let size = name.len().min(field.len() - 1);
+    field[..size].copy_from_slice(&name.as_bytes()[..size]);
+    field
+}
+
+/// Architecture number of the lead, as defined by arch_canon in rpmrc
+pub fn arch_number(arch: &str) -> Option<u16> {
+    match arch {
+        "i386" | "i486" | "i586" | "i686" | "athlon" | "pentium3" | "pentium4" | "x86_64"
+        | "amd64" | "ia32e" => Some(1),
+        "alpha" | "alphaev5" | "alphaev56" | "alphaev6" | "alphaev67" => Some(2),
+        "sparc" | "sparcv8" | "sparcv9" => Some(3),
+        "mips" => Some(4),
+        "ppc" => Some(5),
+        "m68k" => Some(6),
+        "ia64" => Some(9),
+        "arm" | "armv5tel" | "armv6l" | "armv7l" | "armv7hl" => Some(12),
+        "s390" => Some(14),
+        "s390x" => Some(15),
+        "ppc64" | "ppc64le" | "ppc64p7" => Some(16),
+        "aarch64" => Some(19),
+        "riscv64" => Some(22),
+        _ => None,
+    }
+}
+
+/// OS number of the lead, as defined by os_canon in rpmrc
+pub fn os_number(os: &str) -> Option<u16> {
+    match os.to_ascii_lowercase().as_str() {
+        "linux" => Some(1),
+        "irix" => Some(2),
+        "solaris" | "sunos5" => Some(3),
+        "sunos4" => Some(4),
+        "aix" => Some(5),
+        "hp-ux" | "hpux10" => Some(6),
+        "osf1" => Some(7),
+        "freebsd" => Some(8),
+        "sco_sv" => Some(9),
+        "irix64" => Some(10),
+        "nextstep" => Some(11),
+        "bsd_os" | "bsdi" => Some(12),
+        "machten" => Some(13),
+        "cygwin32_nt" => Some(14),
+        "cygwin32_95" => Some(15),
+        "unix_sv" => Some(16),
+        "mint" => Some(17),
+        "os/390" => Some(18),
+        "vm/esa" => Some(19),
+        "linux/390" | "linux/esa" => Some(20),
+        "darwin" | "macosx" => Some(21),
+        _ => None,
+    }
+}
+
 impl fmt::Display for Lead {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "magic: {:?}", self.magic)?;
@@ -146,6 +238,23 @@ impl fmt::Debug for Lead {
     }
 }
 
+/// serde supports arrays up to 32 items, the name is written as a sequence of bytes
+#[cfg(feature = "serde")]
+mod name_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::convert::TryInto;
+
+    pub fn serialize<S: Serializer>(name: &[u8; 66], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(name.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 66], D::Error> {
+        let name = Vec::<u8>::deserialize(deserializer)?;
+        name.try_into()
+            .map_err(|name: Vec<u8>| D::Error::invalid_length(name.len(), &"66 bytes"))
+    }
+}
+
 impl Default for Lead {
     fn default() -> Self {
         Lead {
@@ -178,11 +287,11 @@ impl PartialEq for Lead {
 }
 
 pub trait LeadWriter {
-    fn write_lead(&mut self, lead: &Lead) -> io::Result<()>;
+    fn write_lead(&mut self, lead: &Lead) -> Result<()>;
 }
 
 impl<W: Write> LeadWriter for W {
-    fn write_lead(&mut self, lead: &Lead) -> io::Result<()> {
+    fn write_lead(&mut self, lead: &Lead) -> Result<()> {
         lead.write(self)
     }
 }
@@ -210,4 +319,40 @@ mod tests {
 
         assert_eq!(lead, lead2);
     }
+
+    #[test]
+    fn test_lead_signature_type() -> Result<()> {
+        let lead = Lead {
+            signature_type: 0x0102,
+            osnum: 0x0304,
+            ..Default::default()
+        };
+
+        let mut data = Vec::new();
+        lead.write(&mut data)?;
+        assert_eq!(&data[76..80], &[3, 4, 1, 2]);
+
+        let lead2 = Lead::read(&mut Cursor::new(data))?;
+        assert_eq!(lead2.signature_type, 0x0102);
+        assert_eq!(lead2.osnum, 0x0304);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lead_new() -> Result<()> {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/hello-1.0-1.noarch.rpm");
+        let original = std::fs::read(path)?;
+
+        let mut data = Vec::new();
+        Lead::new("hello-1.0-1", "x86_64", "linux", Type::Binary).write(&mut data)?;
+        assert_eq!(data, &original[..LEAD_SIZE]);
+
+        let long = "a".repeat(100);
+        let lead = Lead::new(&long, "aarch64", "Linux", Type::Source);
+        assert_eq!(lead.name(), &long[..65]);
+        assert_eq!((lead.archnum, lead.osnum), (19, 1));
+        assert_eq!(Lead::from_str(long).name.last(), Some(&0));
+        Ok(())
+    }
 }
@@ -1,7 +1,13 @@
+pub mod depsolve;
+pub mod digest;
+pub mod error;
+pub mod exit;
 pub mod header;
 pub mod lead;
 pub mod payload;
 pub mod rpm;
+pub mod signature;
 
 pub(crate) mod utils;
+pub use error::{Result, RpmError};
 pub use rpm::*;
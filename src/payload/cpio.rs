@@ -1,15 +1,58 @@
-use filetime::{set_file_mtime, FileTime};
+use filetime::{set_file_mtime, set_symlink_file_times, FileTime};
+use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Seek, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::utils::{align_n_bytes, HexReader, HexWriter};
+use super::{CpioStream, FileInfo, XattrManifest};
+use crate::digest::DigestAlgo;
+use crate::error::{self, RpmError};
+use crate::utils::{
+    align_n_bytes, read_hex_as_u32_from, HexReader, HexWriter, S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO,
+    S_IFLNK, S_IFMT, S_IFREG,
+};
 
 const MAGIC: &[u8] = b"070701";
-const TRAILER: &str = "TRAILER!!!";
+const CRC_MAGIC: &[u8] = b"070702";
+const ODC_MAGIC: &[u8] = b"070707";
+pub(crate) const TRAILER: &str = "TRAILER!!!";
+
+/// Variant of the "new" portable cpio format
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CpioFormat {
+    /// `070701`, the checksum is always zero
+    #[default]
+    Newc,
+    /// `070702`, the checksum is the sum of the data bytes
+    Crc,
+    /// `070707`, the old portable format with octal fields and without alignment
+    Odc,
+}
+
+impl CpioFormat {
+    fn magic(self) -> &'static [u8] {
+        match self {
+            CpioFormat::Newc => MAGIC,
+            CpioFormat::Crc => CRC_MAGIC,
+            CpioFormat::Odc => ODC_MAGIC,
+        }
+    }
+}
+
+/// Sum of the data bytes as stored in the header of `070702` entries
+pub fn cpio_checksum(sum: u32, data: &[u8]) -> u32 {
+    data.iter()
+        .fold(sum, |sum, byte| sum.wrapping_add((*byte).into()))
+}
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileEntry {
     pub name: String,
     pub ino: u32,
@@ -23,19 +66,27 @@ pub struct FileEntry {
     pub dev_minor: u32,
     pub rdev_major: u32,
     pub rdev_minor: u32,
+    pub format: CpioFormat,
+    /// Sum of the data bytes, set by `write_cpio_record` for the Crc format
+    pub checksum: u32,
 }
 
 impl FileEntry {
-    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+    pub fn read<R: Read>(reader: &mut R) -> error::Result<Self> {
         let mut magic = [0_u8; 6];
         reader.read_exact(&mut magic)?;
 
-        if magic != MAGIC {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Error: incorrect magic of cpio entry {:x?}", magic),
-            ));
-        }
+        let format = match &magic[..] {
+            MAGIC => CpioFormat::Newc,
+            CRC_MAGIC => CpioFormat::Crc,
+            ODC_MAGIC => return Self::read_odc(reader),
+            _ => {
+                return Err(RpmError::CorruptedPayload(format!(
+                    "incorrect magic of cpio entry {:x?}",
+                    magic
+                )))
+            }
+        };
 
         let ino = reader.read_hex_as_u32()?;
         let mode = reader.read_hex_as_u32()?;
@@ -51,20 +102,21 @@ impl FileEntry {
         let name_size = reader.read_hex_as_u32()?;
         let mut checksum = [0_u8; 8];
         reader.read_exact(&mut checksum)?;
+        // the field of newc entries is not used, it is not required to be valid
+        let checksum = match format {
+            CpioFormat::Crc => read_hex_as_u32_from(&checksum)?,
+            _ => 0,
+        };
 
         // optimise later
         let mut name_bytes = vec![0_u8; name_size as usize];
         reader.read_exact(&mut name_bytes)?;
         let name = if name_size > 0 {
             let size = (name_size - 1) as usize;
-            String::from_utf8(name_bytes[0..size].to_vec()).map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Error: incorrect utf8 symbol: {}", e),
-                )
-            })?
+            String::from_utf8(name_bytes[0..size].to_vec())
+                .map_err(|e| RpmError::CorruptedPayload(format!("incorrect utf8 symbol: {}", e)))?
         } else {
-            return Err(io::Error::new(io::ErrorKind::Other, "incorrect cpio name"));
+            return Err(RpmError::CorruptedPayload("incorrect cpio name".to_owned()));
         };
 
         // aligning to 4 bytes: name +
@@ -85,11 +137,16 @@ impl FileEntry {
             dev_minor,
             rdev_major,
             rdev_minor,
+            format,
+            checksum,
         })
     }
 
     fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
-        writer.write_all(MAGIC)?;
+        if self.format == CpioFormat::Odc {
+            return self.write_odc(writer);
+        }
+        writer.write_all(self.format.magic())?;
         writer.write_u32_as_hex(self.ino)?;
         writer.write_u32_as_hex(self.mode)?;
         writer.write_u32_as_hex(self.uid)?;
@@ -103,8 +160,11 @@ impl FileEntry {
         writer.write_u32_as_hex(self.rdev_minor)?;
         let name_size = (self.name.len() + 1) as u32;
         writer.write_u32_as_hex(name_size)?;
-        let checksum = [0_u8; 8];
-        writer.write_all(&checksum)?;
+        let checksum = match self.format {
+            CpioFormat::Crc => self.checksum,
+            _ => 0,
+        };
+        writer.write_u32_as_hex(checksum)?;
 
         let mut name = self.name.as_bytes().to_vec();
         name.push(0_u8);
@@ -115,6 +175,112 @@ impl FileEntry {
         let pad = vec![0_u8; number];
         writer.write_all(&pad)
     }
+
+    /// Reads an odc header after its magic, the device numbers are split as 8-bit minors
+    fn read_odc<R: Read>(reader: &mut R) -> error::Result<Self> {
+        let dev = read_octal(reader, 6)?;
+        let ino = read_octal(reader, 6)?;
+        let mode = read_octal(reader, 6)?;
+        let uid = read_octal(reader, 6)?;
+        let gid = read_octal(reader, 6)?;
+        let nlink = read_octal(reader, 6)?;
+        let rdev = read_octal(reader, 6)?;
+        let mtime = read_octal(reader, 11)?;
+        let name_size = read_octal(reader, 6)?;
+        let file_size = read_octal(reader, 11)?;
+
+        let mut name_bytes = vec![0_u8; name_size as usize];
+        reader.read_exact(&mut name_bytes)?;
+        if name_bytes.pop() != Some(0) {
+            return Err(RpmError::CorruptedPayload("incorrect cpio name".to_owned()));
+        }
+        let name = String::from_utf8(name_bytes)
+            .map_err(|e| RpmError::CorruptedPayload(format!("incorrect utf8 symbol: {}", e)))?;
+
+        Ok(FileEntry {
+            name,
+            ino,
+            mode,
+            uid,
+            gid,
+            nlink,
+            mtime,
+            file_size,
+            dev_major: major(dev),
+            dev_minor: minor(dev),
+            rdev_major: major(rdev),
+            rdev_minor: minor(rdev),
+            format: CpioFormat::Odc,
+            checksum: 0,
+        })
+    }
+
+    fn write_odc<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let name_size = (self.name.len() + 1) as u32;
+        writer.write_all(ODC_MAGIC)?;
+        write_octal(writer, self.dev_major << 8 | self.dev_minor, 6)?;
+        write_octal(writer, self.ino, 6)?;
+        write_octal(writer, self.mode, 6)?;
+        write_octal(writer, self.uid, 6)?;
+        write_octal(writer, self.gid, 6)?;
+        write_octal(writer, self.nlink, 6)?;
+        write_octal(writer, self.rdev_major << 8 | self.rdev_minor, 6)?;
+        write_octal(writer, self.mtime, 11)?;
+        write_octal(writer, name_size, 6)?;
+        write_octal(writer, self.file_size, 11)?;
+        writer.write_all(self.name.as_bytes())?;
+        writer.write_all(&[0])
+    }
+}
+
+impl FileEntry {
+    /// Bytes after the entry data up to the next header
+    pub fn data_padding(&self) -> u32 {
+        match self.format {
+            CpioFormat::Odc => 0,
+            _ => align_n_bytes(self.file_size, 4),
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    /// Regular file sharing its inode with other entries
+    pub fn is_hardlink(&self) -> bool {
+        self.is_file() && self.nlink > 1
+    }
+
+    /// Character or block device
+    pub fn is_device(&self) -> bool {
+        matches!(self.mode & S_IFMT, S_IFCHR | S_IFBLK)
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.mode & S_IFMT == S_IFIFO
+    }
+
+    /// Device node or FIFO, created only with `ExtractOptions::special_files`
+    pub fn is_special(&self) -> bool {
+        self.is_device() || self.is_fifo()
+    }
+
+    fn link_key(&self) -> (u32, u32, u32) {
+        (self.dev_major, self.dev_minor, self.ino)
+    }
+
+    /// Entry types which can be extracted on the current platform
+    pub(crate) fn is_supported(&self) -> bool {
+        self.is_dir() || self.is_file() || (self.is_symlink() && cfg!(unix))
+    }
 }
 
 impl Default for FileEntry {
@@ -132,6 +298,8 @@ impl Default for FileEntry {
             dev_minor: 0,
             rdev_major: 0,
             rdev_minor: 0,
+            format: CpioFormat::Newc,
+            checksum: 0,
         }
     }
 }
@@ -174,6 +342,7 @@ impl TryFrom<&PathBuf> for FileEntry {
                 dev_minor: minor(meta.dev() as u32),
                 rdev_major: major(meta.rdev() as u32),
                 rdev_minor: minor(meta.rdev() as u32),
+                ..Default::default()
             })
         }
         #[cfg(all(windows))]
@@ -193,11 +362,37 @@ impl TryFrom<&PathBuf> for FileEntry {
                 dev_minor: 0,
                 rdev_major: 0,
                 rdev_minor: 0,
+                ..Default::default()
             })
         }
     }
 }
 
+fn read_octal<R: Read>(reader: &mut R, width: usize) -> io::Result<u32> {
+    let mut bytes = vec![0_u8; width];
+    reader.read_exact(&mut bytes)?;
+    std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|field| u32::from_str_radix(field, 8).ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Error: incorrect octal field {:x?}", bytes),
+            )
+        })
+}
+
+fn write_octal<W: Write>(writer: &mut W, value: u32, width: usize) -> io::Result<()> {
+    let field = format!("{:0width$o}", value, width = width);
+    if field.len() > width {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Error: {} does not fit into an odc field", value),
+        ));
+    }
+    writer.write_all(field.as_bytes())
+}
+
 fn major(x: u32) -> u32 {
     (x >> 8) & 0x7F
 }
@@ -207,18 +402,7 @@ fn minor(x: u32) -> u32 {
 }
 
 pub fn read_entries<R: Read + Seek>(reader: &mut R) -> io::Result<Vec<FileEntry>> {
-    let mut entries = Vec::new();
-
-    loop {
-        let entry = FileEntry::read(reader)?;
-        let position = align_n_bytes(entry.file_size, 4) + entry.file_size;
-        reader.seek(io::SeekFrom::Current(position.into()))?;
-        if entry.name == TRAILER {
-            break;
-        }
-        entries.push(entry);
-    }
-    Ok(entries)
+    CpioEntries::new(reader).collect()
 }
 
 pub fn read_entry<R: Read + Seek, W: Write>(
@@ -227,297 +411,1337 @@ pub fn read_entry<R: Read + Seek, W: Write>(
 ) -> io::Result<(FileEntry, u64)> {
     let entry = FileEntry::read(reader)?;
     let number = io_copy_exact(reader, writer, entry.file_size)?;
-    let position = align_n_bytes(entry.file_size, 4);
+    let position = entry.data_padding();
     reader.seek(io::SeekFrom::Current(position.into()))?;
     Ok((entry, number.into()))
 }
 
-pub fn extract_entry<R: Read + Seek>(
-    reader: &mut R,
-    dir: &PathBuf,
-    creates_dir: bool,
-    change_owner: bool,
-) -> io::Result<(FileEntry, u64)> {
-    let entry = FileEntry::read(reader)?;
+/// What to do when a file being extracted already exists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overwrite {
+    Replace,
+    Skip,
+    Error,
+}
 
-    // write content to file only if it is not a last pseudo
-    if entry.name != TRAILER {
-        let path = dir.join(&entry.name);
-        let mut number = 0;
+pub type EntryFilter = Arc<dyn Fn(&FileEntry) -> bool + Send + Sync>;
 
-        if entry.nlink == 2 {
-            std::fs::create_dir_all(&path)?;
-        } else {
-            if creates_dir {
-                let parent = path.parent();
-                if let Some(p) = parent {
-                    std::fs::create_dir_all(p)?;
-                }
-            }
+#[derive(Clone)]
+pub struct ExtractOptions {
+    pub creates_dir: bool,
+    pub change_owner: bool,
+    pub overwrite: Overwrite,
+    /// Only entries accepted by the filter are extracted
+    pub filter: Option<EntryFilter>,
+    /// Upper bound for the size of a single entry
+    pub max_file_size: Option<u64>,
+    /// Upper bound for the total size of all extracted entries
+    pub max_total_size: Option<u64>,
+    /// Flushes the data of every extracted file to disk before it is closed
+    pub sync: bool,
+    /// Extended attributes restored on extracted files and directories
+    pub xattrs: Option<Arc<XattrManifest>>,
+    /// Creates device nodes and FIFOs on Unix, devices usually require root.
+    /// They are skipped as unsupported otherwise.
+    pub special_files: bool,
+}
+
+impl ExtractOptions {
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&FileEntry) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
 
-            let mut writer = OpenOptions::new().create(true).write(true).open(&path)?;
-            number = io_copy_exact(reader, &mut writer, entry.file_size)?;
+    pub(crate) fn accepts(&self, entry: &FileEntry) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter(entry))
+    }
+
+    /// Entry types which can be extracted with these options on the current platform
+    pub(crate) fn supports(&self, entry: &FileEntry) -> bool {
+        let special =
+            (entry.is_fifo() && cfg!(unix)) || (entry.is_device() && cfg!(target_os = "linux"));
+        entry.is_supported() || (self.special_files && special)
+    }
 
-            let position = align_n_bytes(entry.file_size, 4);
-            reader.seek(io::SeekFrom::Current(position.into()))?;
+    /// Applies the overwrite policy to an existing path, returns false if it has to be skipped
+    pub(crate) fn may_write(&self, path: &Path) -> io::Result<bool> {
+        if path.symlink_metadata().is_err() {
+            return Ok(true);
+        }
+        match self.overwrite {
+            Overwrite::Replace => Ok(true),
+            Overwrite::Skip => Ok(false),
+            Overwrite::Error => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Error: {} already exists", path.display()),
+            )),
         }
+    }
 
-        #[cfg(all(unix))]
-        {
-            if change_owner {
-                use nix::unistd::{chown, Gid, Uid};
-                use std::os::unix::fs::PermissionsExt;
-
-                let metadata = path.metadata()?;
-                let mut permissions = metadata.permissions();
-                permissions.set_mode(entry.mode);
-                std::fs::set_permissions(&path, permissions)?;
-                chown(
-                    &path,
-                    Some(Uid::from_raw(entry.uid)),
-                    Some(Gid::from_raw(entry.gid)),
-                )
-                .map_err(|e| {
-                    io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("Error: can not change owner {}", e),
-                    )
-                })?
+    /// Restores extended attributes of the entry, failures are reported as warnings
+    pub(crate) fn restore_xattrs(
+        &self,
+        path: &Path,
+        entry: &FileEntry,
+        report: &mut ExtractReport,
+    ) {
+        if let Some(manifest) = &self.xattrs {
+            if let Err(e) = manifest.restore(&entry.name, path) {
+                report.warnings.push(format!("{}: {}", entry.name, e));
             }
         }
+    }
 
-        let mtime = FileTime::from_unix_time(entry.mtime.into(), 0);
-        set_file_mtime(&path, mtime)?;
-        Ok((entry, number.into()))
-    } else {
-        Ok((entry, 0))
+    pub(crate) fn check_limits(&self, entry: &FileEntry, total: &mut u64) -> io::Result<()> {
+        let size = u64::from(entry.file_size);
+        *total += size;
+
+        if self.max_file_size.is_some_and(|max| size > max) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Error: {} exceeds the file size limit", entry.name),
+            ));
+        }
+        if self.max_total_size.is_some_and(|max| *total > max) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Error: payload exceeds the total size limit",
+            ));
+        }
+        Ok(())
     }
 }
 
-pub fn extract_entries<R: Read + Seek>(
-    reader: &mut R,
-    dir: &PathBuf,
-    creates_dir: bool,
-    change_owner: bool,
-) -> io::Result<Vec<FileEntry>> {
-    let mut entries = Vec::new();
-    loop {
-        let (entry, _) = extract_entry(reader, dir, creates_dir, change_owner)?;
-        if entry.name == TRAILER {
-            break;
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            creates_dir: true,
+            change_owner: false,
+            overwrite: Overwrite::Replace,
+            filter: None,
+            max_file_size: None,
+            max_total_size: None,
+            sync: false,
+            xattrs: None,
+            special_files: false,
         }
-        entries.push(entry);
     }
-    Ok(entries)
 }
 
-const BUFSIZE: usize = 8 * 1024;
+impl fmt::Debug for ExtractOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtractOptions")
+            .field("creates_dir", &self.creates_dir)
+            .field("change_owner", &self.change_owner)
+            .field("overwrite", &self.overwrite)
+            .field("filter", &self.filter.is_some())
+            .field("max_file_size", &self.max_file_size)
+            .field("max_total_size", &self.max_total_size)
+            .field("sync", &self.sync)
+            .field("xattrs", &self.xattrs.is_some())
+            .field("special_files", &self.special_files)
+            .finish()
+    }
+}
 
-fn io_copy_exact<R: Read, W: Write>(reader: &mut R, writer: &mut W, count: u32) -> io::Result<u32> {
-    let mut buf = [0_u8; BUFSIZE];
-    let buf_count = count as usize / BUFSIZE;
-    let buf_left = count as usize % BUFSIZE;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkipReason {
+    /// Rejected by the filter
+    Filtered,
+    /// Path exists and the overwrite policy is Skip
+    Exists,
+    /// Sockets, devices and FIFOs unless requested, or symlinks on platforms without them
+    Unsupported,
+    /// Path can not be created
+    PermissionDenied,
+    /// Path or symlink target leaves the extraction directory
+    UnsafePath,
+}
 
-    for _ in 0..buf_count {
-        reader.read_exact(&mut buf)?;
-        writer.write_all(&buf)?;
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Filtered => write!(f, "filtered"),
+            SkipReason::Exists => write!(f, "already exists"),
+            SkipReason::Unsupported => write!(f, "unsupported entry type"),
+            SkipReason::PermissionDenied => write!(f, "permission denied"),
+            SkipReason::UnsafePath => write!(f, "unsafe path"),
+        }
     }
+}
+
+#[derive(Debug, Default)]
+pub struct ExtractReport {
+    /// Extracted entries
+    pub entries: Vec<FileEntry>,
+    pub bytes_written: u64,
+    pub files_created: usize,
+    pub dirs_created: usize,
+    pub symlinks_created: usize,
+    /// Links to files created earlier in the same extraction
+    pub hardlinks_created: usize,
+    /// Device nodes and FIFOs
+    pub specials_created: usize,
+    pub skipped: Vec<(String, SkipReason)>,
+    /// Non-fatal problems, e.g. failures to change an owner
+    pub warnings: Vec<String>,
+    pub duration: Duration,
+}
 
-    if buf_left > 0 {
-        let mut buf2 = vec![0_u8; buf_left];
-        reader.read_exact(&mut buf2)?;
-        writer.write_all(&buf2)?;
+impl ExtractReport {
+    pub(crate) fn skip(&mut self, entry: &FileEntry, reason: SkipReason) {
+        self.skipped.push((entry.name.clone(), reason));
     }
 
-    Ok(count)
-}
+    /// Records a failure to restore metadata as a warning, if it is caused by permissions
+    pub(crate) fn restored(&mut self, entry: &FileEntry, result: io::Result<()>) -> io::Result<()> {
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                self.warnings.push(format!("{}: {}", entry.name, e));
+                Ok(())
+            }
+            result => result,
+        }
+    }
 
-struct CpioFiles<T> {
-    reader: T,
+    /// True if some entries could not be extracted, entries skipped on request do not count
+    pub fn is_partial(&self) -> bool {
+        !self.warnings.is_empty()
+            || self
+                .skipped
+                .iter()
+                .any(|(_, reason)| *reason != SkipReason::Filtered && *reason != SkipReason::Exists)
+    }
 }
 
-impl<T: Read + Seek> CpioFiles<T> {
-    pub fn new(reader: T) -> Self {
-        CpioFiles { reader }
+impl fmt::Display for ExtractReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Extracted {} files, {} directories, {} symlinks, {} hardlinks, {} special files ({} bytes), skipped {}, warnings {} in {:.2?}",
+            self.files_created,
+            self.dirs_created,
+            self.symlinks_created,
+            self.hardlinks_created,
+            self.specials_created,
+            self.bytes_written,
+            self.skipped.len(),
+            self.warnings.len(),
+            self.duration
+        )
     }
 }
 
-impl<T: Read + Seek> Iterator for CpioFiles<T> {
-    type Item = (FileEntry, Vec<u8>);
+pub fn extract_entry<R: Read>(
+    reader: &mut R,
+    dir: &PathBuf,
+    creates_dir: bool,
+    change_owner: bool,
+) -> io::Result<(FileEntry, u64)> {
+    let entry = FileEntry::read(reader)?;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut bytes = Vec::new();
-        let (entry, _) = read_entry(&mut self.reader, &mut bytes).unwrap();
-        if entry.name != TRAILER {
-            Some((entry, bytes))
-        } else {
-            None
-        }
+    // write content to file only if it is not a last pseudo
+    if entry.name != TRAILER {
+        let options = ExtractOptions {
+            creates_dir,
+            change_owner,
+            ..Default::default()
+        };
+        let mut report = ExtractReport::default();
+        let mut links = PendingLinks::default();
+        write_entry(reader, &entry, dir, &options, &mut report, &mut links)?;
+        links.finish(&mut report)?;
+        Ok((entry, report.bytes_written))
+    } else {
+        Ok((entry, 0))
     }
 }
 
-struct CpioEntries<T> {
-    reader: T,
+#[deprecated(note = "use extract_entries_with, which returns a report")]
+pub fn extract_entries<R: Read>(
+    reader: &mut R,
+    dir: &PathBuf,
+    creates_dir: bool,
+    change_owner: bool,
+) -> io::Result<Vec<FileEntry>> {
+    let options = ExtractOptions {
+        creates_dir,
+        change_owner,
+        ..Default::default()
+    };
+    extract_entries_with(reader, dir, &options).map(|report| report.entries)
 }
 
-impl<T: Read + Seek> CpioEntries<T> {
-    pub fn new(reader: T) -> Self {
-        CpioEntries { reader }
-    }
-}
+/// Extracts all entries accepted by the options, recording skipped entries in the report
+pub fn extract_entries_with<R: Read>(
+    reader: &mut R,
+    dir: &Path,
+    options: &ExtractOptions,
+) -> io::Result<ExtractReport> {
+    let start = Instant::now();
+    let mut report = ExtractReport::default();
+    let mut links = PendingLinks::default();
+    let mut total = 0;
+    loop {
+        let entry = FileEntry::read(reader)?;
+        if entry.name == TRAILER {
+            break;
+        }
 
-impl<T: Read + Seek> Iterator for CpioEntries<T> {
-    type Item = FileEntry;
+        options.check_limits(&entry, &mut total)?;
+        if !options.accepts(&entry) {
+            skip_entry_data(reader, &entry)?;
+            report.skip(&entry, SkipReason::Filtered);
+            continue;
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let entry = FileEntry::read(&mut self.reader).unwrap();
-        let position = align_n_bytes(entry.file_size, 4) + entry.file_size;
-        self.reader
-            .seek(io::SeekFrom::Current(position.into()))
-            .unwrap();
-
-        if entry.name != TRAILER {
-            Some(entry)
-        } else {
-            None
+        if write_entry(reader, &entry, dir, options, &mut report, &mut links)? {
+            report.entries.push(entry);
         }
     }
+    links.finish(&mut report)?;
+    report.duration = start.elapsed();
+    Ok(report)
 }
 
-pub trait CpioRead {
-    fn read_cpio_entry(&mut self) -> io::Result<FileEntry>;
-    fn read_cpio_entry_payload<W: Write>(
-        &mut self,
-        entry: &FileEntry,
-        write: &mut W,
-    ) -> io::Result<()>;
-}
+/// Writes entry data under the directory, returns false if the entry was skipped
+fn write_entry<R: Read>(
+    reader: &mut R,
+    entry: &FileEntry,
+    dir: &Path,
+    options: &ExtractOptions,
+    report: &mut ExtractReport,
+    links: &mut PendingLinks,
+) -> io::Result<bool> {
+    let path = match entry_path(dir, &entry.name, entry.is_dir())? {
+        Some(path) => path,
+        None => {
+            skip_entry_data(reader, entry)?;
+            report.skip(entry, SkipReason::UnsafePath);
+            return Ok(false);
+        }
+    };
 
-impl<R> CpioRead for R
-where
-    R: Read + Seek,
-{
-    fn read_cpio_entry(&mut self) -> io::Result<FileEntry> {
-        FileEntry::read(self)
+    if entry.is_dir() {
+        std::fs::create_dir_all(&path)?;
+        report.dirs_created += 1;
+        let result = restore_metadata(&path, entry, options.change_owner);
+        report.restored(entry, result)?;
+        options.restore_xattrs(&path, entry, report);
+        return Ok(true);
     }
 
-    fn read_cpio_entry_payload<W: Write>(
-        &mut self,
-        entry: &FileEntry,
-        writer: &mut W,
-    ) -> io::Result<()> {
-        let file_size = entry.file_size;
-        io_copy_exact(self, writer, file_size)?;
-        let position = align_n_bytes(entry.file_size, 4) + entry.file_size;
-        self.seek(io::SeekFrom::Current(position.into()))?;
-        Ok(())
+    if !options.supports(entry) {
+        skip_entry_data(reader, entry)?;
+        report.skip(entry, SkipReason::Unsupported);
+        return Ok(false);
     }
-}
-
-pub trait CpioWriter {
-    fn write_cpio_entry(&mut self, entry: FileEntry) -> io::Result<()>;
 
-    fn write_cpio_entry_payload<R: Read>(&mut self, reader: &mut R) -> io::Result<()>;
+    if options.creates_dir {
+        let parent = path.parent();
+        if let Some(p) = parent {
+            std::fs::create_dir_all(p)?;
+        }
+    }
 
-    fn write_cpio_file(&mut self, path: &PathBuf) -> io::Result<()> {
-        let entry: FileEntry = path.try_into()?;
-        self.write_cpio_entry(entry)?;
-        let mut file = File::open(path)?;
-        self.write_cpio_entry_payload(&mut file)
+    if !options.may_write(&path)? {
+        skip_entry_data(reader, entry)?;
+        report.skip(entry, SkipReason::Exists);
+        return Ok(false);
     }
 
-    fn write_cpio_files(&mut self, paths: Vec<PathBuf>) -> io::Result<()> {
-        for path in &paths {
-            self.write_cpio_file(path)?
+    let mut target = Vec::new();
+    if entry.is_symlink() {
+        reader
+            .by_ref()
+            .take(entry.file_size.into())
+            .read_to_end(&mut target)?;
+        skip_bytes(reader, entry.data_padding().into())?;
+        if !symlink_target_inside(&entry.name, &target) {
+            report.skip(entry, SkipReason::UnsafePath);
+            return Ok(false);
         }
-        self.cpio_close()
     }
 
-    fn write_cpio_record<R: Read>(&mut self, record: FileEntry, data: &mut R) -> io::Result<()> {
-        self.write_cpio_entry(record)?;
-        self.write_cpio_entry_payload(data)
+    // never write through an existing symlink
+    let exists = path.symlink_metadata();
+    if exists.is_ok_and(|metadata| {
+        entry.is_symlink() || entry.is_special() || metadata.file_type().is_symlink()
+    }) {
+        std::fs::remove_file(&path)?;
     }
 
-    fn write_cpio_records<R: Read>(&mut self, records: Vec<(FileEntry, &mut R)>) -> io::Result<()> {
-        for (record, data) in records.into_iter() {
-            self.write_cpio_record(record, data)?;
+    if entry.is_special() {
+        skip_entry_data(reader, entry)?;
+        match create_special(&path, entry) {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                report.skip(entry, SkipReason::PermissionDenied);
+                return Ok(false);
+            }
+            result => result?,
         }
-        self.cpio_close()
+        report.specials_created += 1;
+        let result = restore_metadata(&path, entry, options.change_owner);
+        report.restored(entry, result)?;
+        return Ok(true);
     }
 
-    fn cpio_close(&mut self) -> io::Result<()> {
-        self.write_cpio_entry(FileEntry::default())
+    if entry.is_symlink() {
+        create_symlink(&target, &path)?;
+        report.symlinks_created += 1;
+        return Ok(true);
     }
-}
 
-impl<W> CpioWriter for W
-where
-    W: Write,
-{
-    fn write_cpio_entry(&mut self, entry: FileEntry) -> io::Result<()> {
-        entry.write(self)
+    // data of a hardlink group follows its last member, or precedes others in converted tar
+    if entry.is_hardlink() && entry.file_size == 0 {
+        links.defer(entry, path, report)?;
+        return Ok(true);
     }
 
-    fn write_cpio_entry_payload<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
-        let file_size = io::copy(reader, self)? as u32;
-        let number = align_n_bytes(file_size, 4) as usize;
-        let pad = vec![0_u8; number];
-        self.write_all(&pad)
+    let open = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&path);
+    let mut writer = match open {
+        Ok(writer) => writer,
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            skip_entry_data(reader, entry)?;
+            report.skip(entry, SkipReason::PermissionDenied);
+            return Ok(false);
+        }
+        Err(e) => return Err(e),
+    };
+    preallocate(&writer, entry.file_size.into());
+    let number = io_copy_exact(reader, &mut writer, entry.file_size)?;
+    if options.sync {
+        writer.sync_data()?;
     }
+    skip_bytes(reader, entry.data_padding().into())?;
+    report.bytes_written += u64::from(number);
+    report.files_created += 1;
+
+    let result = restore_metadata(&path, entry, options.change_owner);
+    report.restored(entry, result)?;
+    options.restore_xattrs(&path, entry, report);
+
+    links.link(entry, &path, report)?;
+    Ok(true)
 }
 
-pub struct CpioBuilder<W: Write> {
-    writer: Option<W>,
-    records: Vec<(FileEntry, Box<dyn Read>)>,
+/// Members of hardlink groups waiting for the member which carries the data,
+/// and extracted files which members appearing later are linked to, as tar does
+#[derive(Default)]
+pub(crate) struct PendingLinks {
+    pending: HashMap<(u32, u32, u32), Vec<PathBuf>>,
+    extracted: HashMap<(u32, u32, u32), PathBuf>,
 }
 
-impl<W: Write + CpioWriter> CpioBuilder<W> {
-    pub fn new(writer: W) -> Self {
-        CpioBuilder {
-            writer: Some(writer),
-            records: Vec::new(),
+impl PendingLinks {
+    /// Target for a member without data, None if the data of its group is not extracted yet
+    pub(crate) fn deferred(&mut self, entry: &FileEntry, path: PathBuf) -> Option<PathBuf> {
+        match self.extracted.get(&entry.link_key()) {
+            Some(target) => Some(target.clone()),
+            None => {
+                self.pending.entry(entry.link_key()).or_default().push(path);
+                None
+            }
         }
     }
 
-    pub fn add_raw_file(mut self, path: &PathBuf) -> io::Result<Self> {
-        let record: FileEntry = path.try_into()?;
-        let reader = File::open(path)?;
-        self.records.push((record, Box::new(reader)));
-        Ok(self)
+    /// Deferred members of the group to link to the extracted file
+    pub(crate) fn extracted(&mut self, entry: &FileEntry, path: &Path) -> Vec<PathBuf> {
+        self.extracted.insert(entry.link_key(), path.to_owned());
+        self.pending.remove(&entry.link_key()).unwrap_or_default()
     }
 
-    pub fn add_file(mut self, path: &str, as_path: &str) -> io::Result<Self> {
-        let file = PathBuf::from(path);
-        let mut record: FileEntry = (&file).try_into()?;
-        record.name = as_path.to_owned();
-        let reader = File::open(&file)?;
-        self.records.push((record, Box::new(reader)));
-        Ok(self)
+    /// Groups which never got their data
+    pub(crate) fn into_pending(self) -> impl Iterator<Item = Vec<PathBuf>> {
+        self.pending.into_values()
+    }
+
+    fn defer(
+        &mut self,
+        entry: &FileEntry,
+        path: PathBuf,
+        report: &mut ExtractReport,
+    ) -> io::Result<()> {
+        match self.deferred(entry, path.clone()) {
+            Some(target) => hard_link(&target, &path, report),
+            None => Ok(()),
+        }
+    }
+
+    /// Links deferred members of the group to the extracted file
+    fn link(
+        &mut self,
+        entry: &FileEntry,
+        path: &Path,
+        report: &mut ExtractReport,
+    ) -> io::Result<()> {
+        for link in self.extracted(entry, path) {
+            hard_link(path, &link, report)?;
+        }
+        Ok(())
+    }
+
+    /// Groups without data are empty files
+    fn finish(self, report: &mut ExtractReport) -> io::Result<()> {
+        for paths in self.into_pending() {
+            if let Some((first, links)) = paths.split_first() {
+                File::create(first)?;
+                report.files_created += 1;
+                for link in links {
+                    hard_link(first, link, report)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn hard_link(target: &Path, link: &Path, report: &mut ExtractReport) -> io::Result<()> {
+    if link.symlink_metadata().is_ok() {
+        std::fs::remove_file(link)?;
+    }
+    std::fs::hard_link(target, link)?;
+    report.hardlinks_created += 1;
+    Ok(())
+}
+
+/// Path of the entry under the directory, None if the name leaves the directory
+/// or an existing parent resolves outside of it, e.g. through a symlink extracted earlier.
+/// The path itself is checked for directories only, other entries replace it.
+pub(crate) fn entry_path(dir: &Path, name: &str, is_dir: bool) -> io::Result<Option<PathBuf>> {
+    let path = match normalize_relative(Path::new(name)) {
+        Some(relative) => dir.join(relative),
+        None => return Ok(None),
+    };
+    let root = match dir.canonicalize() {
+        Ok(root) => root,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Some(path)),
+        Err(e) => return Err(e),
+    };
+
+    let checked = if is_dir {
+        path.as_path()
+    } else {
+        path.parent().unwrap_or(dir)
+    };
+    let existing = checked
+        .ancestors()
+        .find(|ancestor| ancestor.symlink_metadata().is_ok())
+        .unwrap_or(dir);
+    match existing.canonicalize() {
+        Ok(resolved) if resolved.starts_with(&root) => Ok(Some(path)),
+        // a dangling symlink can not be written through safely either
+        _ => Ok(None),
+    }
+}
+
+/// True if a relative target stays in the extraction directory when resolved from the link.
+/// Absolute targets are resolved from the directory as the package is installed there.
+pub(crate) fn symlink_target_inside(name: &str, target: &[u8]) -> bool {
+    let target = String::from_utf8_lossy(target);
+    let target = Path::new(target.as_ref());
+    let resolved = if target.is_absolute() {
+        target.to_owned()
+    } else {
+        Path::new(name)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(target)
+    };
+    normalize_relative(&resolved).is_some()
+}
+
+/// Resolves `.` and `..` without touching the filesystem, None if the path leaves its root
+fn normalize_relative(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    Some(normalized)
+}
+
+#[cfg(unix)]
+pub(crate) fn create_symlink(target: &[u8], path: &Path) -> io::Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    std::os::unix::fs::symlink(OsStr::from_bytes(target), path)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn create_symlink(_target: &[u8], _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Error: symlinks are not supported",
+    ))
+}
+
+/// Creates a device node or FIFO with the permissions of the entry
+#[cfg(unix)]
+pub(crate) fn create_special(path: &Path, entry: &FileEntry) -> io::Result<()> {
+    use nix::sys::stat::{mode_t, Mode};
+
+    let mode = Mode::from_bits_truncate((entry.mode & 0o7777) as mode_t);
+    let result = if entry.is_fifo() {
+        nix::unistd::mkfifo(path, mode)
+    } else {
+        create_device(path, entry, mode)
+    };
+    result.map_err(io::Error::from)
+}
+
+#[cfg(target_os = "linux")]
+fn create_device(path: &Path, entry: &FileEntry, mode: nix::sys::stat::Mode) -> nix::Result<()> {
+    use nix::sys::stat::{makedev, mknod, SFlag};
+
+    let kind = if entry.mode & S_IFMT == S_IFBLK {
+        SFlag::S_IFBLK
+    } else {
+        SFlag::S_IFCHR
+    };
+    let device = makedev(entry.rdev_major.into(), entry.rdev_minor.into());
+    mknod(path, kind, mode, device)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn create_device(_path: &Path, _entry: &FileEntry, _mode: nix::sys::stat::Mode) -> nix::Result<()> {
+    Err(nix::Error::ENOTSUP)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn create_special(_path: &Path, _entry: &FileEntry) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Error: device nodes and FIFOs are not supported",
+    ))
+}
+
+/// Reserves disk space for a file of known size, so large files are not fragmented.
+/// Filesystems without fallocate support get the size only.
+pub(crate) fn preallocate(file: &File, size: u64) {
+    if size == 0 {
+        return;
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use nix::fcntl::{fallocate, FallocateFlags};
+        use std::os::unix::io::AsRawFd;
+
+        let len = i64::try_from(size).unwrap_or(i64::MAX);
+        if fallocate(file.as_raw_fd(), FallocateFlags::empty(), 0, len).is_ok() {
+            return;
+        }
+    }
+    let _ = file.set_len(size);
+}
+
+/// Applies mode, owner and modification time of the entry to the extracted path
+pub(crate) fn restore_metadata(
+    path: &Path,
+    entry: &FileEntry,
+    change_owner: bool,
+) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        if change_owner {
+            use nix::unistd::{chown, Gid, Uid};
+            use std::os::unix::fs::PermissionsExt;
+
+            let metadata = path.metadata()?;
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(entry.mode);
+            std::fs::set_permissions(path, permissions)?;
+            chown(
+                path,
+                Some(Uid::from_raw(entry.uid)),
+                Some(Gid::from_raw(entry.gid)),
+            )
+            .map_err(|e| {
+                io::Error::new(
+                    io::Error::from(e).kind(),
+                    format!("Error: can not change owner {}", e),
+                )
+            })?
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = change_owner;
+
+    let mtime = FileTime::from_unix_time(entry.mtime.into(), 0);
+    if entry.is_special() {
+        // set_file_mtime opens the path, which blocks on a FIFO, the node is not a symlink
+        set_symlink_file_times(path, mtime, mtime)
+    } else {
+        set_file_mtime(path, mtime)
+    }
+}
+
+/// Skips entry data together with the alignment padding
+pub(crate) fn skip_entry_data<R: Read>(reader: &mut R, entry: &FileEntry) -> io::Result<()> {
+    let size = entry.data_padding() + entry.file_size;
+    skip_bytes(reader, size.into())
+}
+
+/// Reads and discards bytes, so the reader does not have to implement Seek
+pub(crate) fn skip_bytes<R: Read>(reader: &mut R, count: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.by_ref().take(count), &mut io::sink())?;
+    if skipped < count {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Error: unexpected end of cpio archive",
+        ));
+    }
+    Ok(())
+}
+
+const BUFSIZE: usize = 256 * 1024;
+
+fn io_copy_exact<R: Read, W: Write>(reader: &mut R, writer: &mut W, count: u32) -> io::Result<u32> {
+    let mut buf = vec![0_u8; BUFSIZE.min(count as usize)];
+    let mut left = count as usize;
+
+    while left > 0 {
+        let n = left.min(BUFSIZE);
+        reader.read_exact(&mut buf[..n])?;
+        writer.write_all(&buf[..n])?;
+        left -= n;
+    }
+
+    Ok(count)
+}
+
+/// Entries of a cpio archive, data of the entries is skipped.
+/// Iteration stops at the trailer or after the first error.
+pub struct CpioEntries<T> {
+    reader: T,
+    finished: bool,
+}
+
+impl<T: Read + Seek> CpioEntries<T> {
+    pub fn new(reader: T) -> Self {
+        CpioEntries {
+            reader,
+            finished: false,
+        }
+    }
+
+    fn next_entry(&mut self) -> io::Result<Option<FileEntry>> {
+        let entry = FileEntry::read(&mut self.reader)?;
+        let position = entry.data_padding() + entry.file_size;
+        self.reader.seek(io::SeekFrom::Current(position.into()))?;
+        Ok(Some(entry).filter(|entry| entry.name != TRAILER))
+    }
+}
+
+impl<T: Read + Seek> Iterator for CpioEntries<T> {
+    type Item = io::Result<FileEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let entry = self.next_entry().transpose();
+        self.finished = !matches!(entry, Some(Ok(_)));
+        entry
+    }
+}
+
+/// Entries of a cpio archive together with their data read into memory.
+/// Iteration stops at the trailer or after the first error.
+pub struct CpioFiles<T> {
+    payload: CpioStream<T>,
+    finished: bool,
+}
+
+impl<T: Read + Seek> CpioFiles<T> {
+    pub fn new(reader: T) -> Self {
+        CpioFiles {
+            payload: CpioStream::new(reader),
+            finished: false,
+        }
+    }
+
+    fn next_file(&mut self) -> io::Result<Option<(FileEntry, Vec<u8>)>> {
+        let mut member = match self.payload.next_member()? {
+            Some(member) => member,
+            None => return Ok(None),
+        };
+        let mut data = Vec::with_capacity(member.entry().file_size as usize);
+        member.read_to_end(&mut data)?;
+        Ok(Some((member.into_entry(), data)))
+    }
+}
+
+impl<T: Read + Seek> Iterator for CpioFiles<T> {
+    type Item = io::Result<(FileEntry, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let file = self.next_file().transpose();
+        self.finished = !matches!(file, Some(Ok(_)));
+        file
+    }
+}
+
+pub trait CpioRead {
+    fn read_cpio_entry(&mut self) -> io::Result<FileEntry>;
+    fn read_cpio_entry_payload<W: Write>(
+        &mut self,
+        entry: &FileEntry,
+        write: &mut W,
+    ) -> io::Result<()>;
+}
+
+impl<R> CpioRead for R
+where
+    R: Read + Seek,
+{
+    fn read_cpio_entry(&mut self) -> io::Result<FileEntry> {
+        Ok(FileEntry::read(self)?)
+    }
+
+    fn read_cpio_entry_payload<W: Write>(
+        &mut self,
+        entry: &FileEntry,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        let file_size = entry.file_size;
+        io_copy_exact(self, writer, file_size)?;
+        let position = entry.data_padding() + entry.file_size;
+        self.seek(io::SeekFrom::Current(position.into()))?;
+        Ok(())
+    }
+}
+
+pub trait CpioWriter {
+    fn write_cpio_entry(&mut self, entry: FileEntry) -> io::Result<()>;
+
+    /// Writes entry data followed by the alignment padding of the newc formats
+    fn write_cpio_entry_payload<R: Read>(&mut self, reader: &mut R) -> io::Result<()>;
+
+    /// Writes entry data without padding, as odc entries are stored
+    fn write_cpio_entry_data<R: Read>(&mut self, reader: &mut R) -> io::Result<u64>;
+
+    fn write_cpio_file(&mut self, path: &PathBuf) -> io::Result<()> {
+        let entry: FileEntry = path.try_into()?;
+        self.write_cpio_entry(entry)?;
+        let mut file = File::open(path)?;
+        self.write_cpio_entry_payload(&mut file)
+    }
+
+    fn write_cpio_files(&mut self, paths: Vec<PathBuf>) -> io::Result<()> {
+        for path in &paths {
+            self.write_cpio_file(path)?
+        }
+        self.cpio_close()
+    }
+
+    /// Writes the entry and its data, data of Crc entries is buffered to sum it up first
+    fn write_cpio_record<R: Read>(
+        &mut self,
+        mut record: FileEntry,
+        data: &mut R,
+    ) -> io::Result<()> {
+        match record.format {
+            CpioFormat::Newc => {
+                self.write_cpio_entry(record)?;
+                return self.write_cpio_entry_payload(data);
+            }
+            CpioFormat::Odc => {
+                self.write_cpio_entry(record)?;
+                return self.write_cpio_entry_data(data).map(drop);
+            }
+            CpioFormat::Crc => {}
+        }
+
+        let mut buffer = Vec::with_capacity(record.file_size as usize);
+        data.take(record.file_size.into())
+            .read_to_end(&mut buffer)?;
+        record.checksum = cpio_checksum(0, &buffer);
+        self.write_cpio_entry(record)?;
+        self.write_cpio_entry_payload(&mut buffer.as_slice())
+    }
+
+    fn write_cpio_records<R: Read>(&mut self, records: Vec<(FileEntry, &mut R)>) -> io::Result<()> {
+        for (record, data) in records.into_iter() {
+            self.write_cpio_record(record, data)?;
+        }
+        self.cpio_close()
+    }
+
+    fn cpio_close(&mut self) -> io::Result<()> {
+        self.cpio_close_as(CpioFormat::Newc)
+    }
+
+    /// Writes the trailer, its format should match the entries
+    fn cpio_close_as(&mut self, format: CpioFormat) -> io::Result<()> {
+        self.write_cpio_entry(FileEntry {
+            format,
+            ..Default::default()
+        })
+    }
+}
+
+impl<W> CpioWriter for W
+where
+    W: Write,
+{
+    fn write_cpio_entry(&mut self, entry: FileEntry) -> io::Result<()> {
+        entry.write(self)
+    }
+
+    fn write_cpio_entry_data<R: Read>(&mut self, reader: &mut R) -> io::Result<u64> {
+        io::copy(reader, self)
+    }
+
+    fn write_cpio_entry_payload<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let file_size = io::copy(reader, self)? as u32;
+        let number = align_n_bytes(file_size, 4) as usize;
+        let pad = vec![0_u8; number];
+        self.write_all(&pad)
+    }
+}
+
+struct CpioRecord {
+    entry: FileEntry,
+    data: Box<dyn Read>,
+    path: Option<PathBuf>,
+    size: u32,
+}
+
+/// Owner in the `user[:group]` form, ids are numeric or `root`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Owner {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl FromStr for Owner {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (user, group) = match s.find(':') {
+            Some(position) => (&s[..position], Some(&s[position + 1..])),
+            None => (s, None),
+        };
+        let id = |s: &str| -> io::Result<Option<u32>> {
+            match s {
+                "" => Ok(None),
+                "root" => Ok(Some(0)),
+                _ => s.parse().map(Some).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Error: incorrect owner {}, use numeric ids or root", s),
+                    )
+                }),
+            }
+        };
+        Ok(Owner {
+            uid: id(user)?,
+            gid: group.map(id).transpose()?.flatten(),
+        })
+    }
+}
+
+/// Metadata forced on every member instead of the values of the source files
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EntryOverrides {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Permission bits kept on files and directories, e.g. `0o755`
+    pub mode_mask: Option<u32>,
+    pub mtime: Option<u32>,
+    /// Later modification times are set to this one
+    pub mtime_clamp: Option<u32>,
+}
+
+impl EntryOverrides {
+    pub fn apply(&self, entry: &mut FileEntry) {
+        if let Some(uid) = self.uid {
+            entry.uid = uid;
+        }
+        if let Some(gid) = self.gid {
+            entry.gid = gid;
+        }
+        if let Some(mask) = self.mode_mask.filter(|_| entry.is_file() || entry.is_dir()) {
+            entry.mode &= S_IFMT | mask;
+        }
+        if let Some(mtime) = self.mtime {
+            entry.mtime = mtime;
+        }
+        if let Some(clamp) = self.mtime_clamp {
+            entry.mtime = entry.mtime.min(clamp);
+        }
+    }
+}
+
+pub struct CpioBuilder<W: Write> {
+    writer: Option<W>,
+    records: Vec<CpioRecord>,
+    dedup_identical_content: bool,
+    linked: bool,
+    xattrs: Option<XattrManifest>,
+    overrides: EntryOverrides,
+    deterministic: bool,
+    format: CpioFormat,
+}
+
+impl<W: Write + CpioWriter> CpioBuilder<W> {
+    pub fn new(writer: W) -> Self {
+        CpioBuilder {
+            writer: Some(writer),
+            records: Vec::new(),
+            dedup_identical_content: false,
+            linked: false,
+            xattrs: None,
+            overrides: EntryOverrides::default(),
+            deterministic: false,
+            format: CpioFormat::Newc,
+        }
+    }
+
+    pub fn add_raw_file(mut self, path: &PathBuf) -> io::Result<Self> {
+        let record: FileEntry = path.try_into()?;
+        self.push(record, path)?;
+        Ok(self)
+    }
+
+    pub fn add_file(mut self, path: &str, as_path: &str) -> io::Result<Self> {
+        let file = PathBuf::from(path);
+        let mut record: FileEntry = (&file).try_into()?;
+        record.name = as_path.to_owned();
+        self.push(record, &file)?;
+        Ok(self)
+    }
+
+    /// Adds the file with the permission bits of `mode` instead of the source ones
+    pub fn add_file_with_mode(mut self, path: &str, as_path: &str, mode: u32) -> io::Result<Self> {
+        let file = PathBuf::from(path);
+        let mut record: FileEntry = (&file).try_into()?;
+        record.name = as_path.to_owned();
+        record.mode = record.mode & S_IFMT | mode & 0o7777;
+        self.push(record, &file)?;
+        Ok(self)
+    }
+
+    /// Adds an entry without a source file, `data` is its content, e.g. the target of a symlink
+    pub fn add_entry(mut self, mut entry: FileEntry, data: Vec<u8>) -> Self {
+        entry.file_size = data.len() as u32;
+        self.overrides.apply(&mut entry);
+        self.records.push(CpioRecord {
+            size: entry.file_size,
+            entry,
+            data: Box::new(io::Cursor::new(data)),
+            path: None,
+        });
+        self
+    }
+
+    /// Adds `as_path` as a hardlink of the already added regular file `target`,
+    /// files linked on disk are detected without it
+    pub fn add_hardlink(mut self, as_path: &str, target: &str) -> io::Result<Self> {
+        let path = self
+            .records
+            .iter()
+            .find(|record| record.entry.name == target && record.entry.is_file())
+            .and_then(|record| record.path.clone())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Error: hardlink target {} is not added", target),
+                )
+            })?;
+
+        let mut entry: FileEntry = (&path).try_into()?;
+        entry.name = as_path.to_owned();
+        self.push(entry, &path)?;
+        // members are grouped by inode only if it has several links
+        let key = self.records[self.records.len() - 1].entry.link_key();
+        for record in self.records.iter_mut() {
+            if record.entry.is_file() && record.entry.link_key() == key {
+                record.entry.nlink = record.entry.nlink.max(2);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Adds the symlink `path` as `as_path`, the link is stored instead of its target
+    pub fn add_symlink(mut self, path: &str, as_path: &str) -> io::Result<Self> {
+        let meta = Path::new(path).symlink_metadata()?;
+        let target = std::fs::read_link(path)?
+            .to_str()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Error: symlink target of {} is not utf-8", path),
+                )
+            })?
+            .to_owned();
+
+        let mut entry = FileEntry {
+            name: as_path.to_owned(),
+            mode: S_IFLNK | 0o777,
+            nlink: 1,
+            mtime: FileTime::from_last_modification_time(&meta).unix_seconds() as u32,
+            file_size: target.len() as u32,
+            ..Default::default()
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            entry.uid = meta.uid();
+            entry.gid = meta.gid();
+        }
+        self.overrides.apply(&mut entry);
+        self.records.push(CpioRecord {
+            size: entry.file_size,
+            entry,
+            data: Box::new(io::Cursor::new(target.into_bytes())),
+            path: None,
+        });
+        Ok(self)
+    }
+
+    /// Stores byte-identical regular files once, as hardlinks of each other
+    pub fn dedup_identical_content(mut self, enabled: bool) -> Self {
+        self.dedup_identical_content = enabled;
+        self
+    }
+
+    /// Captures user attributes and IMA signatures of added files,
+    /// cpio has no place for them, so they are kept in a sidecar manifest
+    pub fn xattrs(mut self, enabled: bool) -> Self {
+        self.xattrs = if enabled {
+            Some(XattrManifest::new())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Owner of all members, a missing user or group keeps the value of the files
+    pub fn owner(mut self, owner: Owner) -> Self {
+        self.overrides.uid = owner.uid.or(self.overrides.uid);
+        self.overrides.gid = owner.gid.or(self.overrides.gid);
+        self
+    }
+
+    /// Keeps only the permission bits of the mask on files and directories
+    pub fn mode_mask(mut self, mask: u32) -> Self {
+        self.overrides.mode_mask = Some(mask & 0o7777);
+        self
+    }
+
+    /// Modification time of all members, e.g. from SOURCE_DATE_EPOCH
+    pub fn mtime(mut self, mtime: u32) -> Self {
+        self.overrides.mtime = Some(mtime);
+        self
+    }
+
+    /// Modification times later than the epoch are set to it
+    pub fn clamp_mtime(mut self, epoch: u32) -> Self {
+        self.overrides.mtime_clamp = Some(epoch);
+        self
+    }
+
+    /// Same archive from the same files regardless of the order they are added in
+    /// and of their inodes: entries are sorted by name, inodes are numbered from 1
+    /// and device ids of non-device entries are zeroed
+    pub fn deterministic(mut self, enabled: bool) -> Self {
+        self.deterministic = enabled;
+        self
+    }
+
+    /// Format of all members, data of Crc members is buffered while it is written
+    pub fn format(mut self, format: CpioFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Attributes captured from added files, if enabled
+    pub fn xattr_manifest(&self) -> Option<&XattrManifest> {
+        self.xattrs.as_ref()
+    }
+
+    /// File metadata as written to the archive, to be used in the package header
+    pub fn files(&mut self) -> io::Result<Vec<FileInfo>> {
+        self.link()?;
+        Ok(self
+            .records
+            .iter()
+            .map(|record| FileInfo {
+                size: record.size.into(),
+                ..FileInfo::from(&record.entry)
+            })
+            .collect())
     }
 
     pub fn build(self) -> io::Result<()> {
+        self.write_records(None).map(|_| ())
+    }
+
+    /// Writes the archive and returns hex digests of regular files by member name,
+    /// the data is hashed while it is copied, hardlinks share the digest of the group
+    pub fn build_with_digests(self, algo: DigestAlgo) -> io::Result<HashMap<String, String>> {
+        self.write_records(Some(algo))
+    }
+
+    fn write_records(mut self, algo: Option<DigestAlgo>) -> io::Result<HashMap<String, String>> {
+        self.link()?;
         match self {
             CpioBuilder {
                 writer: Some(mut writer),
                 records,
+                format,
+                ..
             } => {
-                for (record, mut data) in records.into_iter() {
-                    writer.write_cpio_record(record, &mut data)?;
+                let mut by_inode = HashMap::new();
+                let mut members = Vec::new();
+                for mut record in records.into_iter() {
+                    record.entry.format = format;
+                    match algo.filter(|_| record.entry.is_file()) {
+                        Some(algo) => {
+                            let (name, ino) = (record.entry.name.clone(), record.entry.ino);
+                            let mut reader = algo.reader(record.data);
+                            writer.write_cpio_record(record.entry, &mut reader)?;
+                            // only the last member of a hardlink group carries the data
+                            by_inode.insert(ino, reader.finish());
+                            members.push((name, ino));
+                        }
+                        None => writer.write_cpio_record(record.entry, &mut record.data)?,
+                    }
                 }
-                writer.cpio_close()
+                writer.cpio_close_as(format)?;
+                Ok(members
+                    .into_iter()
+                    .map(|(name, ino)| (name, by_inode[&ino].clone()))
+                    .collect())
             }
             _ => Err(io::Error::new(io::ErrorKind::Other, "Writer not found")),
         }
     }
+
+    fn push(&mut self, mut entry: FileEntry, path: &Path) -> io::Result<()> {
+        self.overrides.apply(&mut entry);
+        if let Some(manifest) = self.xattrs.as_mut().filter(|_| !entry.is_symlink()) {
+            manifest.capture(&entry.name, path)?;
+        }
+        // directories carry no data, the size from the metadata is not used
+        let data: Box<dyn Read> = if entry.is_dir() {
+            entry.file_size = 0;
+            Box::new(io::empty())
+        } else {
+            Box::new(File::open(path)?)
+        };
+        self.records.push(CpioRecord {
+            size: entry.file_size,
+            entry,
+            data,
+            path: Some(path.to_owned()),
+        });
+        Ok(())
+    }
+
+    /// Groups files sharing an inode, or identical content if enabled, into hardlinks:
+    /// members share a renumbered inode and only the last member carries the data
+    fn link(&mut self) -> io::Result<()> {
+        if self.linked {
+            return Ok(());
+        }
+        self.linked = true;
+        if self.deterministic {
+            self.records.sort_by(|a, b| a.entry.name.cmp(&b.entry.name));
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut by_inode = HashMap::new();
+        let mut by_content = HashMap::new();
+
+        for (i, record) in self.records.iter().enumerate() {
+            let entry = &record.entry;
+            if !entry.is_file() {
+                groups.push(vec![i]);
+                continue;
+            }
+
+            let inode = Some(entry.link_key()).filter(|_| entry.nlink > 1);
+            let content = match &record.path {
+                Some(path) if self.dedup_identical_content && entry.file_size > 0 => {
+                    let digest = DigestAlgo::Sha256.digest(&mut File::open(path)?)?;
+                    Some((entry.file_size, digest))
+                }
+                _ => None,
+            };
+
+            let group = inode
+                .and_then(|key| by_inode.get(&key))
+                .or_else(|| content.as_ref().and_then(|key| by_content.get(key)))
+                .copied()
+                .unwrap_or_else(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+            groups[group].push(i);
+            if let Some(key) = inode {
+                by_inode.entry(key).or_insert(group);
+            }
+            if let Some(key) = content {
+                by_content.entry(key).or_insert(group);
+            }
+        }
+
+        for (group, members) in groups.iter().enumerate() {
+            let first = &self.records[members[0]].entry;
+            let (dev_major, dev_minor, ino) = if self.deterministic {
+                (0, 0, group as u32 + 1)
+            } else {
+                (first.dev_major, first.dev_minor, members[0] as u32 + 1)
+            };
+
+            for (position, &i) in members.iter().enumerate() {
+                let record = &mut self.records[i];
+                record.entry.ino = ino;
+                record.entry.dev_major = dev_major;
+                record.entry.dev_minor = dev_minor;
+                if self.deterministic && !record.entry.is_device() {
+                    record.entry.rdev_major = 0;
+                    record.entry.rdev_minor = 0;
+                }
+                if record.entry.is_file() {
+                    record.entry.nlink = members.len() as u32;
+                }
+                if position + 1 < members.len() {
+                    record.entry.file_size = 0;
+                    record.data = Box::new(io::empty());
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl CpioBuilder<File> {
     pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let writer = OpenOptions::new().create(true).write(true).open(path)?;
-        Ok(CpioBuilder {
-            writer: Some(writer),
-            records: Vec::new(),
-        })
+        Ok(CpioBuilder::new(writer))
     }
 }
 
@@ -532,4 +1756,466 @@ mod tests {
         assert_eq!(entry, FileEntry::default());
         Ok(())
     }
+
+    #[test]
+    fn test_cpio_iterators() -> io::Result<()> {
+        let mut cpio = Vec::new();
+        for (name, data) in &[("./a", &b"first"[..]), ("./b", b"second")] {
+            let entry = FileEntry {
+                name: (*name).to_owned(),
+                mode: S_IFREG | 0o644,
+                nlink: 1,
+                file_size: data.len() as u32,
+                ..Default::default()
+            };
+            cpio.write_cpio_record(entry, &mut &data[..])?;
+        }
+        cpio.cpio_close()?;
+
+        let files = CpioFiles::new(io::Cursor::new(&cpio)).collect::<io::Result<Vec<_>>>()?;
+        let files: Vec<_> = files
+            .iter()
+            .map(|(entry, data)| (entry.name.as_str(), data.as_slice()))
+            .collect();
+        assert_eq!(files, vec![("./a", &b"first"[..]), ("./b", b"second")]);
+        let names: Vec<_> = CpioEntries::new(io::Cursor::new(&cpio))
+            .map(|entry| entry.map(|entry| entry.name))
+            .collect::<io::Result<_>>()?;
+        assert_eq!(names, vec!["./a", "./b"]);
+
+        // truncated inside the data of the second entry, the trailer takes 124 bytes
+        let truncated = &cpio[..cpio.len() - 127];
+        let mut files = CpioFiles::new(io::Cursor::new(truncated));
+        assert_eq!(files.next().unwrap()?.0.name, "./a");
+        assert!(files.next().unwrap().is_err());
+        assert!(files.next().is_none());
+        let mut entries = CpioEntries::new(io::Cursor::new(truncated));
+        assert!(entries.by_ref().take(2).all(|entry| entry.is_ok()));
+        assert!(entries.next().unwrap().is_err());
+        assert!(entries.next().is_none());
+
+        // corrupt magic of an entry in the middle
+        let second = cpio[1..].windows(6).position(|w| w == MAGIC).unwrap() + 1;
+        let mut corrupt = cpio.clone();
+        corrupt[second] = b'1';
+        let results: Vec<_> = CpioEntries::new(io::Cursor::new(&corrupt)).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_odc_round_trip() -> io::Result<()> {
+        let records: &[(&str, u32, &[u8])] = &[
+            ("./etc", S_IFDIR | 0o755, b""),
+            ("./etc/odd", S_IFREG | 0o644, b"odd size"),
+            ("./etc/next", S_IFREG | 0o600, b"x"),
+        ];
+        let entries: Vec<_> = records
+            .iter()
+            .enumerate()
+            .map(|(ino, (name, mode, data))| FileEntry {
+                name: (*name).to_owned(),
+                ino: ino as u32 + 1,
+                mode: *mode,
+                uid: 1000,
+                mtime: 1_700_000_000,
+                file_size: data.len() as u32,
+                dev_major: 8,
+                dev_minor: 1,
+                format: CpioFormat::Odc,
+                ..Default::default()
+            })
+            .collect();
+        let mut cpio = Vec::new();
+        for (entry, (name, mode, data)) in entries.iter().zip(records) {
+            let entry = FileEntry {
+                name: (*name).to_owned(),
+                mode: *mode,
+                ..*entry
+            };
+            cpio.write_cpio_record(entry, &mut &data[..])?;
+        }
+        cpio.cpio_close_as(CpioFormat::Odc)?;
+
+        assert!(cpio.starts_with(b"070707004001000001040755001750"));
+        assert_eq!(read_entries(&mut io::Cursor::new(&cpio))?, entries);
+        let files = CpioFiles::new(io::Cursor::new(&cpio)).collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(files[1].1, b"odd size");
+
+        let dir = tempfile::tempdir()?;
+        let report =
+            extract_entries_with(&mut cpio.as_slice(), dir.path(), &ExtractOptions::default())?;
+        assert_eq!((report.dirs_created, report.files_created), (1, 2));
+        assert_eq!(std::fs::read(dir.path().join("etc/next"))?, b"x");
+
+        let large = FileEntry {
+            ino: 1 << 18,
+            format: CpioFormat::Odc,
+            ..Default::default()
+        };
+        let err = Vec::new().write_cpio_entry(large).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_large_file_with_sync() -> io::Result<()> {
+        let data: Vec<u8> = (0..BUFSIZE * 2 + 7).map(|i| i as u8).collect();
+        let entry = FileEntry {
+            name: "./large".to_owned(),
+            mode: 0o100_644,
+            nlink: 1,
+            file_size: data.len() as u32,
+            ..Default::default()
+        };
+        let mut cpio = Vec::new();
+        cpio.write_cpio_record(entry, &mut data.as_slice())?;
+        cpio.cpio_close()?;
+
+        let dir = tempfile::tempdir()?;
+        let options = ExtractOptions {
+            sync: true,
+            ..Default::default()
+        };
+        let report = extract_entries_with(&mut cpio.as_slice(), dir.path(), &options)?;
+        assert_eq!(report.bytes_written, data.len() as u64);
+        assert_eq!(std::fs::read(dir.path().join("large"))?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_types_by_mode_not_nlink() -> io::Result<()> {
+        let mut cpio = Vec::new();
+        let records: &[(&str, u32, u32, &[u8])] = &[
+            ("./share", S_IFDIR | 0o755, 5, b""),
+            ("./share/a", S_IFREG | 0o644, 2, b"linked"),
+            ("./share/doc", S_IFDIR | 0o755, 1, b""),
+            ("./share/doc/b", S_IFREG | 0o644, 1, b"b"),
+        ];
+        for (ino, (name, mode, nlink, data)) in records.iter().enumerate() {
+            let entry = FileEntry {
+                name: (*name).to_owned(),
+                ino: ino as u32,
+                mode: *mode,
+                nlink: *nlink,
+                file_size: data.len() as u32,
+                ..Default::default()
+            };
+            cpio.write_cpio_record(entry, &mut &data[..])?;
+        }
+        cpio.cpio_close()?;
+
+        let dir = tempfile::tempdir()?;
+        let report =
+            extract_entries_with(&mut cpio.as_slice(), dir.path(), &ExtractOptions::default())?;
+        assert_eq!((report.dirs_created, report.files_created), (2, 2));
+        assert!(dir.path().join("share/doc").is_dir());
+        assert_eq!(std::fs::read(dir.path().join("share/a"))?, b"linked");
+        assert_eq!(std::fs::read(dir.path().join("share/doc/b"))?, b"b");
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_extract_special_files() -> io::Result<()> {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+        let mut cpio = Vec::new();
+        let records = &[
+            ("./dev/null", S_IFCHR | 0o666),
+            ("./run/fifo", S_IFIFO | 0o600),
+        ];
+        for (name, mode) in records {
+            let entry = FileEntry {
+                name: (*name).to_owned(),
+                mode: *mode,
+                nlink: 1,
+                rdev_major: 1,
+                rdev_minor: 3,
+                ..Default::default()
+            };
+            cpio.write_cpio_entry(entry)?;
+        }
+        cpio.cpio_close()?;
+
+        let entry = FileEntry::read(&mut cpio.as_slice())?;
+        assert!(entry.is_device() && entry.is_special() && !entry.is_supported());
+
+        let dir = tempfile::tempdir()?;
+        let report =
+            extract_entries_with(&mut cpio.as_slice(), dir.path(), &ExtractOptions::default())?;
+        assert_eq!(report.specials_created, 0);
+        assert!(report
+            .skipped
+            .iter()
+            .all(|(_, r)| *r == SkipReason::Unsupported));
+        assert_eq!(report.skipped.len(), 2);
+        assert!(dir.path().join("run/fifo").symlink_metadata().is_err());
+
+        let options = ExtractOptions {
+            special_files: true,
+            ..Default::default()
+        };
+        let report = extract_entries_with(&mut cpio.as_slice(), dir.path(), &options)?;
+        let fifo = dir.path().join("run/fifo").symlink_metadata()?;
+        assert!(fifo.file_type().is_fifo());
+        assert_eq!(fifo.mode() & 0o777, 0o600);
+
+        // devices need root, otherwise they are skipped
+        match dir.path().join("dev/null").symlink_metadata() {
+            Ok(device) => {
+                assert!(device.file_type().is_char_device());
+                assert_eq!(device.rdev(), nix::sys::stat::makedev(1, 3));
+                assert_eq!(report.specials_created, 2);
+            }
+            Err(_) => assert_eq!(
+                report.skipped,
+                vec![("./dev/null".to_owned(), SkipReason::PermissionDenied)]
+            ),
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_symlinks_inside_dir() -> io::Result<()> {
+        let mut cpio = Vec::new();
+        let records: &[(&str, u32, &[u8])] = &[
+            ("./usr/bin/hello", S_IFREG | 0o755, b"hello"),
+            ("./usr/bin/hi", S_IFLNK | 0o777, b"hello"),
+            ("./usr/lib/up", S_IFLNK | 0o777, b"../../etc"),
+            ("./etc/escape", S_IFLNK | 0o777, b"../../outside"),
+            ("../evil", S_IFREG | 0o644, b"evil"),
+            ("./tmp", S_IFLNK | 0o777, b"/tmp"),
+            ("./tmp/evil", S_IFREG | 0o644, b"evil"),
+        ];
+        for (name, mode, data) in records {
+            let entry = FileEntry {
+                name: (*name).to_owned(),
+                mode: *mode,
+                nlink: 1,
+                file_size: data.len() as u32,
+                ..Default::default()
+            };
+            cpio.write_cpio_record(entry, &mut &data[..])?;
+        }
+        cpio.cpio_close()?;
+
+        let root = tempfile::tempdir()?;
+        let dir = root.path().join("extracted");
+        std::fs::create_dir(&dir)?;
+        let report = extract_entries_with(&mut cpio.as_slice(), &dir, &ExtractOptions::default())?;
+
+        let link = dir.join("usr/bin/hi");
+        assert!(link.symlink_metadata()?.file_type().is_symlink());
+        assert_eq!(std::fs::read_link(&link)?, Path::new("hello"));
+        assert_eq!(std::fs::read(&link)?, b"hello");
+        assert!(dir.join("usr/lib/up").symlink_metadata().is_ok());
+        assert_eq!(report.symlinks_created, 3);
+
+        let unsafe_names: Vec<_> = report
+            .skipped
+            .iter()
+            .filter(|(_, reason)| *reason == SkipReason::UnsafePath)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(unsafe_names, vec!["./etc/escape", "../evil", "./tmp/evil"]);
+        assert!(report.is_partial());
+        assert!(!root.path().join("evil").exists());
+        assert!(dir.join("etc/escape").symlink_metadata().is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_and_extract_hardlinks() -> io::Result<()> {
+        use crate::header::{Tag, Tags};
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir()?;
+        let staging = dir.path().join("staging");
+        std::fs::create_dir(&staging)?;
+        std::fs::write(staging.join("a"), "shared content")?;
+        std::fs::hard_link(staging.join("a"), staging.join("b"))?;
+        std::fs::write(staging.join("c"), "shared content")?;
+        std::fs::write(staging.join("d"), "other content")?;
+
+        let add = |builder: CpioBuilder<File>, name: &str| {
+            let path = staging.join(name);
+            builder.add_file(path.to_str().unwrap(), &format!("./usr/share/{}", name))
+        };
+        let cpio = dir.path().join("linked.cpio");
+        let mut builder = CpioBuilder::open(&cpio)?.dedup_identical_content(true);
+        for name in &["a", "b", "c", "d"] {
+            builder = add(builder, name)?;
+        }
+
+        let files = builder.files()?;
+        let nlinks: Vec<u32> = files.iter().map(|f| f.nlink).collect();
+        assert_eq!(nlinks, vec![3, 3, 3, 1]);
+        assert_eq!(files[0].inode, files[2].inode);
+        assert_ne!(files[0].inode, files[3].inode);
+        assert!(files.iter().take(3).all(|f| f.size == 14));
+
+        let mut tags = Tags::<Tag>::new();
+        tags.insert_files(&files);
+        let inodes = tags.get_as_u32_array_or(Tag::FileInodes);
+        assert_eq!(inodes[0], inodes[1]);
+        assert_eq!(tags.get_as_u32_array_or(Tag::FileNLinks), nlinks);
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::DirNames),
+            vec!["/usr/share/"]
+        );
+        builder.build()?;
+
+        // the shared content is stored once, with the last member
+        let entries = read_entries(&mut File::open(&cpio)?)?;
+        let sizes: Vec<u32> = entries.iter().map(|e| e.file_size).collect();
+        assert_eq!(sizes, vec![0, 0, 14, 13]);
+
+        let target = dir.path().join("target");
+        let report = extract_entries_with(&mut File::open(&cpio)?, &target, &Default::default())?;
+        assert_eq!(report.files_created, 2);
+        assert_eq!(report.hardlinks_created, 2);
+
+        let share = target.join("usr/share");
+        let inode = share.join("a").metadata()?.ino();
+        for name in &["b", "c"] {
+            assert_eq!(share.join(name).metadata()?.ino(), inode);
+        }
+        assert_eq!(std::fs::read_to_string(share.join("a"))?, "shared content");
+        assert_eq!(share.join("a").metadata()?.nlink(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_with_overrides() -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let staging = dir.path().join("staging");
+        std::fs::create_dir(&staging)?;
+        std::fs::write(staging.join("a"), "a")?;
+        std::fs::set_permissions(staging.join("a"), std::fs::Permissions::from_mode(0o4775))?;
+
+        let cpio = dir.path().join("overrides.cpio");
+        let owner: Owner = "root:123".parse()?;
+        let mut builder = CpioBuilder::open(&cpio)?
+            .owner(owner)
+            .mode_mask(0o755)
+            .mtime(1_600_000_000);
+        for (name, as_path) in &[("", "./opt"), ("a", "./opt/a")] {
+            let path = staging.join(name);
+            builder = builder.add_file(path.to_str().unwrap(), as_path)?;
+        }
+        builder.build()?;
+
+        let entries = read_entries(&mut File::open(&cpio)?)?;
+        let modes: Vec<u32> = entries.iter().map(|e| e.mode).collect();
+        assert_eq!(modes, vec![0o040_755, 0o100_755]);
+        for entry in &entries {
+            assert_eq!((entry.uid, entry.gid), (0, 123));
+            assert_eq!(entry.mtime, 1_600_000_000);
+        }
+
+        assert_eq!(
+            ":0".parse::<Owner>()?,
+            Owner {
+                uid: None,
+                gid: Some(0)
+            }
+        );
+        assert!("builder".parse::<Owner>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deterministic_build() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let build = |staging: &Path, names: &[&str]| -> io::Result<Vec<u8>> {
+            std::fs::create_dir(staging)?;
+            std::fs::write(staging.join("a"), "shared")?;
+            std::fs::hard_link(staging.join("a"), staging.join("b"))?;
+            std::fs::write(staging.join("c"), "new")?;
+            for (name, mtime) in &[("a", 1_000), ("c", 2_000_000_000)] {
+                set_file_mtime(staging.join(name), FileTime::from_unix_time(*mtime, 0))?;
+            }
+
+            let cpio = staging.with_extension("cpio");
+            let mut builder = CpioBuilder::open(&cpio)?
+                .deterministic(true)
+                .clamp_mtime(1_600_000_000);
+            for name in names {
+                let path = staging.join(name);
+                builder = builder.add_file(path.to_str().unwrap(), &format!("./{}", name))?;
+            }
+            builder.build()?;
+            std::fs::read(cpio)
+        };
+
+        let first = build(&dir.path().join("first"), &["a", "b", "c"])?;
+        let second = build(&dir.path().join("second"), &["c", "b", "a"])?;
+        assert_eq!(first, second);
+
+        let entries = read_entries(&mut io::Cursor::new(&first))?;
+        let inodes: Vec<(&str, u32, u32)> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.ino, e.mtime))
+            .collect();
+        assert_eq!(
+            inodes,
+            vec![
+                ("./a", 1, 1_000),
+                ("./b", 1, 1_000),
+                ("./c", 2, 1_600_000_000)
+            ]
+        );
+        assert!(entries.iter().all(|e| e.dev_major == 0 && e.dev_minor == 0));
+
+        let target = dir.path().join("target");
+        let report = extract_entries_with(&mut first.as_slice(), &target, &Default::default())?;
+        assert_eq!(report.hardlinks_created, 1);
+        assert_eq!(std::fs::read_to_string(target.join("b"))?, "shared");
+        assert_eq!(std::fs::read_to_string(target.join("c"))?, "new");
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_explicit_hardlink() -> io::Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir()?;
+        let source = dir.path().join("source");
+        std::fs::write(&source, "linked")?;
+
+        let cpio = dir.path().join("explicit.cpio");
+        let builder = CpioBuilder::open(&cpio)?
+            .add_file(source.to_str().unwrap(), "./bin/a")?
+            .add_hardlink("./bin/b", "./bin/a")?;
+        assert!(CpioBuilder::new(Vec::new())
+            .add_hardlink("./bin/b", "./bin/a")
+            .is_err());
+        builder.build()?;
+
+        let entries = read_entries(&mut File::open(&cpio)?)?;
+        let links: Vec<(u32, u32, u32)> = entries
+            .iter()
+            .map(|e| (e.ino, e.nlink, e.file_size))
+            .collect();
+        assert_eq!(links, vec![(1, 2, 0), (1, 2, 6)]);
+
+        let target = dir.path().join("target");
+        extract_entries_with(&mut File::open(&cpio)?, &target, &Default::default())?;
+        let bin = target.join("bin");
+        assert_eq!(
+            bin.join("a").metadata()?.ino(),
+            bin.join("b").metadata()?.ino()
+        );
+        assert_eq!(std::fs::read_to_string(bin.join("a"))?, "linked");
+        Ok(())
+    }
 }
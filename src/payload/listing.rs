@@ -0,0 +1,448 @@
+use chrono::{Local, TimeZone};
+use std::cmp::Reverse;
+use std::io;
+use std::str::FromStr;
+
+use super::{FileInfo, RPMPayload};
+use crate::utils::{S_IFDIR, S_IFLNK, S_IFMT};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Mtime,
+}
+
+impl FromStr for SortKey {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(SortKey::Name),
+            "size" => Ok(SortKey::Size),
+            "mtime" => Ok(SortKey::Mtime),
+            key => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Error: unknown sort key {}", key),
+            )),
+        }
+    }
+}
+
+impl FileInfo {
+    pub fn is_dir(&self) -> bool {
+        u32::from(self.mode) & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        u32::from(self.mode) & S_IFMT == S_IFLNK
+    }
+
+    /// Mode in the `ls -l` form, e.g. `-rwxr-xr-x`
+    pub fn mode_string(&self) -> String {
+        mode_to_string(self.mode)
+    }
+}
+
+/// Mode in the `ls -l` form, e.g. `-rwsr-xr-x`. Setuid, setgid and sticky bits replace
+/// the execute bits with `s` and `t`, or with `S` and `T` if the execute bit is not set.
+pub fn mode_to_string(mode: u16) -> String {
+    let kind = match u32::from(mode) & S_IFMT {
+        S_IFDIR => 'd',
+        S_IFLNK => 'l',
+        0o020_000 => 'c',
+        0o060_000 => 'b',
+        0o010_000 => 'p',
+        0o140_000 => 's',
+        _ => '-',
+    };
+
+    let mut string = String::with_capacity(10);
+    string.push(kind);
+    // shift of the permission bits with the special bit and its letter
+    for &(shift, special, letter) in &[(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        string.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        string.push(match (mode & special != 0, bits & 0o1 != 0) {
+            (true, true) => letter,
+            (true, false) => letter.to_ascii_uppercase(),
+            (false, true) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+/// Columns of verbose file listings, shared by rpm-info and rpm-payload-ls
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ListingFormat {
+    /// Sizes with a binary unit suffix, e.g. `1.5K`
+    pub human_readable: bool,
+    /// Append the file class after the path
+    pub classify: bool,
+    /// Append the digest labeled with its algorithm, e.g. `sha256:abcd...`
+    pub digests: bool,
+}
+
+impl ListingFormat {
+    /// Mode, size, mtime and path, then the class if classify is set, the digest
+    /// if digests is set and the capabilities if the file has them, separated by tabs
+    pub fn line(&self, file: &FileInfo) -> String {
+        let size = if self.human_readable {
+            human_size(file.size)
+        } else {
+            file.size.to_string()
+        };
+        let mtime = Local
+            .timestamp_opt(file.mtime.into(), 0)
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+
+        let line = format!(
+            "{} {:>10} {} {}",
+            file.mode_string(),
+            size,
+            mtime,
+            file.name
+        );
+        self.extra_columns(line, file)
+    }
+
+    /// Line of `rpm -q --dump`: path size mtime digest mode owner group
+    /// isconfig isdoc rdev symlink, then the class and capabilities as in line().
+    /// Ghost files have size 0 and a zero digest as they are not in the payload.
+    pub fn dump(&self, file: &FileInfo) -> String {
+        let size = if file.is_ghost() { 0 } else { file.size };
+        let digest = if file.is_ghost() || file.digest.is_empty() {
+            "0".repeat(32)
+        } else {
+            file.digest.clone()
+        };
+        let linkname = if file.linkname.is_empty() {
+            "X"
+        } else {
+            &file.linkname
+        };
+
+        let line = format!(
+            "{} {} {} {} 0{:o} {} {} {} {} {} {}",
+            file.name,
+            size,
+            file.mtime,
+            digest,
+            file.mode,
+            file.user,
+            file.group,
+            u8::from(file.is_config()),
+            u8::from(file.is_doc()),
+            file.rdev,
+            linkname
+        );
+        self.extra_columns(line, file)
+    }
+
+    fn extra_columns(&self, mut line: String, file: &FileInfo) -> String {
+        if self.classify {
+            line.push('\t');
+            line.push_str(&file.class);
+        }
+        if self.digests {
+            line.push('\t');
+            line.push_str(&file.labeled_digest().unwrap_or_default());
+        }
+        if !file.caps.is_empty() {
+            line.push('\t');
+            line.push_str(&file.caps);
+        }
+        line
+    }
+}
+
+/// Files listed as `rpm -qlv` does, one line per file with mode, owner, group, size,
+/// mtime and path, symlinks with `-> target`. Columns are aligned over all files.
+pub fn format_file_listing(payload: &RPMPayload) -> String {
+    let width =
+        |column: fn(&FileInfo) -> usize| payload.files.iter().map(column).max().unwrap_or_default();
+    let user_width = width(|file| file.user.len());
+    let group_width = width(|file| file.group.len());
+    let size_width = width(|file| file.size.to_string().len());
+
+    let mut listing = String::new();
+    for file in &payload.files {
+        let mtime = Local
+            .timestamp_opt(file.mtime.into(), 0)
+            .single()
+            .map(|t| t.format("%b %d %H:%M").to_string())
+            .unwrap_or_default();
+        listing.push_str(&format!(
+            "{}  {:<uw$} {:<gw$}  {:>sw$} {} {}",
+            file.mode_string(),
+            file.user,
+            file.group,
+            file.size,
+            mtime,
+            file.name,
+            uw = user_width,
+            gw = group_width,
+            sw = size_width,
+        ));
+        if file.is_symlink() {
+            listing.push_str(" -> ");
+            listing.push_str(&file.linkname);
+        }
+        listing.push('\n');
+    }
+    listing
+}
+
+/// Sorts files by the key, ties are ordered by name
+pub fn sort_files(files: &mut [FileInfo], key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Name => files.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Size => files.sort_by(|a, b| a.size.cmp(&b.size).then(a.name.cmp(&b.name))),
+        SortKey::Mtime => files.sort_by(|a, b| a.mtime.cmp(&b.mtime).then(a.name.cmp(&b.name))),
+    }
+    if reverse {
+        files.reverse();
+    }
+}
+
+/// Returns n largest regular files, the largest first
+pub fn largest_files(files: &[FileInfo], n: usize) -> Vec<&FileInfo> {
+    let mut largest: Vec<&FileInfo> = files.iter().filter(|f| !f.is_dir()).collect();
+    largest.sort_by_key(|f| (Reverse(f.size), &f.name));
+    largest.truncate(n);
+    largest
+}
+
+/// Formats size with a binary unit suffix, as `ls -h` does
+pub fn human_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64, mtime: u32, mode: u16) -> FileInfo {
+        FileInfo {
+            name: name.to_owned(),
+            size,
+            mtime,
+            mode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sort_and_largest_files() {
+        let mut files = vec![
+            file("/usr/bin/b", 10, 3, 0o100_755),
+            file("/usr/share/a", 4096, 1, 0o040_755),
+            file("/usr/bin/a", 300, 2, 0o100_644),
+            file("/usr/bin/c", 10, 1, 0o100_644),
+        ];
+        let names =
+            |files: &[FileInfo]| -> Vec<String> { files.iter().map(|f| f.name.clone()).collect() };
+
+        sort_files(&mut files, SortKey::Size, false);
+        assert_eq!(
+            names(&files),
+            vec!["/usr/bin/b", "/usr/bin/c", "/usr/bin/a", "/usr/share/a"]
+        );
+        sort_files(&mut files, SortKey::Mtime, true);
+        assert_eq!(
+            names(&files),
+            vec!["/usr/bin/b", "/usr/bin/a", "/usr/share/a", "/usr/bin/c"]
+        );
+
+        let largest: Vec<&str> = largest_files(&files, 2)
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(largest, vec!["/usr/bin/a", "/usr/bin/b"]);
+
+        assert_eq!(files[0].mode_string(), "-rwxr-xr-x");
+        assert_eq!(files[2].mode_string(), "drwxr-xr-x");
+        assert_eq!(human_size(1000), "1000");
+        assert_eq!(human_size(1536), "1.5K");
+        assert_eq!(human_size(20 * 1024 * 1024), "20M");
+    }
+
+    #[test]
+    fn test_mode_to_string() {
+        assert_eq!(mode_to_string(0o100_644), "-rw-r--r--");
+        assert_eq!(mode_to_string(0o104_755), "-rwsr-xr-x");
+        assert_eq!(mode_to_string(0o102_711), "-rwx--s--x");
+        assert_eq!(mode_to_string(0o106_644), "-rwSr-Sr--");
+        assert_eq!(mode_to_string(0o041_777), "drwxrwxrwt");
+        assert_eq!(mode_to_string(0o041_776), "drwxrwxrwT");
+        assert_eq!(mode_to_string(0o120_777), "lrwxrwxrwx");
+        assert_eq!(mode_to_string(0o020_620), "crw--w----");
+        assert_eq!(mode_to_string(0o060_660), "brw-rw----");
+    }
+
+    #[test]
+    fn test_format_file_listing() {
+        let payload = RPMPayload {
+            files: vec![
+                FileInfo {
+                    user: "root".to_owned(),
+                    group: "root".to_owned(),
+                    ..file("/usr/bin/foo", 1234, 1_600_000_000, 0o100_755)
+                },
+                FileInfo {
+                    user: "nobody".to_owned(),
+                    group: "root".to_owned(),
+                    linkname: "foo".to_owned(),
+                    ..file("/usr/bin/bar", 3, 1_600_000_000, 0o120_777)
+                },
+            ],
+            ..Default::default()
+        };
+        let mtime = Local
+            .timestamp_opt(1_600_000_000, 0)
+            .unwrap()
+            .format("%b %d %H:%M")
+            .to_string();
+
+        assert_eq!(
+            format_file_listing(&payload),
+            format!(
+                "-rwxr-xr-x  root   root  1234 {0} /usr/bin/foo\n\
+                 lrwxrwxrwx  nobody root     3 {0} /usr/bin/bar -> foo\n",
+                mtime
+            )
+        );
+        assert_eq!(format_file_listing(&RPMPayload::default()), "");
+    }
+
+    #[test]
+    fn test_dump() {
+        use crate::payload::FileFlags;
+
+        let config = FileInfo {
+            flags: (FileFlags::CONFIGURATION | FileFlags::NOREPLACE).bits(),
+            digest: "ab".repeat(32),
+            ..file("/etc/foo.conf", 10, 1_600_000_000, 0o100_644)
+        };
+        assert!(config.is_config() && !config.is_doc() && !config.is_ghost());
+        assert_eq!(
+            ListingFormat::default().dump(&config),
+            format!(
+                "/etc/foo.conf 10 1600000000 {} 0100644 root root 1 0 0 X",
+                "ab".repeat(32)
+            )
+        );
+
+        let ghost = FileInfo {
+            flags: (FileFlags::GHOST | FileFlags::DOC).bits(),
+            ..config
+        };
+        assert!(ghost.is_ghost() && ghost.is_doc() && !ghost.is_config());
+        assert_eq!(
+            ListingFormat::default().dump(&ghost),
+            format!(
+                "/etc/foo.conf 0 1600000000 {} 0100644 root root 0 1 0 X",
+                "0".repeat(32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_listing_format() {
+        use crate::digest::DigestAlgo;
+        use crate::header::{RType, Tag};
+        use crate::rpm::HeaderTags;
+
+        let mut tags = HeaderTags::new();
+        tags.insert_files(&[
+            FileInfo {
+                name: "/usr/bin/hello".to_owned(),
+                size: 1536,
+                mode: 0o100_755,
+                digest: "ab".repeat(32),
+                ..Default::default()
+            },
+            FileInfo {
+                name: "/usr/bin/ping".to_owned(),
+                size: 20,
+                mtime: 1_600_000_000,
+                mode: 0o104_755,
+                linkname: "".to_owned(),
+                ..Default::default()
+            },
+        ])
+        .insert(Tag::FileDigestAlgo, RType::Int32(8))
+        .insert(Tag::FileClass, RType::Int32Array(vec![1, 0]))
+        .insert(
+            Tag::ClassDict,
+            RType::StringArray(vec!["".to_owned(), "ELF 64-bit LSB executable".to_owned()]),
+        )
+        .insert(
+            Tag::FileCaps,
+            RType::StringArray(vec!["".to_owned(), "cap_net_raw=ep".to_owned()]),
+        );
+        let files = tags.files();
+
+        let mtime = |time| {
+            Local
+                .timestamp_opt(time, 0)
+                .single()
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+        let format = ListingFormat {
+            human_readable: true,
+            classify: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format.line(&files[0]),
+            format!(
+                "-rwxr-xr-x       1.5K {} /usr/bin/hello\tELF 64-bit LSB executable",
+                mtime(0)
+            )
+        );
+        assert_eq!(
+            format.line(&files[1]),
+            format!(
+                "-rwsr-xr-x         20 {} /usr/bin/ping\t\tcap_net_raw=ep",
+                mtime(1_600_000_000)
+            )
+        );
+        let format = ListingFormat {
+            digests: true,
+            ..Default::default()
+        };
+        assert!(format
+            .line(&files[0])
+            .ends_with(&format!("/usr/bin/hello\tsha256:{}", "ab".repeat(32))));
+        assert!(format
+            .line(&files[1])
+            .ends_with("/usr/bin/ping\t\tcap_net_raw=ep"));
+        assert_eq!(files[0].digest_algorithm(), Some(DigestAlgo::Sha256));
+        assert_eq!(
+            ListingFormat::default().dump(&files[1]),
+            "/usr/bin/ping 20 1600000000 00000000000000000000000000000000 0104755 \
+             root root 0 0 0 X\tcap_net_raw=ep"
+        );
+    }
+}
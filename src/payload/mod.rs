@@ -1,10 +1,21 @@
 mod cpio;
+mod listing;
+mod stream;
+mod ustar;
+mod xattrs;
 
 pub use cpio::*;
+pub use listing::*;
+pub use stream::*;
+pub use ustar::*;
+pub use xattrs::*;
 
 use bitflags::bitflags;
 
-#[derive(Debug, Clone)]
+use crate::digest::DigestAlgo;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileInfo {
     pub name: String,
     pub size: u64,
@@ -13,10 +24,21 @@ pub struct FileInfo {
     pub flags: u32,
     pub mtime: u32,
     pub digest: String,
+    /// rpm id of the digest algorithm, 0 if it is not known, see `digest_algorithm`
+    pub digest_algo: u32,
     pub mode: u16,
     pub linkname: String,
     pub device: u32,
     pub inode: u32,
+    pub nlink: u32,
+    /// Device number of character and block devices
+    pub rdev: u32,
+    /// File type as described by libmagic, e.g. `ELF 64-bit LSB executable`
+    pub class: String,
+    /// Capabilities in the `cap_net_raw=ep` form
+    pub caps: String,
+    /// Attributes compared by `verify_files`, `VerifyAttrs` bits of FileVerifyFlags
+    pub verify_flags: u32,
 }
 
 impl Default for FileInfo {
@@ -29,26 +51,127 @@ impl Default for FileInfo {
             flags: 0,
             mtime: 0,
             digest: "".to_owned(),
+            digest_algo: 0,
             mode: 33188,
-            linkname: "root".to_owned(),
+            linkname: "".to_owned(),
             device: 0,
             inode: 0,
+            nlink: 1,
+            rdev: 0,
+            class: "".to_owned(),
+            caps: "".to_owned(),
+            verify_flags: u32::MAX,
+        }
+    }
+}
+
+impl From<&FileEntry> for FileInfo {
+    fn from(entry: &FileEntry) -> Self {
+        FileInfo {
+            name: entry.name.trim_start_matches('.').to_owned(),
+            size: entry.file_size.into(),
+            user: entry.uid.to_string(),
+            group: entry.gid.to_string(),
+            mtime: entry.mtime,
+            mode: entry.mode as u16,
+            linkname: "".to_owned(),
+            device: entry.dev_major << 8 | entry.dev_minor,
+            inode: entry.ino,
+            nlink: entry.nlink,
+            rdev: entry.rdev_major << 8 | entry.rdev_minor,
+            ..Default::default()
         }
     }
 }
 
-#[derive(Debug, Default)]
+/// Payload members are usually relative, e.g. `./usr/bin/hello`
+pub(crate) fn normalize_path(name: &str) -> String {
+    let name = name
+        .strip_prefix('.')
+        .filter(|name| name.starts_with('/'))
+        .unwrap_or(name);
+    format!("/{}", name.trim_start_matches('/'))
+}
+
+impl FileInfo {
+    pub fn file_flags(&self) -> FileFlags {
+        FileFlags::from_bits_truncate(self.flags)
+    }
+
+    /// `%config` file
+    pub fn is_config(&self) -> bool {
+        self.file_flags().contains(FileFlags::CONFIGURATION)
+    }
+
+    /// `%doc` file
+    pub fn is_doc(&self) -> bool {
+        self.file_flags().contains(FileFlags::DOC)
+    }
+
+    /// `%ghost` file, it is owned by the package but not included in the payload
+    pub fn is_ghost(&self) -> bool {
+        self.file_flags().contains(FileFlags::GHOST)
+    }
+
+    pub fn digest_algorithm(&self) -> Option<DigestAlgo> {
+        DigestAlgo::from_rpm_id(self.digest_algo).ok()
+    }
+
+    /// Digest prefixed with its algorithm, e.g. `sha256:abcd...`
+    pub fn labeled_digest(&self) -> Option<String> {
+        let algo = self
+            .digest_algorithm()
+            .filter(|_| !self.digest.is_empty())?;
+        Some(format!(
+            "{}:{}",
+            algo.to_string().to_lowercase(),
+            self.digest
+        ))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RPMPayload {
     pub size: u64,
+    /// The sizes come from LongFileSizes or LongArchiveSize, the package has files over 4 GiB
+    pub long_sizes: bool,
     pub format: String,
     pub compressor: String,
     pub flags: String,
     pub files: Vec<FileInfo>,
 }
 
+impl RPMPayload {
+    /// Files having any of the given flags
+    pub fn files_with(&self, flags: FileFlags) -> Vec<&FileInfo> {
+        self.files
+            .iter()
+            .filter(|file| file.file_flags().intersects(flags))
+            .collect()
+    }
+
+    pub fn config_files(&self) -> Vec<&FileInfo> {
+        self.files_with(FileFlags::CONFIGURATION)
+    }
+
+    pub fn doc_files(&self) -> Vec<&FileInfo> {
+        self.files_with(FileFlags::DOC)
+    }
+
+    pub fn license_files(&self) -> Vec<&FileInfo> {
+        self.files_with(FileFlags::LICENSE)
+    }
+
+    pub fn ghost_files(&self) -> Vec<&FileInfo> {
+        self.files_with(FileFlags::GHOST)
+    }
+}
+
 // https://github.com/eclipse/packagedrone/blob/master/bundles/org.eclipse.packagedrone.utils.rpm/src/org/eclipse/packagedrone/utils/rpm/FileFlags.java
 bitflags! {
-    struct FileFlags: u32 {
+    #[derive(Default)]
+    pub struct FileFlags: u32 {
         // from %%config
         const CONFIGURATION = 1 << 0;
         // from %%doc
@@ -59,6 +182,8 @@ bitflags! {
         const MISSINGOK = 1 << 3;
         // from %%config(noreplace)
         const NOREPLACE = 1 << 4;
+        // the spec file of a source package
+        const SPECFILE = 1 << 5;
         // from %%ghost
         const GHOST = 1 << 6;
         // from %%license
@@ -72,3 +197,42 @@ bitflags! {
         const ARTIFACT = 1 << 12;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RPMFile, RPMInfo};
+    use std::convert::TryFrom;
+    use std::io;
+    use std::path::Path;
+
+    #[test]
+    fn test_files_by_flags() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let info = RPMInfo::try_from(&RPMFile::open(path)?)?;
+        let names = |files: Vec<&FileInfo>| -> Vec<String> {
+            files.into_iter().map(|f| f.name.clone()).collect()
+        };
+
+        assert_eq!(names(info.payload.config_files()), vec!["/etc/hello.conf"]);
+        assert_eq!(
+            names(info.payload.doc_files()),
+            vec!["/usr/share/doc/hello/README"]
+        );
+        assert_eq!(
+            names(info.payload.license_files()),
+            vec!["/usr/share/licenses/hello/LICENSE"]
+        );
+        assert_eq!(
+            names(info.payload.ghost_files()),
+            vec!["/var/log/hello.log"]
+        );
+
+        let payload = RPMPayload {
+            files: vec![FileInfo::default()],
+            ..Default::default()
+        };
+        assert!(payload.config_files().is_empty());
+        Ok(())
+    }
+}
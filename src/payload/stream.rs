@@ -0,0 +1,230 @@
+use std::io::{self, Read};
+
+use super::{cpio_checksum, skip_bytes, CpioFormat, FileEntry, TRAILER};
+
+/// Reads a cpio archive member by member, without buffering the member data.
+/// The borrow of a `Member` ends before the next one is read, its unread data is skipped.
+pub struct CpioStream<R> {
+    reader: R,
+    /// Data of the current member left in the reader
+    data_left: u64,
+    /// Padding after the data of the current member
+    padding_left: u64,
+    finished: bool,
+    verify_checksums: bool,
+    /// Checksum expected for the data of the current member, if it is verified
+    expected: Option<u32>,
+    sum: u32,
+}
+
+impl<R: Read> CpioStream<R> {
+    pub fn new(reader: R) -> Self {
+        CpioStream {
+            reader,
+            data_left: 0,
+            padding_left: 0,
+            finished: false,
+            verify_checksums: false,
+            expected: None,
+            sum: 0,
+        }
+    }
+
+    /// Verifies the data of Crc members read to the end, a mismatch is an InvalidData error
+    pub fn verify_checksums(mut self, enabled: bool) -> Self {
+        self.verify_checksums = enabled;
+        self
+    }
+
+    /// Next member of the archive, data of the previous member which was not read is skipped
+    pub fn next_member(&mut self) -> io::Result<Option<Member<'_, R>>> {
+        if self.finished {
+            return Ok(None);
+        }
+        self.skip_member()?;
+
+        let entry = FileEntry::read(&mut self.reader)?;
+        if entry.name == TRAILER {
+            self.finished = true;
+            return Ok(None);
+        }
+        self.data_left = entry.file_size.into();
+        self.padding_left = entry.data_padding().into();
+        self.expected = Some(entry.checksum)
+            .filter(|_| self.verify_checksums && entry.format == CpioFormat::Crc);
+        self.sum = 0;
+        Ok(Some(Member {
+            entry,
+            stream: self,
+        }))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn skip_member(&mut self) -> io::Result<()> {
+        let count = self.data_left + self.padding_left;
+        self.data_left = 0;
+        self.padding_left = 0;
+        skip_bytes(&mut self.reader, count)
+    }
+}
+
+/// Member of a cpio archive, reads its data up to `file_size` bytes
+pub struct Member<'a, R> {
+    entry: FileEntry,
+    stream: &'a mut CpioStream<R>,
+}
+
+impl<'a, R: Read> Member<'a, R> {
+    pub fn entry(&self) -> &FileEntry {
+        &self.entry
+    }
+
+    pub fn into_entry(self) -> FileEntry {
+        self.entry
+    }
+
+    /// Skips the data left, the same is done by the next `next_member` call
+    pub fn skip(self) -> io::Result<FileEntry> {
+        self.stream.skip_member()?;
+        Ok(self.entry)
+    }
+}
+
+impl<'a, R: Read> Read for Member<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let stream = &mut *self.stream;
+        if stream.data_left == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let max = stream.data_left.min(buf.len() as u64) as usize;
+        let n = stream.reader.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Error: unexpected end of cpio archive",
+            ));
+        }
+        stream.data_left -= n as u64;
+        if stream.expected.is_some() {
+            stream.sum = cpio_checksum(stream.sum, &buf[..n]);
+        }
+
+        // the member is exhausted, its padding is consumed right away
+        if stream.data_left == 0 {
+            stream.skip_member()?;
+            if matches!(stream.expected.take(), Some(sum) if sum != stream.sum) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Error: checksum mismatch of cpio entry {}", self.entry.name),
+                ));
+            }
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::CpioWriter;
+    use crate::utils::align_n_bytes;
+    use crate::utils::S_IFREG;
+
+    const LARGE: u32 = 64 * 1024 * 1024 + 3;
+
+    fn entry(name: &str, file_size: u32) -> FileEntry {
+        FileEntry {
+            name: name.to_owned(),
+            mode: S_IFREG | 0o644,
+            file_size,
+            ..Default::default()
+        }
+    }
+
+    /// Archive with a large member generated on the fly, it is never held in memory
+    fn archive() -> io::Result<impl Read> {
+        let mut head = Vec::new();
+        head.write_cpio_record(entry("./small", 5), &mut &b"hello"[..])?;
+        head.write_cpio_entry(entry("./large", LARGE))?;
+
+        let mut tail = vec![0_u8; align_n_bytes(LARGE, 4) as usize];
+        tail.write_cpio_record(entry("./last", 4), &mut &b"last"[..])?;
+        tail.cpio_close()?;
+
+        Ok(io::Cursor::new(head)
+            .chain(io::repeat(b'x').take(LARGE.into()))
+            .chain(io::Cursor::new(tail)))
+    }
+
+    #[test]
+    fn test_stream_members() -> io::Result<()> {
+        let mut payload = CpioStream::new(archive()?);
+
+        let mut member = payload.next_member()?.unwrap();
+        let mut data = String::new();
+        member.read_to_string(&mut data)?;
+        assert_eq!(
+            (member.entry().name.as_str(), data.as_str()),
+            ("./small", "hello")
+        );
+
+        let mut member = payload.next_member()?.unwrap();
+        assert_eq!(io::copy(&mut member, &mut io::sink())?, u64::from(LARGE));
+        assert_eq!(member.read(&mut [0; 4])?, 0);
+
+        let member = payload.next_member()?.unwrap();
+        assert_eq!(member.skip()?.name, "./last");
+        assert!(payload.next_member()?.is_none());
+        assert!(payload.next_member()?.is_none());
+
+        // unread data is skipped
+        let mut payload = CpioStream::new(archive()?);
+        let mut names = Vec::new();
+        while let Some(mut member) = payload.next_member()? {
+            member.read_exact(&mut [0; 2])?;
+            names.push(member.into_entry().name);
+        }
+        assert_eq!(names, vec!["./small", "./large", "./last"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc_checksums() -> io::Result<()> {
+        let mut archive = Vec::new();
+        let crc = FileEntry {
+            format: CpioFormat::Crc,
+            ..entry("./crc", 5)
+        };
+        archive.write_cpio_record(crc, &mut &b"hello"[..])?;
+        archive.cpio_close_as(CpioFormat::Crc)?;
+        assert!(archive.starts_with(b"070702"));
+
+        let mut payload = CpioStream::new(archive.as_slice()).verify_checksums(true);
+        let mut member = payload.next_member()?.unwrap();
+        assert_eq!(member.entry().checksum, 532);
+        member.read_to_end(&mut Vec::new())?;
+        assert!(payload.next_member()?.is_none());
+
+        let position = archive.windows(5).position(|w| w == b"hello").unwrap();
+        archive[position] = b'j';
+        let mut payload = CpioStream::new(archive.as_slice()).verify_checksums(true);
+        let err = payload
+            .next_member()?
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // not verified unless requested
+        let mut payload = CpioStream::new(archive.as_slice());
+        payload
+            .next_member()?
+            .unwrap()
+            .read_to_end(&mut Vec::new())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Cursor, Read};
+use tar::{EntryType, Header, PaxExtensions};
+
+use super::{skip_bytes, CpioWriter, FileEntry};
+use crate::utils::{align_n_bytes, S_IFDIR, S_IFLNK, S_IFREG};
+
+const BLOCK: usize = 512;
+
+/// Payload as a cpio archive: tar payloads, given by the PayloadFormat tag
+/// or recognized by the ustar magic, are converted while they are read
+pub(crate) fn cpio_payload<'a, R: Read + 'a>(
+    mut reader: R,
+    format: &str,
+) -> io::Result<Box<dyn Read + 'a>> {
+    let mut block = Vec::with_capacity(BLOCK);
+    (&mut reader).take(BLOCK as u64).read_to_end(&mut block)?;
+    let is_tar = format == "tar" || (block.len() == BLOCK && &block[257..262] == b"ustar");
+
+    let reader = Cursor::new(block).chain(reader);
+    if is_tar {
+        Ok(Box::new(TarReader::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Reads a tar archive as a newc cpio archive. Hardlinks refer to an earlier member,
+/// they are written as empty members sharing its inode.
+pub struct TarReader<R> {
+    reader: R,
+    /// Converted headers, symlink targets and paddings
+    buffer: Cursor<Vec<u8>>,
+    /// Data of the current member left in the tar archive
+    data_left: u64,
+    /// Paddings after the data of the current member
+    paddings: Option<(u64, u32)>,
+    inodes: HashMap<String, u32>,
+    next_ino: u32,
+    finished: bool,
+}
+
+impl<R: Read> TarReader<R> {
+    pub fn new(reader: R) -> Self {
+        TarReader {
+            reader,
+            buffer: Cursor::new(Vec::new()),
+            data_left: 0,
+            paddings: None,
+            inodes: HashMap::new(),
+            next_ino: 1,
+            finished: false,
+        }
+    }
+
+    /// Converts the next tar member, extension headers are applied to the member they precede
+    fn next_entry(&mut self) -> io::Result<()> {
+        let mut name = None;
+        let mut link_name = None;
+
+        let (header, size) = loop {
+            let mut block = [0_u8; BLOCK];
+            let read = read_block(&mut self.reader, &mut block)?;
+            if read == 0 || block.iter().all(|&b| b == 0) {
+                return self.finish();
+            }
+            if read < BLOCK {
+                return Err(unexpected());
+            }
+
+            let header = Header::from_byte_slice(&block).clone();
+            let size = header.entry_size()?;
+            match header.entry_type() {
+                EntryType::GNULongName => name = Some(self.read_data(size)?),
+                EntryType::GNULongLink => link_name = Some(self.read_data(size)?),
+                EntryType::XHeader => {
+                    let data = self.read_data(size)?;
+                    for extension in PaxExtensions::new(&data) {
+                        let extension = extension?;
+                        match extension.key_bytes() {
+                            b"path" => name = Some(extension.value_bytes().to_vec()),
+                            b"linkpath" => link_name = Some(extension.value_bytes().to_vec()),
+                            _ => {}
+                        }
+                    }
+                }
+                EntryType::XGlobalHeader => {
+                    self.read_data(size)?;
+                }
+                _ => break (header, size),
+            }
+        };
+
+        let name = name.unwrap_or_else(|| header.path_bytes().to_vec());
+        let name = cpio_name(&name)?;
+        let link_name = link_name.or_else(|| header.link_name_bytes().map(|n| n.to_vec()));
+        let mut entry = FileEntry {
+            mode: header.mode()? & 0o7777,
+            uid: u32::try_from(header.uid()?).unwrap_or_default(),
+            gid: u32::try_from(header.gid()?).unwrap_or_default(),
+            mtime: u32::try_from(header.mtime()?).unwrap_or_default(),
+            ino: self.next_ino,
+            nlink: 1,
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+
+        let entry_type = header.entry_type();
+        match entry_type {
+            EntryType::Regular | EntryType::Continuous => {
+                entry.mode |= S_IFREG;
+                entry.file_size = u32::try_from(size).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Error: {} is too large for a cpio archive", name),
+                    )
+                })?;
+                self.inodes.insert(name.clone(), entry.ino);
+            }
+            EntryType::Directory => entry.mode |= S_IFDIR,
+            EntryType::Symlink => {
+                entry.mode |= S_IFLNK;
+                data = link_name.unwrap_or_default();
+                entry.file_size = data.len() as u32;
+            }
+            EntryType::Link => {
+                let target = cpio_name(&link_name.unwrap_or_default())?;
+                entry.mode |= S_IFREG;
+                entry.nlink = 2;
+                entry.ino = *self.inodes.get(&target).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Error: {} links to a missing member {}", name, target),
+                    )
+                })?;
+            }
+            EntryType::Char | EntryType::Block | EntryType::Fifo => {
+                entry.mode |= match entry_type {
+                    EntryType::Char => 0o020_000,
+                    EntryType::Block => 0o060_000,
+                    _ => 0o010_000,
+                };
+                entry.rdev_major = header.device_major()?.unwrap_or_default();
+                entry.rdev_minor = header.device_minor()?.unwrap_or_default();
+            }
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Error: tar member {} of type {:?} is not supported",
+                        name, other
+                    ),
+                ))
+            }
+        }
+        entry.name = name;
+        self.next_ino += 1;
+
+        // only regular files keep the tar data, the rest is converted into the buffer
+        if entry.is_file() {
+            self.data_left = size;
+        } else {
+            self.read_data(size)?;
+        }
+        self.paddings = Some((
+            tar_padding(self.data_left),
+            align_n_bytes(entry.file_size, 4),
+        ));
+
+        let mut buffer = Vec::new();
+        buffer.write_cpio_entry(entry)?;
+        buffer.extend_from_slice(&data);
+        self.buffer = Cursor::new(buffer);
+        Ok(())
+    }
+
+    /// Reads member data together with its padding
+    fn read_data(&mut self, size: u64) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        (&mut self.reader).take(size).read_to_end(&mut data)?;
+        if (data.len() as u64) < size {
+            return Err(unexpected());
+        }
+        skip_bytes(&mut self.reader, tar_padding(size))?;
+        Ok(data)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        buffer.cpio_close()?;
+        self.buffer = Cursor::new(buffer);
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for TarReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.buffer.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+
+            if self.data_left > 0 {
+                let max = self.data_left.min(buf.len() as u64) as usize;
+                let n = self.reader.read(&mut buf[..max])?;
+                if n == 0 {
+                    return Err(unexpected());
+                }
+                self.data_left -= n as u64;
+                return Ok(n);
+            }
+
+            if let Some((tar_padding, cpio_padding)) = self.paddings.take() {
+                skip_bytes(&mut self.reader, tar_padding)?;
+                self.buffer = Cursor::new(vec![0; cpio_padding as usize]);
+                continue;
+            }
+
+            if self.finished {
+                return Ok(0);
+            }
+            self.next_entry()?;
+        }
+    }
+}
+
+/// Fills the block, returns a smaller number of bytes only at the end of the archive
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < block.len() {
+        match reader.read(&mut block[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+/// Member names of rpm payloads are relative to the root, e.g. `./usr/bin/hello`
+fn cpio_name(name: &[u8]) -> io::Result<String> {
+    // GNU long names are terminated with NUL
+    let name = name.split(|&b| b == 0).next().unwrap_or_default();
+    let name = std::str::from_utf8(name).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Error: incorrect utf8 symbol: {}", e),
+        )
+    })?;
+    let name = name.trim_end_matches('/');
+    let name = name.strip_prefix("./").unwrap_or(name);
+    Ok(format!("./{}", name.trim_start_matches('/')))
+}
+
+fn tar_padding(size: u64) -> u64 {
+    (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64
+}
+
+fn unexpected() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "Error: unexpected end of tar archive",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::{extract_entries_with, read_entries};
+
+    fn append(builder: &mut tar::Builder<Vec<u8>>, kind: EntryType, name: &str, data: &[u8]) {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_mode(0o644);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(1_600_000_000);
+        header.set_size(if kind.is_file() { data.len() as u64 } else { 0 });
+        if kind.is_symlink() || kind.is_hard_link() {
+            header
+                .set_link_name(std::str::from_utf8(data).unwrap())
+                .unwrap();
+        }
+        let content: &[u8] = if kind.is_file() { data } else { &[] };
+        builder.append_data(&mut header, name, content).unwrap();
+    }
+
+    #[test]
+    fn test_tar_as_cpio() -> io::Result<()> {
+        let long_name = format!("usr/share/{}", "x".repeat(120));
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, EntryType::Directory, "./usr/share/", b"");
+        append(&mut builder, EntryType::Regular, "./usr/share/a", b"hello");
+        append(&mut builder, EntryType::Symlink, "./usr/share/b", b"a");
+        append(
+            &mut builder,
+            EntryType::Link,
+            "./usr/share/c",
+            b"./usr/share/a",
+        );
+        append(&mut builder, EntryType::Regular, &long_name, &[1; 600]);
+        let tar = builder.into_inner()?;
+
+        let mut cpio = Vec::new();
+        cpio_payload(tar.as_slice(), "")?.read_to_end(&mut cpio)?;
+        let entries = read_entries(&mut Cursor::new(&cpio))?;
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "./usr/share",
+                "./usr/share/a",
+                "./usr/share/b",
+                "./usr/share/c",
+                &format!("./{}", long_name),
+            ]
+        );
+        assert!(entries[0].is_dir());
+        assert_eq!(entries[1].file_size, 5);
+        assert!(entries[2].is_symlink());
+        assert!(entries[3].is_hardlink());
+        assert_eq!(entries[3].ino, entries[1].ino);
+        assert_eq!(entries[4].file_size, 600);
+        assert_eq!(entries[4].mtime, 1_600_000_000);
+
+        let dir = tempfile::tempdir()?;
+        let report = extract_entries_with(&mut cpio.as_slice(), dir.path(), &Default::default())?;
+        assert_eq!(report.files_created, 2);
+        assert_eq!(report.hardlinks_created, 1);
+        let share = dir.path().join("usr/share");
+        assert_eq!(std::fs::read(share.join("c"))?, b"hello");
+
+        // cpio payloads are passed through
+        let mut data = Vec::new();
+        cpio_payload(cpio.as_slice(), "cpio")?.read_to_end(&mut data)?;
+        assert_eq!(data, cpio);
+        Ok(())
+    }
+}
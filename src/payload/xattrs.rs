@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use super::normalize_path;
+
+/// Extended attribute holding the IMA signature, stored by rpm in the FileSignatures tag
+pub const IMA_XATTR: &str = "security.ima";
+
+/// Name and value of an extended attribute
+pub type Xattr = (String, Vec<u8>);
+
+/// Only user attributes and IMA signatures are carried over,
+/// other namespaces belong to the system the files were created on
+pub fn is_preserved_xattr(name: &str) -> bool {
+    name.starts_with("user.") || name == IMA_XATTR
+}
+
+/// Extended attributes of archive members, keyed by the normalized member path
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct XattrManifest(BTreeMap<String, Vec<Xattr>>);
+
+impl XattrManifest {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> &[Xattr] {
+        self.0
+            .get(&normalize_path(name))
+            .map_or(&[], |attrs| attrs.as_slice())
+    }
+
+    pub fn insert(&mut self, name: &str, attr: Xattr) {
+        self.0.entry(normalize_path(name)).or_default().push(attr);
+    }
+
+    /// Reads preserved attributes of the source file and records them for the member
+    pub fn capture(&mut self, name: &str, path: &Path) -> io::Result<()> {
+        for attr in read_xattrs(path)? {
+            self.insert(name, attr);
+        }
+        Ok(())
+    }
+
+    /// Sets the recorded attributes of the member on the extracted path
+    pub fn restore(&self, name: &str, path: &Path) -> io::Result<()> {
+        for (attr, value) in self.get(name) {
+            write_xattr(path, attr, value)?;
+        }
+        Ok(())
+    }
+
+    /// Writes one attribute per line: path, name and hex encoded value separated by tabs
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for (path, attrs) in &self.0 {
+            for (name, value) in attrs {
+                if [path, name].iter().any(|s| s.contains(&['\t', '\n'][..])) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "Error: {} {} can not be written to the manifest",
+                            path, name
+                        ),
+                    ));
+                }
+                writeln!(writer, "{}\t{}\t{}", path, name, hex::encode(value))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut manifest = XattrManifest::new();
+        for line in reader.lines() {
+            let line = line?;
+            let parts: Vec<&str> = line.split('\t').collect();
+            match parts[..] {
+                [path, name, value] => {
+                    let value = hex::decode(value).map_err(|_| incorrect_line(&line))?;
+                    manifest.insert(path, (name.to_owned(), value));
+                }
+                [""] => {}
+                _ => return Err(incorrect_line(&line)),
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+fn incorrect_line(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Error: incorrect xattr manifest line {}", line),
+    )
+}
+
+/// Preserved attributes of the path, filesystems without attributes have none
+#[cfg(unix)]
+pub fn read_xattrs(path: &Path) -> io::Result<Vec<Xattr>> {
+    if !xattr::SUPPORTED_PLATFORM {
+        return Ok(Vec::new());
+    }
+
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(e) if is_unsupported(&e) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut attrs = Vec::new();
+    for name in names {
+        let name = name.to_string_lossy().to_string();
+        if !is_preserved_xattr(&name) {
+            continue;
+        }
+        if let Some(value) = xattr::get(path, &name)? {
+            attrs.push((name, value));
+        }
+    }
+    attrs.sort();
+    Ok(attrs)
+}
+
+#[cfg(not(unix))]
+pub fn read_xattrs(_path: &Path) -> io::Result<Vec<Xattr>> {
+    Ok(Vec::new())
+}
+
+#[cfg(unix)]
+fn write_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    xattr::set(path, name, value).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Error: can not set {} on {}: {}", name, path.display(), e),
+        )
+    })
+}
+
+#[cfg(not(unix))]
+fn write_xattr(path: &Path, name: &str, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "Error: can not set {} on {}, xattrs are not supported",
+            name,
+            path.display()
+        ),
+    ))
+}
+
+#[cfg(unix)]
+fn is_unsupported(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(nix::errno::Errno::EOPNOTSUPP as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xattr_manifest() -> io::Result<()> {
+        let mut manifest = XattrManifest::new();
+        manifest.insert("./usr/bin/hello", ("user.a".to_owned(), b"1".to_vec()));
+        manifest.insert("/usr/bin/hello", (IMA_XATTR.to_owned(), vec![3, 2, 0xff]));
+        manifest.insert("etc/hello.conf", ("user.b".to_owned(), Vec::new()));
+
+        let mut data = Vec::new();
+        manifest.write(&mut data)?;
+        assert_eq!(
+            String::from_utf8_lossy(&data),
+            "/etc/hello.conf\tuser.b\t\n\
+             /usr/bin/hello\tuser.a\t31\n\
+             /usr/bin/hello\tsecurity.ima\t0302ff\n"
+        );
+        assert_eq!(XattrManifest::read(data.as_slice())?, manifest);
+        assert_eq!(manifest.get("./etc/hello.conf").len(), 1);
+        assert!(XattrManifest::read(b"/a\tuser.a\tzz\n".as_ref()).is_err());
+        assert!(is_preserved_xattr("user.mime_type"));
+        assert!(!is_preserved_xattr("security.selinux"));
+        Ok(())
+    }
+
+    // tmpfs supports user attributes since Linux 6.6, older kernels skip the test
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_xattr_round_trip() -> io::Result<()> {
+        let dir = match tempfile::tempdir_in("/dev/shm") {
+            Ok(dir) => dir,
+            Err(_) => return Ok(()),
+        };
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+        std::fs::write(&source, "data")?;
+        std::fs::write(&target, "data")?;
+        if xattr::set(&source, "user.test", b"value").is_err() {
+            return Ok(());
+        }
+        xattr::set(&source, "trusted.test", b"value").ok();
+
+        let mut manifest = XattrManifest::new();
+        manifest.capture("./source", &source)?;
+        assert_eq!(
+            manifest.get("/source"),
+            &[("user.test".to_owned(), b"value".to_vec())]
+        );
+
+        manifest.restore("./source", &target)?;
+        assert_eq!(xattr::get(&target, "user.test")?, Some(b"value".to_vec()));
+        Ok(())
+    }
+}
@@ -1,21 +1,54 @@
 use chrono::Utc;
-use std::convert::AsRef;
+use glob::Pattern;
+use std::collections::HashMap;
+use std::convert::{AsRef, TryFrom};
 use std::fs::read_to_string;
 use std::fs::{File, OpenOptions};
 use std::io;
 use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
 
+use super::compression::Compression;
+use super::dependency::{Dependency, DependencyFlags, DependencyKind, VersionConstraint};
 use super::file::RPMFile;
-use crate::payload::FileInfo;
+use super::nevra::Nevra;
+use super::scriptlets::ScriptFlags;
+use crate::digest::DigestAlgo;
+use crate::payload::{
+    normalize_path, read_xattrs, CpioBuilder, FileEntry, FileFlags, FileInfo, IMA_XATTR,
+};
+use crate::utils::{S_IFDIR, S_IFLNK, S_IFMT};
 
-use crate::header::{SignatureTag, Tag, Tags};
-use crate::lead::Lead;
+use crate::header::{timestamp, RType, SignatureTag, Tag, Tags};
+use crate::lead::{Lead, Type, NOARCH};
 
-struct InnerPath {
-    path: String,
-    user: String,
-    group: String,
-    mode: u8,
+/// File added by `add_file_with`, metadata which is not set comes from the source file
+/// and from the default user and group of the builder
+#[derive(Debug, Default, Clone)]
+pub struct FileOptions {
+    /// Path the package installs the file at
+    pub path: String,
+    /// Local file, the install path inside the build root by default
+    pub source: Option<PathBuf>,
+    /// Permission bits, e.g. `0o4755`, the file type is kept
+    pub mode: Option<u16>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    /// Ghost files are listed in the header only, they are not stored in the payload
+    pub flags: FileFlags,
+}
+
+impl FileOptions {
+    pub fn new(path: &str) -> Self {
+        FileOptions {
+            path: path.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn is_ghost(&self) -> bool {
+        self.flags.contains(FileFlags::GHOST)
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -24,32 +57,57 @@ pub struct RPMBuilder {
     package_name: Option<String>,
     version: Option<String>,
     release: String,
-    epoch: u8,
+    epoch: u32,
     arch: String,
+    platform: Option<String>,
+    optflags: Option<String>,
+    build_archs: Option<Vec<String>>,
     package_group: Option<String>,
     license: Option<String>,
     source_rpm: Option<String>,
     build_time: i64,
+    /// Modification times later than it are set to it
+    clamp_mtime: Option<i64>,
     build_host: String,
     summary: Option<String>,
     description: Option<String>,
     packager: Option<String>,
     os: Option<String>,
     distribution: Option<String>,
+    dist_url: Option<String>,
     vendor: Option<String>,
     url: Option<String>,
     pre_install: Option<String>,
     post_install: Option<String>,
     pre_uninstall: Option<String>,
     post_uninstall: Option<String>,
-    //  BINARY, SOURCE
-    package_type: Option<String>,
+    pre_trans: Option<String>,
+    post_trans: Option<String>,
+    pre_trans_prog: Option<String>,
+    post_trans_prog: Option<String>,
+    verify_script: Option<(String, String)>,
+    verify_script_flags: Option<ScriptFlags>,
     default_user: String,
     default_group: String,
     directories: Vec<String>,
-    files: Vec<String>,
-    links: Vec<String>,
-    compression: String,
+    files: Vec<FileOptions>,
+    /// Symlinks with their targets
+    links: Vec<(String, String)>,
+    /// Source directories with the prefix their contents are installed under
+    trees: Vec<(PathBuf, String)>,
+    source_package: bool,
+    /// Tags by numeric id, written after and over the others
+    custom_tags: Vec<(u32, RType)>,
+    custom_signature_tags: Vec<(u32, RType)>,
+    /// File names of the Source and Patch tags
+    sources: Vec<String>,
+    patches: Vec<String>,
+    excludes: Vec<String>,
+    build_root: Option<PathBuf>,
+    compression: Compression,
+    dependencies: Vec<(DependencyKind, Dependency)>,
+    xattrs: bool,
+    file_digest: Option<DigestAlgo>,
 }
 
 impl RPMBuilder {
@@ -59,11 +117,10 @@ impl RPMBuilder {
             epoch: 0,
             release: "1".to_owned(),
             arch: "noarch".to_owned(),
-            build_host: hostname::get().unwrap().into_string().unwrap(),
+            build_host: build_host(),
             build_time,
             default_user: "root".to_owned(),
             default_group: "root".to_owned(),
-            compression: "gzip".to_owned(),
             ..Default::default()
         }
     }
@@ -88,7 +145,7 @@ impl RPMBuilder {
         self
     }
 
-    pub fn epoch(mut self, epoch: u8) -> Self {
+    pub fn epoch(mut self, epoch: u32) -> Self {
         self.epoch = epoch;
         self
     }
@@ -98,6 +155,24 @@ impl RPMBuilder {
         self
     }
 
+    /// Target platform, `<arch>-unknown-linux-gnu` by default
+    pub fn platform(mut self, platform: &str) -> Self {
+        self.platform = Some(platform.to_owned());
+        self
+    }
+
+    /// Compiler flags the package was built with, `-O2 -g` by default
+    pub fn optflags(mut self, optflags: &str) -> Self {
+        self.optflags = Some(optflags.to_owned());
+        self
+    }
+
+    /// Architectures the package is built for, only noarch packages have them by default
+    pub fn build_archs(mut self, archs: Vec<&str>) -> Self {
+        self.build_archs = Some(archs.into_iter().map(|arch| arch.to_owned()).collect());
+        self
+    }
+
     pub fn package_group(mut self, group: String) -> Self {
         self.package_group = Some(group);
         self
@@ -118,6 +193,17 @@ impl RPMBuilder {
         self
     }
 
+    /// Same package from the same inputs: the build time is the epoch, later
+    /// modification times of files are clamped to it and the build host is
+    /// `reproducible` unless it is set afterwards. Building fails for epochs
+    /// outside of the unsigned 32 bits rpm stores them in
+    pub fn reproducible(mut self, epoch: i64) -> Self {
+        self.build_time = epoch;
+        self.clamp_mtime = Some(epoch);
+        self.build_host = "reproducible".to_owned();
+        self
+    }
+
     pub fn summary(mut self, summary: &str) -> Self {
         self.summary = Some(summary.to_owned());
         self
@@ -143,8 +229,73 @@ impl RPMBuilder {
         self
     }
 
-    pub fn compression(mut self, format: &str) -> Self {
-        self.compression = format.to_owned();
+    pub fn vendor(mut self, vendor: &str) -> Self {
+        self.vendor = Some(vendor.to_owned());
+        self
+    }
+
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_owned());
+        self
+    }
+
+    pub fn packager(mut self, packager: &str) -> Self {
+        self.packager = Some(packager.to_owned());
+        self
+    }
+
+    /// Target operating system, `linux` by default
+    pub fn os(mut self, os: &str) -> Self {
+        self.os = Some(os.to_owned());
+        self
+    }
+
+    pub fn distribution(mut self, distribution: &str) -> Self {
+        self.distribution = Some(distribution.to_owned());
+        self
+    }
+
+    /// URL of the distribution the package belongs to
+    pub fn dist_url(mut self, url: &str) -> Self {
+        self.dist_url = Some(url.to_owned());
+        self
+    }
+
+    /// Payload codec and level, gzip level 9 by default
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn requires(self, name: &str, constraint: VersionConstraint) -> Self {
+        self.dependency(DependencyKind::Requires, name, constraint)
+    }
+
+    /// Capabilities besides `name = [epoch:]version-release`, which is always provided
+    pub fn provides(self, name: &str, constraint: VersionConstraint) -> Self {
+        self.dependency(DependencyKind::Provides, name, constraint)
+    }
+
+    pub fn conflicts(self, name: &str, constraint: VersionConstraint) -> Self {
+        self.dependency(DependencyKind::Conflicts, name, constraint)
+    }
+
+    pub fn obsoletes(self, name: &str, constraint: VersionConstraint) -> Self {
+        self.dependency(DependencyKind::Obsoletes, name, constraint)
+    }
+
+    pub fn recommends(self, name: &str, constraint: VersionConstraint) -> Self {
+        self.dependency(DependencyKind::Recommends, name, constraint)
+    }
+
+    fn dependency(
+        mut self,
+        kind: DependencyKind,
+        name: &str,
+        constraint: VersionConstraint,
+    ) -> Self {
+        let dependency = Dependency::with_constraint(name, &constraint);
+        self.dependencies.push((kind, dependency));
         self
     }
 
@@ -173,21 +324,80 @@ impl RPMBuilder {
         self
     }
 
-    // preTrans
-    // postTrans
+    /// Script run before the transaction, before any package is installed
+    pub fn pre_trans(mut self, file: &str) -> Self {
+        self.pre_trans = Some(file.to_owned());
+        self
+    }
+
+    /// Script run after the transaction, when all packages are installed
+    pub fn post_trans(mut self, file: &str) -> Self {
+        self.post_trans = Some(file.to_owned());
+        self
+    }
+
+    /// Interpreter of the pretrans script with arguments, e.g. `/bin/bash -e` or `<lua>`
+    pub fn pre_trans_prog(mut self, prog: &str) -> Self {
+        self.pre_trans_prog = Some(prog.to_owned());
+        self
+    }
+
+    pub fn post_trans_prog(mut self, prog: &str) -> Self {
+        self.post_trans_prog = Some(prog.to_owned());
+        self
+    }
+
+    /// Script run by `rpm -V` with its interpreter, e.g. `/bin/sh`
+    pub fn verify_script(mut self, file: &str, prog: &str) -> Self {
+        self.verify_script = Some((file.to_owned(), prog.to_owned()));
+        self
+    }
+
+    pub fn verify_script_flags(mut self, flags: ScriptFlags) -> Self {
+        self.verify_script_flags = Some(flags);
+        self
+    }
+
+    /// Digest algorithm of FileDigests, SHA-256 by default, MD5 installs on EL6
+    pub fn file_digest(mut self, algo: DigestAlgo) -> Self {
+        self.file_digest = Some(algo);
+        self
+    }
+
+    /// Stores IMA signatures of added files, rpm has no tag for other attributes
+    pub fn xattrs(mut self, enabled: bool) -> Self {
+        self.xattrs = enabled;
+        self
+    }
+
+    /// Directory the added paths are read from, e.g. `%{buildroot}`,
+    /// the package installs them at the added paths
+    pub fn build_root<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.build_root = Some(dir.as_ref().to_owned());
+        self
+    }
 
+    /// Adds the file with the default user and group and the mode of the source file
     pub fn add_file(mut self, file: &str) -> Self {
-        self.files.push(file.to_owned());
+        self.files.push(FileOptions::new(file));
         self
     }
 
     pub fn add_files(mut self, files: Vec<&str>) -> Self {
         for file in &files {
-            self.files.push((*file).to_owned());
+            self.files.push(FileOptions::new(file));
         }
         self
     }
 
+    /// Adds the file with its own source, mode, owner and flags
+    pub fn add_file_with(mut self, options: FileOptions) -> Self {
+        self.files.push(options);
+        self
+    }
+
+    /// Adds the directory of the build root, or an empty directory with mode 0755
+    /// if it does not exist there
     pub fn add_directory(mut self, dir: &str) -> Self {
         self.directories.push(dir.to_owned());
         self
@@ -200,53 +410,273 @@ impl RPMBuilder {
         self
     }
 
-    pub fn add_link(mut self, link: &str) -> Self {
-        self.links.push(link.to_owned());
+    /// Adds the symlink `link` pointing to `target`, e.g. a relative `libdemo.so.1`
+    pub fn add_link(mut self, link: &str, target: &str) -> Self {
+        self.links.push((link.to_owned(), target.to_owned()));
         self
     }
 
-    pub fn add_links(mut self, links: Vec<&str>) -> Self {
-        for link in links.into_iter() {
-            self.links.push(link.to_owned());
+    pub fn add_links(mut self, links: Vec<(&str, &str)>) -> Self {
+        for (link, target) in links.into_iter() {
+            self.links.push((link.to_owned(), target.to_owned()));
         }
         self
     }
 
-    pub fn build(self) -> io::Result<RPMFile<File>> {
-        let filename = self
-            .filename
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "No rpm file is defined"))?;
+    /// Adds every directory, regular file and symlink under `src_root` with the same
+    /// relative path under `install_prefix`, `src_root` itself is not added
+    pub fn add_tree<P: AsRef<Path>>(mut self, src_root: P, install_prefix: &str) -> Self {
+        self.trees
+            .push((src_root.as_ref().to_owned(), install_prefix.to_owned()));
+        self
+    }
+
+    /// Glob of paths relative to the tree root which `add_tree` skips, e.g. `*.pyc`,
+    /// the contents of an excluded directory are skipped too
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.excludes.push(pattern.to_owned());
+        self
+    }
+
+    /// Adds a main header tag by its id, e.g. a private tag in the 60000 range,
+    /// the value replaces the one the builder writes for a known tag
+    pub fn custom_tag(mut self, tag: u32, value: RType) -> Self {
+        self.custom_tags.push((tag, value));
+        self
+    }
+
+    /// Adds a signature header tag by its id, sizes and digests of the package
+    /// are still computed on writing
+    pub fn custom_signature_tag(mut self, tag: u32, value: RType) -> Self {
+        self.custom_signature_tags.push((tag, value));
+        self
+    }
+
+    /// Builds a source package: the spec, sources and patches are stored flat
+    /// in the payload as `rpmbuild -bs` does, the package has no Platform,
+    /// OptFlags, SourceRpm and self-provide. The Arch tag keeps the build
+    /// architecture while the lead has the `NOARCH` number
+    pub fn source_package(mut self) -> Self {
+        self.source_package = true;
+        self
+    }
+
+    /// Adds the spec file of a source package under its file name
+    pub fn add_spec(mut self, file: &str) -> Self {
+        self.files.push(FileOptions {
+            flags: FileFlags::SPECFILE,
+            ..flat_file(file)
+        });
+        self
+    }
+
+    /// Adds a source of a source package under its file name, e.g. a tarball
+    pub fn add_source(mut self, file: &str) -> Self {
+        let options = flat_file(file);
+        self.sources.push(options.path.clone());
+        self.files.push(options);
+        self
+    }
+
+    pub fn add_patch(mut self, file: &str) -> Self {
+        let options = flat_file(file);
+        self.patches.push(options.path.clone());
+        self.files.push(options);
+        self
+    }
 
-        let writer = OpenOptions::new().create(true).write(true).open(filename)?;
+    /// Creates the package file with the header tags, nothing is written to it,
+    /// `build_package` writes a complete package
+    pub fn build(mut self) -> io::Result<RPMFile<File>> {
+        self.expand_trees()?;
+        let sources: Vec<Option<PathBuf>> = self
+            .files
+            .iter()
+            .map(|file| self.file_source(file))
+            .collect();
+        self.build_with(&sources)
+    }
 
-        let mut file_infos: Vec<FileInfo> = Vec::new();
+    /// Writes the lead, both headers and the payload of the added directories,
+    /// files and links, the payload is compressed in memory
+    pub fn build_package(mut self) -> io::Result<RPMFile<File>> {
+        self.expand_trees()?;
+        let filename = self.filename.clone().ok_or_else(no_filename)?;
+        let mut cpio = Vec::new();
+        let options = self.file_options();
+        let files = self.write_cpio(&mut cpio, &options)?;
+        let sources: Vec<Option<PathBuf>> = files
+            .iter()
+            .map(|file| match options.get(&normalize_path(&file.name)) {
+                Some(options) => self.file_source(options),
+                None => Some(self.source(&file.name)),
+            })
+            .collect();
+        let digest_algo = self.digest_algo();
+        let mut rpm = self.build_with(&sources)?;
 
-        for file in self.files {
-            file_infos.push(FileInfo {
-                name: file,
-                ..Default::default()
-            });
+        // members of a hardlink group share the inode, their size is counted once
+        let mut inodes = HashMap::new();
+        for file in &files {
+            inodes.insert(file.inode, file.size);
         }
+        rpm.header_tags
+            .insert_files(&files)
+            .insert_size(inodes.values().sum())
+            .insert(Tag::FileDigestAlgo, RType::Int32(digest_algo.rpm_id()));
+
+        rpm.write_package(&mut cpio.as_slice())?;
+        Ok(RPMFile::open(filename)?)
+    }
+
+    /// Header tags of the package, IMA signatures are read from the signed files
+    fn build_with(self, signed: &[Option<PathBuf>]) -> io::Result<RPMFile<File>> {
+        let build_time = timestamp(self.build_time)?;
+        let digest_algo = self.digest_algo();
+        let filename = self.filename.ok_or_else(no_filename)?;
+
+        let writer = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(filename)?;
+
+        let signatures = if self.xattrs {
+            file_signatures(signed)?
+        } else {
+            Vec::new()
+        };
 
         let package_name = self.package_name.clone().unwrap_or_default();
+        let version = self.version.unwrap_or_default();
+        let nevra = Nevra {
+            name: package_name.clone(),
+            version: version.clone(),
+            release: self.release.clone(),
+            ..Default::default()
+        };
+        let evr = if self.epoch > 0 {
+            format!("{}:{}-{}", self.epoch, version, self.release)
+        } else {
+            format!("{}-{}", version, self.release)
+        };
+        let self_provide = Dependency::new(&package_name, DependencyFlags::EQUAL, &evr);
+        let os = self.os.unwrap_or_else(|| "linux".to_owned());
+        let rpm_type = if self.source_package {
+            Type::Source
+        } else {
+            Type::Binary
+        };
+        let mut lead = Lead::new(&nevra.nvr(), &self.arch, &os, rpm_type);
+        if self.source_package {
+            lead.archnum = NOARCH;
+        }
+
+        let arch = self.arch;
+        let platform = self
+            .platform
+            .unwrap_or_else(|| format!("{}-unknown-linux-gnu", arch));
+        let build_archs = self.build_archs.unwrap_or_else(|| {
+            if arch == "noarch" {
+                vec![arch.clone()]
+            } else {
+                Vec::new()
+            }
+        });
 
         let mut header_tags = Tags::<Tag>::new();
         header_tags
-            .insert_name(package_name.clone())
+            .insert_name(package_name)
             .insert_epoch(self.epoch)
-            .insert_version(self.version.unwrap_or_default())
-            .insert_arch(self.arch)
+            .insert_version(version)
+            .insert_release(self.release)
+            .insert_arch(arch)
+            .insert_os(os)
             .insert_group(self.package_group.unwrap_or_default())
             .insert_size(0)
             .insert_license(self.license.unwrap_or_default())
-            .insert_source_rpm(self.source_rpm.unwrap_or_default())
-            .insert_build_time(self.build_time)
+            .insert_build_time(build_time)
             .insert_build_host(self.build_host)
             .insert_summary(self.summary.unwrap_or_default())
             .insert_description(self.description.unwrap_or_default())
-            .insert_payload_format("cpio".to_owned())
-            .insert_payload_compressor(self.compression)
-            .insert_payload_flags("6".to_owned());
+            .insert_rpm_version(env!("CARGO_PKG_VERSION").to_owned())
+            .insert_payload_format("cpio".to_owned());
+
+        if self.source_package {
+            header_tags.insert(Tag::SourcePackage, RType::Int32(1));
+            if !self.sources.is_empty() {
+                header_tags.insert(Tag::Source, RType::StringArray(self.sources));
+            }
+            if !self.patches.is_empty() {
+                header_tags.insert(Tag::Patch, RType::StringArray(self.patches));
+            }
+        } else {
+            header_tags
+                .insert_platform(platform)
+                .insert_opt_flags(self.optflags.unwrap_or_else(|| "-O2 -g".to_owned()))
+                .insert_source_rpm(self.source_rpm.unwrap_or_default());
+        }
+
+        if let Some(vendor) = self.vendor {
+            header_tags.insert_vendor(vendor);
+        }
+
+        if let Some(url) = self.url {
+            header_tags.insert_url(url);
+        }
+
+        if let Some(packager) = self.packager {
+            header_tags.insert_packager(packager);
+        }
+
+        if let Some(distribution) = self.distribution {
+            header_tags.insert_distribution(distribution);
+        }
+
+        if let Some(url) = self.dist_url {
+            header_tags.insert_disturl(url);
+        }
+
+        if let Some(compressor) = self.compression.compressor() {
+            header_tags
+                .insert_payload_compressor(compressor.to_owned())
+                .insert_payload_flags(self.compression.flags());
+        }
+
+        if !build_archs.is_empty() {
+            header_tags.insert_build_archs(build_archs);
+        }
+
+        let rpmlib = rpmlib_requires(&self.compression, digest_algo, !self.source_package);
+        let mut dependencies = self.dependencies;
+        if !self.source_package {
+            dependencies.push((DependencyKind::Provides, self_provide));
+        }
+        for dependency in rpmlib {
+            dependencies.push((DependencyKind::Requires, dependency));
+        }
+        for kind in [
+            DependencyKind::Requires,
+            DependencyKind::Provides,
+            DependencyKind::Conflicts,
+            DependencyKind::Obsoletes,
+            DependencyKind::Recommends,
+        ]
+        .iter()
+        {
+            let declared: Vec<Dependency> = dependencies
+                .iter()
+                .filter(|(k, _)| k == kind)
+                .map(|(_, dependency)| dependency.clone())
+                .collect();
+            if !declared.is_empty() {
+                Dependency::insert_tags(&mut header_tags, *kind, &declared);
+            }
+        }
+
+        if signatures.iter().any(|signature| !signature.is_empty()) {
+            header_tags.insert_file_signatures(signatures);
+        }
 
         if let Some(file) = self.pre_install {
             let contents = read_to_string(file)?;
@@ -268,24 +698,363 @@ impl RPMBuilder {
             header_tags.insert_post_uninstall(contents);
         }
 
+        if let Some(file) = self.pre_trans {
+            let contents = read_to_string(file)?;
+            header_tags
+                .insert_pre_trans(contents)
+                .insert_pre_trans_prog(script_prog(self.pre_trans_prog));
+        }
+
+        if let Some(file) = self.post_trans {
+            let contents = read_to_string(file)?;
+            header_tags
+                .insert_post_trans(contents)
+                .insert_post_trans_prog(script_prog(self.post_trans_prog));
+        }
+
+        if let Some((file, prog)) = self.verify_script {
+            let contents = read_to_string(file)?;
+            header_tags
+                .insert_verify_script(contents)
+                .insert_verify_script_prog(script_prog(Some(prog)));
+            if let Some(flags) = self.verify_script_flags {
+                header_tags.insert_verify_script_flags(flags.bits());
+            }
+        }
+
+        for (tag, value) in self.custom_tags {
+            header_tags.insert_by_id(tag, value);
+        }
+
         let mut signature_tags = Tags::<SignatureTag>::new();
         signature_tags.insert_payload_size(0);
-
-        let lead = Lead::from_str(package_name);
+        for (tag, value) in self.custom_signature_tags {
+            signature_tags.insert_by_id(tag, value);
+        }
 
         Ok(RPMFile {
             lead,
-            header_tags,
-            signature_tags,
+            header_tags: header_tags.into(),
+            signature_tags: signature_tags.into(),
             payload_offset: 0,
+            decompressors: Default::default(),
+            signature_range: 0..0,
+            header_range: 0..0,
             file: writer,
         })
     }
 }
 
+impl RPMBuilder {
+    /// Local path of an added path, it is inside the build root if it is set
+    fn source(&self, path: &str) -> PathBuf {
+        match &self.build_root {
+            Some(root) => root.join(path.trim_start_matches('/')),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// Options of the added files by their normalized paths, the first one wins
+    fn file_options(&self) -> HashMap<String, &FileOptions> {
+        let mut options = HashMap::new();
+        for file in &self.files {
+            options.entry(normalize_path(&file.path)).or_insert(file);
+        }
+        options
+    }
+
+    /// Local path of an added file, ghost files have none
+    fn file_source(&self, file: &FileOptions) -> Option<PathBuf> {
+        if file.is_ghost() {
+            return None;
+        }
+        Some(
+            file.source
+                .clone()
+                .unwrap_or_else(|| self.source(&file.path)),
+        )
+    }
+
+    /// Adds the contents of the trees as files and links, sorted by path
+    fn expand_trees(&mut self) -> io::Result<()> {
+        let excludes = self
+            .excludes
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Error: exclude pattern {} is not valid: {}", pattern, e),
+                    )
+                })
+            })
+            .collect::<io::Result<Vec<Pattern>>>()?;
+
+        for (root, prefix) in std::mem::take(&mut self.trees) {
+            let relative = |path: &Path| -> String {
+                let relative = path.strip_prefix(&root).unwrap_or(path);
+                relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            };
+            let walker = WalkDir::new(&root)
+                .min_depth(1)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_entry(|entry| {
+                    let path = relative(entry.path());
+                    !excludes.iter().any(|pattern| pattern.matches(&path))
+                });
+
+            for entry in walker {
+                let entry = entry.map_err(io::Error::from)?;
+                let path = format!(
+                    "{}/{}",
+                    prefix.trim_end_matches('/'),
+                    relative(entry.path())
+                );
+                if entry.path_is_symlink() {
+                    let target = std::fs::read_link(entry.path())?;
+                    self.links
+                        .push((path, target.to_string_lossy().into_owned()));
+                } else if entry.file_type().is_dir() || entry.file_type().is_file() {
+                    self.files.push(FileOptions {
+                        source: Some(entry.into_path()),
+                        ..FileOptions::new(&path)
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Payload entry which has no source file
+    fn entry(&self, name: &str, mode: u32) -> FileEntry {
+        FileEntry {
+            name: name.to_owned(),
+            mode,
+            nlink: if mode & S_IFMT == S_IFDIR { 2 } else { 1 },
+            mtime: self.build_time as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Header metadata of a ghost file, the mode, mtime and owner are taken
+    /// from the build root if the file exists there, the inode is set by `write_cpio`
+    fn ghost_info(&self, file: &FileOptions) -> io::Result<FileInfo> {
+        let source = file
+            .source
+            .clone()
+            .unwrap_or_else(|| self.source(&file.path));
+        let mut info = FileInfo {
+            name: normalize_path(&file.path),
+            mtime: self.build_time as u32,
+            ..Default::default()
+        };
+        if source.exists() {
+            let entry = FileEntry::try_from(&source)?;
+            info.mode = entry.mode as u16;
+            info.mtime = entry.mtime;
+            info.user = entry.uid.to_string();
+            info.group = entry.gid.to_string();
+        }
+        if let Some(epoch) = self.clamp_mtime {
+            info.mtime = info.mtime.min(timestamp(epoch)?);
+        }
+        if let Some(mode) = file.mode {
+            info.mode = info.mode & S_IFMT as u16 | mode & 0o7777;
+        }
+        Ok(info)
+    }
+
+    fn digest_algo(&self) -> DigestAlgo {
+        self.file_digest.unwrap_or(DigestAlgo::Sha256)
+    }
+
+    /// Writes the payload sorted by path, as rpm looks files up,
+    /// and returns the files of the header in the same order, ghost files included
+    fn write_cpio(
+        &self,
+        writer: &mut Vec<u8>,
+        file_options: &HashMap<String, &FileOptions>,
+    ) -> io::Result<Vec<FileInfo>> {
+        // files of source packages are stored by their names
+        let member = |path: &str| {
+            let path = normalize_path(path);
+            if self.source_package {
+                path.trim_start_matches('/').to_owned()
+            } else {
+                format!(".{}", path)
+            }
+        };
+        let mut builder = CpioBuilder::new(writer).deterministic(true);
+        if let Some(epoch) = self.clamp_mtime {
+            builder = builder.clamp_mtime(timestamp(epoch)?);
+        }
+        for path in &self.directories {
+            let source = self.source(path);
+            builder = if source.is_dir() {
+                builder.add_file(&source.to_string_lossy(), &member(path))?
+            } else {
+                builder.add_entry(self.entry(&member(path), S_IFDIR | 0o755), Vec::new())
+            };
+        }
+
+        let mut ghosts = Vec::new();
+        for file in &self.files {
+            let source = match self.file_source(file) {
+                Some(source) => source.to_string_lossy().into_owned(),
+                None => {
+                    ghosts.push(self.ghost_info(file)?);
+                    continue;
+                }
+            };
+            builder = match file.mode {
+                Some(mode) => {
+                    builder.add_file_with_mode(&source, &member(&file.path), mode.into())?
+                }
+                None => builder.add_file(&source, &member(&file.path))?,
+            };
+        }
+
+        let mut targets = HashMap::new();
+        for (path, target) in &self.links {
+            targets.insert(normalize_path(path), target.clone());
+            let entry = self.entry(&member(path), S_IFLNK | 0o777);
+            builder = builder.add_entry(entry, target.clone().into_bytes());
+        }
+
+        let mut files = builder.files()?;
+        let mut digests = builder.build_with_digests(self.digest_algo())?;
+        // ghosts continue the inode numbering of the payload, as separate files
+        let mut inode = files.iter().map(|file| file.inode).max().unwrap_or(0);
+        for mut ghost in ghosts {
+            inode += 1;
+            ghost.inode = inode;
+            files.push(ghost);
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for file in files.iter_mut() {
+            let options = file_options.get(&normalize_path(&file.name));
+            file.user = options
+                .and_then(|options| options.user.clone())
+                .unwrap_or_else(|| self.default_user.clone());
+            file.group = options
+                .and_then(|options| options.group.clone())
+                .unwrap_or_else(|| self.default_group.clone());
+            file.flags = options.map_or(0, |options| options.flags.bits());
+            if let Some(target) = targets.remove(&file.name) {
+                file.linkname = target;
+            } else if let Some(digest) = digests.remove(&member(&file.name)) {
+                file.digest = digest;
+            }
+        }
+        Ok(files)
+    }
+}
+
+fn no_filename() -> io::Error {
+    io::Error::other("No rpm file is defined")
+}
+
+/// File of a source package, stored under its file name
+fn flat_file(file: &str) -> FileOptions {
+    let name = Path::new(file).file_name().map_or_else(
+        || file.to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    FileOptions {
+        source: Some(PathBuf::from(file)),
+        ..FileOptions::new(&name)
+    }
+}
+
+/// Features of rpm the package relies on, as rpmbuild declares them
+fn rpmlib_requires(
+    compression: &Compression,
+    file_digest: DigestAlgo,
+    prefixed: bool,
+) -> Vec<Dependency> {
+    let mut requires = vec![Dependency::rpmlib("CompressedFileNames", "3.0.4-1")];
+    // rpm older than 4.6 knows only MD5 file digests
+    if file_digest != DigestAlgo::Md5 {
+        requires.push(Dependency::rpmlib("FileDigests", "4.6.0-1"));
+    }
+    // members of source packages have no `./` prefix
+    if prefixed {
+        requires.push(Dependency::rpmlib("PayloadFilesHavePrefix", "4.0-1"));
+    }
+    match compression {
+        Compression::Zstd { .. } => requires.push(Dependency::rpmlib("PayloadIsZstd", "5.4.18-1")),
+        Compression::Xz { .. } => requires.push(Dependency::rpmlib("PayloadIsXz", "5.2-1")),
+        Compression::Bzip2 { .. } => requires.push(Dependency::rpmlib("PayloadIsBzip2", "3.0.5-1")),
+        _ => {}
+    }
+    requires
+}
+
+/// Interpreter and its arguments, `/bin/sh` by default
+fn script_prog(prog: Option<String>) -> Vec<String> {
+    prog.as_deref()
+        .unwrap_or("/bin/sh")
+        .split_whitespace()
+        .map(|arg| arg.to_owned())
+        .collect()
+}
+
+/// Hex encoded `security.ima` attributes of the files, empty for ghost files
+fn file_signatures(files: &[Option<PathBuf>]) -> io::Result<Vec<String>> {
+    let mut signatures = Vec::with_capacity(files.len());
+    for file in files {
+        let file = match file {
+            Some(file) => file,
+            None => {
+                signatures.push(String::new());
+                continue;
+            }
+        };
+        let signature = read_xattrs(file)?
+            .into_iter()
+            .find(|(name, _)| name == IMA_XATTR)
+            .map(|(_, value)| hex::encode(value));
+        signatures.push(signature.unwrap_or_default());
+    }
+    Ok(signatures)
+}
+
+/// Host name of the build machine, "localhost" if it is unknown
+/// or the crate is built without the hostname feature
+fn build_host() -> String {
+    #[cfg(feature = "hostname")]
+    {
+        if let Some(name) = hostname::get()
+            .ok()
+            .and_then(|name| name.into_string().ok())
+        {
+            return name;
+        }
+    }
+    "localhost".to_owned()
+}
+
+/// Timestamp of the `SOURCE_DATE_EPOCH` variable, to be passed to `RPMBuilder::reproducible`
+pub fn source_date_epoch() -> Option<i64> {
+    parse_epoch(&std::env::var("SOURCE_DATE_EPOCH").ok()?)
+}
+
+fn parse_epoch(value: &str) -> Option<i64> {
+    value.trim().parse().ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::{align_n_bytes, S_IFREG};
+    use crate::{verify_files, RPMInfo, VerifyAttrs, VerifyStatus};
+    use std::convert::{TryFrom, TryInto};
     use tempfile::tempdir;
     #[test]
     fn test_builder_smoke() {
@@ -300,11 +1069,811 @@ mod tests {
             .release("1234")
             .epoch(1)
             .arch("noarch")
-            .compression("gzip")
+            .compression(Compression::Gzip { level: 9 })
             .filename(file)
             .build()
             .unwrap();
 
         rpm.write_head().unwrap();
     }
+
+    #[test]
+    fn test_builder_platform() {
+        let dir = tempdir().unwrap();
+        let builder = RPMBuilder::new()
+            .package_name("Test")
+            .version("0.1")
+            .filename(dir.path().join("test.rpm"));
+
+        let rpm = builder.clone().build().unwrap();
+        let tags = &rpm.header_tags;
+        assert_eq!(
+            tags.get_as_string_or(Tag::Platform),
+            "noarch-unknown-linux-gnu"
+        );
+        assert_eq!(tags.get_as_string_or(Tag::OptFlags), "-O2 -g");
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::BuildArchs),
+            vec!["noarch".to_owned()]
+        );
+
+        let rpm = builder.clone().arch("x86_64").build().unwrap();
+        let tags = &rpm.header_tags;
+        assert_eq!(
+            tags.get_as_string_or(Tag::Platform),
+            "x86_64-unknown-linux-gnu"
+        );
+        assert!(tags.get_value(Tag::BuildArchs).is_none());
+
+        let rpm = builder
+            .arch("x86_64")
+            .platform("x86_64-redhat-linux-gnu")
+            .optflags("-O2")
+            .build_archs(vec!["x86_64", "i686"])
+            .build()
+            .unwrap();
+        let info = RPMInfo::try_from(&rpm).unwrap();
+        assert_eq!(info.platform, "x86_64-redhat-linux-gnu");
+        assert_eq!(info.optflags, "-O2");
+        assert_eq!(
+            info.build_archs,
+            vec!["x86_64".to_owned(), "i686".to_owned()]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_package() -> io::Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("usr/share/demo"))?;
+        std::fs::write(root.join("usr/share/demo/readme"), "hello\n")?;
+
+        let compressions = vec![
+            (Compression::Gzip { level: 6 }, "6"),
+            (
+                Compression::Zstd {
+                    level: 19,
+                    workers: 2,
+                },
+                "19T2",
+            ),
+            (Compression::Xz { level: 2 }, "2"),
+            (Compression::Bzip2 { level: 9 }, "9"),
+            (Compression::None, ""),
+        ];
+        // codecs of disabled features fail to write
+        let compressions = compressions
+            .into_iter()
+            .filter(|(compression, _)| compression.writer(io::sink()).is_ok());
+        for (compression, flags) in compressions {
+            let file = dir.path().join(format!("demo-{}.rpm", flags));
+            let rpm = RPMBuilder::new()
+                .package_name("demo")
+                .version("1.0")
+                .compression(compression)
+                .build_root(&root)
+                .add_directory("/usr/share/demo")
+                .add_file("/usr/share/demo/readme")
+                .add_link("/usr/share/demo/link", "readme")
+                .filename(&file)
+                .build_package()?;
+
+            assert_eq!(rpm.header_tags.get_as_string_or(Tag::PayloadFlags), flags);
+            let files = rpm.header_tags.files();
+            let names: Vec<&str> = files.iter().map(|file| file.name.as_str()).collect();
+            assert_eq!(
+                names,
+                vec![
+                    "/usr/share/demo",
+                    "/usr/share/demo/link",
+                    "/usr/share/demo/readme"
+                ]
+            );
+            assert_eq!(files[1].linkname, "readme");
+            assert_eq!(files[2].size, 6);
+            assert_eq!(files[2].digest, DigestAlgo::Sha256.digest_bytes(b"hello\n"));
+            assert!(files.iter().all(|file| file.user == "root"));
+            assert_eq!(RPMInfo::try_from(&rpm)?.size, 12);
+
+            // the signature header covers the main header and the payload
+            let bytes = std::fs::read(&file)?;
+            let be = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+            let signature_size = 16 + be(104) * 16 + be(108);
+            let start = (96 + signature_size + align_n_bytes(signature_size, 8)) as usize;
+            let header = &bytes[start..rpm.payload_offset as usize];
+            let signature_tags = &rpm.signature_tags;
+            assert_eq!(
+                signature_tags
+                    .get_value(SignatureTag::Size)
+                    .and_then(|size| size.as_u64()),
+                Some((bytes.len() - start) as u64)
+            );
+            assert_eq!(
+                signature_tags.get_as_string_or(SignatureTag::SHA256Header),
+                DigestAlgo::Sha256.digest_bytes(header)
+            );
+
+            let mut readme = Vec::new();
+            let rpm = RPMFile::open(&file)?;
+            let size = rpm.signature_tags.payload_size();
+            let mut payload = Vec::new();
+            assert_eq!(RPMFile::open(&file)?.copy_payload_to(&mut payload)?, size);
+            rpm.extract_file("/usr/share/demo/readme", &mut readme)?;
+            assert_eq!(readme, b"hello\n");
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_file_options() -> io::Result<()> {
+        use crate::payload::FileFlags;
+
+        let dir = tempdir()?;
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("etc/demo"))?;
+        std::fs::write(root.join("etc/demo/demo.conf"), "key=value\n")?;
+        // a ghost which exists in the build root keeps its mode, not its size or inode
+        std::fs::write(root.join("etc/demo/state"), "running\n")?;
+        std::fs::write(dir.path().join("demo.sh"), "#!/bin/sh\n")?;
+        let file = dir.path().join("demo.rpm");
+
+        RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .default_user("daemon")
+            .default_group("daemon")
+            .build_root(&root)
+            .add_directory("/etc/demo")
+            .add_file_with(FileOptions {
+                mode: Some(0o640),
+                user: Some("app".to_owned()),
+                group: Some("adm".to_owned()),
+                flags: FileFlags::CONFIGURATION | FileFlags::NOREPLACE,
+                ..FileOptions::new("/etc/demo/demo.conf")
+            })
+            .add_file_with(FileOptions {
+                source: Some(dir.path().join("demo.sh")),
+                mode: Some(0o755),
+                ..FileOptions::new("/etc/demo/run.sh")
+            })
+            .add_file_with(FileOptions {
+                flags: FileFlags::GHOST,
+                ..FileOptions::new("/etc/demo/state")
+            })
+            .filename(&file)
+            .build_package()?;
+
+        let rpm = RPMFile::open(&file)?;
+        let tags = &rpm.header_tags;
+        let names: Vec<String> = tags.files().into_iter().map(|file| file.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "/etc/demo",
+                "/etc/demo/demo.conf",
+                "/etc/demo/run.sh",
+                "/etc/demo/state"
+            ]
+        );
+        let modes: Vec<u16> = tags
+            .get_as_u16_array_or(Tag::FileModes)
+            .iter()
+            .map(|mode| mode & 0o7777)
+            .collect();
+        assert_eq!(modes[1..], [0o640, 0o755, 0o644]);
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::FileUserName),
+            vec!["daemon", "app", "daemon", "daemon"]
+        );
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::FileGroupName),
+            vec!["daemon", "adm", "daemon", "daemon"]
+        );
+        assert_eq!(
+            tags.get_as_u32_array_or(Tag::FileFlags),
+            vec![
+                0,
+                (FileFlags::CONFIGURATION | FileFlags::NOREPLACE).bits(),
+                0,
+                FileFlags::GHOST.bits()
+            ]
+        );
+        assert_eq!(tags.get_as_u32_array_or(Tag::FileSizes)[3], 0);
+        assert_eq!(tags.get_as_u32_array_or(Tag::FileInodes), vec![1, 2, 3, 4]);
+        assert_eq!(tags.get_as_u32_array_or(Tag::FileDevices), vec![0, 0, 0, 0]);
+        assert_eq!(tags.get_as_u32_array_or(Tag::FileNLinks)[3], 1);
+
+        // ghost files are not stored in the payload
+        let entries: Vec<String> = RPMFile::open(&file)?
+            .list_payload()?
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(
+            entries,
+            vec!["./etc/demo", "./etc/demo/demo.conf", "./etc/demo/run.sh"]
+        );
+        let mut script = Vec::new();
+        RPMFile::open(&file)?.extract_file("/etc/demo/run.sh", &mut script)?;
+        assert_eq!(script, b"#!/bin/sh\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_links_and_directories() -> io::Result<()> {
+        let dir = tempdir()?;
+        std::fs::write(dir.path().join("libdemo.so.1"), "ELF")?;
+        let file = dir.path().join("demo.rpm");
+
+        RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .build_time(1_700_000_000)
+            .build_root(dir.path().join("missing"))
+            .add_directories(vec!["/opt/demo", "/opt/demo/empty"])
+            .add_file_with(FileOptions {
+                source: Some(dir.path().join("libdemo.so.1")),
+                mode: Some(0o644),
+                ..FileOptions::new("/opt/demo/lib/libdemo.so.1")
+            })
+            .add_link("/opt/demo/lib/libdemo.so", "libdemo.so.1")
+            .filename(&file)
+            .build_package()?;
+
+        let rpm = RPMFile::open(&file)?;
+        let files = rpm.header_tags.files();
+        let summary: Vec<(&str, u32, &str)> = files
+            .iter()
+            .map(|file| {
+                (
+                    file.name.as_str(),
+                    u32::from(file.mode),
+                    file.linkname.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/opt/demo", S_IFDIR | 0o755, ""),
+                ("/opt/demo/empty", S_IFDIR | 0o755, ""),
+                ("/opt/demo/lib/libdemo.so", S_IFLNK | 0o777, "libdemo.so.1"),
+                ("/opt/demo/lib/libdemo.so.1", S_IFREG | 0o644, ""),
+            ]
+        );
+        assert_eq!(
+            (files[0].mtime, files[2].mtime),
+            (1_700_000_000, 1_700_000_000)
+        );
+        // parents of added files are listed without being added
+        assert_eq!(
+            rpm.header_tags.get_as_string_array_or(Tag::DirNames),
+            vec!["/opt/", "/opt/demo/", "/opt/demo/lib/"]
+        );
+
+        let entries: Vec<(String, u32)> = rpm
+            .list_payload()?
+            .into_iter()
+            .map(|entry| (entry.name, entry.file_size))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("./opt/demo".to_owned(), 0),
+                ("./opt/demo/empty".to_owned(), 0),
+                ("./opt/demo/lib/libdemo.so".to_owned(), 12),
+                ("./opt/demo/lib/libdemo.so.1".to_owned(), 3),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_info_round_trip() -> crate::Result<()> {
+        let dir = tempdir()?;
+        let file = dir.path().join("demo.rpm");
+
+        RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .release("3.el8")
+            .epoch(2)
+            .arch("x86_64")
+            .optflags("-O3")
+            .package_group("Applications/System".to_owned())
+            .license("MIT".to_owned())
+            .source_rpm("demo-1.0-3.el8.src.rpm".to_owned())
+            .build_time(1_700_000_000)
+            .build_host("builder.example.com")
+            .summary("Demo package")
+            .description("Demo package for tests")
+            .requires("glibc", VersionConstraint::Any)
+            .compression(Compression::None)
+            .filename(&file)
+            .build_package()?;
+
+        let info = RPMInfo::try_from(&RPMFile::open(&file)?)?;
+        assert_eq!(info.nvr(), "demo-1.0-3.el8");
+        assert_eq!(info.epoch, 2);
+        assert_eq!(info.arch, "x86_64");
+        assert_eq!(info.platform, "x86_64-unknown-linux-gnu");
+        assert_eq!(info.optflags, "-O3");
+        assert!(info.build_archs.is_empty());
+        assert_eq!(info.group, "Applications/System");
+        assert_eq!(info.license, "MIT");
+        assert_eq!(info.source_rpm, "demo-1.0-3.el8.src.rpm");
+        assert_eq!(info.build_time, 1_700_000_000);
+        assert_eq!(info.build_host, "builder.example.com");
+        assert_eq!(info.summary, "Demo package");
+        assert_eq!(info.description, "Demo package for tests");
+        assert_eq!(info.requires[0].to_string(), "glibc");
+        assert_eq!(info.provides, vec![info.self_provide()]);
+        assert_eq!(info.payload.format, "cpio");
+
+        // BuildTime is int32, the builder does not wrap other timestamps
+        let error = RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .build_time(-1)
+            .filename(dir.path().join("negative.rpm"))
+            .build_package()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_file_digests() -> crate::Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("opt/demo"))?;
+        std::fs::write(root.join("opt/demo/data"), "payload\n")?;
+        std::fs::hard_link(root.join("opt/demo/data"), root.join("opt/demo/copy"))?;
+        std::fs::write(root.join("opt/demo/empty"), "")?;
+        std::os::unix::fs::symlink("data", root.join("opt/demo/link"))?;
+
+        for algo in [DigestAlgo::Sha256, DigestAlgo::Md5].iter() {
+            let file = dir.path().join(format!("demo-{}.rpm", algo));
+            let rpm = RPMBuilder::new()
+                .package_name("demo")
+                .version("1.0")
+                .file_digest(*algo)
+                .build_root(&root)
+                .add_directory("/opt/demo")
+                .add_files(vec!["/opt/demo/data", "/opt/demo/copy", "/opt/demo/empty"])
+                .add_link("/opt/demo/link", "data")
+                .filename(&file)
+                .build_package()?;
+
+            let tags = &rpm.header_tags;
+            assert_eq!(
+                tags.get_value(Tag::FileDigestAlgo),
+                Some(RType::Int32(algo.rpm_id()))
+            );
+            let basenames = tags.get_as_string_array_or(Tag::BaseNames);
+            let digests = tags.get_as_string_array_or(Tag::FileMD5s);
+            let data = algo.digest_bytes(b"payload\n");
+            let pairs: Vec<(&str, &str)> = basenames
+                .iter()
+                .zip(digests.iter())
+                .map(|(name, digest)| (name.as_str(), digest.as_str()))
+                .collect();
+            assert_eq!(
+                pairs,
+                vec![
+                    ("demo", ""),
+                    ("copy", data.as_str()),
+                    ("data", data.as_str()),
+                    ("empty", algo.digest_bytes(b"").as_str()),
+                    ("link", ""),
+                ]
+            );
+            let file_digests = Dependency::rpmlib("FileDigests", "4.6.0-1");
+            assert_eq!(
+                rpm.requires().contains(&file_digests),
+                *algo != DigestAlgo::Md5
+            );
+
+            // the files of the build root match the header as `rpm -V` checks them
+            let info = RPMInfo::try_from(&rpm)?;
+            for result in verify_files(&info, &root) {
+                match result.status {
+                    VerifyStatus::Checked { differs, unknown } => {
+                        assert!(!(differs | unknown).contains(VerifyAttrs::DIGEST))
+                    }
+                    status => panic!("{}: {:?}", result.name, status),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_tree() -> io::Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().join("stage");
+        std::fs::create_dir_all(root.join("share/demo/cache"))?;
+        std::fs::create_dir_all(root.join("bin"))?;
+        std::fs::write(root.join("bin/tool"), "#!/bin/sh\n")?;
+        std::fs::write(root.join("share/demo/readme"), "hello\n")?;
+        std::fs::write(root.join("share/demo/readme.tmp"), "draft\n")?;
+        std::fs::write(root.join("share/demo/cache/data"), "cached\n")?;
+        std::os::unix::fs::symlink("tool", root.join("bin/tool-link"))?;
+
+        let build = |file: &Path| {
+            RPMBuilder::new()
+                .package_name("demo")
+                .version("1.0")
+                .build_time(1_700_000_000)
+                .default_user("demo")
+                .add_tree(&root, "/opt/demo/")
+                .exclude("*.tmp")
+                .exclude("share/demo/cache")
+                .filename(file)
+                .build_package()
+        };
+        let rpm = build(&dir.path().join("demo.rpm"))?;
+
+        let tags = &rpm.header_tags;
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::BaseNames),
+            vec!["bin", "tool", "tool-link", "share", "demo", "readme"]
+        );
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::DirNames),
+            vec![
+                "/opt/demo/",
+                "/opt/demo/bin/",
+                "/opt/demo/share/",
+                "/opt/demo/share/demo/"
+            ]
+        );
+        let files = tags.files();
+        assert_eq!(u32::from(files[0].mode) & S_IFMT, S_IFDIR);
+        assert_eq!(files[2].linkname, "tool");
+        assert!(files.iter().all(|file| file.user == "demo"));
+
+        // the same tree builds the same package
+        let again = build(&dir.path().join("again.rpm"))?;
+        assert_eq!(
+            std::fs::read(dir.path().join("demo.rpm"))?,
+            std::fs::read(dir.path().join("again.rpm"))?
+        );
+        assert_eq!(again.header_tags.files(), files);
+
+        let invalid = RPMBuilder::new()
+            .add_tree(&root, "/opt/demo")
+            .exclude("[")
+            .filename(dir.path().join("invalid.rpm"))
+            .build_package();
+        assert_eq!(invalid.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_reproducible() -> io::Result<()> {
+        let dir = tempdir()?;
+        // two copies of the build root, the files differ in their inodes
+        let old = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        for copy in ["first", "second"].iter() {
+            let root = dir.path().join(copy);
+            std::fs::create_dir_all(root.join("stage/doc"))?;
+            std::fs::create_dir_all(root.join("run"))?;
+            std::fs::write(root.join("stage/doc/old"), "old\n")?;
+            std::fs::write(root.join("stage/doc/new"), "new\n")?;
+            std::fs::write(root.join("run/demo.pid"), copy)?;
+            filetime::set_file_mtime(root.join("stage/doc/old"), old)?;
+        }
+
+        let epoch = 1_700_000_000;
+        let build = |name: &str| -> io::Result<Vec<u8>> {
+            let root = dir.path().join(name);
+            let file = dir.path().join(format!("{}.rpm", name));
+            RPMBuilder::new()
+                .package_name("demo")
+                .version("1.0")
+                .reproducible(epoch)
+                .build_root(&root)
+                .add_tree(root.join("stage"), "/usr/share/demo")
+                .add_directory("/usr/share/demo/empty")
+                .add_file_with(FileOptions {
+                    flags: FileFlags::GHOST,
+                    ..FileOptions::new("/usr/share/demo/doc/new.log")
+                })
+                .add_file_with(FileOptions {
+                    flags: FileFlags::GHOST,
+                    ..FileOptions::new("/run/demo.pid")
+                })
+                .filename(&file)
+                .build_package()?;
+            std::fs::read(file)
+        };
+
+        let first = build("first")?;
+        // a file touched between the builds does not change the package
+        let root = dir.path().join("second/stage");
+        filetime::set_file_mtime(root.join("doc/new"), filetime::FileTime::now())?;
+        let second = build("second")?;
+        assert!(first == second);
+
+        let rpm = RPMFile::open(dir.path().join("second.rpm"))?;
+        let mtimes: Vec<(String, u32)> = rpm
+            .header_tags
+            .files()
+            .into_iter()
+            .map(|file| (file.name, file.mtime))
+            .collect();
+        assert!(mtimes
+            .iter()
+            .all(|(name, mtime)| if name.ends_with("/old") {
+                *mtime == 1_600_000_000
+            } else {
+                *mtime == epoch as u32
+            }));
+        assert_eq!(mtimes.len(), 6);
+        assert_eq!(
+            rpm.header_tags.get_as_string_or(Tag::BuildHost),
+            "reproducible"
+        );
+
+        for epoch in [-1, 1 << 33].iter() {
+            let error = RPMBuilder::new()
+                .package_name("demo")
+                .version("1.0")
+                .reproducible(*epoch)
+                .add_tree(&root, "/usr/share/demo")
+                .filename(dir.path().join("invalid.rpm"))
+                .build_package()
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_source_package() -> io::Result<()> {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/source");
+        let data = |name: &str| data.join(name).to_string_lossy().into_owned();
+        let dir = tempdir()?;
+        let file = dir.path().join("hello-1.0-1.src.rpm");
+
+        RPMBuilder::new()
+            .package_name("hello")
+            .version("1.0")
+            .arch("x86_64")
+            .source_package()
+            .add_spec(&data("hello.spec"))
+            .add_source(&data("hello-1.0.tar.gz"))
+            .add_patch(&data("hello-greeting.patch"))
+            .requires("make", VersionConstraint::Any)
+            .filename(&file)
+            .build_package()?;
+
+        let rpm = RPMFile::open(&file)?;
+        let tags = &rpm.header_tags;
+        assert_eq!(rpm.lead.rpm_type, Type::Source);
+        // the lead is noarch, the header keeps the build architecture
+        assert_eq!(rpm.lead.archnum, NOARCH);
+        assert_eq!(tags.get_as_string_or(Tag::Arch), "x86_64");
+        assert!(rpm.check_lead_consistency().is_empty());
+        assert_eq!(tags.get_value(Tag::SourcePackage), Some(RType::Int32(1)));
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::Source),
+            vec!["hello-1.0.tar.gz"]
+        );
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::Patch),
+            vec!["hello-greeting.patch"]
+        );
+        assert_eq!(tags.get_as_string_or(Tag::Arch), "x86_64");
+        for tag in [Tag::Platform, Tag::OptFlags, Tag::SourceRpm].iter() {
+            assert!(!tags.contains(*tag), "{:?}", tag);
+        }
+        assert!(rpm.provides().is_empty());
+        let requires: Vec<String> = rpm.requires().iter().map(|dep| dep.name.clone()).collect();
+        assert_eq!(
+            requires,
+            vec!["make", "rpmlib(CompressedFileNames)", "rpmlib(FileDigests)"]
+        );
+
+        assert_eq!(
+            tags.get_as_string_array_or(Tag::BaseNames),
+            vec!["hello-1.0.tar.gz", "hello-greeting.patch", "hello.spec"]
+        );
+        assert_eq!(tags.get_as_string_array_or(Tag::DirNames), vec![""]);
+        let flags: Vec<FileFlags> = tags.files().iter().map(|file| file.file_flags()).collect();
+        assert_eq!(
+            flags,
+            vec![FileFlags::empty(), FileFlags::empty(), FileFlags::SPECFILE]
+        );
+        let names: Vec<String> = rpm
+            .list_payload()?
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(
+            names,
+            vec!["hello-1.0.tar.gz", "hello-greeting.patch", "hello.spec"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_custom_tags() -> io::Result<()> {
+        let dir = tempdir()?;
+        let file = dir.path().join("demo.rpm");
+        let values = vec![
+            RType::Null,
+            RType::Char('x'),
+            RType::CharArray(vec![b'a', b'b']),
+            RType::Int8(8),
+            RType::Int8Array(vec![1, 2]),
+            RType::Int16(16),
+            RType::Int16Array(vec![3, 4]),
+            RType::Int32(32),
+            RType::Int32Array(vec![5, 6]),
+            RType::Int64(64),
+            RType::Int64Array(vec![7, 8]),
+            RType::String("build-id".to_owned()),
+            RType::Bin(vec![0xde, 0xad, 0xbe, 0xef]),
+            RType::StringArray(vec!["first".to_owned(), "".to_owned(), "third".to_owned()]),
+            RType::I18nstring("i18n".to_owned()),
+        ];
+
+        let mut builder = RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .custom_tag(Tag::Vendor as u32, RType::String("Custom".to_owned()))
+            .custom_signature_tag(5999, RType::Bin(vec![1, 2, 3]))
+            .filename(&file);
+        for (i, value) in values.iter().enumerate() {
+            builder = builder.custom_tag(60000 + i as u32, value.clone());
+        }
+        builder.build_package()?;
+
+        let mut rpm = RPMFile::open(&file)?;
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(
+                rpm.header_tags.get_by_id(60000 + i as u32).as_ref(),
+                Some(value)
+            );
+        }
+        assert_eq!(rpm.header_tags.get_as_string_or(Tag::Vendor), "Custom");
+        assert_eq!(
+            rpm.signature_tags.get_by_id(5999),
+            Some(RType::Bin(vec![1, 2, 3]))
+        );
+        // the package is still intact
+        assert!(!rpm.verify_header_digests()?.is_mismatch());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_epoch() {
+        assert_eq!(parse_epoch("1700000000"), Some(1_700_000_000));
+        assert_eq!(parse_epoch(" 1700000000\n"), Some(1_700_000_000));
+        assert_eq!(parse_epoch("yesterday"), None);
+        assert_eq!(parse_epoch(""), None);
+    }
+
+    #[test]
+    fn test_build_metadata() -> io::Result<()> {
+        let dir = tempdir()?;
+        let file = dir.path().join("demo.rpm");
+
+        RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .vendor("Demo Vendor")
+            .url("https://example.com/demo")
+            .packager("Demo Packager <demo@example.com>")
+            .distribution("Demo Linux")
+            .dist_url("https://example.com/dist")
+            .filename(&file)
+            .build_package()?;
+
+        let rpm = RPMFile::open(&file)?;
+        let tags = &rpm.header_tags;
+        assert_eq!(tags.get_as_string_or(Tag::Vendor), "Demo Vendor");
+        assert_eq!(tags.get_as_string_or(Tag::Url), "https://example.com/demo");
+        assert_eq!(
+            tags.get_as_string_or(Tag::Packager),
+            "Demo Packager <demo@example.com>"
+        );
+        assert_eq!(tags.get_as_string_or(Tag::Distribution), "Demo Linux");
+        assert_eq!(
+            tags.get_as_string_or(Tag::DistUrl),
+            "https://example.com/dist"
+        );
+        assert_eq!(tags.get_as_string_or(Tag::Os), "linux");
+        assert_eq!(
+            tags.get_as_string_or(Tag::Platform),
+            "noarch-unknown-linux-gnu"
+        );
+        assert_eq!(
+            tags.get_as_string_or(Tag::RpmVersion),
+            env!("CARGO_PKG_VERSION")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_dependencies() -> io::Result<()> {
+        let dir = tempdir()?;
+        let file = dir.path().join("demo.rpm");
+        let deps = |list: Vec<Dependency>| -> Vec<String> {
+            list.iter().map(|dep| dep.to_string()).collect()
+        };
+
+        RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .release("2")
+            .epoch(1)
+            .compression(Compression::None)
+            .requires(
+                "glibc",
+                VersionConstraint::GreaterOrEqual("2.17".to_owned()),
+            )
+            .requires("/bin/sh", VersionConstraint::Any)
+            .provides("demo-api", VersionConstraint::Equal("3".to_owned()))
+            .conflicts("demo-legacy", VersionConstraint::Less("1:0.9".to_owned()))
+            .obsoletes(
+                "old-demo",
+                VersionConstraint::LessOrEqual("0.5-1".to_owned()),
+            )
+            .recommends("demo-doc", VersionConstraint::Greater("0.1".to_owned()))
+            .filename(&file)
+            .build_package()?;
+
+        let rpm = RPMFile::open(&file)?;
+        assert_eq!(
+            deps(rpm.requires()),
+            vec![
+                "glibc >= 2.17",
+                "/bin/sh",
+                "rpmlib(CompressedFileNames) <= 3.0.4-1",
+                "rpmlib(FileDigests) <= 4.6.0-1",
+                "rpmlib(PayloadFilesHavePrefix) <= 4.0-1",
+            ]
+        );
+        assert!(rpm.requires()[2]
+            .dependency_flags()
+            .contains(DependencyFlags::RPMLIB));
+        assert_eq!(deps(rpm.provides()), vec!["demo-api = 3", "demo = 1:1.0-2"]);
+        assert_eq!(deps(rpm.conflicts()), vec!["demo-legacy < 1:0.9"]);
+        assert_eq!(deps(rpm.obsoletes()), vec!["old-demo <= 0.5-1"]);
+        assert_eq!(
+            deps(rpm.header_tags.dependencies(DependencyKind::Recommends)),
+            vec!["demo-doc > 0.1"]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_build_zstd_requires() -> io::Result<()> {
+        let dir = tempdir()?;
+        let file = dir.path().join("demo.rpm");
+
+        RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .compression(Compression::Zstd {
+                level: 3,
+                workers: 0,
+            })
+            .filename(&file)
+            .build_package()?;
+
+        let rpm = RPMFile::open(&file)?;
+        let zstd = Dependency::rpmlib("PayloadIsZstd", "5.4.18-1");
+        assert!(rpm.requires().contains(&zstd));
+        Ok(())
+    }
 }
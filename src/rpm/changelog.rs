@@ -0,0 +1,104 @@
+use chrono::{TimeZone, Utc};
+use itertools::multizip;
+use std::fmt;
+
+use super::file::RPMFile;
+use crate::error::{Result, RpmError};
+use crate::header::{Tag, Tags};
+
+/// Entry of the %changelog section, the newest entries come first
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangelogEntry {
+    pub time: i64,
+    /// Author with the version, e.g. `Jane Doe <jane@example.com> - 1.0-1`
+    pub author: String,
+    pub text: String,
+}
+
+impl ChangelogEntry {
+    /// Entries of the changelog tags, the tags must be of the same length
+    pub fn from_tags(tags: &Tags<Tag>) -> Result<Vec<Self>> {
+        let times = tags.get_as_u32_array_or(Tag::ChangelogTime);
+        let authors = tags.get_as_string_array_or(Tag::ChangelogName);
+        let texts = tags.get_as_string_array_or(Tag::ChangelogText);
+
+        for (tag, actual) in &[
+            (Tag::ChangelogName, authors.len()),
+            (Tag::ChangelogText, texts.len()),
+        ] {
+            if *actual != times.len() {
+                return Err(RpmError::TagLengthMismatch {
+                    tag: *tag,
+                    expected: times.len(),
+                    actual: *actual,
+                });
+            }
+        }
+
+        Ok(multizip((times, authors, texts))
+            .map(|(time, author, text)| ChangelogEntry {
+                time: time.into(),
+                author,
+                text,
+            })
+            .collect())
+    }
+}
+
+/// As `rpm -q --changelog` prints it, e.g. `* Tue Mar 12 2024 Jane Doe - 1.0-1`
+impl fmt::Display for ChangelogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let date = Utc
+            .timestamp_opt(self.time, 0)
+            .single()
+            .map(|date| date.format("%a %b %d %Y").to_string())
+            .unwrap_or_default();
+        writeln!(f, "* {} {}", date, self.author)?;
+        writeln!(f, "{}", self.text)
+    }
+}
+
+impl<T> RPMFile<T> {
+    pub fn changelog(&self) -> Result<Vec<ChangelogEntry>> {
+        self.header_tags.changelog()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::RType;
+    use std::path::Path;
+
+    #[test]
+    fn test_changelog() -> Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(path)?;
+        let changelog = rpm.changelog()?;
+        assert_eq!(changelog.len(), 2);
+        assert_eq!(changelog[1].author, "John Doe <john@example.com> - 0.9-1");
+        assert_eq!(
+            changelog[1].to_string(),
+            "* Sat Jul 22 2023 John Doe <john@example.com> - 0.9-1\n- Initial package\n"
+        );
+
+        rpm.header_tags.insert(
+            Tag::ChangelogText,
+            RType::StringArray(vec!["- Only one".to_owned()]),
+        );
+        assert!(matches!(
+            rpm.changelog(),
+            Err(RpmError::TagLengthMismatch {
+                tag: Tag::ChangelogText,
+                expected: 2,
+                actual: 1,
+            })
+        ));
+
+        for tag in &[Tag::ChangelogTime, Tag::ChangelogName, Tag::ChangelogText] {
+            rpm.header_tags.remove(*tag);
+        }
+        assert!(rpm.changelog()?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,405 @@
+#[cfg(feature = "bzip2")]
+use bzip2::{read::BzDecoder, write::BzEncoder};
+#[cfg(feature = "gzip")]
+use flate2::{read::GzDecoder, write::GzEncoder};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+#[cfg(feature = "xz")]
+use xz2::{read::XzDecoder, write::XzEncoder};
+#[cfg(feature = "zstd")]
+use zstd::stream::{read::Decoder, write::Encoder};
+
+use super::tags::HeaderTags;
+use crate::error::{Result, RpmError};
+use crate::header::Tag;
+
+/// Payload codec and its options, the level is stored in PayloadFlags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip {
+        level: u32,
+    },
+    /// `workers` above 0 compress the stream in that many threads
+    Zstd {
+        level: i32,
+        workers: u32,
+    },
+    Xz {
+        level: u32,
+    },
+    Bzip2 {
+        level: u32,
+    },
+    /// Payload is stored as is
+    None,
+}
+
+/// gzip level 9, the payload is not compressed without the gzip feature
+impl Default for Compression {
+    fn default() -> Self {
+        if cfg!(feature = "gzip") {
+            Compression::Gzip { level: 9 }
+        } else {
+            Compression::None
+        }
+    }
+}
+
+impl Compression {
+    /// PayloadCompressor value, rpm omits the tag for an uncompressed payload
+    pub fn compressor(&self) -> Option<&'static str> {
+        match self {
+            Compression::Gzip { .. } => Some("gzip"),
+            Compression::Zstd { .. } => Some("zstd"),
+            Compression::Xz { .. } => Some("xz"),
+            Compression::Bzip2 { .. } => Some("bzip2"),
+            Compression::None => None,
+        }
+    }
+
+    /// PayloadFlags value as rpm writes it, e.g. `19` or `19T8` for zstd workers
+    pub fn flags(&self) -> String {
+        match *self {
+            Compression::Zstd { level, workers } if workers > 0 => {
+                format!("{}T{}", level, workers)
+            }
+            Compression::Zstd { level, .. } => level.to_string(),
+            Compression::Gzip { level }
+            | Compression::Xz { level }
+            | Compression::Bzip2 { level } => level.to_string(),
+            Compression::None => String::new(),
+        }
+    }
+
+    /// Codec named by PayloadCompressor with the level and workers of PayloadFlags,
+    /// codec defaults are used if the flags are missing
+    pub fn from_names(compressor: &str, flags: &str) -> Result<Self> {
+        let digits = flags
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(flags.len());
+        let level = flags[..digits].parse::<u32>().ok();
+        let workers = flags[digits..]
+            .strip_prefix('T')
+            .and_then(|workers| workers.parse().ok())
+            .unwrap_or(0);
+
+        match compressor_name(compressor) {
+            "gzip" => Ok(Compression::Gzip {
+                level: level.unwrap_or(9),
+            }),
+            "zstd" => Ok(Compression::Zstd {
+                level: level.map_or(3, |level| level as i32),
+                workers,
+            }),
+            "xz" | "lzma" => Ok(Compression::Xz {
+                level: level.unwrap_or(3),
+            }),
+            "bzip2" => Ok(Compression::Bzip2 {
+                level: level.unwrap_or(9),
+            }),
+            "identity" => Ok(Compression::None),
+            format => Err(RpmError::UnsupportedCompressor(format.to_owned())),
+        }
+    }
+
+    /// Compression of the package described by PayloadCompressor and PayloadFlags,
+    /// the payload is uncompressed without PayloadCompressor
+    pub fn from_tags(tags: &HeaderTags) -> Result<Self> {
+        Self::from_names(
+            &tags.get_as_string_or(Tag::PayloadCompressor),
+            &tags.get_as_string_or(Tag::PayloadFlags),
+        )
+    }
+
+    /// Compressing writer, the stream is finished when the writer is dropped
+    pub fn writer<'a, W: Write + 'a>(&self, writer: W) -> Result<Box<dyn Write + 'a>> {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip { level } => Ok(Box::new(GzEncoder::new(
+                writer,
+                flate2::Compression::new(level),
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level, workers } => {
+                let mut encoder = Encoder::new(writer, level)?;
+                if workers > 0 {
+                    encoder.multithread(workers)?;
+                }
+                Ok(Box::new(encoder.auto_finish()))
+            }
+            #[cfg(feature = "xz")]
+            Compression::Xz { level } => Ok(Box::new(XzEncoder::new(writer, level))),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 { level } => Ok(Box::new(BzEncoder::new(
+                writer,
+                bzip2::Compression::new(level),
+            ))),
+            Compression::None => Ok(Box::new(writer)),
+            #[allow(unreachable_patterns)]
+            compression => Err(RpmError::DisabledCompressor(
+                compression.compressor().unwrap_or_default(),
+            )),
+        }
+    }
+}
+
+/// Payload codec which is looked up by the PayloadCompressor value
+pub trait Decompressor: Send + Sync {
+    /// Decompressing reader over the stored payload
+    fn wrap<'a>(&self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>>;
+
+    /// PayloadCompressor values of the codec
+    fn names(&self) -> &[&str];
+}
+
+#[cfg(feature = "gzip")]
+struct Gzip;
+
+#[cfg(feature = "gzip")]
+impl Decompressor for Gzip {
+    fn wrap<'a>(&self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(GzDecoder::new(reader)))
+    }
+
+    fn names(&self) -> &[&str] {
+        &["gzip", "gzdio"]
+    }
+}
+
+#[cfg(feature = "bzip2")]
+struct Bzip2;
+
+#[cfg(feature = "bzip2")]
+impl Decompressor for Bzip2 {
+    fn wrap<'a>(&self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(BzDecoder::new(reader)))
+    }
+
+    fn names(&self) -> &[&str] {
+        &["bzip2", "bzdio"]
+    }
+}
+
+#[cfg(feature = "zstd")]
+struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl Decompressor for Zstd {
+    fn wrap<'a>(&self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(Decoder::new(reader)?))
+    }
+
+    fn names(&self) -> &[&str] {
+        &["zstd", "zstdio"]
+    }
+}
+
+#[cfg(feature = "xz")]
+struct Xz;
+
+#[cfg(feature = "xz")]
+impl Decompressor for Xz {
+    fn wrap<'a>(&self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(XzDecoder::new(reader)))
+    }
+
+    fn names(&self) -> &[&str] {
+        &["xz", "xzdio", "lzma", "lzdio"]
+    }
+}
+
+/// Uncompressed payload, PayloadCompressor is missing or empty
+struct Identity;
+
+impl Decompressor for Identity {
+    fn wrap<'a>(&self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(reader)
+    }
+
+    fn names(&self) -> &[&str] {
+        &["identity", "", "none", "ufdio"]
+    }
+}
+
+/// Decompressors of a package by compressor name, gzip, bzip2, zstd and xz by default
+#[derive(Clone)]
+pub struct Decompressors(Vec<Arc<dyn Decompressor>>);
+
+impl Decompressors {
+    /// Adds a codec, it replaces the codecs registered earlier for the same names
+    pub fn register<D: Decompressor + 'static>(&mut self, decompressor: D) -> &mut Self {
+        self.0.push(Arc::new(decompressor));
+        self
+    }
+
+    pub fn get(&self, compressor: &str) -> Option<&dyn Decompressor> {
+        self.0
+            .iter()
+            .rev()
+            .find(|decompressor| decompressor.names().contains(&compressor))
+            .map(|decompressor| decompressor.as_ref())
+    }
+
+    /// Decompressing reader of the compressor, fails for unknown compressors
+    /// and for the codecs of disabled features
+    pub fn wrap<'a, R: Read + 'a>(
+        &self,
+        compressor: &str,
+        reader: R,
+    ) -> Result<Box<dyn Read + 'a>> {
+        match (self.get(compressor), disabled_codec(compressor)) {
+            (Some(decompressor), _) => Ok(decompressor.wrap(Box::new(reader))?),
+            (None, Some(codec)) => Err(RpmError::DisabledCompressor(codec)),
+            (None, None) => Err(RpmError::UnsupportedCompressor(compressor.to_owned())),
+        }
+    }
+}
+
+impl Default for Decompressors {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut decompressors: Vec<Arc<dyn Decompressor>> = vec![Arc::new(Identity)];
+        #[cfg(feature = "gzip")]
+        decompressors.push(Arc::new(Gzip));
+        #[cfg(feature = "bzip2")]
+        decompressors.push(Arc::new(Bzip2));
+        #[cfg(feature = "zstd")]
+        decompressors.push(Arc::new(Zstd));
+        #[cfg(feature = "xz")]
+        decompressors.push(Arc::new(Xz));
+        Decompressors(decompressors)
+    }
+}
+
+impl fmt::Debug for Decompressors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|decompressor| decompressor.names()))
+            .finish()
+    }
+}
+
+/// Canonical compressor name, the io names of rpm macros such as `zstdio` included.
+/// An empty, `none` or `ufdio` compressor is `identity`: the payload is not compressed
+pub(crate) fn compressor_name(compressor: &str) -> &str {
+    match compressor {
+        "gzdio" => "gzip",
+        "bzdio" => "bzip2",
+        "xzdio" => "xz",
+        "lzdio" => "lzma",
+        "zstdio" => "zstd",
+        "" | "none" | "ufdio" => "identity",
+        name => name,
+    }
+}
+
+/// Feature of a known codec which is not compiled in
+fn disabled_codec(compressor: &str) -> Option<&'static str> {
+    match compressor_name(compressor) {
+        "gzip" if !cfg!(feature = "gzip") => Some("gzip"),
+        "bzip2" if !cfg!(feature = "bzip2") => Some("bzip2"),
+        "zstd" if !cfg!(feature = "zstd") => Some("zstd"),
+        "xz" | "lzma" if !cfg!(feature = "xz") => Some("xz"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_flags() {
+        let cases = vec![
+            (Compression::Gzip { level: 9 }, "gzip", "9"),
+            (Compression::Bzip2 { level: 5 }, "bzip2", "5"),
+            (Compression::Xz { level: 2 }, "xz", "2"),
+            (
+                Compression::Zstd {
+                    level: 19,
+                    workers: 0,
+                },
+                "zstd",
+                "19",
+            ),
+            (
+                Compression::Zstd {
+                    level: 19,
+                    workers: 8,
+                },
+                "zstd",
+                "19T8",
+            ),
+            (Compression::None, "", ""),
+        ];
+        for (compression, compressor, flags) in cases {
+            assert_eq!(compression.compressor().unwrap_or_default(), compressor);
+            assert_eq!(compression.flags(), flags);
+            assert_eq!(
+                Compression::from_names(compressor, flags).unwrap(),
+                compression
+            );
+        }
+
+        assert_eq!(
+            Compression::from_names("zstd", "").unwrap(),
+            Compression::Zstd {
+                level: 3,
+                workers: 0
+            }
+        );
+        assert_eq!(
+            Compression::from_names("xzdio", "7").unwrap(),
+            Compression::Xz { level: 7 }
+        );
+        assert_eq!(
+            Compression::from_names("ufdio", "").unwrap(),
+            Compression::None
+        );
+        assert!(matches!(
+            Compression::from_names("lz4", "1"),
+            Err(RpmError::UnsupportedCompressor(ref c)) if c == "lz4"
+        ));
+    }
+
+    #[test]
+    fn test_disabled_codecs() {
+        let codecs = vec![
+            (
+                "gzip",
+                cfg!(feature = "gzip"),
+                Compression::Gzip { level: 9 },
+            ),
+            (
+                "bzip2",
+                cfg!(feature = "bzip2"),
+                Compression::Bzip2 { level: 9 },
+            ),
+            (
+                "zstd",
+                cfg!(feature = "zstd"),
+                Compression::Zstd {
+                    level: 3,
+                    workers: 0,
+                },
+            ),
+            ("xz", cfg!(feature = "xz"), Compression::Xz { level: 3 }),
+        ];
+        for (codec, enabled, compression) in codecs {
+            let decompressors = Decompressors::default();
+            assert_eq!(decompressors.get(codec).is_some(), enabled);
+            if enabled {
+                continue;
+            }
+            let err = decompressors.wrap(codec, io::empty()).err().unwrap();
+            assert!(matches!(err, RpmError::DisabledCompressor(c) if c == codec));
+            assert_eq!(
+                err.to_string(),
+                format!("Error: support for {} is not compiled in", codec)
+            );
+            let err = compression.writer(io::sink()).err().unwrap();
+            assert!(matches!(err, RpmError::DisabledCompressor(c) if c == codec));
+        }
+    }
+}
@@ -0,0 +1,512 @@
+use bitflags::bitflags;
+use std::cmp::Ordering;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use super::file::RPMFile;
+use crate::header::{RType, Tag, Tags};
+
+bitflags! {
+    pub struct DependencyFlags: u32 {
+        const LESS = 1 << 1;
+        const GREATER = 1 << 2;
+        const EQUAL = 1 << 3;
+        // from Requires(pre) and similar
+        const PREREQ = 1 << 6;
+        const INTERP = 1 << 8;
+        const SCRIPT_PRE = 1 << 9;
+        const SCRIPT_POST = 1 << 10;
+        const SCRIPT_PREUN = 1 << 11;
+        const SCRIPT_POSTUN = 1 << 12;
+        const SCRIPT_VERIFY = 1 << 13;
+        // added by the dependency generators
+        const FIND_REQUIRES = 1 << 14;
+        const FIND_PROVIDES = 1 << 15;
+        // rpmlib(feature) requirements
+        const RPMLIB = 1 << 24;
+        const CONFIG = 1 << 28;
+
+        const SENSE = Self::LESS.bits | Self::GREATER.bits | Self::EQUAL.bits;
+    }
+}
+
+/// Kinds of dependencies, each stored in its own name, flags and version tags
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DependencyKind {
+    Requires,
+    Provides,
+    Conflicts,
+    Obsoletes,
+    Recommends,
+    Suggests,
+    Supplements,
+    Enhances,
+}
+
+impl DependencyKind {
+    /// Name, flags and version tags of the kind
+    pub fn tags(self) -> (Tag, Tag, Tag) {
+        match self {
+            DependencyKind::Requires => (Tag::RequireName, Tag::RequireFlags, Tag::RequireVersion),
+            DependencyKind::Provides => (Tag::ProvideName, Tag::ProvideFlags, Tag::ProvideVersion),
+            DependencyKind::Conflicts => {
+                (Tag::ConflictName, Tag::ConflictFlags, Tag::ConflictVersion)
+            }
+            DependencyKind::Obsoletes => {
+                (Tag::ObsoleteName, Tag::Obsoleteflags, Tag::ObsoleteVersion)
+            }
+            DependencyKind::Recommends => (
+                Tag::RecommendName,
+                Tag::RecommendFlags,
+                Tag::RecommendVersion,
+            ),
+            DependencyKind::Suggests => (Tag::SuggestName, Tag::SuggestFlags, Tag::SuggestVersion),
+            DependencyKind::Supplements => (
+                Tag::SupplementName,
+                Tag::SupplementFlags,
+                Tag::SupplementVersion,
+            ),
+            DependencyKind::Enhances => (Tag::EnhanceName, Tag::EnhanceFlags, Tag::EnhanceVersion),
+        }
+    }
+}
+
+/// Version part of a declared dependency, `[epoch:]version[-release]` with an operator
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionConstraint {
+    Any,
+    Less(String),
+    LessOrEqual(String),
+    Equal(String),
+    GreaterOrEqual(String),
+    Greater(String),
+}
+
+impl VersionConstraint {
+    /// Sense flags of the operator
+    pub fn flags(&self) -> DependencyFlags {
+        match self {
+            VersionConstraint::Any => DependencyFlags::empty(),
+            VersionConstraint::Less(_) => DependencyFlags::LESS,
+            VersionConstraint::LessOrEqual(_) => DependencyFlags::LESS | DependencyFlags::EQUAL,
+            VersionConstraint::Equal(_) => DependencyFlags::EQUAL,
+            VersionConstraint::GreaterOrEqual(_) => {
+                DependencyFlags::GREATER | DependencyFlags::EQUAL
+            }
+            VersionConstraint::Greater(_) => DependencyFlags::GREATER,
+        }
+    }
+
+    pub fn version(&self) -> &str {
+        match self {
+            VersionConstraint::Any => "",
+            VersionConstraint::Less(evr)
+            | VersionConstraint::LessOrEqual(evr)
+            | VersionConstraint::Equal(evr)
+            | VersionConstraint::GreaterOrEqual(evr)
+            | VersionConstraint::Greater(evr) => evr,
+        }
+    }
+}
+
+/// Requires or Provides entry, e.g. `glibc >= 2.17`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dependency {
+    pub name: String,
+    pub flags: u32,
+    pub version: String,
+}
+
+impl Dependency {
+    pub fn new(name: &str, flags: DependencyFlags, version: &str) -> Self {
+        Dependency {
+            name: name.to_owned(),
+            flags: flags.bits(),
+            version: version.to_owned(),
+        }
+    }
+
+    pub fn with_constraint(name: &str, constraint: &VersionConstraint) -> Self {
+        Dependency::new(name, constraint.flags(), constraint.version())
+    }
+
+    /// Requirement on a feature of rpm itself, e.g. `rpmlib(PayloadIsZstd) <= 5.4.18-1`
+    pub fn rpmlib(feature: &str, version: &str) -> Self {
+        let flags = DependencyFlags::RPMLIB | DependencyFlags::LESS | DependencyFlags::EQUAL;
+        Dependency::new(&format!("rpmlib({})", feature), flags, version)
+    }
+
+    /// Writes dependencies to the name, flags and version tags of the kind
+    pub fn insert_tags(tags: &mut Tags<Tag>, kind: DependencyKind, dependencies: &[Dependency]) {
+        let (names, flags, versions) = kind.tags();
+        let strings = |f: fn(&Dependency) -> &String| {
+            RType::StringArray(dependencies.iter().map(|dep| f(dep).clone()).collect())
+        };
+        tags.insert(names, strings(|dep| &dep.name))
+            .insert(
+                flags,
+                RType::Int32Array(dependencies.iter().map(|dep| dep.flags).collect()),
+            )
+            .insert(versions, strings(|dep| &dep.version));
+    }
+
+    /// Reads dependencies from the name, flags and version tags of one kind
+    pub fn from_tags(tags: &Tags<Tag>, names: Tag, flags: Tag, versions: Tag) -> Vec<Self> {
+        let flags = tags.get_as_u32_array_or(flags);
+        let versions = tags.get_as_string_array_or(versions);
+
+        tags.get_as_string_array_or(names)
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| Dependency {
+                name,
+                flags: flags.get(i).copied().unwrap_or_default(),
+                version: versions.get(i).cloned().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    pub fn dependency_flags(&self) -> DependencyFlags {
+        DependencyFlags::from_bits_truncate(self.flags)
+    }
+
+    pub fn is_rpmlib(&self) -> bool {
+        self.name.starts_with("rpmlib(")
+            || self.dependency_flags().contains(DependencyFlags::RPMLIB)
+    }
+
+    /// Range intersection of rpm: the provide satisfies the requirement if the names
+    /// are equal and the version ranges overlap, e.g. `foo < 2.0` satisfies `foo > 1.0`.
+    /// A side without a version, e.g. `Provides: foo`, satisfies any version of the other.
+    /// A missing epoch is 0, a missing release on either side matches any release.
+    pub fn is_satisfied_by(&self, provide: &Dependency) -> bool {
+        if self.name != provide.name {
+            return false;
+        }
+
+        let sense = self.dependency_flags() & DependencyFlags::SENSE;
+        let other_sense = provide.dependency_flags() & DependencyFlags::SENSE;
+        if sense.is_empty() || other_sense.is_empty() {
+            return true;
+        }
+
+        let both = |flag| sense.contains(flag) && other_sense.contains(flag);
+        match evrcmp(&self.version, &provide.version) {
+            Ordering::Less => {
+                sense.contains(DependencyFlags::GREATER)
+                    || other_sense.contains(DependencyFlags::LESS)
+            }
+            Ordering::Greater => {
+                sense.contains(DependencyFlags::LESS)
+                    || other_sense.contains(DependencyFlags::GREATER)
+            }
+            Ordering::Equal => {
+                both(DependencyFlags::EQUAL)
+                    || both(DependencyFlags::LESS)
+                    || both(DependencyFlags::GREATER)
+            }
+        }
+    }
+}
+
+impl FromStr for Dependency {
+    type Err = io::Error;
+
+    /// Parses `name` or `name <op> version` as written in spec files
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let (name, flags, version) = match parts[..] {
+            [name] => (name, DependencyFlags::empty(), ""),
+            [name, operator, version] => {
+                let flags = match operator {
+                    "<" => DependencyFlags::LESS,
+                    "<=" => DependencyFlags::LESS | DependencyFlags::EQUAL,
+                    "=" => DependencyFlags::EQUAL,
+                    ">=" => DependencyFlags::GREATER | DependencyFlags::EQUAL,
+                    ">" => DependencyFlags::GREATER,
+                    _ => return Err(incorrect_dependency(s)),
+                };
+                (name, flags, version)
+            }
+            _ => return Err(incorrect_dependency(s)),
+        };
+        Ok(Dependency::new(name, flags, version))
+    }
+}
+
+fn incorrect_dependency(s: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Error: incorrect dependency {}", s),
+    )
+}
+
+impl<T> RPMFile<T> {
+    pub fn requires(&self) -> Vec<Dependency> {
+        self.header_tags.dependencies(DependencyKind::Requires)
+    }
+
+    pub fn provides(&self) -> Vec<Dependency> {
+        self.header_tags.dependencies(DependencyKind::Provides)
+    }
+
+    pub fn obsoletes(&self) -> Vec<Dependency> {
+        self.header_tags.dependencies(DependencyKind::Obsoletes)
+    }
+
+    pub fn conflicts(&self) -> Vec<Dependency> {
+        self.header_tags.dependencies(DependencyKind::Conflicts)
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sense = self.dependency_flags() & DependencyFlags::SENSE;
+        if sense.is_empty() {
+            return write!(f, "{}", self.name);
+        }
+
+        let mut operator = String::new();
+        if sense.contains(DependencyFlags::LESS) {
+            operator.push('<');
+        }
+        if sense.contains(DependencyFlags::GREATER) {
+            operator.push('>');
+        }
+        if sense.contains(DependencyFlags::EQUAL) {
+            operator.push('=');
+        }
+        write!(f, "{} {} {}", self.name, operator, self.version)
+    }
+}
+
+/// Compares `[epoch:]version[-release]` strings, a missing epoch is 0
+/// and a missing release matches any release
+pub fn evrcmp(a: &str, b: &str) -> Ordering {
+    let (a_epoch, a_version, a_release) = parse_evr(a);
+    let (b_epoch, b_version, b_release) = parse_evr(b);
+
+    a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| rpmvercmp(a_version, b_version))
+        .then_with(|| match (a_release, b_release) {
+            (Some(a), Some(b)) => rpmvercmp(a, b),
+            _ => Ordering::Equal,
+        })
+}
+
+fn parse_evr(evr: &str) -> (u64, &str, Option<&str>) {
+    let (epoch, rest) = match evr.find(':') {
+        Some(position) => (evr[..position].parse().unwrap_or(0), &evr[position + 1..]),
+        None => (0, evr),
+    };
+    match rest.rfind('-') {
+        Some(position) => (epoch, &rest[..position], Some(&rest[position + 1..])),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Version comparison of rpm: alphanumeric segments are compared one by one,
+/// numbers are newer than letters, `~` sorts before and `^` after anything
+pub fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let is_separator = |c: u8| !c.is_ascii_alphanumeric() && c != b'~' && c != b'^';
+    let mut one = a.as_bytes();
+    let mut two = b.as_bytes();
+
+    while !one.is_empty() || !two.is_empty() {
+        while one.first().is_some_and(|&c| is_separator(c)) {
+            one = &one[1..];
+        }
+        while two.first().is_some_and(|&c| is_separator(c)) {
+            two = &two[1..];
+        }
+
+        match (one.first(), two.first()) {
+            (Some(b'~'), Some(b'~')) => {
+                one = &one[1..];
+                two = &two[1..];
+                continue;
+            }
+            (Some(b'~'), _) => return Ordering::Less,
+            (_, Some(b'~')) => return Ordering::Greater,
+            (Some(b'^'), Some(b'^')) => {
+                one = &one[1..];
+                two = &two[1..];
+                continue;
+            }
+            (Some(b'^'), None) => return Ordering::Greater,
+            (Some(b'^'), _) => return Ordering::Less,
+            (None, Some(b'^')) => return Ordering::Less,
+            (_, Some(b'^')) => return Ordering::Greater,
+            (Some(_), Some(_)) => {}
+            _ => break,
+        }
+
+        let is_number = one[0].is_ascii_digit();
+        let segment = |data: &[u8]| {
+            data.iter()
+                .take_while(|c| {
+                    if is_number {
+                        c.is_ascii_digit()
+                    } else {
+                        c.is_ascii_alphabetic()
+                    }
+                })
+                .count()
+        };
+        let (one_segment, one_rest) = one.split_at(segment(one));
+        let (two_segment, two_rest) = two.split_at(segment(two));
+        one = one_rest;
+        two = two_rest;
+
+        // segments of different types, numbers are newer
+        if two_segment.is_empty() {
+            return if is_number {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let order = if is_number {
+            let trim = |s: &[u8]| -> usize { s.iter().take_while(|&&c| c == b'0').count() };
+            let one_segment = &one_segment[trim(one_segment)..];
+            let two_segment = &two_segment[trim(two_segment)..];
+            one_segment
+                .len()
+                .cmp(&two_segment.len())
+                .then_with(|| one_segment.cmp(two_segment))
+        } else {
+            one_segment.cmp(two_segment)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+
+    match (one.is_empty(), two.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpmvercmp() {
+        let cases = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "2.0", Ordering::Less),
+            ("2.0.1", "2.0", Ordering::Greater),
+            ("1.010", "1.9", Ordering::Greater),
+            ("1.05", "1.5", Ordering::Equal),
+            ("1.0a", "1.0", Ordering::Greater),
+            ("1a", "1.1", Ordering::Less),
+            ("2_0", "2.0", Ordering::Equal),
+            ("1.0~rc1", "1.0", Ordering::Less),
+            ("1.0~rc1", "1.0~rc2", Ordering::Less),
+            ("1.0^git1", "1.0", Ordering::Greater),
+            ("1.0^git1", "1.0.1", Ordering::Less),
+            ("1.0^", "1.0", Ordering::Greater),
+        ];
+        for (a, b, expected) in cases.iter() {
+            assert_eq!(rpmvercmp(a, b), *expected, "{} {}", a, b);
+            assert_eq!(rpmvercmp(b, a), expected.reverse(), "{} {}", b, a);
+        }
+
+        assert_eq!(evrcmp("1:1.0-1", "2.0-1"), Ordering::Greater);
+        assert_eq!(evrcmp("1.0", "1.0-5"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_is_satisfied_by() -> io::Result<()> {
+        let cases = [
+            ("a = 1.0", "a = 1.0", true),
+            ("a = 1.0", "a = 1.1", false),
+            ("a = 1.0", "a < 1.1", true),
+            ("a = 1.0", "a > 1.0", false),
+            ("a < 1.0", "a = 0.9", true),
+            ("a < 1.0", "a = 1.0", false),
+            ("a < 1.0", "a < 2.0", true),
+            ("a < 1.0", "a > 0.5", true),
+            ("a < 1.0", "a > 1.0", false),
+            ("a < 1.0", "a >= 1.0", false),
+            ("a < 1.0", "a <= 1.0", true),
+            ("a <= 1.0", "a >= 1.0", true),
+            ("a <= 1.0", "a > 1.0", false),
+            ("a <= 1.0", "a = 1.0", true),
+            ("a > 1.0", "a = 1.0", false),
+            ("a > 1.0", "a >= 1.0", true),
+            ("a > 1.0", "a < 2.0", true),
+            ("a > 1.0", "a <= 1.0", false),
+            ("a >= 1.0", "a = 1.0", true),
+            ("a >= 1.0", "a < 1.0", false),
+            ("a >= 1.0", "a <= 1.0", true),
+            ("a = 1.0-1", "a = 1.0", true),
+            ("a = 1.0-1", "a = 1.0-2", false),
+            ("a = 1:1.0", "a = 1.0", false),
+            ("a = 0:1.0", "a = 1.0", true),
+            ("a", "a = 5", true),
+            ("a >= 2", "a", true),
+            ("a", "b", false),
+        ];
+        for (require, provide, expected) in cases.iter() {
+            let result = require
+                .parse::<Dependency>()?
+                .is_satisfied_by(&provide.parse()?);
+            assert_eq!(result, *expected, "{} by {}", require, provide);
+        }
+
+        let require: Dependency = "libfoo >= 1.2".parse()?;
+        assert_eq!(
+            require.dependency_flags(),
+            DependencyFlags::GREATER | DependencyFlags::EQUAL
+        );
+        assert_eq!(require.to_string(), "libfoo >= 1.2");
+        assert!("libfoo ~ 1.2".parse::<Dependency>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_dependencies() -> io::Result<()> {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/hello-1.0-1.noarch.rpm");
+        let rpm = RPMFile::open(path)?;
+        let names = |deps: Vec<Dependency>| -> Vec<String> {
+            deps.iter().map(Dependency::to_string).collect()
+        };
+        assert!(names(rpm.provides()).contains(&"hello = 1.0-1".to_owned()));
+        assert!(rpm.requires().iter().any(Dependency::is_rpmlib));
+        assert_eq!(names(rpm.obsoletes()), vec!["hello-old <= 1.0"]);
+        assert_eq!(names(rpm.conflicts()), vec!["hello-legacy < 0.9"]);
+
+        // versions and flags may be shorter than names
+        let mut tags = Tags::<Tag>::new();
+        tags.insert(
+            Tag::ObsoleteName,
+            RType::StringArray(vec!["old".to_owned(), "older".to_owned()]),
+        )
+        .insert(Tag::Obsoleteflags, RType::Int32Array(vec![8]))
+        .insert(
+            Tag::ObsoleteVersion,
+            RType::StringArray(vec!["".to_owned()]),
+        );
+        let (names_tag, flags_tag, versions_tag) = DependencyKind::Obsoletes.tags();
+        let obsoletes = Dependency::from_tags(&tags, names_tag, flags_tag, versions_tag);
+        assert_eq!(
+            obsoletes[1],
+            Dependency::new("older", DependencyFlags::empty(), "")
+        );
+        assert_eq!(obsoletes[0].dependency_flags(), DependencyFlags::EQUAL);
+        Ok(())
+    }
+}
@@ -0,0 +1,619 @@
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::task;
+
+use super::RPMFile;
+use crate::payload::{
+    create_special, create_symlink, entry_path, restore_metadata, skip_bytes, skip_entry_data,
+    symlink_target_inside, ExtractOptions, ExtractReport, FileEntry, PendingLinks, SkipReason,
+    TRAILER,
+};
+
+const CHANNEL_SIZE: usize = 16;
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractProgress {
+    /// Name of the entry being extracted
+    pub name: String,
+    /// Bytes of the entry written so far
+    pub bytes: u64,
+    /// Number of entries completely extracted
+    pub entries: usize,
+    /// Set on the last event if the future was dropped before the extraction
+    /// completed, the partially written file was removed
+    pub cancelled: bool,
+}
+
+/// Progress events of an extraction, a slot of the channel is reserved
+/// to report the cancellation when the extraction is dropped
+struct Progress {
+    sender: Option<mpsc::Sender<ExtractProgress>>,
+    cancellation: Option<mpsc::OwnedPermit<ExtractProgress>>,
+    last: ExtractProgress,
+}
+
+impl Progress {
+    fn new(sender: Option<mpsc::Sender<ExtractProgress>>) -> Self {
+        let cancellation = sender
+            .clone()
+            .and_then(|sender| sender.try_reserve_owned().ok());
+        Self {
+            sender,
+            cancellation,
+            last: ExtractProgress {
+                name: String::new(),
+                bytes: 0,
+                entries: 0,
+                cancelled: false,
+            },
+        }
+    }
+
+    /// Events are dropped while the channel is full, the extraction does not wait for the receiver
+    fn send(&mut self, name: &str, bytes: u64, entries: usize) {
+        if let Some(sender) = &self.sender {
+            self.last = ExtractProgress {
+                name: name.to_owned(),
+                bytes,
+                entries,
+                cancelled: false,
+            };
+            let _ = sender.try_send(self.last.clone());
+        }
+    }
+
+    /// The extraction completed or failed, it was not cancelled
+    fn finish(&mut self) {
+        self.cancellation = None;
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(permit) = self.cancellation.take() {
+            permit.send(ExtractProgress {
+                cancelled: true,
+                ..self.last.clone()
+            });
+        }
+    }
+}
+
+enum Message {
+    Entry(FileEntry),
+    Data(Vec<u8>),
+    EndOfEntry,
+    Skipped(FileEntry, SkipReason),
+}
+
+enum Output {
+    Directory,
+    File(File, PartialFile),
+    Symlink(Vec<u8>),
+    /// Member of a hardlink group without data
+    HardLink,
+    /// Device node or FIFO, already created
+    Special,
+    Skipped,
+}
+
+/// Entry being written
+struct Current {
+    entry: FileEntry,
+    path: PathBuf,
+    output: Output,
+    bytes: u64,
+}
+
+/// Removes a partially written file, unless it was completed
+struct PartialFile {
+    path: PathBuf,
+    completed: bool,
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.completed {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl<T: 'static + Read + Seek + Send> RPMFile<T> {
+    /// Extracts the payload without blocking the runtime: decompression and cpio
+    /// parsing run on a blocking task, files are written with `tokio::fs`.
+    /// Files are written under a temporary name and renamed when completed,
+    /// so dropping the future does not leave half-written files behind.
+    /// Progress events are dropped while the channel is full, one slot of it is
+    /// reserved for the event reporting that the extraction was dropped.
+    pub async fn extract_async<P: AsRef<Path>>(
+        self,
+        dir: P,
+        options: ExtractOptions,
+        progress: Option<mpsc::Sender<ExtractProgress>>,
+    ) -> io::Result<ExtractReport> {
+        let mut progress = Progress::new(progress);
+        let result = self
+            .extract_entries_async(dir.as_ref(), options, &mut progress)
+            .await;
+        progress.finish();
+        result
+    }
+
+    async fn extract_entries_async(
+        self,
+        dir: &Path,
+        options: ExtractOptions,
+        progress: &mut Progress,
+    ) -> io::Result<ExtractReport> {
+        let start = Instant::now();
+        let dir = dir.to_path_buf();
+        let (sender, mut receiver) = mpsc::channel(CHANNEL_SIZE);
+
+        let reader_dir = dir.clone();
+        let reader_options = options.clone();
+        let reader =
+            task::spawn_blocking(move || read_payload(self, &reader_dir, &reader_options, sender));
+
+        let mut report = ExtractReport::default();
+        let mut links = PendingLinks::default();
+        let mut current: Option<Current> = None;
+
+        while let Some(message) = receiver.recv().await {
+            match message {
+                Message::Entry(entry) => {
+                    // checked here, as symlinks of earlier entries are created by this task
+                    let (entry_dir, name, is_dir) =
+                        (dir.clone(), entry.name.clone(), entry.is_dir());
+                    let path = task::spawn_blocking(move || entry_path(&entry_dir, &name, is_dir))
+                        .await
+                        .map_err(join_error)??;
+                    let (path, output) = match path {
+                        Some(path) => {
+                            let output = open_output(&path, &entry, &options).await?;
+                            if let Output::Skipped = output {
+                                report.skip(&entry, SkipReason::PermissionDenied);
+                            }
+                            (path, output)
+                        }
+                        None => {
+                            report.skip(&entry, SkipReason::UnsafePath);
+                            (PathBuf::new(), Output::Skipped)
+                        }
+                    };
+                    current = Some(Current {
+                        entry,
+                        path,
+                        output,
+                        bytes: 0,
+                    });
+                }
+                Message::Data(data) => {
+                    let current = current.as_mut().ok_or_else(unexpected)?;
+                    match &mut current.output {
+                        Output::File(file, _) => file.write_all(&data).await?,
+                        Output::Symlink(target) => target.extend_from_slice(&data),
+                        _ => {}
+                    }
+                    current.bytes += data.len() as u64;
+
+                    progress.send(&current.entry.name, current.bytes, report.entries.len());
+                }
+                Message::EndOfEntry => {
+                    let Current {
+                        entry,
+                        path,
+                        output,
+                        bytes,
+                    } = current.take().ok_or_else(unexpected)?;
+
+                    match output {
+                        Output::Directory => report.dirs_created += 1,
+                        Output::Special => report.specials_created += 1,
+                        Output::File(mut file, mut partial) => {
+                            file.flush().await?;
+                            if options.sync {
+                                file.sync_data().await?;
+                            }
+                            drop(file);
+                            fs::rename(&partial.path, &path).await?;
+                            partial.completed = true;
+                            report.files_created += 1;
+                            report.bytes_written += bytes;
+                            for link in links.extracted(&entry, &path) {
+                                hard_link(&path, &link, &mut report).await?;
+                            }
+                        }
+                        Output::Symlink(target) => {
+                            if !symlink_target_inside(&entry.name, &target) {
+                                report.skip(&entry, SkipReason::UnsafePath);
+                                continue;
+                            }
+                            let link = path.clone();
+                            task::spawn_blocking(move || create_symlink(&target, &link))
+                                .await
+                                .map_err(join_error)??;
+                            report.symlinks_created += 1;
+                            report.entries.push(entry);
+                            continue;
+                        }
+                        Output::HardLink => {
+                            if let Some(target) = links.deferred(&entry, path.clone()) {
+                                hard_link(&target, &path, &mut report).await?;
+                            }
+                            report.entries.push(entry);
+                            continue;
+                        }
+                        Output::Skipped => continue,
+                    }
+
+                    let change_owner = options.change_owner;
+                    let xattrs = options.xattrs.clone();
+                    let (entry, result, xattrs_result) = task::spawn_blocking(move || {
+                        let result = restore_metadata(&path, &entry, change_owner);
+                        let xattrs_result =
+                            xattrs.map_or(Ok(()), |manifest| manifest.restore(&entry.name, &path));
+                        (entry, result, xattrs_result)
+                    })
+                    .await
+                    .map_err(join_error)?;
+                    report.restored(&entry, result)?;
+                    if let Err(e) = xattrs_result {
+                        report.warnings.push(format!("{}: {}", entry.name, e));
+                    }
+                    report.entries.push(entry);
+                }
+                Message::Skipped(entry, reason) => report.skip(&entry, reason),
+            }
+        }
+
+        // the channel is closed either at the end of the payload or on a read error
+        reader.await.map_err(join_error)??;
+        if current.is_some() {
+            return Err(unexpected());
+        }
+        // groups without data are empty files
+        for paths in links.into_pending() {
+            if let Some((first, others)) = paths.split_first() {
+                File::create(first).await?;
+                report.files_created += 1;
+                for link in others {
+                    hard_link(first, link, &mut report).await?;
+                }
+            }
+        }
+        report.duration = start.elapsed();
+        Ok(report)
+    }
+}
+
+async fn open_output(
+    path: &Path,
+    entry: &FileEntry,
+    options: &ExtractOptions,
+) -> io::Result<Output> {
+    if entry.is_dir() {
+        fs::create_dir_all(path).await?;
+        return Ok(Output::Directory);
+    }
+
+    if let Some(parent) = path.parent().filter(|_| options.creates_dir) {
+        fs::create_dir_all(parent).await?;
+    }
+    // never write through an existing symlink
+    if let Ok(metadata) = fs::symlink_metadata(path).await {
+        if entry.is_symlink() || entry.is_special() || metadata.file_type().is_symlink() {
+            fs::remove_file(path).await?;
+        }
+    }
+
+    if entry.is_special() {
+        let node = path.to_owned();
+        let special = FileEntry {
+            mode: entry.mode,
+            rdev_major: entry.rdev_major,
+            rdev_minor: entry.rdev_minor,
+            ..Default::default()
+        };
+        let created = task::spawn_blocking(move || create_special(&node, &special))
+            .await
+            .map_err(join_error)?;
+        return match created {
+            Ok(()) => Ok(Output::Special),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(Output::Skipped),
+            Err(e) => Err(e),
+        };
+    }
+
+    if entry.is_symlink() {
+        return Ok(Output::Symlink(Vec::new()));
+    }
+    // data of a hardlink group follows its last member
+    if entry.is_hardlink() && entry.file_size == 0 {
+        return Ok(Output::HardLink);
+    }
+
+    let partial = PartialFile {
+        path: partial_path(path),
+        completed: false,
+    };
+    match File::create(&partial.path).await {
+        Ok(file) => {
+            // only a size hint, the data is written in chunks anyway
+            let _ = file.set_len(entry.file_size.into()).await;
+            Ok(Output::File(file, partial))
+        }
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(Output::Skipped),
+        Err(e) => Err(e),
+    }
+}
+
+fn read_payload<T: 'static + Read + Seek>(
+    file: RPMFile<T>,
+    dir: &Path,
+    options: &ExtractOptions,
+    sender: mpsc::Sender<Message>,
+) -> io::Result<()> {
+    let mut reader = file.into_uncompress_reader()?;
+    let mut total = 0;
+
+    loop {
+        let entry = FileEntry::read(&mut reader)?;
+        if entry.name == TRAILER {
+            return Ok(());
+        }
+
+        options.check_limits(&entry, &mut total)?;
+        let skip = if !options.accepts(&entry) {
+            Some(SkipReason::Filtered)
+        } else if !options.supports(&entry) {
+            Some(SkipReason::Unsupported)
+        } else if !entry.is_dir() && !options.may_write(&dir.join(&entry.name))? {
+            Some(SkipReason::Exists)
+        } else {
+            None
+        };
+
+        // a closed channel means the extraction was cancelled
+        if let Some(reason) = skip {
+            skip_entry_data(&mut reader, &entry)?;
+            if sender
+                .blocking_send(Message::Skipped(entry, reason))
+                .is_err()
+            {
+                return Ok(());
+            }
+            continue;
+        }
+
+        let (size, padding) = if entry.is_dir() {
+            (0, 0)
+        } else {
+            (entry.file_size, entry.data_padding())
+        };
+        if sender.blocking_send(Message::Entry(entry)).is_err() {
+            return Ok(());
+        }
+
+        let mut remaining = u64::from(size);
+        while remaining > 0 {
+            let mut chunk = Vec::new();
+            (&mut reader)
+                .take(remaining.min(CHUNK_SIZE))
+                .read_to_end(&mut chunk)?;
+            if chunk.is_empty() {
+                return Err(unexpected());
+            }
+            remaining -= chunk.len() as u64;
+
+            if sender.blocking_send(Message::Data(chunk)).is_err() {
+                return Ok(());
+            }
+        }
+        skip_bytes(&mut reader, padding.into())?;
+
+        if sender.blocking_send(Message::EndOfEntry).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+async fn hard_link(target: &Path, link: &Path, report: &mut ExtractReport) -> io::Result<()> {
+    if fs::symlink_metadata(link).await.is_ok() {
+        fs::remove_file(link).await?;
+    }
+    fs::hard_link(target, link).await?;
+    report.hardlinks_created += 1;
+    Ok(())
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.part", name))
+}
+
+fn unexpected() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "Error: unexpected end of cpio archive",
+    )
+}
+
+fn join_error(e: task::JoinError) -> io::Error {
+    io::Error::other(format!("Error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Overwrite;
+
+    fn hello() -> io::Result<RPMFile<std::fs::File>> {
+        Ok(RPMFile::open(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm"),
+        )?)
+    }
+
+    #[tokio::test]
+    async fn test_extract_async() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (sender, mut receiver) = mpsc::channel(1024);
+
+        let options = ExtractOptions::default().filter(|e| e.name.contains("/usr/"));
+        let report = hello()?
+            .extract_async(dir.path(), options, Some(sender))
+            .await?;
+
+        assert!(report.entries.iter().all(|e| e.name.contains("/usr/")));
+        assert_eq!(report.files_created, 3);
+        assert_eq!(report.symlinks_created, 1);
+        assert_eq!(
+            report.skipped,
+            vec![("./etc/hello.conf".to_owned(), SkipReason::Filtered)]
+        );
+        assert!(!dir.path().join("etc/hello.conf").exists());
+        assert!(dir.path().join("usr/bin/hello").is_file());
+
+        let mut last = None;
+        while let Some(progress) = receiver.recv().await {
+            last = Some(progress);
+        }
+        assert!(!last.unwrap().cancelled);
+
+        // no temporary files are left
+        let mut paths = vec![dir.path().to_path_buf()];
+        while let Some(path) = paths.pop() {
+            for entry in std::fs::read_dir(path)? {
+                let path = entry?.path();
+                assert!(!path.to_string_lossy().ends_with(".part"));
+                if path.is_dir() {
+                    paths.push(path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extract_async_progress() -> io::Result<()> {
+        use std::future::Future;
+
+        // nothing is received during the extraction, a full channel does not block it
+        let dir = tempfile::tempdir()?;
+        let (sender, mut receiver) = mpsc::channel(2);
+        let report = hello()?
+            .extract_async(dir.path(), Default::default(), Some(sender))
+            .await?;
+        assert_eq!(report.files_created, 4);
+        let progress = receiver.recv().await.unwrap();
+        assert!(!progress.cancelled);
+        assert!(receiver.recv().await.is_none());
+
+        // a dropped extraction reports the cancellation
+        let dir = tempfile::tempdir()?;
+        let (sender, mut receiver) = mpsc::channel(1);
+        let mut extraction =
+            Box::pin(hello()?.extract_async(dir.path(), Default::default(), Some(sender)));
+        std::future::poll_fn(|cx| {
+            assert!(extraction.as_mut().poll(cx).is_pending());
+            std::task::Poll::Ready(())
+        })
+        .await;
+        drop(extraction);
+        assert!(receiver.recv().await.unwrap().cancelled);
+        assert!(receiver.recv().await.is_none());
+        Ok(())
+    }
+
+    /// Hello package with a payload of two names sharing one inode
+    fn hardlinked() -> io::Result<RPMFile<io::Cursor<Vec<u8>>>> {
+        use crate::payload::CpioWriter;
+        use crate::utils::S_IFREG;
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut cpio = Vec::new();
+        for (name, data) in &[("./usr/share/a", &b""[..]), ("./usr/share/b", b"shared")] {
+            let entry = FileEntry {
+                name: (*name).to_owned(),
+                ino: 7,
+                mode: S_IFREG | 0o644,
+                nlink: 2,
+                file_size: data.len() as u32,
+                ..Default::default()
+            };
+            cpio.write_cpio_record(entry, &mut &data[..])?;
+        }
+        cpio.cpio_close()?;
+
+        let offset = hello()?.payload_offset as usize;
+        let mut bytes = std::fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm"),
+        )?;
+        bytes.truncate(offset);
+        let mut encoder = GzEncoder::new(bytes, Compression::default());
+        encoder.write_all(&cpio)?;
+        Ok(RPMFile::read(io::Cursor::new(encoder.finish()?))?)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_extract_async_hardlinks() -> io::Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir()?;
+        let options = ExtractOptions {
+            creates_dir: true,
+            ..Default::default()
+        };
+        let report = hardlinked()?
+            .extract_async(dir.path(), options, None)
+            .await?;
+        assert_eq!((report.files_created, report.hardlinks_created), (1, 1));
+
+        let share = dir.path().join("usr/share");
+        assert_eq!(std::fs::read(share.join("a"))?, b"shared");
+        assert_eq!(
+            share.join("a").metadata()?.ino(),
+            share.join("b").metadata()?.ino()
+        );
+
+        // the same archive extracted synchronously
+        let dir = tempfile::tempdir()?;
+        let options = ExtractOptions {
+            creates_dir: true,
+            ..Default::default()
+        };
+        let report = hardlinked()?.extract(dir.path(), &options)?;
+        assert_eq!((report.files_created, report.hardlinks_created), (1, 1));
+        assert_eq!(std::fs::read(dir.path().join("usr/share/a"))?, b"shared");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extract_async_overwrite_error() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        hello()?
+            .extract_async(dir.path(), Default::default(), None)
+            .await?;
+
+        let options = ExtractOptions {
+            overwrite: Overwrite::Error,
+            ..Default::default()
+        };
+        let result = hello()?.extract_async(dir.path(), options, None).await;
+        assert_eq!(
+            result.map_err(|e| e.kind()).unwrap_err(),
+            io::ErrorKind::AlreadyExists
+        );
+        Ok(())
+    }
+}
@@ -1,134 +1,1206 @@
-use bzip2::read::BzDecoder;
-use bzip2::write::BzEncoder;
-use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
+use num_traits::{FromPrimitive, ToPrimitive};
 use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::hash::Hash;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::Path;
-use xz2::read::XzDecoder;
-use xz2::write::XzEncoder;
-use zstd::stream::read::Decoder;
-use zstd::stream::write::Encoder;
 
-use crate::header::{HeaderLead, IndexArray, SignatureTag, Tag, Tags, TagsWrite};
-use crate::lead::{Lead, LeadWriter};
+use super::compression::{compressor_name, Compression, Decompressors};
+use super::lint::header_files;
+use super::tags::{algo_id, HeaderTags, SignatureTags};
+use crate::digest::{DigestAlgo, DigestVerification, HeaderDigestStatus, UnsupportedDigestAlgo};
+use crate::error::Result;
+use crate::header::{
+    header_bytes, HeaderLead, IndexArray, RType, ReadOptions, RegionTag, SignatureTag, Tag, Tags,
+};
+use crate::lead::{Lead, LeadWriter, LEAD_SIZE};
+use crate::payload::{
+    self, normalize_path, CpioStream, ExtractOptions, ExtractReport, FileEntry, FileInfo,
+    XattrManifest, IMA_XATTR,
+};
+#[cfg(feature = "signing")]
+use crate::signature::{self, Keyring, PublicKeyAlgorithm, SecretKey, Signature, SignatureStatus};
 use crate::utils::align_n_bytes;
 
+/// Compressed payload copied by `copy_raw_payload`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawPayload {
+    /// PayloadCompressor, e.g. `zstd`, empty if the tag is missing
+    pub compressor: String,
+    /// Copied bytes
+    pub size: u64,
+}
+
 #[derive(Debug)]
 pub struct RPMFile<T> {
     pub lead: Lead,
-    pub signature_tags: Tags<SignatureTag>,
-    pub header_tags: Tags<Tag>,
+    pub signature_tags: SignatureTags,
+    pub header_tags: HeaderTags,
     pub payload_offset: u64,
+    /// Codecs of the payload by PayloadCompressor
+    pub decompressors: Decompressors,
+    /// Offsets of the headers in the file, empty unless the package was read
+    pub(crate) signature_range: Range<u64>,
+    pub(crate) header_range: Range<u64>,
     pub file: T,
 }
 
-impl RPMFile<File> {
-    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = OpenOptions::new().read(true).open(path)?;
-        Self::read(file)
-    }
+/// Lead and headers of a package with their offsets
+struct Headers {
+    lead: Lead,
+    signature_tags: SignatureTags,
+    header_tags: HeaderTags,
+    signature_range: Range<u64>,
+    header_range: Range<u64>,
 }
 
-impl<T: 'static + Read + Seek> RPMFile<T> {
-    pub fn read(mut reader: T) -> io::Result<Self> {
-        let lead = Lead::read(&mut reader)?;
+impl Headers {
+    fn read<R: Read + Seek>(reader: &mut BufReader<R>, options: &ReadOptions) -> Result<Self> {
+        let lead = Lead::read(reader)?;
 
-        let signature_lead = HeaderLead::read(&mut reader)?;
-        let signature_indexes = IndexArray::read(&mut reader, signature_lead.nindex)?;
-        let signature_tags = Tags::read(
-            &mut reader,
-            &signature_indexes,
-            signature_lead.hsize as usize,
-        )?;
+        let signature_start = reader.stream_position()?;
+        let signature_lead = HeaderLead::read_with(reader, options)?;
+        let signature_tags = if options.skip_signature {
+            let size = signature_lead.nindex as u64 * 16 + u64::from(signature_lead.hsize);
+            reader.seek_relative(size as i64)?;
+            SignatureTags::new()
+        } else {
+            let indexes = IndexArray::read(reader, signature_lead.nindex)?;
+            Tags::read(reader, &indexes, signature_lead.hsize as usize)?.into()
+        };
+        let signature_end = reader.stream_position()?;
 
-        // aligning to 8 bytes
+        // aligning to 8 bytes, the buffered data is kept
         let pos = align_n_bytes(signature_lead.hsize, 8);
+        reader.seek_relative(pos.into())?;
 
-        reader.seek(io::SeekFrom::Current(pos.into()))?;
+        let header_start = reader.stream_position()?;
+        let header = HeaderLead::read_with(reader, options)?;
+        let header_indexes = IndexArray::read(reader, header.nindex)?;
+        let header_tags = Tags::read_filtered(
+            reader,
+            &header_indexes,
+            header.hsize as usize,
+            header_filter(options),
+        )?
+        .into();
+        let header_end = reader.stream_position()?;
 
-        let header = HeaderLead::read(&mut reader)?;
-        let header_indexes = IndexArray::read(&mut reader, header.nindex)?;
-        let header_tags = Tags::read(&mut reader, &header_indexes, header.hsize as usize)?;
-
-        let payload_offset = reader.seek(SeekFrom::Current(0))?;
-
-        Ok(RPMFile {
+        Ok(Headers {
             lead,
             signature_tags,
             header_tags,
+            signature_range: signature_start..signature_end,
+            header_range: header_start..header_end,
+        })
+    }
+
+    /// Reads the headers in order without seeking, the padding after the signature is read
+    fn read_stream<R: Read>(reader: &mut R, options: &ReadOptions) -> Result<Self> {
+        let lead = Lead::read_stream(reader)?;
+
+        let signature_start = LEAD_SIZE as u64;
+        let skip_signature = options.skip_signature;
+        let (signature_size, signature_tags) =
+            read_stream_header(reader, options, |_| !skip_signature)?;
+        let signature_end = signature_start + signature_size;
+
+        let mut padding = [0_u8; 8];
+        let padding = &mut padding[..align_n_bytes(signature_size as u32, 8) as usize];
+        reader.read_exact(padding)?;
+
+        let header_start = signature_end + padding.len() as u64;
+        let (header_size, header_tags) =
+            read_stream_header(reader, options, header_filter(options))?;
+
+        Ok(Headers {
+            lead,
+            signature_tags: SignatureTags(signature_tags),
+            header_tags: HeaderTags(header_tags),
+            signature_range: signature_start..signature_end,
+            header_range: header_start..header_start + header_size,
+        })
+    }
+}
+
+/// Main header tags of the options by their id
+fn header_filter(options: &ReadOptions) -> impl Fn(u32) -> bool + '_ {
+    move |id| match (&options.tags, Tag::from_u32(id)) {
+        (None, _) => true,
+        (Some(tags), Some(tag)) => tags.contains(&tag),
+        (Some(_), None) => false,
+    }
+}
+
+/// Reads a whole header into memory, returns its size with the decoded tags
+fn read_stream_header<R, T, F>(
+    reader: &mut R,
+    options: &ReadOptions,
+    filter: F,
+) -> Result<(u64, Tags<T>)>
+where
+    R: Read,
+    T: FromPrimitive + ToPrimitive + Default + Eq + Hash + Copy + RegionTag,
+    F: Fn(u32) -> bool,
+{
+    let lead = HeaderLead::read_with(reader, options)?;
+    let tags = Tags::read_after_lead(reader, &lead, filter)?;
+    Ok((16 + lead.nindex as u64 * 16 + u64::from(lead.hsize), tags))
+}
+
+impl<T: Read> RPMFile<T> {
+    /// Reads the package from a stream without seeking, e.g. from stdin,
+    /// the payload is left in the stream for `into_payload_reader`
+    pub fn read_stream(reader: T) -> Result<Self> {
+        Self::read_stream_with(reader, &ReadOptions::default())
+    }
+
+    pub fn read_stream_with(mut reader: T, options: &ReadOptions) -> Result<Self> {
+        let headers = Headers::read_stream(&mut reader, options)?;
+        Ok(RPMFile {
+            lead: headers.lead,
+            signature_tags: headers.signature_tags,
+            header_tags: headers.header_tags,
+            file: reader,
+            payload_offset: headers.header_range.end,
+            decompressors: Decompressors::default(),
+            signature_range: headers.signature_range,
+            header_range: headers.header_range,
+        })
+    }
+
+    /// Copies the compressed payload from a stream read by `read_stream` as it is stored,
+    /// the compressor does not have to be supported
+    pub fn copy_raw_payload_from_stream<W: Write>(mut self, writer: &mut W) -> Result<RawPayload> {
+        let size = io::copy(&mut self.file, writer)?;
+        Ok(RawPayload {
+            compressor: self.header_tags.get_as_string_or(Tag::PayloadCompressor),
+            size,
+        })
+    }
+}
+
+impl<T: 'static + Read> RPMFile<T> {
+    /// Decompressed payload as a cpio archive, read from the current position of the reader,
+    /// which is the payload right after reading the package
+    pub fn into_payload_reader(self) -> Result<Box<dyn Read>> {
+        let compressor = self.header_tags.get_as_string_or(Tag::PayloadCompressor);
+        let format = self.header_tags.get_as_string_or(Tag::PayloadFormat);
+        Ok(payload::cpio_payload(
+            self.decompressors.wrap(&compressor, self.file)?,
+            &format,
+        )?)
+    }
+}
+
+impl RPMFile<File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with(path, &ReadOptions::default())
+    }
+
+    pub fn open_with<P: AsRef<Path>>(path: P, options: &ReadOptions) -> Result<Self> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        Self::read_with(file, options)
+    }
+}
+
+impl<T: 'static + Read + Seek> RPMFile<T> {
+    pub fn read(reader: T) -> Result<Self> {
+        Self::read_with(reader, &ReadOptions::default())
+    }
+
+    /// Reads the package, headers above the limits of the options are errors.
+    /// The lead and the headers are read through a buffer, the index entries are small,
+    /// then the reader is positioned at the payload.
+    pub fn read_with(mut reader: T, options: &ReadOptions) -> Result<Self> {
+        let headers = Headers::read(&mut BufReader::new(&mut reader), options)?;
+        let payload_offset = headers.header_range.end;
+        reader.seek(SeekFrom::Start(payload_offset))?;
+
+        Ok(RPMFile {
+            lead: headers.lead,
+            signature_tags: headers.signature_tags,
+            header_tags: headers.header_tags,
             file: reader,
             payload_offset,
+            decompressors: Decompressors::default(),
+            signature_range: headers.signature_range,
+            header_range: headers.header_range,
         })
     }
 
-    pub fn copy_payload(self, path: &Path) -> io::Result<u64> {
+    pub fn copy_payload(self, path: &Path) -> Result<u64> {
         let mut writer = OpenOptions::new().create(true).write(true).open(path)?;
+        self.copy_payload_to(&mut writer)
+    }
+
+    /// Copies the compressed payload as it is stored, e.g. for mirroring,
+    /// the compressor does not have to be supported
+    pub fn copy_raw_payload<W: Write>(mut self, writer: &mut W) -> Result<RawPayload> {
+        self.file.seek(SeekFrom::Start(self.payload_offset))?;
+        self.copy_raw_payload_from_stream(writer)
+    }
+
+    /// Writes the decompressed payload, e.g. to stdout
+    pub fn copy_payload_to<W: Write>(self, writer: &mut W) -> Result<u64> {
         let mut reader = self.into_uncompress_reader()?;
-        io::copy(&mut reader, &mut writer)
+        Ok(io::copy(&mut reader, writer)?)
     }
 
-    fn into_uncompress_reader(mut self) -> io::Result<Box<dyn Read>> {
+    /// Extracts the payload into the directory
+    pub fn extract<P: AsRef<Path>>(
+        self,
+        dir: P,
+        options: &ExtractOptions,
+    ) -> Result<ExtractReport> {
+        let mut reader = self.into_uncompress_reader()?;
+        Ok(payload::extract_entries_with(
+            &mut reader,
+            dir.as_ref(),
+            options,
+        )?)
+    }
+
+    /// Reads file entries from the payload, skipping their content
+    pub fn read_payload_files(self) -> Result<Vec<FileInfo>> {
+        Ok(self.list_payload()?.iter().map(FileInfo::from).collect())
+    }
+
+    /// Reads payload entries, the whole payload is decompressed,
+    /// so truncated payloads and corrupted entries are reported as errors
+    pub fn list_payload(self) -> Result<Vec<FileEntry>> {
+        let mut payload = CpioStream::new(self.into_uncompress_reader()?);
+        let mut entries = Vec::new();
+        while let Some(member) = payload.next_member()? {
+            entries.push(member.skip()?);
+        }
+        Ok(entries)
+    }
+
+    /// Copies the content of a payload file, e.g. `/usr/bin/hello`, into the writer
+    pub fn extract_file<W: Write>(self, name: &str, writer: &mut W) -> Result<u64> {
+        let name = normalize_path(name);
+        let mut payload = CpioStream::new(self.into_uncompress_reader()?);
+        while let Some(mut member) = payload.next_member()? {
+            if normalize_path(&member.entry().name) == name {
+                return Ok(io::copy(&mut member, writer)?);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Error: {} is not found in the payload", name),
+        )
+        .into())
+    }
+
+    /// Decompressed payload as a cpio archive, tar payloads are converted
+    pub(crate) fn into_uncompress_reader(mut self) -> Result<Box<dyn Read>> {
         self.file.seek(SeekFrom::Start(self.payload_offset))?;
+        self.into_payload_reader()
+    }
 
-        let compressor: String = self
-            .header_tags
-            .get_value(Tag::PayloadCompressor)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Compression is not defined"))?
-            .as_string()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Compression is not defined"))?;
-
-        match compressor.as_str() {
-            "gzip" => Ok(Box::new(GzDecoder::new(self.file))),
-            "bzip2" => Ok(Box::new(BzDecoder::new(self.file))),
-            "zstd" => Ok(Box::new(Decoder::new(self.file)?)),
-            "xz" | "lzma" => Ok(Box::new(XzDecoder::new(self.file))),
-            format => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Decompressor \"{}\" is not implemented", format),
-            )),
+    /// Reads the payload without consuming the file
+    pub(crate) fn uncompress_reader(&mut self) -> Result<Box<dyn Read + '_>> {
+        self.file.seek(SeekFrom::Start(self.payload_offset))?;
+        let compressor = self.header_tags.get_as_string_or(Tag::PayloadCompressor);
+        let format = self.header_tags.get_as_string_or(Tag::PayloadFormat);
+        Ok(payload::cpio_payload(
+            self.decompressors.wrap(&compressor, &mut self.file)?,
+            &format,
+        )?)
+    }
+
+    /// Compares PayloadDigest with the digest of the compressed payload, which is streamed
+    pub fn verify_payload_digest(&mut self) -> io::Result<DigestVerification> {
+        let digests = self.header_tags.get_as_string_array_or(Tag::PayloadDigest);
+        let expected = match digests.first() {
+            Some(expected) => expected,
+            None => return Ok(DigestVerification::NotPresent),
+        };
+        let algo = self.payload_digest_algo()?;
+
+        self.file.seek(SeekFrom::Start(self.payload_offset))?;
+        let actual = algo.digest(&mut self.file)?;
+        Ok(DigestVerification::compare(expected, actual))
+    }
+}
+
+impl<T: Read + Seek> RPMFile<T> {
+    /// Compares SHA256Header and SHA1Header with the digests of the main header
+    pub fn verify_header_digests(&mut self) -> io::Result<HeaderDigestStatus> {
+        let header = self.read_raw_header_bytes()?;
+        let verify = |tag: SignatureTag, algo: DigestAlgo| match self.signature_tags.get_ref(tag) {
+            Some(value) => DigestVerification::compare(
+                &value.as_string().unwrap_or_default(),
+                algo.digest_bytes(&header),
+            ),
+            None => DigestVerification::NotPresent,
+        };
+        Ok(HeaderDigestStatus {
+            sha256: verify(SignatureTag::SHA256Header, DigestAlgo::Sha256),
+            sha1: verify(SignatureTag::SHA1Header, DigestAlgo::Sha1),
+        })
+    }
+
+    /// Main header with its immutable region as it is stored in the file,
+    /// signatures and digests of the signature header cover these bytes
+    pub fn read_raw_header_bytes(&mut self) -> io::Result<Vec<u8>> {
+        self.read_range(self.header_range.clone())
+    }
+
+    fn read_range(&mut self, range: Range<u64>) -> io::Result<Vec<u8>> {
+        self.file.seek(SeekFrom::Start(range.start))?;
+        let mut bytes = vec![0_u8; (range.end - range.start) as usize];
+        self.file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Signatures of the header with the payload, numbered as in the signature header and in the main header
+#[cfg(feature = "signing")]
+const LEGACY_SIGNATURES: [SignatureTag; 4] = [
+    SignatureTag::PGP,
+    SignatureTag::GPG,
+    SignatureTag::SigPGP,
+    SignatureTag::SigGPG,
+];
+
+#[cfg(feature = "signing")]
+impl<T: Read + Seek> RPMFile<T> {
+    /// Verifies the header signature, the key is selected from the keyring by the signature issuer.
+    /// Old packages have only PGP or GPG signatures, they cover the header with the payload.
+    pub fn verify_signature(&mut self, keyring: &Keyring) -> io::Result<SignatureStatus> {
+        let find = |tags: &[SignatureTag]| {
+            tags.iter()
+                .find_map(|&tag| self.signature_tags.get_value(tag))
+        };
+        let (value, with_payload) = match find(&[SignatureTag::RSAHeader, SignatureTag::DSAHeader])
+        {
+            Some(value) => (Some(value), false),
+            None => (find(&LEGACY_SIGNATURES), true),
+        };
+
+        let bytes = match value {
+            Some(RType::Bin(bytes)) => bytes,
+            Some(_) => return Err(io::Error::other("Header signature is not a binary value")),
+            None => return Ok(SignatureStatus::Unsigned),
+        };
+
+        let signature = Signature::from_bytes(&bytes)?;
+        let data = if with_payload {
+            let end = self.file.seek(SeekFrom::End(0))?;
+            self.read_range(self.header_range.start..end)?
+        } else {
+            self.read_raw_header_bytes()?
+        };
+        signature::verify(keyring, &signature, &data)
+    }
+}
+
+#[cfg(feature = "signing")]
+impl RPMFile<File> {
+    /// Signs the main header with RSAHeader, or with DSAHeader for Ed25519 keys, and updates
+    /// SHA256Header. Other signatures are removed, the lead, the main header and the payload
+    /// are kept byte-identical.
+    pub fn sign<P: AsRef<Path>>(path: P, key: &SecretKey) -> io::Result<()> {
+        let mut rpm = Self::open(&path)?;
+        let header = rpm.read_raw_header_bytes()?;
+        let created = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs() as u32);
+        let tag = match key.public.algorithm {
+            PublicKeyAlgorithm::Rsa => SignatureTag::RSAHeader,
+            _ => SignatureTag::DSAHeader,
+        };
+
+        rpm.remove_signatures();
+        rpm.signature_tags
+            .insert(tag, RType::Bin(key.sign(&header, created)?))
+            .insert(
+                SignatureTag::SHA256Header,
+                RType::String(DigestAlgo::Sha256.digest_bytes(&header)),
+            );
+        rpm.rewrite_signature(path)
+    }
+
+    /// Removes all signatures, as `rpmsign --delsign` does
+    pub fn delete_signature<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        let mut rpm = Self::open(&path)?;
+        rpm.remove_signatures();
+        rpm.rewrite_signature(path)
+    }
+
+    fn remove_signatures(&mut self) {
+        for &tag in [SignatureTag::RSAHeader, SignatureTag::DSAHeader]
+            .iter()
+            .chain(LEGACY_SIGNATURES.iter())
+        {
+            self.signature_tags.remove(tag);
+        }
+    }
+
+    /// Writes the signature header in place when it fits into the space of the old one,
+    /// ReservedSpace takes the rest. Otherwise the whole package is rewritten.
+    fn rewrite_signature<P: AsRef<Path>>(mut self, path: P) -> io::Result<()> {
+        let space = (self.header_range.start - self.signature_range.start) as usize;
+        self.signature_tags.remove(SignatureTag::ReservedSpace);
+        let mut signature = header_bytes(&self.signature_tags)?;
+
+        // a binary entry takes its index and its data, the data of the last tag is not aligned
+        if let Some(reserved) = space.checked_sub(signature.len() + 16).filter(|&n| n > 0) {
+            self.signature_tags
+                .insert(SignatureTag::ReservedSpace, RType::Bin(vec![0; reserved]));
+            signature = header_bytes(&self.signature_tags)?;
+        }
+
+        if signature.len() == space {
+            let mut file = OpenOptions::new().write(true).open(&path)?;
+            file.seek(SeekFrom::Start(self.signature_range.start))?;
+            return file.write_all(&signature);
+        }
+
+        let path = path.as_ref();
+        let mut name = path.file_name().unwrap_or_default().to_owned();
+        name.push(".tmp");
+        let temp = path.with_file_name(name);
+
+        let mut lead = vec![0_u8; self.signature_range.start as usize];
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_exact(&mut lead)?;
+
+        let mut writer = File::create(&temp)?;
+        writer.write_all(&lead)?;
+        writer.write_all(&signature)?;
+        // the main header starts at 8 bytes boundary
+        writer.write_all(&vec![
+            0_u8;
+            align_n_bytes(signature.len() as u32, 8) as usize
+        ])?;
+        self.file.seek(SeekFrom::Start(self.header_range.start))?;
+        io::copy(&mut self.file, &mut writer)?;
+        drop(writer);
+        std::fs::set_permissions(&temp, std::fs::metadata(path)?.permissions())?;
+        std::fs::rename(temp, path)
+    }
+}
+
+impl<T> RPMFile<T> {
+    /// Offsets of the signature header without its padding, empty unless the package was read
+    pub fn signature_range(&self) -> Range<u64> {
+        self.signature_range.clone()
+    }
+
+    /// Offsets of the main header, empty unless the package was read
+    pub fn header_range(&self) -> Range<u64> {
+        self.header_range.clone()
+    }
+
+    /// The payload continues to the end of the file
+    pub fn payload_range_start(&self) -> u64 {
+        self.payload_offset
+    }
+
+    /// File name extension of the stored payload, e.g. `cpio.zst`,
+    /// unknown compressors are used as they are
+    pub fn payload_extension(&self) -> String {
+        let format = match self.header_tags.get_ref(Tag::PayloadFormat) {
+            Some(RType::String(format)) => format.as_str(),
+            _ => "cpio",
+        };
+        match compressor_name(&self.header_tags.get_as_string_or(Tag::PayloadCompressor)) {
+            "identity" => format.to_owned(),
+            "gzip" => format!("{}.gz", format),
+            "bzip2" => format!("{}.bz2", format),
+            "zstd" => format!("{}.zst", format),
+            compressor => format!("{}.{}", format, compressor),
+        }
+    }
+
+    /// Algorithm of the PayloadDigest tag, SHA256 if it is not defined
+    pub fn payload_digest_algo(&self) -> std::result::Result<DigestAlgo, UnsupportedDigestAlgo> {
+        digest_algo(&self.header_tags, Tag::PayloadDigestAlgo)
+    }
+
+    /// Algorithm of file digests, MD5 if it is not defined
+    pub fn file_digest_algo(&self) -> std::result::Result<DigestAlgo, UnsupportedDigestAlgo> {
+        digest_algo(&self.header_tags, Tag::FileDigestAlgo)
+    }
+
+    /// IMA signatures of the FileSignatures tag, to be restored on extraction
+    pub fn xattr_manifest(&self) -> XattrManifest {
+        let mut manifest = XattrManifest::new();
+        let signatures = self.header_tags.get_as_string_array_or(Tag::FileSignatures);
+        for ((name, _, _), signature) in header_files(&self.header_tags).iter().zip(signatures) {
+            match hex::decode(signature) {
+                Ok(value) if !value.is_empty() => {
+                    manifest.insert(name, (IMA_XATTR.to_owned(), value))
+                }
+                _ => {}
+            }
         }
+        manifest
+    }
+}
+
+fn digest_algo(
+    tags: &Tags<Tag>,
+    tag: Tag,
+) -> std::result::Result<DigestAlgo, UnsupportedDigestAlgo> {
+    match (algo_id(tags, tag), tag) {
+        (Some(id), _) => DigestAlgo::from_rpm_id(id),
+        (None, Tag::PayloadDigestAlgo) => Ok(DigestAlgo::Sha256),
+        (None, _) => Ok(DigestAlgo::Md5),
     }
 }
 
 impl<T: 'static + Write> RPMFile<T> {
-    pub fn write_head(&mut self) -> io::Result<()> {
+    /// Writes the lead and both headers with their immutable regions
+    pub fn write_head(&mut self) -> Result<()> {
+        let header = header_bytes(&self.header_tags)?;
+        self.write_head_with(&header)
+    }
+
+    /// Writes the whole package with the cpio archive as its payload. The payload is
+    /// compressed in memory: sizes and digests of the signature header, which precedes
+    /// the main header, cover both of them.
+    pub fn write_package<R: Read>(&mut self, cpio: &mut R) -> Result<u64> {
+        let compression = Compression::from_tags(&self.header_tags)?;
+        let mut payload = Vec::new();
+        let size = io::copy(cpio, &mut compression.writer(&mut payload)?)?;
+
+        let algo = self.payload_digest_algo().unwrap_or(DigestAlgo::Sha256);
+        self.header_tags
+            .insert(
+                Tag::PayloadDigest,
+                RType::StringArray(vec![algo.digest_bytes(&payload)]),
+            )
+            .insert(Tag::PayloadDigestAlgo, RType::Int32(algo.rpm_id()));
+
+        let header = header_bytes(&self.header_tags)?;
+        self.signature_tags
+            .insert_size((header.len() + payload.len()) as u64)
+            .insert_payload_size(size)
+            .insert_digests(&header, &payload);
+
+        self.write_head_with(&header)?;
+        self.file.write_all(&payload)?;
+        Ok(size)
+    }
+
+    fn write_head_with(&mut self, header: &[u8]) -> Result<()> {
         self.file.write_lead(&self.lead)?;
-        self.file.write_header(&self.signature_tags)?;
-        self.file.write_header(&self.header_tags)?;
+        let signature = header_bytes(&self.signature_tags)?;
+        self.file.write_all(&signature)?;
+        // the main header starts at 8 bytes boundary
+        let pad = vec![0_u8; align_n_bytes(signature.len() as u32, 8) as usize];
+        self.file.write_all(&pad)?;
+        self.file.write_all(header)?;
         Ok(())
     }
 
-    pub fn write_payload(self, path: &Path) -> io::Result<u64> {
-        let mut reader = OpenOptions::new().open(path)?;
+    pub fn write_payload(self, path: &Path) -> Result<u64> {
+        let mut reader = OpenOptions::new().read(true).open(path)?;
         let mut writer = self.into_compress_writer()?;
-        io::copy(&mut reader, &mut writer)
+        Ok(io::copy(&mut reader, &mut writer)?)
+    }
+
+    fn into_compress_writer(self) -> Result<Box<dyn Write>> {
+        Compression::from_tags(&self.header_tags)?.writer(self.file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RpmError;
+    use crate::payload::RPMPayload;
+    #[cfg(feature = "gzip")]
+    use crate::payload::{FileFlags, Overwrite, SkipReason};
+    use crate::{Decompressor, RPMInfo, INFO_TAGS};
+    use std::convert::TryFrom;
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_extract_report() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let dir = tempfile::tempdir()?;
+
+        let report = RPMFile::open(&path)?.extract(dir.path(), &Default::default())?;
+        assert_eq!(report.entries.len(), 6);
+        assert_eq!(report.files_created, 4);
+        assert_eq!(report.dirs_created, 1);
+        assert_eq!(report.symlinks_created, 1);
+        assert_eq!(report.bytes_written, 75);
+        assert!(report.skipped.is_empty());
+
+        let mut conf = Vec::new();
+        let size = RPMFile::open(&path)?.extract_file("/etc/hello.conf", &mut conf)?;
+        assert_eq!(size, conf.len() as u64);
+        assert_eq!(conf, std::fs::read(dir.path().join("etc/hello.conf"))?);
+        let missing = RPMFile::open(&path)?.extract_file("/etc/missing", &mut io::sink());
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(dir
+            .path()
+            .join("usr/bin/hi")
+            .symlink_metadata()?
+            .file_type()
+            .is_symlink());
+
+        let options = ExtractOptions {
+            overwrite: Overwrite::Skip,
+            ..Default::default()
+        };
+        let report = RPMFile::open(&path)?.extract(dir.path(), &options)?;
+        assert_eq!(report.files_created, 0);
+        assert_eq!(report.dirs_created, 1);
+        assert_eq!(report.skipped.len(), 5);
+        assert!(report
+            .skipped
+            .iter()
+            .all(|(_, reason)| *reason == SkipReason::Exists));
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_copy_payload_to() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let dir = tempfile::tempdir()?;
+        let cpio = dir.path().join("hello.cpio");
+        let size = RPMFile::open(&path)?.copy_payload(&cpio)?;
+
+        let mut payload = Vec::new();
+        assert_eq!(RPMFile::open(&path)?.copy_payload_to(&mut payload)?, size);
+        assert_eq!(payload, std::fs::read(&cpio)?);
+        Ok(())
+    }
+
+    /// Counts the reads from the package, each of them is a syscall for files
+    #[cfg(feature = "gzip")]
+    struct CountingReader {
+        inner: io::Cursor<Vec<u8>>,
+        reads: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    #[cfg(feature = "gzip")]
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads.set(self.reads.get() + 1);
+            self.inner.read(buf)
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    impl Seek for CountingReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_buffered_read() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let bytes = std::fs::read(&path)?;
+        let reader = || CountingReader {
+            inner: io::Cursor::new(bytes.clone()),
+            reads: Default::default(),
+        };
+
+        // a buffer of one byte passes every read through
+        let mut unbuffered = reader();
+        let reads = unbuffered.reads.clone();
+        let headers = Headers::read(
+            &mut BufReader::with_capacity(1, &mut unbuffered),
+            &Default::default(),
+        )?;
+        let unbuffered_reads = reads.get();
+
+        let buffered = reader();
+        let reads = buffered.reads.clone();
+        let rpm = RPMFile::read(buffered)?;
+        assert!(
+            reads.get() * 10 < unbuffered_reads,
+            "{} buffered reads, {} unbuffered reads",
+            reads.get(),
+            unbuffered_reads
+        );
+
+        assert_eq!(rpm.header_range, headers.header_range);
+        assert_eq!(rpm.payload_offset, headers.header_range.end);
+        let mut payload = Vec::new();
+        rpm.copy_payload_to(&mut payload)?;
+        let mut expected = Vec::new();
+        RPMFile::open(&path)?.copy_payload_to(&mut expected)?;
+        assert_eq!(payload, expected);
+        Ok(())
+    }
+
+    /// Reader of a pipe, it can not seek
+    #[derive(Debug)]
+    struct NoSeek<R>(R);
+
+    impl<R: Read> Read for NoSeek<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
     }
 
-    fn into_compress_writer(self) -> io::Result<Box<dyn Write>> {
-        let compressor: String = self
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_read_stream() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let bytes = std::fs::read(&path)?;
+        let rpm = RPMFile::read_stream(NoSeek(io::Cursor::new(bytes.clone())))?;
+        let file = RPMFile::open(&path)?;
+
+        assert_eq!(rpm.lead, file.lead);
+        assert_eq!(rpm.signature_tags, file.signature_tags);
+        assert_eq!(rpm.header_tags, file.header_tags);
+        assert_eq!(rpm.signature_range, file.signature_range);
+        assert_eq!(rpm.header_range, file.header_range);
+        assert_eq!(rpm.payload_offset, file.payload_offset);
+        assert_eq!(RPMInfo::try_from(&rpm)?, RPMInfo::try_from(&file)?);
+
+        let mut payload = Vec::new();
+        rpm.into_payload_reader()?.read_to_end(&mut payload)?;
+        let mut expected = Vec::new();
+        file.copy_payload_to(&mut expected)?;
+        assert_eq!(payload, expected);
+
+        let truncated = NoSeek(io::Cursor::new(bytes[..200].to_vec()));
+        let err = RPMFile::read_stream(truncated).unwrap_err();
+        assert!(matches!(err, RpmError::Truncated("header")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_raw_payload() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let bytes = std::fs::read(&path)?;
+        let mut rpm = RPMFile::open(&path)?;
+        let compressor = rpm.header_tags.get_as_string_or(Tag::PayloadCompressor);
+        assert_eq!(compressor, "gzip");
+        assert_eq!(rpm.payload_extension(), "cpio.gz");
+        let raw = &bytes[rpm.payload_range_start() as usize..];
+
+        // unknown compressors are copied without decompression
+        rpm.header_tags
+            .insert(Tag::PayloadCompressor, RType::String("lzip".to_owned()));
+        assert_eq!(rpm.payload_extension(), "cpio.lzip");
+        let mut payload = Vec::new();
+        let copied = rpm.copy_raw_payload(&mut payload)?;
+        assert_eq!(payload, raw);
+        assert_eq!(
+            copied,
+            RawPayload {
+                compressor: "lzip".to_owned(),
+                size: raw.len() as u64
+            }
+        );
+
+        let rpm = RPMFile::read_stream(NoSeek(io::Cursor::new(bytes.clone())))?;
+        let mut payload = Vec::new();
+        let copied = rpm.copy_raw_payload_from_stream(&mut payload)?;
+        assert_eq!(payload, raw);
+        assert_eq!(
+            (copied.compressor, copied.size),
+            (compressor, raw.len() as u64)
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_uncompressed_payload() -> io::Result<()> {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        let dir = tempfile::tempdir()?;
+        let mut expected = Vec::new();
+        RPMFile::open(data.join("hello-1.0-1.noarch.rpm"))?.copy_payload_to(&mut expected)?;
+
+        let path = data.join("hello-ufdio-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(&path)?;
+        assert!(rpm.header_tags.get_value(Tag::PayloadCompressor).is_none());
+        assert_eq!(rpm.payload_extension(), "cpio");
+        assert_eq!(rpm.verify_payload_digest()?, DigestVerification::Ok);
+        let size = rpm.copy_payload(&dir.path().join("payload.cpio"))?;
+        assert_eq!(size, expected.len() as u64);
+        assert_eq!(std::fs::read(dir.path().join("payload.cpio"))?, expected);
+
+        for compressor in &["", "none", "ufdio"] {
+            let mut rpm = RPMFile::open(&path)?;
+            rpm.header_tags
+                .insert_payload_compressor(compressor.to_string());
+            assert_eq!(rpm.read_payload_files()?.len(), 6);
+        }
+        let mut rpm = RPMFile::open(data.join("hello-1.0-1.noarch.rpm"))?;
+        rpm.header_tags
+            .insert_payload_compressor("gzdio".to_owned());
+        let mut payload = Vec::new();
+        rpm.copy_payload_to(&mut payload)?;
+        assert_eq!(payload, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_decompressor() -> io::Result<()> {
+        struct Lzip;
+
+        impl Decompressor for Lzip {
+            fn wrap<'a>(&self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+                Ok(reader)
+            }
+
+            fn names(&self) -> &[&str] {
+                &["lzip"]
+            }
+        }
+
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-ufdio-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(&path)?;
+        rpm.header_tags.insert_payload_compressor("lzip".to_owned());
+        let err = rpm.read_payload_files().unwrap_err();
+        assert!(matches!(err, RpmError::UnsupportedCompressor(ref c) if c == "lzip"));
+
+        let mut rpm = RPMFile::open(&path)?;
+        rpm.header_tags.insert_payload_compressor("lzip".to_owned());
+        rpm.decompressors.register(Lzip);
+        assert_eq!(rpm.payload_extension(), "cpio.lzip");
+        let mut readme = Vec::new();
+        rpm.extract_file("/usr/share/doc/hello/README", &mut readme)?;
+        assert!(!readme.is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_list_payload() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let rpm = RPMFile::open(&path)?;
+        let mut expected: Vec<String> = rpm
             .header_tags
-            .get_value(Tag::PayloadCompressor)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Compression is not defined"))?
-            .as_string()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Compression is not defined"))?;
-
-        match compressor.as_str() {
-            "gzip" => Ok(Box::new(GzEncoder::new(
-                self.file,
-                flate2::Compression::best(),
-            ))),
-            "bzip2" => Ok(Box::new(BzEncoder::new(
-                self.file,
-                bzip2::Compression::best(),
-            ))),
-            "zstd" => Ok(Box::new(Encoder::new(self.file, 3)?)),
-            "xz" | "lzma" => Ok(Box::new(XzEncoder::new(self.file, 3))),
-
-            format => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Decompressor \"{}\" is not implemented", format),
-            )),
+            .files()
+            .into_iter()
+            .filter(|file| !file.file_flags().contains(FileFlags::GHOST))
+            .map(|file| file.name)
+            .collect();
+        let mut names: Vec<String> = rpm
+            .list_payload()?
+            .iter()
+            .map(|entry| normalize_path(&entry.name))
+            .collect();
+        expected.sort();
+        names.sort();
+        assert_eq!(names, expected);
+
+        let dir = tempfile::tempdir()?;
+        let truncated = dir.path().join("truncated.rpm");
+        let bytes = std::fs::read(&path)?;
+        std::fs::write(&truncated, &bytes[..bytes.len() - 40])?;
+        assert!(RPMFile::open(&truncated)?.list_payload().is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_tar_payload() -> io::Result<()> {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        let rpm = RPMFile::open(data.join("hello-tar-1.0-1.noarch.rpm"))?;
+        assert_eq!(RPMInfo::try_from(&rpm)?.payload.format, "tar");
+
+        let names = |files: Vec<FileInfo>| -> Vec<String> {
+            files.into_iter().map(|file| file.name).collect()
+        };
+        assert_eq!(
+            names(rpm.read_payload_files()?),
+            names(RPMFile::open(data.join("hello-1.0-1.noarch.rpm"))?.read_payload_files()?)
+        );
+
+        let dir = tempfile::tempdir()?;
+        let mut rpm = RPMFile::open(data.join("hello-tar-1.0-1.noarch.rpm"))?;
+        assert!(rpm.check_payload_manifest()?.is_consistent());
+        let report = rpm.extract(dir.path(), &Default::default())?;
+        assert_eq!(report.files_created, 4);
+        assert_eq!(report.dirs_created, 1);
+        assert_eq!(report.symlinks_created, 1);
+        assert_eq!(report.bytes_written, 75);
+        assert_eq!(
+            std::fs::read_link(dir.path().join("usr/bin/hi"))?,
+            Path::new("hello")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_digest_algo() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(&path)?;
+        assert_eq!(rpm.file_digest_algo()?, DigestAlgo::Sha256);
+        assert_eq!(rpm.payload_digest_algo()?, DigestAlgo::Sha256);
+
+        rpm.header_tags.insert(Tag::FileDigestAlgo, RType::Int32(3));
+        assert_eq!(rpm.file_digest_algo(), Err(UnsupportedDigestAlgo(3)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_payload_digest() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(&path)?;
+        assert_eq!(rpm.verify_payload_digest()?, DigestVerification::Ok);
+
+        let mut bytes = std::fs::read(&path)?;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let mut corrupted = RPMFile::read(io::Cursor::new(bytes))?;
+        assert!(corrupted.verify_payload_digest()?.is_mismatch());
+
+        corrupted.header_tags.remove(Tag::PayloadDigest);
+        assert_eq!(
+            corrupted.verify_payload_digest()?,
+            DigestVerification::NotPresent
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_header_digests() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(&path)?;
+        let status = rpm.verify_header_digests()?;
+        assert_eq!(status.sha256, DigestVerification::Ok);
+        assert_eq!(status.to_string(), "SHA256 digest OK, SHA1 digest OK");
+
+        // a byte of the Name value in the header data
+        let mut bytes = std::fs::read(&path)?;
+        let header = rpm.header_range().start as usize;
+        let name = header + 16 + bytes[header + 11] as usize * 16;
+        let position = bytes[name..]
+            .windows(6)
+            .position(|w| w == b"hello\0")
+            .unwrap();
+        bytes[name + position] = b'j';
+        let mut corrupted = RPMFile::read(io::Cursor::new(bytes))?;
+        let status = corrupted.verify_header_digests()?;
+        assert!(status.is_mismatch());
+        assert!(status.sha1.is_mismatch());
+
+        corrupted.signature_tags.remove(SignatureTag::SHA256Header);
+        corrupted.signature_tags.remove(SignatureTag::SHA1Header);
+        assert_eq!(corrupted.verify_header_digests()?.to_string(), "MISSING");
+
+        let info = RPMInfo {
+            header_digests: Some(rpm.verify_header_digests()?),
+            ..RPMInfo::try_from(&rpm)?
+        };
+        assert!(info
+            .to_string()
+            .contains("Signature   : (none), SHA256 digest OK, SHA1 digest OK\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_byte_ranges() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(&path)?;
+        let bytes = std::fs::read(&path)?;
+        let field = |at: u64| {
+            let at = at as usize + 8;
+            u32::from_be_bytes(<[u8; 4]>::try_from(&bytes[at..at + 4]).unwrap())
+        };
+
+        let signature = rpm.signature_range();
+        assert_eq!(signature.start, 96);
+        assert_eq!(
+            signature.end - signature.start,
+            u64::from(16 + field(96) * 16 + field(96 + 4))
+        );
+        let header = rpm.header_range();
+        assert_eq!(header.start, signature.end.div_ceil(8) * 8);
+        assert_eq!(header.end, rpm.payload_range_start());
+        assert_eq!(
+            header.end - header.start,
+            u64::from(16 + field(header.start) * 16 + field(header.start + 4))
+        );
+
+        let raw = rpm.read_raw_header_bytes()?;
+        assert_eq!(raw, &bytes[header.start as usize..header.end as usize]);
+        assert!(raw.starts_with(&crate::header::MAGIC_HEADER));
+
+        let written = RPMInfo::try_from(&rpm)?.into_rpm(Vec::new())?;
+        assert!(written.header_range().is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn test_sign() -> io::Result<()> {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        let key = SecretKey::load(data.join("keys/RPM-GPG-SECRET-KEY-test4"))?;
+        let keyring = Keyring::load(data.join("keys/RPM-GPG-KEY-test4"))?;
+        let original = std::fs::read(data.join("hello-1.0-1.noarch.rpm"))?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hello-1.0-1.noarch.rpm");
+        std::fs::write(&path, &original)?;
+
+        let start = RPMFile::open(&path)?.header_range().start as usize;
+        let unchanged = |bytes: &[u8]| {
+            let offset = bytes.len() - (original.len() - start);
+            bytes[..96] == original[..96] && bytes[offset..] == original[start..]
+        };
+
+        // no reserved space in the package, it is rewritten
+        RPMFile::sign(&path, &key)?;
+        let mut rpm = RPMFile::open(&path)?;
+        assert_eq!(
+            rpm.verify_signature(&keyring)?,
+            SignatureStatus::Valid(key.key_id())
+        );
+        assert!(!rpm.verify_header_digests()?.is_mismatch());
+        let signed = std::fs::read(&path)?;
+        assert!(signed.len() > original.len());
+        assert!(unchanged(&signed));
+
+        // the signature is replaced with the reserved space and back in place
+        RPMFile::delete_signature(&path)?;
+        let mut rpm = RPMFile::open(&path)?;
+        assert_eq!(rpm.verify_signature(&keyring)?, SignatureStatus::Unsigned);
+        assert!(rpm
+            .signature_tags
+            .get_value(SignatureTag::ReservedSpace)
+            .is_some());
+        let deleted = std::fs::read(&path)?;
+        assert_eq!(deleted.len(), signed.len());
+        assert!(unchanged(&deleted));
+
+        RPMFile::sign(&path, &key)?;
+        assert!(RPMFile::open(&path)?.verify_signature(&keyring)?.is_valid());
+        assert_eq!(std::fs::read(&path)?.len(), signed.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_options() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let options = ReadOptions {
+            max_index_entries: 8,
+            ..Default::default()
+        };
+        let err = RPMFile::open_with(&path, &options).unwrap_err();
+        assert!(matches!(err, RpmError::HeaderLimit { limit: 8, .. }));
+
+        let options = ReadOptions {
+            max_index_entries: 100,
+            ..options
+        };
+        RPMFile::open_with(&path, &options)?;
+
+        let full = RPMFile::open(&path)?;
+        let options = ReadOptions {
+            skip_signature: true,
+            ..Default::default()
         }
+        .with_tags(INFO_TAGS.iter().copied());
+        let rpm = RPMFile::open_with(&path, &options)?;
+        assert!(rpm.signature_tags.is_empty());
+        assert!(!rpm.header_tags.contains(Tag::BaseNames));
+        assert_eq!(
+            rpm.header_tags.get_ref(Tag::Name),
+            full.header_tags.get_ref(Tag::Name)
+        );
+        assert_eq!(rpm.payload_offset, full.payload_offset);
+
+        let info = RPMInfo::try_from(&rpm)?;
+        assert!(info.payload.files.is_empty());
+        assert_eq!(
+            info,
+            RPMInfo {
+                payload: RPMPayload {
+                    size: 0,
+                    files: Vec::new(),
+                    ..RPMInfo::try_from(&full)?.payload
+                },
+                signature: None,
+                ..RPMInfo::try_from(&full)?
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_xattr_manifest() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(&path)?;
+        assert!(rpm.xattr_manifest().is_empty());
+
+        let mut signatures = vec![String::new(); 7];
+        signatures[1] = "0302ff".to_owned();
+        rpm.header_tags.insert_file_signatures(signatures);
+        assert_eq!(rpm.header_tags.try_get_as_u32(Tag::FileSignatureLength)?, 3);
+        let manifest = rpm.xattr_manifest();
+        assert_eq!(
+            manifest.get("./usr/bin/hello"),
+            &[(IMA_XATTR.to_owned(), vec![3, 2, 0xff])]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_write_package() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut cpio = Vec::new();
+        RPMFile::open(&path)?.copy_payload_to(&mut cpio)?;
+
+        let rpm = RPMFile::open(&path)?;
+        let mut written = RPMFile {
+            file: Vec::new(),
+            lead: rpm.lead,
+            signature_tags: rpm.signature_tags,
+            header_tags: rpm.header_tags,
+            payload_offset: 0,
+            decompressors: Default::default(),
+            signature_range: 0..0,
+            header_range: 0..0,
+        };
+        assert_eq!(
+            written.write_package(&mut cpio.as_slice())?,
+            cpio.len() as u64
+        );
+
+        let bytes = written.file;
+        let rpm = RPMFile::read(io::Cursor::new(bytes.clone()))?;
+        let tags = &rpm.signature_tags;
+        assert_eq!(tags.payload_size(), cpio.len() as u64);
+        let size = tags
+            .get_value(SignatureTag::Size)
+            .and_then(|size| size.as_u64());
+        let size = size.unwrap_or_default() as usize;
+        let package = &bytes[bytes.len() - size..];
+        let header = &bytes[bytes.len() - size..rpm.payload_offset as usize];
+        assert_eq!(
+            tags.get_value(SignatureTag::MD5),
+            Some(RType::Bin(
+                hex::decode(DigestAlgo::Md5.digest_bytes(package)).unwrap()
+            ))
+        );
+        assert_eq!(
+            tags.get_as_string_or(SignatureTag::SHA1Header),
+            DigestAlgo::Sha1.digest_bytes(header)
+        );
+        assert_eq!(
+            tags.get_as_string_or(SignatureTag::SHA256Header),
+            DigestAlgo::Sha256.digest_bytes(header)
+        );
+        assert_eq!(
+            rpm.header_tags.get_as_string_array_or(Tag::PayloadDigest),
+            vec![DigestAlgo::Sha256.digest_bytes(&bytes[rpm.payload_offset as usize..])]
+        );
+
+        let mut payload = Vec::new();
+        rpm.copy_payload_to(&mut payload)?;
+        assert_eq!(payload, cpio);
+        Ok(())
     }
 }
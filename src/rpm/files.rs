@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+use super::file::RPMFile;
+use crate::error::Result;
+use crate::payload::{normalize_path, CpioStream, FileEntry, FileInfo};
+
+/// Payload member with its header metadata and content
+#[derive(Debug)]
+pub struct PayloadFile {
+    pub entry: FileEntry,
+    /// Metadata of the header file with the same path, e.g. digest, flags, user and group
+    pub info: Option<FileInfo>,
+    pub data: Vec<u8>,
+}
+
+/// Payload files in the payload order, content of each file is read into memory.
+/// Use `CpioStream` to read large files without buffering them.
+pub struct PayloadFiles {
+    payload: CpioStream<Box<dyn Read>>,
+    infos: HashMap<String, FileInfo>,
+    finished: bool,
+}
+
+impl PayloadFiles {
+    fn next_file(&mut self) -> Result<Option<PayloadFile>> {
+        let mut member = match self.payload.next_member()? {
+            Some(member) => member,
+            None => return Ok(None),
+        };
+        let mut data = Vec::with_capacity(member.entry().file_size as usize);
+        member.read_to_end(&mut data)?;
+
+        let entry = member.into_entry();
+        let info = self.infos.remove(&normalize_path(&entry.name));
+        Ok(Some(PayloadFile { entry, info, data }))
+    }
+}
+
+impl Iterator for PayloadFiles {
+    type Item = Result<PayloadFile>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let file = self.next_file().transpose();
+        // the payload can not be read after an error
+        self.finished = !matches!(file, Some(Ok(_)));
+        file
+    }
+}
+
+impl<T: 'static + Read + Seek> RPMFile<T> {
+    /// Payload files paired with the header files by path
+    pub fn files(self) -> Result<PayloadFiles> {
+        let infos = self
+            .header_tags
+            .files()
+            .into_iter()
+            .map(|info| (info.name.clone(), info))
+            .collect();
+        Ok(PayloadFiles {
+            payload: CpioStream::new(self.into_uncompress_reader()?),
+            infos,
+            finished: false,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "gzip"))]
+mod tests {
+    use super::*;
+    use crate::payload::FileFlags;
+    use std::path::Path;
+
+    #[test]
+    fn test_payload_files() -> Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let files = RPMFile::open(path)?.files()?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(files.len(), 6);
+
+        let conf = files
+            .iter()
+            .find(|file| file.entry.name == "./etc/hello.conf")
+            .unwrap();
+        let info = conf.info.as_ref().unwrap();
+        assert!(info.file_flags().contains(FileFlags::CONFIGURATION));
+        assert_eq!(info.user, "root");
+        assert_eq!(conf.data.len() as u64, info.size);
+        assert!(files.iter().all(|file| file.info.is_some()));
+        Ok(())
+    }
+}
@@ -1,20 +1,60 @@
 use chrono::{Local, TimeZone};
-use itertools::multizip;
+use std::convert::TryFrom;
 use std::fmt;
 use std::io::{Read, Write};
 
+use super::dependency::{Dependency, DependencyFlags, DependencyKind};
 use super::file::RPMFile;
-use crate::header::{SignatureTag, Tag, Tags};
-use crate::lead::Lead;
-use crate::payload::{FileInfo, RPMPayload};
+use super::tags::HeaderTags;
+use crate::digest::HeaderDigestStatus;
+use crate::error::{Result, RpmError};
+use crate::header::{timestamp, RType, SignatureTag, Tag, Tags};
+use crate::lead::{Lead, Type};
+use crate::payload::RPMPayload;
 
-#[derive(Debug, Default)]
+/// Main header tags of `RPMInfo` without the file list, to read packages faster
+/// with `ReadOptions::with_tags`
+pub const INFO_TAGS: &[Tag] = &[
+    Tag::Name,
+    Tag::Epoch,
+    Tag::Version,
+    Tag::Release,
+    Tag::Arch,
+    Tag::Platform,
+    Tag::OptFlags,
+    Tag::BuildArchs,
+    Tag::Group,
+    Tag::Size,
+    Tag::LongSize,
+    Tag::License,
+    Tag::SourceRpm,
+    Tag::BuildTime,
+    Tag::BuildHost,
+    Tag::Summary,
+    Tag::Description,
+    Tag::RequireName,
+    Tag::RequireFlags,
+    Tag::RequireVersion,
+    Tag::ProvideName,
+    Tag::ProvideFlags,
+    Tag::ProvideVersion,
+    Tag::PayloadFormat,
+    Tag::PayloadCompressor,
+    Tag::PayloadFlags,
+    Tag::LongFileSizes,
+];
+
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RPMInfo {
     pub name: String,
-    pub epoch: u8,
+    pub epoch: u32,
     pub version: String,
     pub release: String,
     pub arch: String,
+    pub platform: String,
+    pub optflags: String,
+    pub build_archs: Vec<String>,
     pub group: String,
     pub size: u64,
     pub license: String,
@@ -23,6 +63,12 @@ pub struct RPMInfo {
     pub build_host: String,
     pub summary: String,
     pub description: String,
+    /// Header signature summary, e.g. `RSA/SHA256, <date>, Key ID 8bf5a2b2`
+    pub signature: Option<String>,
+    /// Set from `RPMFile::verify_header_digests`, it is shown on the Signature line
+    pub header_digests: Option<HeaderDigestStatus>,
+    pub requires: Vec<Dependency>,
+    pub provides: Vec<Dependency>,
     pub payload: RPMPayload,
 }
 
@@ -37,7 +83,15 @@ impl fmt::Display for RPMInfo {
         writeln!(f, "Group       : {}", self.group)?;
         writeln!(f, "Size        : {}", self.size)?;
         writeln!(f, "License     : {}", self.license)?;
-        writeln!(f, "Signature   : (unimplemented)")?;
+        write!(
+            f,
+            "Signature   : {}",
+            self.signature.as_deref().unwrap_or("(none)")
+        )?;
+        match &self.header_digests {
+            Some(digests) => writeln!(f, ", {}", digests)?,
+            None => writeln!(f)?,
+        }
         writeln!(f, "Source RPM  : {}", self.source_rpm)?;
         writeln!(f, "Build Date  : {}", build_time)?;
         writeln!(f, "Build Host  : {}", self.build_host)?;
@@ -47,84 +101,98 @@ impl fmt::Display for RPMInfo {
     }
 }
 
-impl<T: Read> From<&RPMFile<T>> for RPMInfo {
-    fn from(rpm: &RPMFile<T>) -> Self {
+impl<T: Read> TryFrom<&RPMFile<T>> for RPMInfo {
+    type Error = RpmError;
+
+    /// Fails if a required tag is missing, e.g. Name or Version,
+    /// optional tags like Group, License or SourceRpm default to empty strings
+    fn try_from(rpm: &RPMFile<T>) -> Result<Self> {
         let RPMFile {
             signature_tags,
             header_tags,
             ..
         } = rpm;
 
-        let dirs = header_tags.get_as_string_array_or(Tag::DirNames);
-        let dir_indexes = header_tags.get_as_u32_array_or(Tag::DirIndexes);
-        let basenames = header_tags.get_as_string_array_or(Tag::BaseNames);
-        let filesizes = header_tags.get_as_u64_array_or(Tag::FileSizes);
-        let users = header_tags.get_as_string_array_or(Tag::FileUserName);
-        let groups = header_tags.get_as_string_array_or(Tag::FileGroupName);
-        let flags = header_tags.get_as_u32_array_or(Tag::FileFlags);
-        let mtimes = header_tags.get_as_u32_array_or(Tag::FileMTimes);
-        let linknames = header_tags.get_as_string_array_or(Tag::FileGroupName);
-        let modes = header_tags.get_as_u16_array_or(Tag::FileModes);
-        let devices = header_tags.get_as_u32_array_or(Tag::FileDevices);
-        let inodes = header_tags.get_as_u32_array_or(Tag::FileInodes);
-        let digests = header_tags.get_as_string_array_or(Tag::FileMD5s);
-
-        let files: Vec<FileInfo> = multizip((
-            basenames,
-            dir_indexes,
-            filesizes,
-            users,
-            groups,
-            linknames,
-            digests,
-        ))
-        .enumerate()
-        .map(
-            |(i, (name, index, size, user, group, linkname, digest))| FileInfo {
-                name: dirs[index as usize].clone() + &name,
-                size,
-                user,
-                group,
-                flags: flags[i],
-                mtime: mtimes[i],
-                digest,
-                mode: modes[i],
-                linkname,
-                device: devices[i],
-                inode: inodes[i],
-            },
-        )
-        .collect();
-
+        let info = RPMInfo::from_header_tags(header_tags)?;
         let payload = RPMPayload {
-            size: signature_tags.get_as_u64(SignatureTag::PayloadSize),
-            format: header_tags.get_as_string(Tag::PayloadFormat),
-            compressor: header_tags.get_as_string(Tag::PayloadCompressor),
-            flags: header_tags.get_as_string(Tag::PayloadFlags),
-            files,
+            size: signature_tags.payload_size(),
+            long_sizes: info.payload.long_sizes
+                || signature_tags.contains(SignatureTag::LongArchiveSize),
+            ..info.payload
         };
 
-        RPMInfo {
-            name: header_tags.get_as_string(Tag::Name),
-            epoch: header_tags.get_as_u8_default(Tag::Epoch),
-            version: header_tags.get_as_string(Tag::Version),
-            release: header_tags.get_as_string(Tag::Release),
-            arch: header_tags.get_as_string(Tag::Arch),
-            group: header_tags.get_as_string(Tag::Group),
-            size: header_tags.get_as_u64(Tag::Size),
-            license: header_tags.get_as_string_or(Tag::License),
-            source_rpm: header_tags.get_as_string_or(Tag::SourceRpm),
-            build_time: header_tags.get_as_i64(Tag::BuildTime),
-            build_host: header_tags.get_as_string(Tag::BuildHost),
-            summary: header_tags.get_as_string(Tag::Summary),
-            description: header_tags.get_as_string(Tag::Description),
+        Ok(RPMInfo {
+            signature: signature_tags
+                .signature_meta()
+                .ok()
+                .flatten()
+                .map(|meta| meta.to_string()),
             payload,
-        }
+            ..info
+        })
     }
 }
 
 impl RPMInfo {
-    pub fn into_rpm<T: Write>(self, writer: T) -> RPMFile<T> {
+    /// Information of a header without the signature header, e.g. from rpmdb,
+    /// the payload size and the signature are unknown
+    pub fn from_header_tags(header_tags: &HeaderTags) -> Result<Self> {
+        // rpm writes int32, older packages of this crate have int8
+        let epoch = if header_tags.contains(Tag::Epoch) {
+            header_tags.try_get_as(Tag::Epoch, "int32", RType::as_u32)?
+        } else {
+            0
+        };
+
+        Ok(RPMInfo {
+            name: header_tags.try_get_as_string(Tag::Name)?,
+            epoch,
+            version: header_tags.try_get_as_string(Tag::Version)?,
+            release: header_tags.try_get_as_string(Tag::Release)?,
+            arch: header_tags.try_get_as_string(Tag::Arch)?,
+            platform: header_tags.get_as_string_or(Tag::Platform),
+            optflags: header_tags.get_as_string_or(Tag::OptFlags),
+            build_archs: header_tags.get_as_string_array_or(Tag::BuildArchs),
+            group: header_tags.get_as_string_or(Tag::Group),
+            size: header_tags
+                .try_get_as_u64(Tag::LongSize)
+                .or_else(|_| header_tags.try_get_as_u64(Tag::Size))?,
+            license: header_tags.get_as_string_or(Tag::License),
+            source_rpm: header_tags.get_as_string_or(Tag::SourceRpm),
+            build_time: header_tags.try_get_as_i64(Tag::BuildTime)?,
+            build_host: header_tags.try_get_as_string(Tag::BuildHost)?,
+            summary: header_tags.try_get_as_string(Tag::Summary)?,
+            description: header_tags.try_get_as_string(Tag::Description)?,
+            signature: None,
+            header_digests: None,
+            requires: header_tags.dependencies(DependencyKind::Requires),
+            provides: header_tags.dependencies(DependencyKind::Provides),
+            payload: header_tags.payload_info()?,
+        })
+    }
+
+    /// Every package implicitly provides its name with the exact version
+    pub fn self_provide(&self) -> Dependency {
+        let evr = if self.epoch > 0 {
+            format!("{}:{}-{}", self.epoch, self.version, self.release)
+        } else {
+            format!("{}-{}", self.version, self.release)
+        };
+        Dependency::new(&self.name, DependencyFlags::EQUAL, &evr)
+    }
+
+    /// True if the package provides the dependency, explicitly or by its own name
+    pub fn satisfies(&self, dependency: &Dependency) -> bool {
+        dependency.is_satisfied_by(&self.self_provide())
+            || self
+                .provides
+                .iter()
+                .any(|provide| dependency.is_satisfied_by(provide))
+    }
+
+    /// Errors if the build time does not fit the int32 tag
+    pub fn into_rpm<T: Write>(self, writer: T) -> Result<RPMFile<T>> {
+        let build_time = timestamp(self.build_time)?;
         let lead = Lead::from(&self);
         let mut signature_tags = Tags::<SignatureTag>::new();
         let mut header_tags = Tags::<Tag>::new();
@@ -133,12 +201,13 @@ impl RPMInfo {
             .insert_name(self.name)
             .insert_epoch(self.epoch)
             .insert_version(self.version)
+            .insert_release(self.release)
             .insert_arch(self.arch)
             .insert_group(self.group)
             .insert_size(self.size)
             .insert_license(self.license)
             .insert_source_rpm(self.source_rpm)
-            .insert_build_time(self.build_time)
+            .insert_build_time(build_time)
             .insert_build_host(self.build_host)
             .insert_summary(self.summary)
             .insert_description(self.description)
@@ -146,28 +215,250 @@ impl RPMInfo {
             .insert_payload_compressor(self.payload.compressor)
             .insert_payload_flags(self.payload.flags);
 
+        if !self.platform.is_empty() {
+            header_tags.insert_platform(self.platform);
+        }
+        if !self.optflags.is_empty() {
+            header_tags.insert_opt_flags(self.optflags);
+        }
+        if !self.build_archs.is_empty() {
+            header_tags.insert_build_archs(self.build_archs);
+        }
+
+        if !self.requires.is_empty() {
+            Dependency::insert_tags(&mut header_tags, DependencyKind::Requires, &self.requires);
+        }
+        if !self.provides.is_empty() {
+            Dependency::insert_tags(&mut header_tags, DependencyKind::Provides, &self.provides);
+        }
+
         signature_tags.insert_payload_size(self.payload.size);
 
-        RPMFile {
+        Ok(RPMFile {
             lead,
-            header_tags,
-            signature_tags,
+            header_tags: header_tags.into(),
+            signature_tags: signature_tags.into(),
             payload_offset: 0,
+            decompressors: Default::default(),
+            signature_range: 0..0,
+            header_range: 0..0,
             file: writer,
-        }
+        })
     }
 }
 
 impl From<&RPMInfo> for Lead {
     fn from(info: &RPMInfo) -> Self {
-        let mut name = [0_u8; 66];
-        info.name.as_bytes().read(&mut name).unwrap();
+        Lead::new(&info.nvr(), &info.arch, "linux", Type::Binary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::path::Path;
+
+    /// Writes the package of the info with an empty payload and reads it back
+    #[cfg(feature = "gzip")]
+    fn round_trip(info: RPMInfo) -> Result<RPMInfo> {
+        use crate::payload::CpioWriter;
+
+        let mut cpio = Vec::new();
+        cpio.cpio_close()?;
+        let mut rpm = info.into_rpm(Vec::new())?;
+        rpm.write_package(&mut cpio.as_slice())?;
+        RPMInfo::try_from(&RPMFile::read(io::Cursor::new(rpm.file))?)
+    }
 
-        Self {
-            major: 3,
-            minor: 1,
-            name,
+    #[test]
+    fn test_missing_tags() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(path)?;
+
+        rpm.header_tags.remove(Tag::Group);
+        rpm.header_tags.remove(Tag::SourceRpm);
+        let info = RPMInfo::try_from(&rpm)?;
+        assert_eq!((info.name.as_str(), info.group.as_str()), ("hello", ""));
+        assert_eq!(info.source_rpm, "");
+
+        rpm.header_tags.remove(Tag::Version);
+        let err = RPMInfo::try_from(&rpm).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "Error: tag Version is not found");
+
+        rpm.header_tags.insert(Tag::Version, RType::Int32(1));
+        rpm.header_tags
+            .insert(Tag::Size, RType::StringArray(Vec::new()));
+        let err = RPMInfo::try_from(&rpm).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_header_blob() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(path)?;
+        let blob = rpm.read_raw_header_bytes()?;
+        assert_eq!(
+            blob.len() as u64,
+            rpm.header_range().end - rpm.header_range().start
+        );
+
+        let tags = HeaderTags(Tags::read_header_blob(&mut blob.as_slice())?);
+        assert_eq!(tags, rpm.header_tags);
+        // rpmdb entries start with the index count
+        let tags = HeaderTags(Tags::read_header_blob(&mut &blob[8..])?);
+        assert_eq!(tags, rpm.header_tags);
+
+        let info = RPMInfo::from_header_tags(&tags)?;
+        assert_eq!((info.signature.as_deref(), info.payload.size), (None, 0));
+        let expected = RPMInfo::try_from(&rpm)?;
+        assert_eq!(
+            info,
+            RPMInfo {
+                signature: None,
+                payload: RPMPayload {
+                    size: 0,
+                    ..expected.payload
+                },
+                ..expected
+            }
+        );
+
+        let err = Tags::<Tag>::read_header_blob(&mut &blob[..blob.len() - 1]).unwrap_err();
+        assert!(matches!(err, RpmError::Truncated("header")));
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(path)?;
+        let info = RPMInfo {
+            header_digests: Some(rpm.verify_header_digests()?),
+            ..RPMInfo::try_from(&rpm)?
+        };
+
+        let json = serde_json::to_string(&info)?;
+        assert_eq!(serde_json::from_str::<RPMInfo>(&json)?, info);
+        assert_eq!(info.payload.files.len(), 7);
+
+        let json = serde_json::to_string(&rpm.lead)?;
+        assert_eq!(serde_json::from_str::<crate::lead::Lead>(&json)?, rpm.lead);
+
+        let tags = rpm.header_tags.dump();
+        assert_eq!(tags[0].0, "Name");
+        let json = serde_json::to_string(&tags)?;
+        assert_eq!(serde_json::from_str::<Vec<(String, RType)>>(&json)?, tags);
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_info_round_trip() -> Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let info = RPMInfo::try_from(&RPMFile::open(path)?)?;
+        let expected = info.to_string();
+        let build_time = info.build_time;
+
+        let (requires, provides) = (info.requires.clone(), info.provides.clone());
+        assert!(!requires.is_empty() && !provides.is_empty());
+
+        let read = round_trip(info)?;
+        assert_eq!(read.build_time, build_time);
+        assert_eq!(read.to_string(), expected);
+        assert_eq!((&read.requires, &read.provides), (&requires, &provides));
+
+        let info = RPMInfo {
+            size: 1 << 33,
+            ..read
+        };
+        let read = round_trip(info)?;
+        assert_eq!(read.size, 1 << 33);
+        assert_eq!(round_trip(read)?.build_time, build_time);
+
+        // the types of rpm, LongSize is used above 4 GiB
+        let info = RPMInfo {
+            build_time: 1_700_000_000,
+            size: 1 << 33,
             ..Default::default()
+        };
+        let tags = info.into_rpm(Vec::new())?.header_tags;
+        assert_eq!(
+            tags.get_value(Tag::BuildTime),
+            Some(RType::Int32(1_700_000_000))
+        );
+        assert_eq!(tags.get_value(Tag::LongSize), Some(RType::Int64(1 << 33)));
+        assert!(tags.get_value(Tag::Size).is_none());
+        Ok(())
+    }
+
+    /// BuildTime is int32, timestamps outside of it are rejected
+    #[test]
+    fn test_build_time_range() {
+        for build_time in [-1, 1 << 33].iter() {
+            let info = RPMInfo {
+                build_time: *build_time,
+                ..Default::default()
+            };
+            match info.into_rpm(Vec::new()) {
+                Err(RpmError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+                other => panic!("{:?}", other.map(|rpm| rpm.header_tags)),
+            }
         }
     }
+
+    #[test]
+    fn test_epoch() -> Result<()> {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-epoch-1.0-1.noarch.rpm");
+        let rpm = RPMFile::open(path)?;
+        assert_eq!(rpm.header_tags.get_value(Tag::Epoch), Some(RType::Int32(2)));
+        assert_eq!(rpm.header_tags.nevra(), "hello-epoch-2:1.0-1.noarch");
+
+        let info = RPMInfo::try_from(&rpm)?;
+        assert_eq!(info.epoch, 2);
+        assert_eq!(info.self_provide().to_string(), "hello-epoch = 2:1.0-1");
+
+        let written = info.into_rpm(Vec::new())?;
+        assert_eq!(
+            written.header_tags.get_value(Tag::Epoch),
+            Some(RType::Int32(2))
+        );
+
+        // epochs written as int8 are still read
+        let mut rpm = rpm;
+        rpm.header_tags.insert(Tag::Epoch, RType::Int8(3));
+        assert_eq!(RPMInfo::try_from(&rpm)?.epoch, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_sizes() -> Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut rpm = RPMFile::open(path)?;
+        let info = RPMInfo::try_from(&rpm)?;
+        assert!(!info.payload.long_sizes);
+
+        let mut files = info.payload.files;
+        files[0].size = 5 << 30;
+        rpm.header_tags.remove(Tag::FileSizes);
+        rpm.header_tags.insert_files(&files);
+        assert!(rpm.header_tags.get_value(Tag::FileSizes).is_none());
+        rpm.signature_tags.insert_payload_size(6 << 30);
+
+        let info = RPMInfo::try_from(&rpm)?;
+        assert!(info.payload.long_sizes);
+        assert_eq!(info.payload.size, 6 << 30);
+        assert_eq!(info.payload.files[0].size, 5 << 30);
+        assert_eq!(info.payload.files[1].size, files[1].size);
+
+        // only the archive is over 4 GiB
+        rpm.header_tags.remove(Tag::LongFileSizes);
+        assert_eq!(rpm.header_tags.payload_info()?.files[0].size, 0);
+        assert!(RPMInfo::try_from(&rpm)?.payload.long_sizes);
+        Ok(())
+    }
 }
@@ -0,0 +1,67 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::error::Result;
+use crate::header::{HeaderLead, ReadOptions};
+use crate::lead::Lead;
+use crate::utils::align_n_bytes;
+
+/// Package lead with the leads of the signature header and the main header
+#[derive(Debug)]
+pub struct PackageLeads {
+    pub lead: Lead,
+    pub signature: HeaderLead,
+    pub header: HeaderLead,
+}
+
+impl PackageLeads {
+    /// Reads only the leads, the headers are skipped without parsing,
+    /// so the sizes of malformed packages can be inspected
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let options = ReadOptions {
+            max_index_entries: u32::MAX,
+            max_data_size: u32::MAX,
+            ..Default::default()
+        };
+
+        let lead = Lead::read(reader)?;
+        let signature = HeaderLead::read_with(reader, &options)?;
+        let size = signature.nindex as u64 * 16
+            + u64::from(signature.hsize)
+            + u64::from(align_n_bytes(signature.hsize, 8));
+        reader.seek(SeekFrom::Current(size as i64))?;
+        let header = HeaderLead::read_with(reader, &options)?;
+
+        Ok(PackageLeads {
+            lead,
+            signature,
+            header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RPMFile;
+    use std::fs::File;
+    use std::path::Path;
+
+    #[test]
+    fn test_package_leads() -> Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut fh = File::open(&path)?;
+        let leads = PackageLeads::read(&mut fh)?;
+        let file = RPMFile::open(&path)?;
+
+        assert_eq!(leads.lead, file.lead);
+        assert_eq!(leads.lead.signature_type, 5);
+        assert_eq!(
+            leads.signature.hsize as usize + leads.signature.nindex * 16 + 16,
+            (file.signature_range.end - file.signature_range.start) as usize
+        );
+
+        fh.seek(SeekFrom::Start(file.header_range.start))?;
+        assert_eq!(leads.header, HeaderLead::read(&mut fh)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Seek};
+
+use super::tags::{file_names, file_sizes};
+use super::RPMFile;
+use crate::header::{Tag, Tags};
+use crate::lead::{arch_number, Type};
+use crate::payload::{normalize_path, skip_entry_data, FileEntry, FileFlags, TRAILER};
+
+/// Mismatch between a lead field and the header tags it duplicates
+#[derive(Debug, Clone, PartialEq)]
+pub enum LeadDiscrepancy {
+    /// Lead type differs from the type given by the SourcePackage tag
+    Type { lead: Type, header: Type },
+    /// Lead name is not the name-version-release of the package
+    Name { lead: String, header: String },
+    /// Lead architecture number does not match the Arch tag
+    Arch { lead: u16, header: String },
+}
+
+impl fmt::Display for LeadDiscrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeadDiscrepancy::Type { lead, header } => write!(
+                f,
+                "lead type is {}, but the header describes a {} package",
+                lead, header
+            ),
+            LeadDiscrepancy::Name { lead, header } => write!(
+                f,
+                "lead name \"{}\" does not match the package {}",
+                lead, header
+            ),
+            LeadDiscrepancy::Arch { lead, header } => write!(
+                f,
+                "lead architecture number {} does not match the architecture {}",
+                lead, header
+            ),
+        }
+    }
+}
+
+impl<T> RPMFile<T> {
+    /// Compares the lead with the header, which is the source of truth for rpm tools
+    pub fn check_lead_consistency(&self) -> Vec<LeadDiscrepancy> {
+        let tags = &self.header_tags;
+        let mut discrepancies = Vec::new();
+
+        let header_type = if tags.contains(Tag::SourcePackage) {
+            Type::Source
+        } else {
+            Type::Binary
+        };
+        if self.lead.rpm_type != header_type {
+            discrepancies.push(LeadDiscrepancy::Type {
+                lead: self.lead.rpm_type.clone(),
+                header: header_type.clone(),
+            });
+        }
+
+        let name = self.lead.name();
+        let names = package_names(tags);
+        // the field holds 65 bytes, longer names are truncated
+        let matches = names.iter().any(|nvr| {
+            *nvr == name || (name.len() == self.lead.name.len() - 1 && nvr.starts_with(&name))
+        });
+        if !matches {
+            discrepancies.push(LeadDiscrepancy::Name {
+                lead: name,
+                header: names[0].clone(),
+            });
+        }
+
+        // noarch packages carry the number of the build architecture, source
+        // packages that one or NOARCH, only binary ones are compared
+        let arch = tags.get_as_string_or(Tag::Arch);
+        if let (Type::Binary, Some(archnum)) = (header_type, arch_number(&arch)) {
+            if self.lead.archnum != archnum {
+                discrepancies.push(LeadDiscrepancy::Arch {
+                    lead: self.lead.archnum,
+                    header: arch,
+                });
+            }
+        }
+
+        discrepancies
+    }
+}
+
+/// Differences between the payload members and the header file list
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ManifestReport {
+    /// Payload members without a header entry
+    pub payload_only: Vec<String>,
+    /// Header files missing from the payload, %ghost files are not expected there
+    pub header_only: Vec<String>,
+    /// Regular files whose payload size differs from FileSizes
+    pub size_mismatches: Vec<SizeMismatch>,
+}
+
+impl ManifestReport {
+    pub fn is_consistent(&self) -> bool {
+        self.payload_only.is_empty()
+            && self.header_only.is_empty()
+            && self.size_mismatches.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeMismatch {
+    pub name: String,
+    pub header: u64,
+    pub payload: u64,
+}
+
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} has {} bytes in the payload, but {} bytes in the header",
+            self.name, self.payload, self.header
+        )
+    }
+}
+
+impl<T: 'static + Read + Seek> RPMFile<T> {
+    /// Compares payload members with the header file list, the whole payload is decompressed
+    pub fn check_payload_manifest(&mut self) -> io::Result<ManifestReport> {
+        let files = header_files(&self.header_tags);
+        let mut missing: HashMap<&str, u64> = files
+            .iter()
+            .map(|(name, size, _)| (name.as_str(), *size))
+            .collect();
+        let mut report = ManifestReport::default();
+
+        let mut reader = self.uncompress_reader()?;
+        loop {
+            let entry = FileEntry::read(&mut reader)?;
+            if entry.name == TRAILER {
+                break;
+            }
+            skip_entry_data(&mut reader, &entry)?;
+
+            let name = normalize_path(&entry.name);
+            let size = match missing.remove(name.as_str()) {
+                Some(size) => size,
+                None => {
+                    report.payload_only.push(name);
+                    continue;
+                }
+            };
+            // members of a hardlink group carry no data, except the last one
+            let payload = u64::from(entry.file_size);
+            let linked = entry.is_hardlink() && payload == 0;
+            if entry.is_file() && !linked && payload != size {
+                report.size_mismatches.push(SizeMismatch {
+                    name,
+                    header: size,
+                    payload,
+                });
+            }
+        }
+
+        report.header_only = files
+            .iter()
+            .filter(|(name, _, flags)| {
+                !flags.contains(FileFlags::GHOST) && missing.contains_key(name.as_str())
+            })
+            .map(|(name, _, _)| name.clone())
+            .collect();
+        Ok(report)
+    }
+}
+
+/// Paths, sizes and flags of the header file list
+pub(super) fn header_files(tags: &Tags<Tag>) -> Vec<(String, u64, FileFlags)> {
+    let sizes = file_sizes(tags);
+    let flags = tags.get_as_u32_array_or(Tag::FileFlags);
+
+    file_names(tags)
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            (
+                normalize_path(&name),
+                sizes.get(i).copied().unwrap_or_default(),
+                FileFlags::from_bits_truncate(flags.get(i).copied().unwrap_or_default()),
+            )
+        })
+        .collect()
+}
+
+/// Name-version-release of the package, then the same with the epoch
+fn package_names(tags: &Tags<Tag>) -> Vec<String> {
+    let name = tags.get_as_string_or(Tag::Name);
+    let version = tags.get_as_string_or(Tag::Version);
+    let release = tags.get_as_string_or(Tag::Release);
+
+    let mut names = vec![format!("{}-{}-{}", name, version, release)];
+    if let Ok(epoch) = tags.try_get_as_u32(Tag::Epoch) {
+        names.push(format!("{}-{}:{}-{}", name, epoch, version, release));
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::RType;
+    use crate::RPMBuilder;
+    use std::io::Cursor;
+    use std::path::Path;
+    #[cfg(feature = "gzip")]
+    use {
+        crate::payload::{read_entry, CpioWriter},
+        flate2::write::GzEncoder,
+        std::io::Write,
+    };
+
+    fn hello() -> io::Result<RPMFile<Cursor<Vec<u8>>>> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        Ok(RPMFile::read(Cursor::new(std::fs::read(path)?))?)
+    }
+
+    #[test]
+    fn test_lead_consistency() -> io::Result<()> {
+        assert_eq!(hello()?.check_lead_consistency(), vec![]);
+
+        // source type and an unrelated name written into the lead bytes
+        let mut bytes = hello()?.file.into_inner();
+        bytes[7] = 1;
+        bytes[10..21].copy_from_slice(b"other-2.0-1");
+        let rpm = RPMFile::read(Cursor::new(bytes))?;
+        assert_eq!(
+            rpm.check_lead_consistency(),
+            vec![
+                LeadDiscrepancy::Type {
+                    lead: Type::Source,
+                    header: Type::Binary
+                },
+                LeadDiscrepancy::Name {
+                    lead: "other-2.0-1".to_owned(),
+                    header: "hello-1.0-1".to_owned()
+                },
+            ]
+        );
+
+        let mut rpm = hello()?;
+        rpm.header_tags
+            .insert(Tag::Arch, RType::String("aarch64".to_owned()));
+        assert_eq!(
+            rpm.check_lead_consistency(),
+            vec![LeadDiscrepancy::Arch {
+                lead: 1,
+                header: "aarch64".to_owned()
+            }]
+        );
+
+        // names longer than the field are truncated
+        let long_name = "x".repeat(70);
+        let rpm = RPMBuilder::new()
+            .package_name(&long_name)
+            .version("1.0")
+            .arch("x86_64")
+            .filename(tempfile::tempdir()?.path().join("long.rpm"))
+            .build()?;
+        assert_eq!(rpm.lead.name(), &long_name[..65]);
+        assert_eq!(rpm.check_lead_consistency(), vec![]);
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_payload_manifest() -> io::Result<()> {
+        assert_eq!(
+            hello()?.check_payload_manifest()?,
+            ManifestReport::default()
+        );
+
+        // the payload is rebuilt with a longer README and an added member
+        let mut rpm = hello()?;
+        let mut cpio = Vec::new();
+        rpm.uncompress_reader()?.read_to_end(&mut cpio)?;
+        let mut reader = Cursor::new(cpio);
+        let mut payload = Vec::new();
+        loop {
+            let mut data = Vec::new();
+            let (mut entry, _) = read_entry(&mut reader, &mut data)?;
+            if entry.name == TRAILER {
+                break;
+            }
+            if entry.name.ends_with("/README") {
+                data.extend_from_slice(b"more");
+                entry.file_size = data.len() as u32;
+            }
+            payload.write_cpio_record(entry, &mut data.as_slice())?;
+        }
+        let extra = FileEntry {
+            name: "./usr/share/hello/extra".to_owned(),
+            mode: 0o100_644,
+            nlink: 1,
+            file_size: 5,
+            ..Default::default()
+        };
+        payload.write_cpio_record(extra, &mut b"extra".as_ref())?;
+        payload.cpio_close()?;
+
+        let mut bytes = rpm.file.into_inner();
+        bytes.truncate(rpm.payload_offset as usize);
+        let mut encoder = GzEncoder::new(bytes, Default::default());
+        encoder.write_all(&payload)?;
+        let mut rpm = RPMFile::read(Cursor::new(encoder.finish()?))?;
+
+        let report = rpm.check_payload_manifest()?;
+        assert!(!report.is_consistent());
+        assert_eq!(report.payload_only, vec!["/usr/share/hello/extra"]);
+        assert!(report.header_only.is_empty());
+        assert_eq!(
+            report.size_mismatches,
+            vec![SizeMismatch {
+                name: "/usr/share/doc/hello/README".to_owned(),
+                header: 25,
+                payload: 29,
+            }]
+        );
+
+        // %ghost files are expected only in the header
+        rpm.header_tags
+            .insert(Tag::FileFlags, RType::Int32Array(vec![0; 7]));
+        let report = rpm.check_payload_manifest()?;
+        assert_eq!(report.header_only, vec!["/var/log/hello.log"]);
+        Ok(())
+    }
+}
@@ -1,7 +1,35 @@
 mod builder;
+mod changelog;
+mod compression;
+mod dependency;
+#[cfg(feature = "tokio")]
+mod extract_async;
 mod file;
+mod files;
 mod info;
+mod leads;
+mod lint;
+mod nevra;
+mod query;
+mod scriptlets;
+mod split;
+mod tags;
+mod verify;
 
 pub use builder::*;
+pub use changelog::*;
+pub use compression::*;
+pub use dependency::*;
+#[cfg(feature = "tokio")]
+pub use extract_async::*;
 pub use file::*;
+pub use files::*;
 pub use info::*;
+pub use leads::*;
+pub use lint::*;
+pub use nevra::*;
+pub use query::*;
+pub use scriptlets::*;
+pub use split::*;
+pub use tags::*;
+pub use verify::*;
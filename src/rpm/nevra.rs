@@ -0,0 +1,218 @@
+use std::fmt;
+
+use super::info::RPMInfo;
+use super::tags::HeaderTags;
+use crate::header::Tag;
+
+/// Architectures recognized at the end of a package name, other suffixes are part of the release
+const ARCHES: &[&str] = &[
+    "noarch",
+    "src",
+    "nosrc",
+    "x86_64",
+    "i386",
+    "i486",
+    "i586",
+    "i686",
+    "athlon",
+    "aarch64",
+    "armv7hl",
+    "armv7l",
+    "armv6hl",
+    "ppc",
+    "ppc64",
+    "ppc64le",
+    "s390",
+    "s390x",
+    "riscv64",
+    "loongarch64",
+    "mips",
+    "mipsel",
+    "mips64",
+    "mips64el",
+    "ia64",
+    "sparc64",
+    "alpha",
+];
+
+/// Package identity, shown as `name-[epoch:]version-release[.arch]`, epoch 0 is omitted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Nevra {
+    pub name: String,
+    pub epoch: u32,
+    pub version: String,
+    pub release: String,
+    /// Empty if the parsed string has no known architecture suffix
+    pub arch: String,
+}
+
+impl Nevra {
+    /// Parses `name-[epoch:]version-release[.arch]`, the epoch may also prefix the name as
+    /// `epoch:name-version-release`. Version and release are split from the right,
+    /// so the name may contain dashes.
+    pub fn parse(nevra: &str) -> Option<Self> {
+        let (rest, arch) = match nevra.rsplit_once('.') {
+            Some((rest, arch)) if ARCHES.contains(&arch) => (rest, arch),
+            _ => (nevra, ""),
+        };
+        let (rest, release) = rest.rsplit_once('-')?;
+        let (name, version) = rest.rsplit_once('-')?;
+
+        let (epoch, version) = match version.split_once(':') {
+            Some((epoch, version)) => (Some(epoch), version),
+            None => (None, version),
+        };
+        let (epoch, name) = match (epoch, name.split_once(':')) {
+            (None, Some((epoch, name))) => (Some(epoch), name),
+            (epoch, _) => (epoch, name),
+        };
+        let epoch = match epoch {
+            Some(epoch) => epoch.parse().ok()?,
+            None => 0,
+        };
+
+        if [name, version, release].iter().any(|part| part.is_empty()) {
+            return None;
+        }
+        Some(Nevra {
+            name: name.to_owned(),
+            epoch,
+            version: version.to_owned(),
+            release: release.to_owned(),
+            arch: arch.to_owned(),
+        })
+    }
+
+    /// `name-version-release`, as rpm writes into the lead
+    pub fn nvr(&self) -> String {
+        format!("{}-{}-{}", self.name, self.version, self.release)
+    }
+
+    /// `name-version-release.arch`, the file name of a package without `.rpm`
+    pub fn nvra(&self) -> String {
+        format!("{}.{}", self.nvr(), self.arch)
+    }
+}
+
+impl fmt::Display for Nevra {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-", self.name)?;
+        if self.epoch > 0 {
+            write!(f, "{}:", self.epoch)?;
+        }
+        write!(f, "{}-{}", self.version, self.release)?;
+        if !self.arch.is_empty() {
+            write!(f, ".{}", self.arch)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&RPMInfo> for Nevra {
+    fn from(info: &RPMInfo) -> Self {
+        Nevra {
+            name: info.name.clone(),
+            epoch: info.epoch,
+            version: info.version.clone(),
+            release: info.release.clone(),
+            arch: info.arch.clone(),
+        }
+    }
+}
+
+impl From<&HeaderTags> for Nevra {
+    fn from(tags: &HeaderTags) -> Self {
+        Nevra {
+            name: tags.get_as_string_or(Tag::Name),
+            epoch: tags
+                .get_ref(Tag::Epoch)
+                .and_then(|epoch| epoch.as_u32())
+                .unwrap_or_default(),
+            version: tags.get_as_string_or(Tag::Version),
+            release: tags.get_as_string_or(Tag::Release),
+            arch: tags.get_as_string_or(Tag::Arch),
+        }
+    }
+}
+
+impl RPMInfo {
+    /// `name-[epoch:]version-release.arch`, e.g. `bash-5.2.15-3.fc38.x86_64`
+    pub fn nevra(&self) -> String {
+        Nevra::from(self).to_string()
+    }
+
+    pub fn nvr(&self) -> String {
+        Nevra::from(self).nvr()
+    }
+
+    pub fn nvra(&self) -> String {
+        Nevra::from(self).nvra()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nevra(name: &str, epoch: u32, version: &str, release: &str, arch: &str) -> Nevra {
+        Nevra {
+            name: name.to_owned(),
+            epoch,
+            version: version.to_owned(),
+            release: release.to_owned(),
+            arch: arch.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let bash = nevra("bash", 0, "5.2.15", "3.fc38", "x86_64");
+        assert_eq!(
+            Nevra::parse("bash-0:5.2.15-3.fc38.x86_64"),
+            Some(bash.clone())
+        );
+        assert_eq!(
+            Nevra::parse("bash-5.2.15-3.fc38.x86_64"),
+            Some(bash.clone())
+        );
+        assert_eq!(bash.to_string(), "bash-5.2.15-3.fc38.x86_64");
+
+        let name = nevra("python3-rpm-macros", 0, "3.11", "10.fc38", "noarch");
+        assert_eq!(
+            Nevra::parse("python3-rpm-macros-3.11-10.fc38.noarch"),
+            Some(name)
+        );
+
+        let epoch = nevra("perl-IO-Compress", 2, "2.201", "3.el9", "");
+        assert_eq!(
+            Nevra::parse("perl-IO-Compress-2:2.201-3.el9"),
+            Some(epoch.clone())
+        );
+        assert_eq!(
+            Nevra::parse("2:perl-IO-Compress-2.201-3.el9"),
+            Some(epoch.clone())
+        );
+        assert_eq!(epoch.to_string(), "perl-IO-Compress-2:2.201-3.el9");
+        assert_eq!(epoch.nvr(), "perl-IO-Compress-2.201-3.el9");
+
+        assert_eq!(Nevra::parse("bash-5.2.15"), None);
+        assert_eq!(Nevra::parse("bash-x:5.2.15-3"), None);
+        assert_eq!(Nevra::parse("-5.2.15-3"), None);
+    }
+
+    #[test]
+    fn test_info_nevra() {
+        let info = RPMInfo {
+            name: "hello-world".to_owned(),
+            epoch: 1,
+            version: "1.0".to_owned(),
+            release: "1".to_owned(),
+            arch: "noarch".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(info.nevra(), "hello-world-1:1.0-1.noarch");
+        assert_eq!(info.nvr(), "hello-world-1.0-1");
+        assert_eq!(info.nvra(), "hello-world-1.0-1.noarch");
+        assert_eq!(Nevra::parse(&info.nevra()), Some(Nevra::from(&info)));
+    }
+}
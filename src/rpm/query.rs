@@ -0,0 +1,270 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use super::tags::file_names;
+use crate::header::{RType, Tag, Tags};
+
+/// Shown for tags which are not in the header, as rpm does
+const NONE: &str = "(none)";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryFormatError {
+    /// The tag name is not known, the position is the byte offset of its `%`
+    UnknownTag { name: String, position: usize },
+    /// A `%{` or `[` is not closed
+    Unterminated { position: usize },
+}
+
+impl fmt::Display for QueryFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryFormatError::UnknownTag { name, position } => write!(
+                f,
+                "Error: unknown tag {} in query format at position {}",
+                name, position
+            ),
+            QueryFormatError::Unterminated { position } => write!(
+                f,
+                "Error: unterminated query format at position {}",
+                position
+            ),
+        }
+    }
+}
+
+impl Error for QueryFormatError {}
+
+impl From<QueryFormatError> for io::Error {
+    fn from(e: QueryFormatError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Literal(String),
+    Tag {
+        tag: Tag,
+        width: Option<i32>,
+    },
+    /// `[...]` is repeated for each element of the array tags inside
+    Array(Vec<Token>),
+}
+
+/// Formats header tags as `rpm -q --queryformat` does: `%{NAME}` is replaced with the tag
+/// value, `%-10{NAME}` pads it, `[%{FILENAMES}\n]` is repeated for each array element.
+/// Arrays outside of `[...]` are joined with commas.
+pub fn format_query(tags: &Tags<Tag>, template: &str) -> Result<String, QueryFormatError> {
+    let mut parser = Parser {
+        chars: template.char_indices().peekable(),
+    };
+    let tokens = parser.parse(None)?;
+
+    let mut output = String::new();
+    render(tags, &tokens, None, &mut output);
+    Ok(output)
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Tokens up to the end of the template, or up to `]` of the array started at `open`
+    fn parse(&mut self, open: Option<usize>) -> Result<Vec<Token>, QueryFormatError> {
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+
+        while let Some((position, c)) = self.chars.next() {
+            match c {
+                '\\' => literal.push(match self.chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, 'r')) => '\r',
+                    Some((_, c)) => c,
+                    None => '\\',
+                }),
+                '%' if self.chars.peek().map(|&(_, c)| c) == Some('%') => {
+                    self.chars.next();
+                    literal.push('%');
+                }
+                '%' => {
+                    push_literal(&mut tokens, &mut literal);
+                    tokens.push(self.parse_tag(position)?);
+                }
+                '[' => {
+                    push_literal(&mut tokens, &mut literal);
+                    tokens.push(Token::Array(self.parse(Some(position))?));
+                }
+                ']' if open.is_some() => {
+                    push_literal(&mut tokens, &mut literal);
+                    return Ok(tokens);
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if let Some(position) = open {
+            return Err(QueryFormatError::Unterminated { position });
+        }
+        push_literal(&mut tokens, &mut literal);
+        Ok(tokens)
+    }
+
+    /// Reads the optional width and the `{NAME}` after `%`
+    fn parse_tag(&mut self, position: usize) -> Result<Token, QueryFormatError> {
+        let mut width = String::new();
+        while let Some(&(_, c)) = self.chars.peek() {
+            if c != '-' && !c.is_ascii_digit() {
+                break;
+            }
+            width.push(c);
+            self.chars.next();
+        }
+        if !matches!(self.chars.next(), Some((_, '{'))) {
+            return Err(QueryFormatError::Unterminated { position });
+        }
+
+        let mut name = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '}')) => break,
+                Some((_, c)) => name.push(c),
+                None => return Err(QueryFormatError::Unterminated { position }),
+            }
+        }
+
+        let tag = Tag::from_name(&name).ok_or(QueryFormatError::UnknownTag { name, position })?;
+        Ok(Token::Tag {
+            tag,
+            width: width.parse().ok(),
+        })
+    }
+}
+
+fn push_literal(tokens: &mut Vec<Token>, literal: &mut String) {
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(std::mem::take(literal)));
+    }
+}
+
+fn render(tags: &Tags<Tag>, tokens: &[Token], element: Option<usize>, output: &mut String) {
+    for token in tokens {
+        match token {
+            Token::Literal(text) => output.push_str(text),
+            Token::Tag { tag, width } => {
+                let values = values(tags, *tag);
+                let value = match (element, values.len()) {
+                    (_, 0) => NONE.to_owned(),
+                    // scalars are repeated for each element
+                    (_, 1) => values[0].clone(),
+                    (Some(i), _) => values.get(i).map_or(NONE, String::as_str).to_owned(),
+                    (None, _) => values.join(","),
+                };
+                match width {
+                    Some(width) if *width < 0 => {
+                        output.push_str(&format!("{:<1$}", value, width.unsigned_abs() as usize))
+                    }
+                    Some(width) => output.push_str(&format!("{:>1$}", value, *width as usize)),
+                    None => output.push_str(&value),
+                }
+            }
+            Token::Array(inner) => {
+                for i in 0..array_size(tags, inner) {
+                    render(tags, inner, Some(i), output);
+                }
+            }
+        }
+    }
+}
+
+/// Number of iterations of an array, the size of the longest array tag inside it
+fn array_size(tags: &Tags<Tag>, tokens: &[Token]) -> usize {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Literal(_) => 0,
+            Token::Tag { tag, .. } => values(tags, *tag).len(),
+            Token::Array(inner) => array_size(tags, inner),
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Tag value as strings, one for each array element. FileNames is computed as rpm does.
+fn values(tags: &Tags<Tag>, tag: Tag) -> Vec<String> {
+    if tag == Tag::FileNames {
+        return file_names(tags);
+    }
+    match tags.get_ref(tag) {
+        None => Vec::new(),
+        Some(RType::Bin(bytes)) => vec![hex::encode(bytes)],
+        Some(RType::CharArray(a)) => a.iter().map(|&c| char::from(c).to_string()).collect(),
+        Some(RType::Int8Array(a)) => a.iter().map(ToString::to_string).collect(),
+        Some(RType::Int16Array(a)) => a.iter().map(ToString::to_string).collect(),
+        Some(RType::Int32Array(a)) => a.iter().map(ToString::to_string).collect(),
+        Some(RType::Int64Array(a)) => a.iter().map(ToString::to_string).collect(),
+        Some(RType::StringArray(a)) => a.clone(),
+        Some(value) => vec![value.as_string().unwrap_or_default()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpm::HeaderTags;
+    use crate::RPMFile;
+    use std::path::Path;
+
+    fn hello() -> io::Result<HeaderTags> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        Ok(RPMFile::open(path)?.header_tags)
+    }
+
+    #[test]
+    fn test_format_query() -> io::Result<()> {
+        assert_eq!(Tag::from_name("requirename"), Some(Tag::RequireName));
+        assert_eq!(Tag::from_name("Other"), None);
+
+        let tags = hello()?;
+        assert_eq!(
+            format_query(&tags, r"%{NAME}-%{version}-%{RPMTAG_RELEASE}.%{ARCH}\n")?,
+            "hello-1.0-1.noarch\n"
+        );
+        assert_eq!(format_query(&tags, "[%{NAME} ]100%%")?, "hello 100%");
+        assert_eq!(
+            format_query(&tags, "%-7{NAME}|%7{ARCH}|")?,
+            "hello  | noarch|"
+        );
+        assert_eq!(format_query(&tags, "%{ICON}")?, "(none)");
+
+        let names = file_names(&tags);
+        let listing = format_query(&tags, r"[%{FILENAMES} %{FILEUSERNAME}\n]")?;
+        assert_eq!(listing.lines().count(), names.len());
+        assert!(listing.contains("/etc/hello.conf root\n"));
+        assert_eq!(format_query(&tags, "%{FILENAMES}")?, names.join(","));
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_query_errors() -> io::Result<()> {
+        let tags = hello()?;
+        assert_eq!(
+            format_query(&tags, "%{NAME}-%{NOSUCHTAG}"),
+            Err(QueryFormatError::UnknownTag {
+                name: "NOSUCHTAG".to_owned(),
+                position: 8
+            })
+        );
+        assert_eq!(
+            format_query(&tags, "%{NAME"),
+            Err(QueryFormatError::Unterminated { position: 0 })
+        );
+        assert_eq!(
+            format_query(&tags, "x[%{NAME}"),
+            Err(QueryFormatError::Unterminated { position: 1 })
+        );
+        Ok(())
+    }
+}
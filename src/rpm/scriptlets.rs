@@ -0,0 +1,211 @@
+use bitflags::bitflags;
+use std::fmt;
+
+use super::file::RPMFile;
+use crate::header::{RType, Tag, Tags};
+
+bitflags! {
+    pub struct ScriptFlags: u32 {
+        // macros in the script are expanded
+        const EXPAND = 1;
+        // the script is a query format
+        const QFORMAT = 1 << 1;
+        // a failure stops the transaction
+        const CRITICAL = 1 << 2;
+    }
+}
+
+/// Script with the interpreter it is run by
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scriptlet {
+    /// Empty if only the program is run, e.g. `%post -p /sbin/ldconfig`
+    pub script: String,
+    /// Interpreter with its arguments, `/bin/sh` if the package does not set it
+    pub interpreter: String,
+    pub flags: u32,
+}
+
+impl Scriptlet {
+    pub fn script_flags(&self) -> ScriptFlags {
+        ScriptFlags::from_bits_truncate(self.flags)
+    }
+
+    fn from_tags(tags: &Tags<Tag>, script: Tag, prog: Tag, flags: Tag) -> Option<Self> {
+        let script = tags.get_ref(script).and_then(|value| value.as_string());
+        let interpreter = match (tags.get_ref(prog), &script) {
+            (Some(RType::StringArray(args)), _) => args.join(" "),
+            (Some(value), _) => value.as_string().unwrap_or_default(),
+            (None, Some(_)) => "/bin/sh".to_owned(),
+            (None, None) => return None,
+        };
+        let script = script.unwrap_or_default();
+        Some(Scriptlet {
+            script,
+            interpreter,
+            flags: tags
+                .get_ref(flags)
+                .and_then(|value| value.as_u32())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Install and transaction scripts of a package
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scriptlets {
+    pub pre_install: Option<Scriptlet>,
+    pub post_install: Option<Scriptlet>,
+    pub pre_uninstall: Option<Scriptlet>,
+    pub post_uninstall: Option<Scriptlet>,
+    pub pre_trans: Option<Scriptlet>,
+    pub post_trans: Option<Scriptlet>,
+    /// Run by `rpm -V` after the files are verified
+    pub verify_script: Option<Scriptlet>,
+}
+
+impl Scriptlets {
+    /// Scripts with their rpm names, in the order of `rpm -q --scripts`
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &Scriptlet)> {
+        vec![
+            ("preinstall", &self.pre_install),
+            ("postinstall", &self.post_install),
+            ("preuninstall", &self.pre_uninstall),
+            ("postuninstall", &self.post_uninstall),
+            ("pretrans", &self.pre_trans),
+            ("posttrans", &self.post_trans),
+            ("verify", &self.verify_script),
+        ]
+        .into_iter()
+        .filter_map(|(name, scriptlet)| scriptlet.as_ref().map(|s| (name, s)))
+    }
+}
+
+impl From<&Tags<Tag>> for Scriptlets {
+    fn from(tags: &Tags<Tag>) -> Self {
+        let scriptlet = |script, prog, flags| Scriptlet::from_tags(tags, script, prog, flags);
+        Scriptlets {
+            pre_install: scriptlet(Tag::PreIn, Tag::PreInProg, Tag::PreInFlags),
+            post_install: scriptlet(Tag::PostIn, Tag::PostInProg, Tag::PostInFlags),
+            pre_uninstall: scriptlet(Tag::PreUn, Tag::PreUnProg, Tag::PreUnFlags),
+            post_uninstall: scriptlet(Tag::PostUn, Tag::PostUnProg, Tag::PostUnFlags),
+            pre_trans: scriptlet(Tag::PreTrans, Tag::PreTransProg, Tag::PreTransFlags),
+            post_trans: scriptlet(Tag::PostTrans, Tag::PostTransProg, Tag::PosttransFlags),
+            verify_script: scriptlet(
+                Tag::VerifyScript,
+                Tag::VerifyScriptProg,
+                Tag::VerifyScriptFlags,
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Scriptlets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, scriptlet) in self.iter() {
+            if scriptlet.script.is_empty() {
+                writeln!(f, "{} program: {}", name, scriptlet.interpreter)?;
+                continue;
+            }
+            writeln!(f, "{} scriptlet (using {}):", name, scriptlet.interpreter)?;
+            writeln!(f, "{}", scriptlet.script.trim_end_matches('\n'))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> RPMFile<T> {
+    pub fn scriptlets(&self) -> Scriptlets {
+        self.header_tags.scriptlets()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpm::RPMBuilder;
+    use std::io;
+
+    #[test]
+    fn test_trans_scriptlets_round_trip() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let pre = dir.path().join("pretrans.sh");
+        let post = dir.path().join("posttrans.lua");
+        std::fs::write(&pre, "echo pretrans\n")?;
+        std::fs::write(&post, "print(\"posttrans\")\n")?;
+
+        let path = dir.path().join("scripts.rpm");
+        let mut rpm = RPMBuilder::new()
+            .package_name("scripts")
+            .version("1.0")
+            .pre_trans(pre.to_str().unwrap())
+            .pre_trans_prog("/bin/bash -e")
+            .post_trans(post.to_str().unwrap())
+            .post_trans_prog("<lua>")
+            .filename(&path)
+            .build()?;
+        rpm.write_head()?;
+        drop(rpm);
+
+        let scriptlets = RPMFile::open(&path)?.scriptlets();
+        assert_eq!(
+            scriptlets.pre_trans,
+            Some(Scriptlet {
+                script: "echo pretrans\n".to_owned(),
+                interpreter: "/bin/bash -e".to_owned(),
+                flags: 0,
+            })
+        );
+        assert_eq!(scriptlets.post_trans.as_ref().unwrap().interpreter, "<lua>");
+        assert!(scriptlets.pre_install.is_none());
+        assert_eq!(
+            scriptlets.to_string(),
+            "pretrans scriptlet (using /bin/bash -e):\n\
+             echo pretrans\n\
+             posttrans scriptlet (using <lua>):\n\
+             print(\"posttrans\")\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_scriptlet_round_trip() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let script = dir.path().join("verify.sh");
+        std::fs::write(&script, "test -f /etc/hello.conf\n")?;
+
+        let path = dir.path().join("verify.rpm");
+        let mut rpm = RPMBuilder::new()
+            .package_name("verify")
+            .version("1.0")
+            .verify_script(script.to_str().unwrap(), "/bin/sh")
+            .verify_script_flags(ScriptFlags::EXPAND | ScriptFlags::CRITICAL)
+            .filename(&path)
+            .build()?;
+        rpm.write_head()?;
+        drop(rpm);
+
+        let scriptlets = RPMFile::open(&path)?.scriptlets();
+        let verify = scriptlets.verify_script.as_ref().unwrap();
+        assert_eq!(verify.script, "test -f /etc/hello.conf\n");
+        assert_eq!(
+            verify.script_flags(),
+            ScriptFlags::EXPAND | ScriptFlags::CRITICAL
+        );
+        assert_eq!(
+            scriptlets.to_string(),
+            "verify scriptlet (using /bin/sh):\ntest -f /etc/hello.conf\n"
+        );
+
+        // %post -p /sbin/ldconfig has only the program
+        let mut tags = Tags::<Tag>::new();
+        tags.insert(Tag::PostInProg, RType::String("/sbin/ldconfig".to_owned()));
+        let scriptlets = Scriptlets::from(&tags);
+        assert_eq!(scriptlets.post_install.as_ref().unwrap().script, "");
+        assert!(scriptlets.pre_install.is_none());
+        assert_eq!(
+            scriptlets.to_string(),
+            "postinstall program: /sbin/ldconfig\n"
+        );
+        Ok(())
+    }
+}
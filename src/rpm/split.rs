@@ -0,0 +1,140 @@
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::RPMFile;
+use crate::header::{HeaderLead, Tag};
+use crate::lead::Lead;
+use crate::utils::align_n_bytes;
+
+const LEAD_SIZE: usize = 96;
+
+const LEAD_FILE: &str = "lead.bin";
+const SIGNATURE_FILE: &str = "signature.hdr";
+const HEADER_FILE: &str = "main.hdr";
+const PAYLOAD_FILE: &str = "payload.cpio";
+
+/// Writes lead, signature header, main header and compressed payload of a package
+/// into separate files, the signature padding is dropped and restored by `join`
+pub fn split<T: Read + Seek>(rpm: &mut RPMFile<T>, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let reader = &mut rpm.file;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut lead = vec![0_u8; LEAD_SIZE];
+    reader.read_exact(&mut lead)?;
+    let signature = read_header_blob(reader)?;
+    let padding = align_n_bytes(signature.len() as u32, 8);
+    reader.seek(SeekFrom::Current(padding.into()))?;
+    let header = read_header_blob(reader)?;
+
+    fs::write(dir.join(LEAD_FILE), lead)?;
+    fs::write(dir.join(SIGNATURE_FILE), signature)?;
+    fs::write(dir.join(HEADER_FILE), header)?;
+
+    let compressor = rpm
+        .header_tags
+        .get_value(Tag::PayloadCompressor)
+        .and_then(|value| value.as_string());
+    let mut payload = File::create(dir.join(payload_name(compressor.as_deref())))?;
+    io::copy(&mut rpm.file, &mut payload)?;
+    Ok(())
+}
+
+/// Reassembles a package from files written by `split`, returns the package size
+pub fn join(dir: &Path, out: &Path) -> io::Result<u64> {
+    let lead = fs::read(dir.join(LEAD_FILE))?;
+    Lead::read(&mut Cursor::new(&lead))?;
+    let signature = fs::read(dir.join(SIGNATURE_FILE))?;
+    HeaderLead::read(&mut signature.as_slice())?;
+    let header = fs::read(dir.join(HEADER_FILE))?;
+    HeaderLead::read(&mut header.as_slice())?;
+    let padding = vec![0_u8; align_n_bytes(signature.len() as u32, 8) as usize];
+
+    let mut writer = File::create(out)?;
+    writer.write_all(&lead)?;
+    writer.write_all(&signature)?;
+    writer.write_all(&padding)?;
+    writer.write_all(&header)?;
+
+    let mut payload = File::open(find_payload(dir)?)?;
+    let size = io::copy(&mut payload, &mut writer)?;
+    writer.flush()?;
+
+    Ok((lead.len() + signature.len() + padding.len() + header.len()) as u64 + size)
+}
+
+/// Reads a header together with its lead, index and store
+fn read_header_blob<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut blob = vec![0_u8; 16];
+    reader.read_exact(&mut blob)?;
+    let lead = HeaderLead::read(&mut blob.as_slice())?;
+
+    let size = lead.nindex * 16 + lead.hsize as usize;
+    reader.take(size as u64).read_to_end(&mut blob)?;
+    if blob.len() != 16 + size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Error: header is truncated",
+        ));
+    }
+    Ok(blob)
+}
+
+fn payload_name(compressor: Option<&str>) -> String {
+    let extension = match compressor {
+        Some("gzip") => "gz",
+        Some("bzip2") => "bz2",
+        Some("zstd") => "zst",
+        Some("xz") => "xz",
+        Some("lzma") => "lzma",
+        _ => return PAYLOAD_FILE.to_owned(),
+    };
+    format!("{}.{}", PAYLOAD_FILE, extension)
+}
+
+fn find_payload(dir: &Path) -> io::Result<PathBuf> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_payload = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(PAYLOAD_FILE));
+        if is_payload {
+            return Ok(path);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Error: no payload file in {}", dir.display()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_join() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+
+        for name in &["hello-1.0-1.noarch.rpm", "signed-key1-1.0-1.noarch.rpm"] {
+            let path = data.join(name);
+            let parts = dir.path().join(name);
+            let out = dir.path().join("joined.rpm");
+
+            split(&mut RPMFile::open(&path)?, &parts)?;
+            let size = join(&parts, &out)?;
+
+            let original = fs::read(&path)?;
+            assert_eq!(size, original.len() as u64);
+            assert!(original == fs::read(&out)?, "{} differs", name);
+        }
+        assert!(dir
+            .path()
+            .join("hello-1.0-1.noarch.rpm/payload.cpio.gz")
+            .is_file());
+        Ok(())
+    }
+}
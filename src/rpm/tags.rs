@@ -0,0 +1,328 @@
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+
+use super::changelog::ChangelogEntry;
+use super::dependency::{Dependency, DependencyKind};
+use super::nevra::Nevra;
+use super::scriptlets::Scriptlets;
+use crate::error::Result;
+use crate::header::{RType, SignatureTag, Tag, Tags};
+use crate::payload::{FileInfo, RPMPayload};
+use crate::signature::SignatureMeta;
+
+/// Full paths of the header files. BaseNames with DirNames and DirIndexes are used by
+/// rpm 4, old packages have OldFileNames and FileNames is the computed tag of rpm
+pub fn file_names(tags: &Tags<Tag>) -> Vec<String> {
+    if let Some(basenames) = tags.get_ref(Tag::BaseNames) {
+        let dirs = match tags.get_ref(Tag::DirNames) {
+            Some(RType::StringArray(dirs)) => dirs.as_slice(),
+            _ => &[],
+        };
+        let dir_indexes = tags.get_as_u32_array_or(Tag::DirIndexes);
+        let basenames = match basenames {
+            RType::StringArray(names) => names.as_slice(),
+            _ => &[],
+        };
+        return basenames
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let dir = dir_indexes
+                    .get(i)
+                    .and_then(|&index| dirs.get(index as usize))
+                    .map(String::as_str);
+                dir.unwrap_or_default().to_owned() + name
+            })
+            .collect();
+    }
+    [Tag::FileNames, Tag::OldFileNames]
+        .iter()
+        .find_map(|&tag| tags.get_ref(tag))
+        .and_then(|names| names.as_string_array())
+        .unwrap_or_default()
+}
+
+/// Sizes of the header files, LongFileSizes is used if the package has files over 4 GiB
+pub fn file_sizes(tags: &Tags<Tag>) -> Vec<u64> {
+    if tags.contains(Tag::LongFileSizes) {
+        tags.get_as_u64_array_or(Tag::LongFileSizes)
+    } else {
+        tags.get_as_u64_array_or(Tag::FileSizes)
+    }
+}
+
+/// Algorithm id of PayloadDigestAlgo or FileDigestAlgo, arrays have it first
+pub(super) fn algo_id(tags: &Tags<Tag>, tag: Tag) -> Option<u32> {
+    tags.get_ref(tag).and_then(|value| {
+        value
+            .as_u32()
+            .or_else(|| value.as_u32_array().and_then(|ids| ids.first().copied()))
+    })
+}
+
+/// Main header of a package, dereferences to the raw tags
+#[derive(Debug, Default, PartialEq)]
+pub struct HeaderTags(pub Tags<Tag>);
+
+impl HeaderTags {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// `name-[epoch:]version-release.arch`, e.g. `hello-1.0-1.noarch`
+    pub fn nevra(&self) -> String {
+        Nevra::from(self).to_string()
+    }
+
+    /// Files listed in the header
+    pub fn files(&self) -> Vec<FileInfo> {
+        let filesizes = file_sizes(self);
+        let users = self.get_as_string_array_or(Tag::FileUserName);
+        let groups = self.get_as_string_array_or(Tag::FileGroupName);
+        let flags = self.get_as_u32_array_or(Tag::FileFlags);
+        let mtimes = self.get_as_u32_array_or(Tag::FileMTimes);
+        let linknames = self.get_as_string_array_or(Tag::FileLinktos);
+        let modes = self.get_as_u16_array_or(Tag::FileModes);
+        let devices = self.get_as_u32_array_or(Tag::FileDevices);
+        let inodes = self.get_as_u32_array_or(Tag::FileInodes);
+        let digests = self.get_as_string_array_or(Tag::FileMD5s);
+        // FileMD5s has MD5 digests unless FileDigestAlgo is defined
+        let digest_algo = algo_id(self, Tag::FileDigestAlgo).unwrap_or(1);
+        let nlinks = self.get_as_u32_array_or(Tag::FileNLinks);
+        let rdevs = self.get_as_u16_array_or(Tag::FilerDevs);
+        let classes = self.get_as_u32_array_or(Tag::FileClass);
+        let class_dict = self.get_as_string_array_or(Tag::ClassDict);
+        let caps = self.get_as_string_array_or(Tag::FileCaps);
+        let verify_flags = self.get_as_u32_array_or(Tag::FileVerifyFlags);
+
+        // arrays shorter than the file names are padded with defaults, the tags are optional
+        file_names(self)
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let string = |strings: &[String]| strings.get(i).cloned().unwrap_or_default();
+                FileInfo {
+                    name,
+                    size: filesizes.get(i).copied().unwrap_or_default(),
+                    user: string(&users),
+                    group: string(&groups),
+                    flags: flags.get(i).copied().unwrap_or_default(),
+                    mtime: mtimes.get(i).copied().unwrap_or_default(),
+                    digest: string(&digests),
+                    digest_algo,
+                    mode: modes.get(i).copied().unwrap_or_default(),
+                    linkname: string(&linknames),
+                    device: devices.get(i).copied().unwrap_or_default(),
+                    inode: inodes.get(i).copied().unwrap_or_default(),
+                    nlink: nlinks.get(i).copied().unwrap_or(1),
+                    rdev: rdevs.get(i).copied().unwrap_or_default().into(),
+                    class: classes
+                        .get(i)
+                        .and_then(|&class| class_dict.get(class as usize))
+                        .cloned()
+                        .unwrap_or_default(),
+                    caps: string(&caps),
+                    // rpm verifies all attributes if the tag is missing
+                    verify_flags: verify_flags.get(i).copied().unwrap_or(u32::MAX),
+                }
+            })
+            .collect()
+    }
+
+    pub fn dependencies(&self, kind: DependencyKind) -> Vec<Dependency> {
+        let (names, flags, versions) = kind.tags();
+        Dependency::from_tags(self, names, flags, versions)
+    }
+
+    pub fn scriptlets(&self) -> Scriptlets {
+        Scriptlets::from(&self.0)
+    }
+
+    pub fn changelog(&self) -> Result<Vec<ChangelogEntry>> {
+        ChangelogEntry::from_tags(self)
+    }
+
+    /// Payload format and files, the size is stored in the signature header
+    pub fn payload_info(&self) -> Result<RPMPayload> {
+        Ok(RPMPayload {
+            size: 0,
+            long_sizes: self.contains(Tag::LongFileSizes),
+            format: self.try_get_as_string(Tag::PayloadFormat)?,
+            compressor: self.get_as_string_or(Tag::PayloadCompressor),
+            flags: self.get_as_string_or(Tag::PayloadFlags),
+            files: self.files(),
+        })
+    }
+}
+
+/// Signature header of a package, dereferences to the raw tags
+#[derive(Debug, Default, PartialEq)]
+pub struct SignatureTags(pub Tags<SignatureTag>);
+
+impl SignatureTags {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Size of the uncompressed payload, 0 if the signature header has no valid size
+    pub fn payload_size(&self) -> u64 {
+        self.get_ref(SignatureTag::LongArchiveSize)
+            .or_else(|| self.get_ref(SignatureTag::PayloadSize))
+            .and_then(|size| size.as_u64())
+            .unwrap_or_default()
+    }
+
+    /// Details of the header signature, if the package is signed
+    pub fn signature_meta(&self) -> io::Result<Option<SignatureMeta>> {
+        SignatureMeta::from_tags(self)
+    }
+}
+
+impl fmt::Display for HeaderTags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for SignatureTags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Tags<Tag>> for HeaderTags {
+    fn from(tags: Tags<Tag>) -> Self {
+        HeaderTags(tags)
+    }
+}
+
+impl From<Tags<SignatureTag>> for SignatureTags {
+    fn from(tags: Tags<SignatureTag>) -> Self {
+        SignatureTags(tags)
+    }
+}
+
+impl Deref for HeaderTags {
+    type Target = Tags<Tag>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for HeaderTags {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Deref for SignatureTags {
+    type Target = Tags<SignatureTag>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SignatureTags {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::RType;
+    use crate::RPMFile;
+    use std::path::Path;
+
+    #[test]
+    fn test_header_tags() -> io::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let rpm = RPMFile::open(path)?;
+        let tags = &rpm.header_tags;
+
+        assert_eq!(tags.nevra(), "hello-1.0-1.noarch");
+        assert_eq!(tags.files().len(), 7);
+        let files = tags.files();
+        assert_eq!(files[2].name, "/usr/bin/hi");
+        assert_eq!(
+            (files[2].linkname.as_str(), files[2].group.as_str()),
+            ("hello", "root")
+        );
+        assert!(files
+            .iter()
+            .filter(|file| file.name != "/usr/bin/hi")
+            .all(|file| file.linkname.is_empty()));
+        assert!(tags
+            .dependencies(DependencyKind::Provides)
+            .iter()
+            .any(|provide| provide.to_string() == "hello = 1.0-1"));
+        assert!(tags.scriptlets().post_install.is_some());
+        assert_eq!(tags.payload_info()?.compressor, "gzip");
+        assert!(rpm.signature_tags.payload_size() > 0);
+        assert_eq!(rpm.signature_tags.signature_meta()?, None);
+
+        let mut tags = HeaderTags::new();
+        tags.insert_name("hello".to_owned())
+            .insert_version("1.0".to_owned())
+            .insert_release("1".to_owned())
+            .insert_arch("x86_64".to_owned())
+            .insert(Tag::Epoch, RType::Int32(2))
+            .insert(Tag::ChangelogTime, RType::Int32Array(vec![1_600_000_000]))
+            .insert(
+                Tag::ChangelogName,
+                RType::StringArray(vec!["Jane Doe <jane@example.com> - 1.0-1".to_owned()]),
+            )
+            .insert(
+                Tag::ChangelogText,
+                RType::StringArray(vec!["- Initial package".to_owned()]),
+            );
+        assert_eq!(tags.nevra(), "hello-2:1.0-1.x86_64");
+
+        // missing and short file arrays are padded
+        let mut files = rpm.header_tags;
+        files.remove(Tag::FileLinktos);
+        files.insert(Tag::FileModes, RType::Int16Array(vec![0o100644]));
+        let infos = files.files();
+        assert_eq!(infos.len(), 7);
+        assert!(infos.iter().all(|file| file.linkname.is_empty()));
+        assert_eq!((infos[0].mode, infos[1].mode), (0o100644, 0));
+        assert_eq!(
+            tags.changelog()?[0].to_string(),
+            "* Sun Sep 13 2020 Jane Doe <jane@example.com> - 1.0-1\n- Initial package\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_names() {
+        let strings = |names: &[&str]| {
+            RType::StringArray(names.iter().map(|name| name.to_string()).collect())
+        };
+        let mut tags = Tags::<Tag>::new();
+        assert!(file_names(&tags).is_empty());
+
+        tags.insert(
+            Tag::OldFileNames,
+            strings(&["/etc/hello.conf", "/usr/bin/hello"]),
+        );
+        assert_eq!(file_names(&tags), vec!["/etc/hello.conf", "/usr/bin/hello"]);
+
+        tags.insert(Tag::FileNames, strings(&["/usr/bin/hello"]));
+        assert_eq!(file_names(&tags), vec!["/usr/bin/hello"]);
+
+        tags.insert(Tag::DirNames, strings(&["/etc/", "/usr/bin/"]))
+            .insert(Tag::DirIndexes, RType::Int32Array(vec![1, 0, 1]))
+            .insert(Tag::BaseNames, strings(&["hello", "hello.conf", "hi"]));
+        assert_eq!(
+            file_names(&tags),
+            vec!["/usr/bin/hello", "/etc/hello.conf", "/usr/bin/hi"]
+        );
+
+        let files = HeaderTags(tags).files();
+        assert_eq!(files[1].name, "/etc/hello.conf");
+    }
+}
@@ -0,0 +1,349 @@
+use bitflags::bitflags;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use super::info::RPMInfo;
+use crate::payload::{FileFlags, FileInfo};
+use crate::utils::{S_IFBLK, S_IFCHR, S_IFLNK, S_IFMT, S_IFREG};
+
+// https://github.com/rpm-software-management/rpm/blob/master/include/rpm/rpmfiles.h
+bitflags! {
+    /// File attributes as FileVerifyFlags stores them
+    pub struct VerifyAttrs: u32 {
+        const DIGEST = 1 << 0;
+        const SIZE = 1 << 1;
+        const LINKTO = 1 << 2;
+        const USER = 1 << 3;
+        const GROUP = 1 << 4;
+        const MTIME = 1 << 5;
+        const MODE = 1 << 6;
+        const RDEV = 1 << 7;
+        const CAPS = 1 << 8;
+    }
+}
+
+/// Letters of `rpm -V` in the order they are printed
+const ATTR_LETTERS: [(VerifyAttrs, char); 9] = [
+    (VerifyAttrs::SIZE, 'S'),
+    (VerifyAttrs::MODE, 'M'),
+    (VerifyAttrs::DIGEST, '5'),
+    (VerifyAttrs::RDEV, 'D'),
+    (VerifyAttrs::LINKTO, 'L'),
+    (VerifyAttrs::USER, 'U'),
+    (VerifyAttrs::GROUP, 'G'),
+    (VerifyAttrs::MTIME, 'T'),
+    (VerifyAttrs::CAPS, 'P'),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyStatus {
+    /// Attributes which differ from the header and which could not be checked,
+    /// both are empty if the file matches
+    Checked {
+        differs: VerifyAttrs,
+        unknown: VerifyAttrs,
+    },
+    Missing,
+    /// Ghost file which does not exist, rpm does not create ghost files
+    GhostMissing,
+}
+
+/// Result of comparing an installed or extracted file with the package header
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileVerifyResult {
+    pub name: String,
+    pub file_flags: FileFlags,
+    pub status: VerifyStatus,
+}
+
+impl FileVerifyResult {
+    pub fn is_ok(&self) -> bool {
+        match &self.status {
+            VerifyStatus::Checked { differs, unknown } => differs.is_empty() && unknown.is_empty(),
+            VerifyStatus::Missing => false,
+            VerifyStatus::GhostMissing => true,
+        }
+    }
+
+    /// Attributes in the `rpm -V` form, e.g. `S.5....T.`: a letter if the attribute differs,
+    /// `?` if it could not be checked, `.` if it matches or is not checked
+    pub fn attrs_string(&self) -> String {
+        let (differs, unknown) = match &self.status {
+            VerifyStatus::Checked { differs, unknown } => (*differs, *unknown),
+            _ => (VerifyAttrs::empty(), VerifyAttrs::empty()),
+        };
+        ATTR_LETTERS
+            .iter()
+            .map(|&(attr, letter)| {
+                if differs.contains(attr) {
+                    letter
+                } else if unknown.contains(attr) {
+                    '?'
+                } else {
+                    '.'
+                }
+            })
+            .collect()
+    }
+
+    /// File type letter of `rpm -V`, `c` for config files, `d` for documentation and so on
+    fn marker(&self) -> char {
+        [
+            (FileFlags::CONFIGURATION, 'c'),
+            (FileFlags::DOC, 'd'),
+            (FileFlags::GHOST, 'g'),
+            (FileFlags::LICENSE, 'l'),
+            (FileFlags::PUBKEY, 'P'),
+            (FileFlags::README, 'r'),
+        ]
+        .iter()
+        .find(|(flag, _)| self.file_flags.contains(*flag))
+        .map_or(' ', |&(_, marker)| marker)
+    }
+}
+
+impl fmt::Display for FileVerifyResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            VerifyStatus::Missing => write!(f, "missing   {} {}", self.marker(), self.name),
+            _ => write!(
+                f,
+                "{}  {} {}",
+                self.attrs_string(),
+                self.marker(),
+                self.name
+            ),
+        }
+    }
+}
+
+/// Compares the package files under root with the header: size, mode, digest, device number,
+/// symlink target, owner, group and mtime, as `rpm -V` does. Attributes excluded by
+/// FileVerifyFlags or not applicable to the file type are skipped, capabilities are not checked.
+pub fn verify_files(info: &RPMInfo, root: &Path) -> Vec<FileVerifyResult> {
+    info.payload
+        .files
+        .iter()
+        .map(|file| FileVerifyResult {
+            name: file.name.clone(),
+            file_flags: file.file_flags(),
+            status: verify_file(file, &root.join(file.name.trim_start_matches('/'))),
+        })
+        .collect()
+}
+
+fn verify_file(file: &FileInfo, path: &Path) -> VerifyStatus {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && file.is_ghost() => {
+            return VerifyStatus::GhostMissing
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return VerifyStatus::Missing,
+        Err(_) => {
+            return VerifyStatus::Checked {
+                differs: VerifyAttrs::empty(),
+                unknown: checked_attrs(file),
+            }
+        }
+    };
+
+    let attrs = checked_attrs(file);
+    let mut differs = VerifyAttrs::empty();
+    let mut unknown = VerifyAttrs::empty();
+    let mut check = |attr: VerifyAttrs, matches: Option<bool>| {
+        if attrs.contains(attr) {
+            match matches {
+                Some(true) => {}
+                Some(false) => differs.insert(attr),
+                None => unknown.insert(attr),
+            }
+        }
+    };
+
+    check(VerifyAttrs::SIZE, Some(metadata.len() == file.size));
+    check(
+        VerifyAttrs::MODE,
+        stat::mode(&metadata).map(|mode| mode == file.mode),
+    );
+    if attrs.contains(VerifyAttrs::DIGEST) {
+        check(VerifyAttrs::DIGEST, digest_matches(file, path));
+    }
+    check(
+        VerifyAttrs::RDEV,
+        stat::rdev(&metadata).map(|rdev| rdev == file.rdev),
+    );
+    check(
+        VerifyAttrs::LINKTO,
+        fs::read_link(path)
+            .ok()
+            .map(|target| target.to_string_lossy() == file.linkname),
+    );
+    check(
+        VerifyAttrs::USER,
+        stat::user(&metadata).map(|user| user == file.user),
+    );
+    check(
+        VerifyAttrs::GROUP,
+        stat::group(&metadata).map(|group| group == file.group),
+    );
+    check(
+        VerifyAttrs::MTIME,
+        metadata
+            .modified()
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map(|mtime| mtime.as_secs() == u64::from(file.mtime)),
+    );
+
+    VerifyStatus::Checked { differs, unknown }
+}
+
+/// Attributes of FileVerifyFlags which apply to the file type, as rpm selects them
+fn checked_attrs(file: &FileInfo) -> VerifyAttrs {
+    let mut attrs = VerifyAttrs::from_bits_truncate(file.verify_flags);
+    attrs.remove(VerifyAttrs::CAPS);
+    // only regular files have content
+    let content = VerifyAttrs::DIGEST | VerifyAttrs::SIZE | VerifyAttrs::MTIME;
+    match u32::from(file.mode) & S_IFMT {
+        S_IFREG => attrs.remove(VerifyAttrs::LINKTO | VerifyAttrs::RDEV),
+        S_IFLNK => attrs.remove(content | VerifyAttrs::MODE | VerifyAttrs::RDEV),
+        S_IFCHR | S_IFBLK => attrs.remove(content | VerifyAttrs::LINKTO),
+        _ => attrs.remove(content | VerifyAttrs::LINKTO | VerifyAttrs::RDEV),
+    }
+    // ghost files are not in the payload, their content is not known
+    if file.is_ghost() {
+        attrs.remove(content | VerifyAttrs::LINKTO | VerifyAttrs::RDEV);
+    }
+    attrs
+}
+
+/// None if the algorithm is not supported or the file can not be read
+fn digest_matches(file: &FileInfo, path: &Path) -> Option<bool> {
+    let algo = file.digest_algorithm()?;
+    let digest = algo.digest(&mut File::open(path).ok()?).ok()?;
+    Some(digest.eq_ignore_ascii_case(&file.digest))
+}
+
+#[cfg(unix)]
+mod stat {
+    use nix::unistd::{Gid, Group, Uid, User};
+    use std::fs::Metadata;
+    use std::os::unix::fs::MetadataExt;
+
+    pub fn mode(metadata: &Metadata) -> Option<u16> {
+        Some(metadata.mode() as u16)
+    }
+
+    pub fn rdev(metadata: &Metadata) -> Option<u32> {
+        Some(metadata.rdev() as u32)
+    }
+
+    /// Name of the owner, the id if the user is not known as rpm shows it
+    pub fn user(metadata: &Metadata) -> Option<String> {
+        let user = User::from_uid(Uid::from_raw(metadata.uid())).ok()?;
+        Some(user.map_or_else(|| metadata.uid().to_string(), |user| user.name))
+    }
+
+    pub fn group(metadata: &Metadata) -> Option<String> {
+        let group = Group::from_gid(Gid::from_raw(metadata.gid())).ok()?;
+        Some(group.map_or_else(|| metadata.gid().to_string(), |group| group.name))
+    }
+}
+
+#[cfg(not(unix))]
+mod stat {
+    use std::fs::Metadata;
+
+    pub fn mode(_: &Metadata) -> Option<u16> {
+        None
+    }
+
+    pub fn rdev(_: &Metadata) -> Option<u32> {
+        None
+    }
+
+    pub fn user(_: &Metadata) -> Option<String> {
+        None
+    }
+
+    pub fn group(_: &Metadata) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "gzip"))]
+mod tests {
+    use super::*;
+    use crate::payload::ExtractOptions;
+    use crate::RPMFile;
+    use std::convert::TryFrom;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_verify_files() -> crate::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/hello-1.0-1.noarch.rpm");
+        let mut info = RPMInfo::try_from(&RPMFile::open(&path)?)?;
+        let root = tempfile::tempdir()?;
+        RPMFile::open(&path)?.extract(root.path(), &ExtractOptions::default())?;
+
+        // extracted files keep the owner of the test and the umask unless run as root
+        for file in &mut info.payload.files {
+            file.verify_flags =
+                (VerifyAttrs::all() - VerifyAttrs::USER - VerifyAttrs::GROUP - VerifyAttrs::MODE)
+                    .bits();
+        }
+        let results = verify_files(&info, root.path());
+        assert_eq!(results.len(), 7);
+        assert!(results.iter().all(FileVerifyResult::is_ok), "{:?}", results);
+        let ghost = results.last().unwrap();
+        assert_eq!(ghost.status, VerifyStatus::GhostMissing);
+        assert_eq!(ghost.to_string(), ".........  g /var/log/hello.log");
+
+        let root = root.path();
+        fs::write(root.join("etc/hello.conf"), "changed = yes\n")?;
+        filetime::set_file_mtime(
+            root.join("etc/hello.conf"),
+            filetime::FileTime::from_unix_time(1_700_000_100, 0),
+        )?;
+        fs::remove_file(root.join("usr/share/doc/hello/README"))?;
+        fs::remove_file(root.join("usr/bin/hi"))?;
+        std::os::unix::fs::symlink("hello.sh", root.join("usr/bin/hi"))?;
+        // a modified size and digest is reported even if mtime is not verified
+        let hello = info
+            .payload
+            .files
+            .iter_mut()
+            .find(|file| file.name == "/usr/bin/hello")
+            .unwrap();
+        hello.verify_flags = (VerifyAttrs::all() - VerifyAttrs::DIGEST).bits();
+        fs::write(root.join("usr/bin/hello"), "#!/bin/sh\necho hello world\n")?;
+        fs::set_permissions(
+            root.join("usr/bin/hello"),
+            fs::Permissions::from_mode(0o600),
+        )?;
+
+        let lines: Vec<String> = verify_files(&info, root)
+            .iter()
+            .filter(|result| !result.is_ok())
+            .map(ToString::to_string)
+            .collect();
+        let hello = if nix::unistd::getuid().is_root() {
+            "SM.....T."
+        } else {
+            "SM...UGT."
+        };
+        assert_eq!(
+            lines,
+            vec![
+                "S.5....T.  c /etc/hello.conf".to_owned(),
+                format!("{}    /usr/bin/hello", hello),
+                "....L....    /usr/bin/hi".to_owned(),
+                "missing   d /usr/share/doc/hello/README".to_owned(),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::invalid;
+use super::packet::*;
+use crate::header::{Tag, Tags};
+
+const ARMOR_BEGIN: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+const ARMOR_END: &str = "-----END PGP PUBLIC KEY BLOCK-----";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicKey {
+    pub algorithm: PublicKeyAlgorithm,
+    pub created: u32,
+    pub key_id: KeyId,
+    pub fingerprint: Vec<u8>,
+    /// Curve OID of elliptic curve keys
+    pub curve: Option<Vec<u8>>,
+    pub user_ids: Vec<String>,
+    pub mpis: Vec<Vec<u8>>,
+}
+
+impl PublicKey {
+    /// Parses a v4 public key or subkey packet body
+    pub fn parse(body: &[u8]) -> io::Result<Self> {
+        let mut data = body;
+        let version = *data
+            .first()
+            .ok_or_else(|| invalid("Error: empty key packet"))?;
+        if version != 4 {
+            return Err(invalid(format!(
+                "Error: public key version {} is not supported",
+                version
+            )));
+        }
+        if data.len() < 6 {
+            return Err(invalid("Error: truncated public key packet"));
+        }
+        let created = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+        let algorithm = PublicKeyAlgorithm::from(data[5]);
+        data = &data[6..];
+
+        let mut curve = None;
+        let mut mpis = Vec::new();
+        if algorithm.is_ecc() {
+            let size = usize::from(
+                *data
+                    .first()
+                    .ok_or_else(|| invalid("Error: missing curve OID"))?,
+            );
+            if data.len() < size + 1 {
+                return Err(invalid("Error: truncated curve OID"));
+            }
+            curve = Some(data[1..=size].to_vec());
+            data = &data[size + 1..];
+            // ECDH keys are followed by KDF parameters, which are not MPIs
+            mpis.push(read_mpi(&mut data)?.to_vec());
+        } else {
+            while !data.is_empty() {
+                mpis.push(read_mpi(&mut data)?.to_vec());
+            }
+        }
+
+        // v4 fingerprint: SHA1 over 0x99, two-octet length and the packet body
+        let mut hasher = Sha1::new();
+        hasher.update([0x99]);
+        hasher.update((body.len() as u16).to_be_bytes());
+        hasher.update(body);
+        let fingerprint = hasher.finalize().to_vec();
+        let key_id = KeyId::from_fingerprint(&fingerprint)
+            .ok_or_else(|| invalid("Error: incorrect key fingerprint"))?;
+
+        Ok(PublicKey {
+            algorithm,
+            created,
+            key_id,
+            fingerprint,
+            curve,
+            user_ids: Vec::new(),
+            mpis,
+        })
+    }
+
+    /// Reads all primary keys and subkeys from binary OpenPGP data
+    pub fn from_bytes(data: &[u8]) -> io::Result<Vec<Self>> {
+        let mut keys: Vec<PublicKey> = Vec::new();
+        let mut user_ids = Vec::new();
+
+        for packet in read_packets(data)? {
+            match packet.tag {
+                PACKET_PUBLIC_KEY | PACKET_PUBLIC_SUBKEY => keys.push(Self::parse(packet.body)?),
+                PACKET_USER_ID => {
+                    user_ids.push(String::from_utf8_lossy(packet.body).to_string());
+                }
+                _ => {}
+            }
+        }
+
+        for key in keys.iter_mut() {
+            key.user_ids = user_ids.clone();
+        }
+        Ok(keys)
+    }
+
+    /// Reads all keys from an ASCII-armored public key block
+    pub fn from_armored(text: &str) -> io::Result<Vec<Self>> {
+        let mut keys = Vec::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find(ARMOR_BEGIN) {
+            let block = &rest[start + ARMOR_BEGIN.len()..];
+            let end = block
+                .find(ARMOR_END)
+                .ok_or_else(|| invalid("Error: unterminated armored key block"))?;
+            keys.extend(Self::from_bytes(&dearmor(&block[..end])?)?);
+            rest = &block[end + ARMOR_END.len()..];
+        }
+
+        if keys.is_empty() {
+            return Err(invalid("Error: no public keys found"));
+        }
+        Ok(keys)
+    }
+}
+
+/// A set of public keys indexed by key id and fingerprint
+#[derive(Debug, Default)]
+pub struct Keyring {
+    keys: Vec<PublicKey>,
+    by_id: HashMap<KeyId, usize>,
+    by_fingerprint: HashMap<Vec<u8>, usize>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Loads keys from a file or from every key file in a directory
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut keyring = Keyring::new();
+        if path.as_ref().is_dir() {
+            keyring.add_dir(path)?;
+        } else {
+            keyring.add_file(path)?;
+        }
+        Ok(keyring)
+    }
+
+    pub fn add_key(&mut self, key: PublicKey) -> &mut Self {
+        let position = self.keys.len();
+        self.by_id.insert(key.key_id, position);
+        self.by_fingerprint
+            .insert(key.fingerprint.clone(), position);
+        self.keys.push(key);
+        self
+    }
+
+    /// Adds keys from an armored text or binary OpenPGP data, returns number of added keys
+    pub fn add_bytes(&mut self, data: &[u8]) -> io::Result<usize> {
+        let keys = match std::str::from_utf8(data) {
+            Ok(text) if text.contains(ARMOR_BEGIN) => PublicKey::from_armored(text)?,
+            _ => PublicKey::from_bytes(data)?,
+        };
+        let count = keys.len();
+        for key in keys {
+            self.add_key(key);
+        }
+        Ok(count)
+    }
+
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<usize> {
+        let data = fs::read(path)?;
+        self.add_bytes(&data)
+    }
+
+    /// Adds keys from all armored key files in a directory, other files are ignored
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<usize> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+
+        let mut count = 0;
+        for path in paths.iter().filter(|p| p.is_file()) {
+            let data = fs::read(path)?;
+            if String::from_utf8_lossy(&data).contains(ARMOR_BEGIN) {
+                count += self.add_bytes(&data)?;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Adds keys stored in the Pubkeys tag of gpg-pubkey headers
+    pub fn add_header(&mut self, tags: &Tags<Tag>) -> io::Result<usize> {
+        let mut count = 0;
+        for key in tags.get_as_string_array_or(Tag::PubKeys) {
+            count += if key.contains(ARMOR_BEGIN) {
+                self.add_bytes(key.as_bytes())?
+            } else {
+                self.add_bytes(&dearmor(&key)?)?
+            };
+        }
+        Ok(count)
+    }
+
+    pub fn get(&self, key_id: &KeyId) -> Option<&PublicKey> {
+        self.by_id.get(key_id).map(|&i| &self.keys[i])
+    }
+
+    pub fn get_by_fingerprint(&self, fingerprint: &[u8]) -> Option<&PublicKey> {
+        self.by_fingerprint.get(fingerprint).map(|&i| &self.keys[i])
+    }
+
+    /// Selects the key that issued a signature
+    pub fn find_issuer(&self, signature: &Signature) -> Option<&PublicKey> {
+        signature
+            .issuer_fingerprint
+            .as_deref()
+            .and_then(|fingerprint| self.get_by_fingerprint(fingerprint))
+            .or_else(|| signature.issuer.and_then(|id| self.get(&id)))
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Decodes the base64 body of an armored block, skipping armor headers and the checksum
+pub(super) fn dearmor(block: &str) -> io::Result<Vec<u8>> {
+    let mut lines = block
+        .lines()
+        .map(str::trim)
+        .skip_while(|l| l.is_empty())
+        .peekable();
+
+    // armor headers are separated from the body by an empty line
+    if lines.peek().is_some_and(|l| l.contains(':')) {
+        lines.by_ref().find(|l| l.is_empty());
+    }
+
+    let encoded: String = lines.take_while(|l| !l.starts_with('=')).collect();
+    base64::decode(encoded).map_err(|e| invalid(format!("Error: incorrect armored key {}", e)))
+}
@@ -0,0 +1,24 @@
+#[cfg(feature = "signing")]
+mod keyring;
+pub mod packet;
+#[cfg(feature = "signing")]
+mod sign;
+#[cfg(feature = "signing")]
+mod verify;
+
+#[cfg(feature = "signing")]
+pub use keyring::*;
+pub use packet::*;
+#[cfg(feature = "signing")]
+pub use sign::*;
+#[cfg(feature = "signing")]
+pub use verify::*;
+
+use std::io;
+
+fn invalid<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,511 @@
+use chrono::{Local, TimeZone};
+use omnom::prelude::*;
+use std::fmt;
+use std::io::{self, Read};
+
+use super::invalid;
+use crate::header::{RType, SignatureTag, Tags};
+
+pub(super) const SUBPACKET_CREATION_TIME: u8 = 2;
+pub(super) const SUBPACKET_ISSUER: u8 = 16;
+pub(super) const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+pub const PACKET_SIGNATURE: u8 = 2;
+pub const PACKET_SECRET_KEY: u8 = 5;
+pub const PACKET_PUBLIC_KEY: u8 = 6;
+pub const PACKET_USER_ID: u8 = 13;
+pub const PACKET_PUBLIC_SUBKEY: u8 = 14;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyId(pub [u8; 8]);
+
+impl KeyId {
+    pub fn from_fingerprint(fingerprint: &[u8]) -> Option<Self> {
+        if fingerprint.len() < 8 {
+            return None;
+        }
+        let mut id = [0_u8; 8];
+        id.copy_from_slice(&fingerprint[fingerprint.len() - 8..]);
+        Some(KeyId(id))
+    }
+
+    /// Short form used by rpm, e.g. in `rpm -K` output
+    pub fn short(&self) -> String {
+        hex::encode(&self.0[4..])
+    }
+}
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PublicKeyAlgorithm {
+    Rsa,
+    Dsa,
+    EdDsa,
+    Other(u8),
+}
+
+impl PublicKeyAlgorithm {
+    /// Elliptic curve algorithms: ECDH, ECDSA and EdDSA
+    pub fn is_ecc(&self) -> bool {
+        matches!(
+            self,
+            PublicKeyAlgorithm::EdDsa
+                | PublicKeyAlgorithm::Other(18)
+                | PublicKeyAlgorithm::Other(19)
+        )
+    }
+}
+
+impl From<u8> for PublicKeyAlgorithm {
+    fn from(id: u8) -> Self {
+        match id {
+            1..=3 => PublicKeyAlgorithm::Rsa,
+            17 => PublicKeyAlgorithm::Dsa,
+            22 => PublicKeyAlgorithm::EdDsa,
+            other => PublicKeyAlgorithm::Other(other),
+        }
+    }
+}
+
+impl fmt::Display for PublicKeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublicKeyAlgorithm::Rsa => write!(f, "RSA"),
+            PublicKeyAlgorithm::Dsa => write!(f, "DSA"),
+            PublicKeyAlgorithm::EdDsa => write!(f, "EdDSA"),
+            PublicKeyAlgorithm::Other(id) => write!(f, "algorithm {}", id),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha224,
+    Other(u8),
+}
+
+impl From<u8> for HashAlgorithm {
+    fn from(id: u8) -> Self {
+        match id {
+            1 => HashAlgorithm::Md5,
+            2 => HashAlgorithm::Sha1,
+            8 => HashAlgorithm::Sha256,
+            9 => HashAlgorithm::Sha384,
+            10 => HashAlgorithm::Sha512,
+            11 => HashAlgorithm::Sha224,
+            other => HashAlgorithm::Other(other),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Md5 => write!(f, "MD5"),
+            HashAlgorithm::Sha1 => write!(f, "SHA1"),
+            HashAlgorithm::Sha256 => write!(f, "SHA256"),
+            HashAlgorithm::Sha384 => write!(f, "SHA384"),
+            HashAlgorithm::Sha512 => write!(f, "SHA512"),
+            HashAlgorithm::Sha224 => write!(f, "SHA224"),
+            HashAlgorithm::Other(id) => write!(f, "hash {}", id),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Packet<'a> {
+    pub tag: u8,
+    pub body: &'a [u8],
+}
+
+/// Splits binary OpenPGP data into packets, supporting both old and new packet formats
+pub fn read_packets(data: &[u8]) -> io::Result<Vec<Packet<'_>>> {
+    let mut packets = Vec::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let ctb = rest[0];
+        if ctb & 0x80 == 0 {
+            return Err(invalid(format!(
+                "Error: incorrect OpenPGP packet header {:#x}",
+                ctb
+            )));
+        }
+
+        let (tag, header_size, body_size) = if ctb & 0x40 == 0 {
+            let tag = (ctb >> 2) & 0x0f;
+            match ctb & 0x03 {
+                0 => (tag, 2, usize::from(*byte_at(rest, 1)?)),
+                1 => (tag, 3, usize::from((&rest[1..]).read_be::<u16>()?)),
+                2 => (tag, 5, (&rest[1..]).read_be::<u32>()? as usize),
+                _ => (tag, 1, rest.len() - 1),
+            }
+        } else {
+            let tag = ctb & 0x3f;
+            match *byte_at(rest, 1)? {
+                first @ 0..=191 => (tag, 2, usize::from(first)),
+                first @ 192..=223 => {
+                    let second = *byte_at(rest, 2)?;
+                    (
+                        tag,
+                        3,
+                        ((usize::from(first) - 192) << 8) + usize::from(second) + 192,
+                    )
+                }
+                255 => (tag, 6, (&rest[2..]).read_be::<u32>()? as usize),
+                _ => {
+                    return Err(invalid(
+                        "Error: partial OpenPGP packet lengths are not supported",
+                    ))
+                }
+            }
+        };
+
+        let end = header_size + body_size;
+        if end > rest.len() {
+            return Err(invalid("Error: truncated OpenPGP packet"));
+        }
+        packets.push(Packet {
+            tag,
+            body: &rest[header_size..end],
+        });
+        rest = &rest[end..];
+    }
+
+    Ok(packets)
+}
+
+/// Reads a multiprecision integer, returning its bytes without the bit count
+pub fn read_mpi<'a>(data: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let bits: u16 = data.read_be()?;
+    let size = usize::from(bits).div_ceil(8);
+    if size > data.len() {
+        return Err(invalid("Error: truncated OpenPGP MPI"));
+    }
+    let (mpi, rest) = data.split_at(size);
+    *data = rest;
+    Ok(mpi)
+}
+
+/// Writes a multiprecision integer, leading zeroes are dropped
+pub fn write_mpi(out: &mut Vec<u8>, value: &[u8]) {
+    let start = value.iter().position(|&b| b != 0).unwrap_or(value.len());
+    let value = &value[start..];
+    let bits = value.first().map_or(0, |&b| {
+        (value.len() - 1) * 8 + (8 - b.leading_zeros() as usize)
+    });
+    out.extend_from_slice(&(bits as u16).to_be_bytes());
+    out.extend_from_slice(value);
+}
+
+/// Writes a packet in the new format
+pub fn write_packet(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0xc0 | tag];
+    match body.len() {
+        size @ 0..=191 => packet.push(size as u8),
+        size @ 192..=8383 => {
+            let size = size - 192;
+            packet.extend_from_slice(&[(size >> 8) as u8 + 192, size as u8]);
+        }
+        size => {
+            packet.push(0xff);
+            packet.extend_from_slice(&(size as u32).to_be_bytes());
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub version: u8,
+    pub signature_type: u8,
+    pub pubkey_algorithm: PublicKeyAlgorithm,
+    pub hash_algorithm: HashAlgorithm,
+    pub created: Option<u32>,
+    pub issuer: Option<KeyId>,
+    pub issuer_fingerprint: Option<Vec<u8>>,
+    pub hash_prefix: [u8; 2],
+    pub mpis: Vec<Vec<u8>>,
+    trailer: Vec<u8>,
+}
+
+impl Signature {
+    /// Parses the first signature packet from binary OpenPGP data,
+    /// as stored in RSAHeader/DSAHeader signature tags
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        let packet = read_packets(data)?
+            .into_iter()
+            .find(|p| p.tag == PACKET_SIGNATURE)
+            .ok_or_else(|| invalid("Error: no OpenPGP signature packet found"))?;
+        Self::parse(packet.body)
+    }
+
+    pub fn parse(body: &[u8]) -> io::Result<Self> {
+        let mut data = body;
+        let version: u8 = data.read_be()?;
+
+        let mut signature = match version {
+            3 => {
+                let hashed_size: u8 = data.read_be()?;
+                if hashed_size != 5 {
+                    return Err(invalid(format!(
+                        "Error: incorrect v3 signature hashed size {}",
+                        hashed_size
+                    )));
+                }
+                let signature_type: u8 = data.read_be()?;
+                let created: u32 = data.read_be()?;
+                let mut issuer = [0_u8; 8];
+                data.read_exact(&mut issuer)?;
+                let pubkey_algorithm: u8 = data.read_be()?;
+                let hash_algorithm: u8 = data.read_be()?;
+
+                let mut trailer = vec![signature_type];
+                trailer.extend_from_slice(&created.to_be_bytes());
+
+                Signature {
+                    version,
+                    signature_type,
+                    pubkey_algorithm: pubkey_algorithm.into(),
+                    hash_algorithm: hash_algorithm.into(),
+                    created: Some(created),
+                    issuer: Some(KeyId(issuer)),
+                    issuer_fingerprint: None,
+                    hash_prefix: [0; 2],
+                    mpis: Vec::new(),
+                    trailer,
+                }
+            }
+            4 => {
+                let signature_type: u8 = data.read_be()?;
+                let pubkey_algorithm: u8 = data.read_be()?;
+                let hash_algorithm: u8 = data.read_be()?;
+                let hashed_size: u16 = data.read_be()?;
+                let hashed = take(&mut data, hashed_size.into())?;
+
+                // the hashed part of the packet followed by the final v4 trailer
+                let hashed_len = 6 + hashed.len();
+                let mut trailer = body[..hashed_len].to_vec();
+                trailer.extend_from_slice(&[4, 0xff]);
+                trailer.extend_from_slice(&(hashed_len as u32).to_be_bytes());
+
+                let mut signature = Signature {
+                    version,
+                    signature_type,
+                    pubkey_algorithm: pubkey_algorithm.into(),
+                    hash_algorithm: hash_algorithm.into(),
+                    created: None,
+                    issuer: None,
+                    issuer_fingerprint: None,
+                    hash_prefix: [0; 2],
+                    mpis: Vec::new(),
+                    trailer,
+                };
+                signature.read_subpackets(hashed)?;
+
+                let unhashed_size: u16 = data.read_be()?;
+                let unhashed = take(&mut data, unhashed_size.into())?;
+                signature.read_subpackets(unhashed)?;
+                signature
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "Error: signature version {} is not supported",
+                    version
+                )))
+            }
+        };
+
+        data.read_exact(&mut signature.hash_prefix)?;
+        while !data.is_empty() {
+            signature.mpis.push(read_mpi(&mut data)?.to_vec());
+        }
+
+        if signature.issuer.is_none() {
+            signature.issuer = signature
+                .issuer_fingerprint
+                .as_deref()
+                .and_then(KeyId::from_fingerprint);
+        }
+
+        Ok(signature)
+    }
+
+    /// Bytes appended to the signed data before hashing
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    fn read_subpackets(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let first: u8 = data.read_be()?;
+            let size = match first {
+                0..=191 => usize::from(first),
+                192..=254 => {
+                    let second: u8 = data.read_be()?;
+                    ((usize::from(first) - 192) << 8) + usize::from(second) + 192
+                }
+                255 => data.read_be::<u32>()? as usize,
+            };
+            let subpacket = take(&mut data, size)?;
+            if subpacket.is_empty() {
+                continue;
+            }
+
+            // the critical bit is irrelevant for reading metadata
+            let content = &subpacket[1..];
+            match subpacket[0] & 0x7f {
+                SUBPACKET_CREATION_TIME => self.created = Some((&content[..]).read_be()?),
+                SUBPACKET_ISSUER if content.len() == 8 => {
+                    let mut issuer = [0_u8; 8];
+                    issuer.copy_from_slice(content);
+                    self.issuer = Some(KeyId(issuer));
+                }
+                SUBPACKET_ISSUER_FINGERPRINT if content.len() > 1 => {
+                    self.issuer_fingerprint = Some(content[1..].to_vec());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Signature details which can be read without verification,
+/// as shown on the Signature line of `rpm -qi`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureMeta {
+    pub version: u8,
+    pub pubkey_algorithm: PublicKeyAlgorithm,
+    pub hash_algorithm: HashAlgorithm,
+    pub created: Option<u32>,
+    pub key_id: Option<KeyId>,
+}
+
+impl SignatureMeta {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        Signature::from_bytes(data).map(|signature| Self::from(&signature))
+    }
+
+    /// Reads the header signature, falling back to legacy header and payload signatures
+    pub fn from_tags(tags: &Tags<SignatureTag>) -> io::Result<Option<Self>> {
+        let names = [
+            SignatureTag::RSAHeader,
+            SignatureTag::DSAHeader,
+            SignatureTag::PGP,
+            SignatureTag::GPG,
+            SignatureTag::SigPGP,
+            SignatureTag::SigGPG,
+        ];
+        for &name in names.iter() {
+            if let Some(RType::Bin(data)) = tags.get_ref(name) {
+                return Self::from_bytes(data).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl From<&Signature> for SignatureMeta {
+    fn from(signature: &Signature) -> Self {
+        SignatureMeta {
+            version: signature.version,
+            pubkey_algorithm: signature.pubkey_algorithm,
+            hash_algorithm: signature.hash_algorithm,
+            created: signature.created,
+            key_id: signature.issuer,
+        }
+    }
+}
+
+impl fmt::Display for SignatureMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let created = self
+            .created
+            .and_then(|time| Local.timestamp_opt(time.into(), 0).single())
+            .map(|time| time.format("%c").to_string())
+            .unwrap_or_default();
+        let key_id = self
+            .key_id
+            .map(|id| id.short())
+            .unwrap_or_else(|| "(unknown)".to_owned());
+
+        write!(
+            f,
+            "{}/{}, {}, Key ID {}",
+            self.pubkey_algorithm, self.hash_algorithm, created, key_id
+        )
+    }
+}
+
+fn take<'a>(data: &mut &'a [u8], size: usize) -> io::Result<&'a [u8]> {
+    if size > data.len() {
+        return Err(invalid("Error: truncated OpenPGP packet"));
+    }
+    let (head, rest) = data.split_at(size);
+    *data = rest;
+    Ok(head)
+}
+
+fn byte_at(data: &[u8], position: usize) -> io::Result<&u8> {
+    data.get(position)
+        .ok_or_else(|| invalid("Error: truncated OpenPGP packet header"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RPMFile;
+    use std::path::Path;
+
+    #[test]
+    fn test_signature_meta() -> io::Result<()> {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        let cases = [
+            (
+                "signed-key1-1.0-1.noarch.rpm",
+                PublicKeyAlgorithm::Rsa,
+                HashAlgorithm::Sha256,
+                "8bf5a2b2",
+            ),
+            (
+                "signed-ed25519-1.0-1.noarch.rpm",
+                PublicKeyAlgorithm::EdDsa,
+                HashAlgorithm::Sha512,
+                "c6b65f20",
+            ),
+        ];
+        for (name, pubkey_algorithm, hash_algorithm, key_id) in cases.iter() {
+            let rpm = RPMFile::open(data.join(name))?;
+            let meta = SignatureMeta::from_tags(&rpm.signature_tags)?.unwrap();
+            assert_eq!(meta.version, 4);
+            assert_eq!(meta.pubkey_algorithm, *pubkey_algorithm);
+            assert_eq!(meta.hash_algorithm, *hash_algorithm);
+            assert_eq!(meta.key_id.unwrap().short(), *key_id);
+        }
+
+        let hello = RPMFile::open(data.join("hello-1.0-1.noarch.rpm"))?;
+        assert_eq!(SignatureMeta::from_tags(&hello.signature_tags)?, None);
+
+        // old format v3 packet: hashed type and time, issuer, algorithms, prefix, MPI
+        let mut packet = vec![0x88, 22, 3, 5, 0, 0x5f, 0, 0, 0];
+        packet.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 1, 8, 0xab, 0xcd, 0, 8, 0xff]);
+        let meta = SignatureMeta::from_bytes(&packet)?;
+        assert_eq!(meta.version, 3);
+        assert_eq!(meta.created, Some(0x5f00_0000));
+        assert_eq!(meta.key_id.unwrap().short(), "05060708");
+        assert!(meta.to_string().starts_with("RSA/SHA256, "));
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use ed25519_dalek::{Verifier, VerifyingKey};
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use std::fmt;
+use std::io;
+
+use super::invalid;
+use super::keyring::*;
+use super::packet::*;
+
+pub(super) const ED25519_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureStatus {
+    /// Signature matches the data and was made by a key from the keyring
+    Valid(KeyId),
+    /// No key in the keyring matches the signature issuer
+    NoMatchingKey(Option<KeyId>),
+    /// Issuer key was found, but the signature does not match the data
+    BadSignature(KeyId),
+    /// No signature is present
+    Unsigned,
+}
+
+impl SignatureStatus {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, SignatureStatus::Valid(_))
+    }
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureStatus::Valid(id) => write!(f, "key ID {}: OK", id.short()),
+            SignatureStatus::NoMatchingKey(Some(id)) => write!(f, "key ID {}: NOKEY", id.short()),
+            SignatureStatus::NoMatchingKey(None) => write!(f, "unknown key ID: NOKEY"),
+            SignatureStatus::BadSignature(id) => write!(f, "key ID {}: BAD", id.short()),
+            SignatureStatus::Unsigned => write!(f, "NOT SIGNED"),
+        }
+    }
+}
+
+/// Verifies an OpenPGP signature over data, selecting the key by the signature issuer
+pub fn verify(
+    keyring: &Keyring,
+    signature: &Signature,
+    data: &[u8],
+) -> io::Result<SignatureStatus> {
+    let key = match keyring.find_issuer(signature) {
+        Some(key) => key,
+        None => return Ok(SignatureStatus::NoMatchingKey(signature.issuer)),
+    };
+
+    let digest = match signature.hash_algorithm {
+        HashAlgorithm::Sha1 => hash::<Sha1>(data, signature.trailer()),
+        HashAlgorithm::Sha224 => hash::<Sha224>(data, signature.trailer()),
+        HashAlgorithm::Sha256 => hash::<Sha256>(data, signature.trailer()),
+        HashAlgorithm::Sha384 => hash::<Sha384>(data, signature.trailer()),
+        HashAlgorithm::Sha512 => hash::<Sha512>(data, signature.trailer()),
+        algorithm => {
+            return Err(invalid(format!(
+                "Error: signature hash {} is not supported",
+                algorithm
+            )))
+        }
+    };
+
+    let valid = digest[..2] == signature.hash_prefix
+        && match (key.algorithm, signature.pubkey_algorithm) {
+            (PublicKeyAlgorithm::Rsa, PublicKeyAlgorithm::Rsa) => {
+                verify_rsa(key, signature, &digest)?
+            }
+            (PublicKeyAlgorithm::EdDsa, PublicKeyAlgorithm::EdDsa) => {
+                verify_eddsa(key, signature, &digest)?
+            }
+            (PublicKeyAlgorithm::Rsa, _) | (PublicKeyAlgorithm::EdDsa, _) => false,
+            (algorithm, _) => {
+                return Err(invalid(format!(
+                    "Error: {} signatures are not supported",
+                    algorithm
+                )))
+            }
+        };
+
+    if valid {
+        Ok(SignatureStatus::Valid(key.key_id))
+    } else {
+        Ok(SignatureStatus::BadSignature(key.key_id))
+    }
+}
+
+fn verify_rsa(key: &PublicKey, signature: &Signature, digest: &[u8]) -> io::Result<bool> {
+    let (n, e) = match key.mpis.as_slice() {
+        [n, e, ..] => (n, e),
+        _ => return Err(invalid("Error: incorrect RSA public key")),
+    };
+    let public = RsaPublicKey::new(BigUint::from_bytes_be(n), BigUint::from_bytes_be(e))
+        .map_err(|e| invalid(format!("Error: incorrect RSA public key {}", e)))?;
+
+    let scheme = match signature.hash_algorithm {
+        HashAlgorithm::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+        HashAlgorithm::Sha224 => Pkcs1v15Sign::new::<Sha224>(),
+        HashAlgorithm::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+        HashAlgorithm::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+        _ => Pkcs1v15Sign::new::<Sha512>(),
+    };
+
+    // MPIs drop leading zeroes, PKCS#1 expects the signature to be of the modulus size
+    let value = signature
+        .mpis
+        .first()
+        .ok_or_else(|| invalid("Error: RSA signature value is missing"))?;
+    let mut padded = vec![0_u8; public.size().saturating_sub(value.len())];
+    padded.extend_from_slice(value);
+
+    Ok(public.verify(scheme, digest, &padded).is_ok())
+}
+
+/// Ed25519 signs the digest itself, the signature is stored as two MPIs R and S
+fn verify_eddsa(key: &PublicKey, signature: &Signature, digest: &[u8]) -> io::Result<bool> {
+    if key.curve.as_deref() != Some(ED25519_OID) {
+        return Err(invalid("Error: only Ed25519 EdDSA keys are supported"));
+    }
+    // the public point is prefixed with 0x40 for the native encoding
+    let point = match key.mpis.first().map(Vec::as_slice) {
+        Some([0x40, point @ ..]) if point.len() == 32 => point,
+        _ => return Err(invalid("Error: incorrect Ed25519 public key")),
+    };
+    let mut bytes = [0_u8; 32];
+    bytes.copy_from_slice(point);
+    let public = VerifyingKey::from_bytes(&bytes)
+        .map_err(|e| invalid(format!("Error: incorrect Ed25519 public key {}", e)))?;
+
+    let (r, s) = match signature.mpis.as_slice() {
+        [r, s, ..] if r.len() <= 32 && s.len() <= 32 => (r, s),
+        _ => return Err(invalid("Error: incorrect EdDSA signature value")),
+    };
+    // MPIs drop leading zeroes, both halves are 32 bytes long
+    let mut value = [0_u8; 64];
+    value[32 - r.len()..32].copy_from_slice(r);
+    value[64 - s.len()..].copy_from_slice(s);
+
+    Ok(public
+        .verify(digest, &ed25519_dalek::Signature::from_bytes(&value))
+        .is_ok())
+}
+
+fn hash<D: Digest>(data: &[u8], trailer: &[u8]) -> Vec<u8> {
+    let mut hasher = D::new();
+    hasher.update(data);
+    hasher.update(trailer);
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::SignatureTag;
+    use crate::RPMFile;
+    use std::convert::TryFrom;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn data_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data")
+            .join(name)
+    }
+
+    #[test]
+    fn test_verify_signature_selects_key_by_issuer() -> io::Result<()> {
+        let keyring = Keyring::load(data_path("keys"))?;
+        assert_eq!(keyring.len(), 4);
+
+        for name in &[
+            "signed-key1-1.0-1.noarch.rpm",
+            "signed-key2-1.0-1.noarch.rpm",
+            "signed-ed25519-1.0-1.noarch.rpm",
+            "signed-legacy-1.0-1.noarch.rpm",
+        ] {
+            let mut rpm = RPMFile::open(data_path(name))?;
+            assert!(rpm.verify_signature(&keyring)?.is_valid());
+        }
+
+        let rpm = RPMFile::open(data_path("signed-ed25519-1.0-1.noarch.rpm"))?;
+        let info = crate::RPMInfo::try_from(&rpm)?;
+        assert!(info.signature.unwrap().starts_with("EdDSA/SHA512, "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_signature_no_matching_key() -> io::Result<()> {
+        let keyring = Keyring::load(data_path("keys/RPM-GPG-KEY-test2"))?;
+        let mut rpm = RPMFile::open(data_path("signed-key1-1.0-1.noarch.rpm"))?;
+
+        match rpm.verify_signature(&keyring)? {
+            SignatureStatus::NoMatchingKey(Some(id)) => assert_eq!(id.short(), "8bf5a2b2"),
+            status => panic!("unexpected status {:?}", status),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_signature_bad_signature() -> io::Result<()> {
+        let keyring = Keyring::load(data_path("keys"))?;
+        for name in &[
+            "signed-key1-1.0-1.noarch.rpm",
+            "signed-ed25519-1.0-1.noarch.rpm",
+        ] {
+            let mut bytes = std::fs::read(data_path(name))?;
+            let position = bytes.windows(9).position(|w| w == b"throwaway").unwrap();
+            bytes[position] = b'T';
+
+            let mut rpm = RPMFile::read(Cursor::new(bytes))?;
+            assert!(matches!(
+                rpm.verify_signature(&keyring)?,
+                SignatureStatus::BadSignature(_)
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_legacy_signature() -> io::Result<()> {
+        let keyring = Keyring::load(data_path("keys/RPM-GPG-KEY-test4"))?;
+        let path = data_path("signed-legacy-1.0-1.noarch.rpm");
+        let rpm = RPMFile::open(&path)?;
+        assert!(rpm.signature_tags.get_value(SignatureTag::PGP).is_some());
+        assert!(rpm
+            .signature_tags
+            .get_value(SignatureTag::RSAHeader)
+            .is_none());
+
+        // the signature covers the payload too
+        let mut bytes = std::fs::read(&path)?;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let mut rpm = RPMFile::read(Cursor::new(bytes))?;
+        assert!(matches!(
+            rpm.verify_signature(&keyring)?,
+            SignatureStatus::BadSignature(_)
+        ));
+        Ok(())
+    }
+}
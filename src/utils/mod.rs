@@ -1,26 +1,40 @@
-use hex::FromHex;
-use omnom::prelude::*;
 use std::io;
 use std::io::{Read, Write};
 
+// file type bits of st_mode
+pub const S_IFMT: u32 = 0o170_000;
+pub const S_IFDIR: u32 = 0o040_000;
+pub const S_IFREG: u32 = 0o100_000;
+pub const S_IFLNK: u32 = 0o120_000;
+pub const S_IFIFO: u32 = 0o010_000;
+pub const S_IFCHR: u32 = 0o020_000;
+pub const S_IFBLK: u32 = 0o060_000;
+
 pub fn align_n_bytes(from: u32, n: u32) -> u32 {
     (n - from % n) % n
 }
 
 pub fn parse_string(bytes: &[u8]) -> String {
     let position = bytes.iter().position(|&x| x == 0).unwrap_or(0);
-    let bytes2 = &bytes[0..position];
-    String::from_utf8_lossy(bytes2).to_string()
+    to_string(&bytes[0..position])
 }
 
 pub fn parse_strings(bytes: &[u8], count: usize) -> Vec<String> {
     bytes
         .split(|x| *x == 0)
         .take(count)
-        .map(|b| String::from_utf8_lossy(b).to_string())
+        .map(to_string)
         .collect()
 }
 
+/// Copies the bytes once, invalid UTF-8 is replaced
+fn to_string(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_owned(),
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
 pub trait HexWriter {
     fn write_u32_as_hex(&mut self, from: u32) -> io::Result<()>;
 }
@@ -46,17 +60,32 @@ where
     fn read_hex_as_u32(&mut self) -> io::Result<u32> {
         let mut raw_bytes = [0_u8; 8];
         self.read_exact(&mut raw_bytes)?;
+        read_hex_as_u32_from(&raw_bytes)
+    }
+}
 
-        Vec::from_hex(raw_bytes)
-            .map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Error: can not parse hex {}", e),
-                )
-            })?
-            .as_slice()
-            .read_be()
+/// Parses 8 ASCII hex digits, both lower and upper case are accepted
+pub fn read_hex_as_u32_from(bytes: &[u8; 8]) -> io::Result<u32> {
+    let mut value = 0_u32;
+    for &byte in bytes {
+        let nibble = match byte {
+            b'0'..=b'9' => byte - b'0',
+            b'a'..=b'f' => byte - b'a' + 10,
+            b'A'..=b'F' => byte - b'A' + 10,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Error: can not parse hex {:?}: invalid byte {:#04x}",
+                        String::from_utf8_lossy(bytes),
+                        byte
+                    ),
+                ))
+            }
+        };
+        value = value << 4 | u32::from(nibble);
     }
+    Ok(value)
 }
 
 #[cfg(test)]
@@ -72,6 +101,17 @@ mod tests {
         assert_eq!(align_n_bytes(39, 8), 1);
     }
 
+    #[test]
+    fn test_parse_strings() {
+        assert_eq!(parse_string(b"hello\0world"), "hello");
+        assert_eq!(parse_string(b"caf\xc3\xa9\0"), "caf\u{e9}");
+        assert_eq!(parse_string(b"caf\xe9\0"), "caf\u{fffd}");
+        assert_eq!(
+            parse_strings(b"a\0b\xff\0c\0", 2),
+            vec!["a".to_owned(), "b\u{fffd}".to_owned()]
+        );
+    }
+
     #[test]
     #[allow(clippy::string_lit_as_bytes)]
     fn test_hex_reader() {
@@ -79,6 +119,14 @@ mod tests {
         assert_eq!("00000101".as_bytes().read_hex_as_u32().unwrap(), 257);
         assert_eq!("000001f1".as_bytes().read_hex_as_u32().unwrap(), 497);
         assert_eq!("ffffffff".as_bytes().read_hex_as_u32().unwrap(), u32::MAX);
+        assert_eq!("000001F1".as_bytes().read_hex_as_u32().unwrap(), 497);
+        assert_eq!(read_hex_as_u32_from(b"DEADbeef").unwrap(), 0xdead_beef);
+
+        let error = read_hex_as_u32_from(b"0000g001").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("0000g001"));
+        assert!(" 0000001".as_bytes().read_hex_as_u32().is_err());
+        assert!("0001".as_bytes().read_hex_as_u32().is_err());
     }
 
     #[test]
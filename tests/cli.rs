@@ -0,0 +1,834 @@
+#![cfg(feature = "cli")]
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn data(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name)
+}
+
+fn cmd(name: &str) -> Command {
+    Command::cargo_bin(name).unwrap()
+}
+
+/// Package cut in the middle of the main header
+fn corrupt(dir: &Path) -> PathBuf {
+    let path = dir.join("corrupt.rpm");
+    let bytes = fs::read(data("hello-1.0-1.noarch.rpm")).unwrap();
+    fs::write(&path, &bytes[..400]).unwrap();
+    path
+}
+
+#[test]
+fn test_rpm_info_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+
+    cmd("rpm-info")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    cmd("rpm-info")
+        .arg("--quiet")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .stdout("");
+    cmd("rpm-info")
+        .arg(corrupt(dir.path()))
+        .assert()
+        .code(3)
+        .stdout("");
+    cmd("rpm-info")
+        .arg(dir.path().join("missing.rpm"))
+        .assert()
+        .code(1)
+        .stdout("");
+    cmd("rpm-info").arg("--unknown").assert().code(2);
+}
+
+#[test]
+fn test_rpm_info_lint() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("source.rpm");
+    let mut bytes = fs::read(data("hello-1.0-1.noarch.rpm")).unwrap();
+    bytes[7] = 1;
+    fs::write(&path, bytes).unwrap();
+
+    cmd("rpm-info")
+        .arg("--lint")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .stdout("");
+    cmd("rpm-info")
+        .args(&["--lint", "--payload"])
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .stdout("");
+    cmd("rpm-info")
+        .arg("--payload")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .code(2);
+    let assert = cmd("rpm-info").arg("--lint").arg(&path).assert().code(3);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("lead type is Source"), "{}", stdout);
+}
+
+#[test]
+fn test_rpm_info_stats_header() {
+    let assert = cmd("rpm-info")
+        .args(&["--stats-header", "--top", "1"])
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", stdout);
+    assert!(lines[0].starts_with("Header: 57 tags"), "{}", stdout);
+    assert!(lines[1].starts_with("FileMD5s"), "{}", stdout);
+}
+
+#[test]
+fn test_rpm_info_files() {
+    let assert = cmd("rpm-info")
+        .arg("--files")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert_eq!(stdout.lines().count(), 7);
+    assert!(stdout.contains("/usr/bin/hi\n"), "{}", stdout);
+
+    let assert = cmd("rpm-info")
+        .arg("--files-verbose")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert_eq!(stdout.lines().count(), 7);
+    let hi = stdout
+        .lines()
+        .find(|line| line.contains("/usr/bin/hi"))
+        .unwrap();
+    assert!(hi.starts_with("lrwxrwxrwx  root root     5 "), "{}", hi);
+    assert!(hi.ends_with(" /usr/bin/hi -> hello"), "{}", hi);
+    assert!(
+        stdout.contains("-rw-r-----  root adm      0 "),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_rpm_info_dump() {
+    let assert = cmd("rpm-info")
+        .arg("--dump")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert_eq!(stdout.lines().count(), 7);
+    assert!(
+        stdout.contains(
+            "/etc/hello.conf 17 1700000000 \
+             6783839fe54f0040d9ee89d265c686aa7386261605d89dfbb4be261d5a956f38 \
+             0100644 root root 1 0 0 X\n"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("/usr/bin/hi 5 1700000000 00000000000000000000000000000000 0120777 root root 0 0 0 hello\n"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_rpm_info_scripts() {
+    cmd("rpm-info")
+        .arg("--scripts")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .stdout(
+            "postinstall scriptlet (using /bin/sh):\necho installed\n\
+             postuninstall scriptlet (using /bin/sh):\necho removed\n",
+        );
+}
+
+#[test]
+fn test_rpm_info_changelog() {
+    cmd("rpm-info")
+        .arg("--changelog")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .stdout(
+            "* Tue Nov 14 2023 Jane Doe <jane@example.com> - 1.0-1\n- Update to 1.0\n\n\
+             * Sat Jul 22 2023 John Doe <john@example.com> - 0.9-1\n- Initial package\n\n",
+        );
+}
+
+#[test]
+fn test_rpm_info_debug() {
+    let assert = cmd("rpm-info")
+        .arg("--debug")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("\nNAME (1000): \"hello\"\n"), "{}", stdout);
+    assert!(stdout.contains("\nARCH (1022): \"noarch\"\n"), "{}", stdout);
+}
+
+#[test]
+fn test_rpm_info_queryformat() {
+    cmd("rpm-info")
+        .args(&[
+            "--queryformat",
+            r"%{NAME}-%{VERSION}-%{RELEASE}.%{ARCH}\n[%{BASENAMES} ]",
+        ])
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .stdout("hello-1.0-1.noarch\nhello.conf hello hi README hello LICENSE hello.log ");
+
+    let assert = cmd("rpm-info")
+        .args(&["--qf", "%{NAME} %{NOSUCHTAG}"])
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .code(1);
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.contains("unknown tag NOSUCHTAG"), "{}", stderr);
+}
+
+#[test]
+fn test_rpm_info_lead() {
+    let assert = cmd("rpm-info")
+        .arg("--lead")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("name: hello-1.0-1\n"), "{}", stdout);
+    assert!(stdout.contains("signature_type: 5\n"), "{}", stdout);
+    assert!(stdout.contains("Signature header:\n"), "{}", stdout);
+    assert!(
+        stdout.contains("Header:\nmagic: [142, 173, 232, 1]\n"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_rpm_info_header_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let header = dir.path().join("hello.header");
+    let mut rpm = rpm_utils::RPMFile::open(data("hello-1.0-1.noarch.rpm")).unwrap();
+    fs::write(&header, rpm.read_raw_header_bytes().unwrap()).unwrap();
+
+    let assert = cmd("rpm-info")
+        .arg("--header-only")
+        .arg(&header)
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Name        : hello\n"), "{}", stdout);
+    assert!(stdout.contains("Signature   : (none)\n"), "{}", stdout);
+
+    let assert = cmd("rpm-info")
+        .arg("--header-only")
+        .arg("--qf")
+        .arg("%{NAME}-%{VERSION}")
+        .arg(&header)
+        .assert()
+        .success();
+    assert_eq!(assert.get_output().stdout, b"hello-1.0");
+
+    cmd("rpm-info")
+        .arg("--header-only")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_stdin_input() {
+    let package = fs::read(data("hello-1.0-1.noarch.rpm")).unwrap();
+
+    let from_file = cmd("rpm2cpio")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let from_stdin = cmd("rpm2cpio")
+        .arg("-")
+        .write_stdin(package.clone())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(from_stdin, from_file);
+    cmd("rpm2cpio")
+        .arg("-")
+        .write_stdin(&package[..200])
+        .assert()
+        .code(3);
+
+    let assert = cmd("rpm-info")
+        .arg("-")
+        .write_stdin(package)
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("hello"), "{}", stdout);
+}
+
+#[test]
+fn test_rpm2cpio_raw() {
+    use std::io::Read;
+
+    let cpio = cmd("rpm2cpio")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let assert = cmd("rpm2cpio")
+        .arg("--raw")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let raw = assert.get_output().stdout.clone();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert_eq!(stderr, format!("gzip payload, {} bytes\n", raw.len()));
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(&raw[..])
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, cpio);
+
+    let dir = tempfile::tempdir().unwrap();
+    cmd("rpm2cpio")
+        .args(&["--raw", "--output"])
+        .arg(dir.path())
+        .arg("-")
+        .write_stdin(fs::read(data("hello-1.0-1.noarch.rpm")).unwrap())
+        .assert()
+        .success();
+    assert_eq!(fs::read(dir.path().join("payload.cpio.gz")).unwrap(), raw);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_json_output() {
+    let assert = cmd("rpm-info")
+        .arg("--json")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let info: rpm_utils::RPMInfo = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!((info.name.as_str(), info.payload.files.len()), ("hello", 7));
+
+    let assert = cmd("rpm-info")
+        .args(&["--json", "--debug"])
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let value: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(value["info"]["name"], "hello");
+    assert_eq!(value["lead"]["major"], 3);
+    assert!(value["header_tags"].as_array().unwrap().len() > 50);
+
+    let dir = tempfile::tempdir().unwrap();
+    let cpio = dir.path().join("hello.cpio");
+    cmd("rpm2cpio")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .arg("--output")
+        .arg(&cpio)
+        .assert()
+        .success();
+    let assert = cmd("cpio-extract")
+        .args(&["--debug", "--json"])
+        .arg(&cpio)
+        .arg("-e")
+        .arg(dir.path().join("root"))
+        .assert()
+        .success();
+    let entries: Vec<rpm_utils::payload::FileEntry> =
+        serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(entries.len(), 6);
+    assert!(entries.iter().any(|entry| entry.name == "./usr/bin/hello"));
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn test_rpm_info_checksig_exit_codes() {
+    cmd("rpm-info")
+        .args(&["--checksig", "--keyring"])
+        .arg(data("keys/RPM-GPG-KEY-test1"))
+        .arg(data("signed-key1-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    cmd("rpm-info")
+        .args(&["--checksig", "--quiet", "--keyring"])
+        .arg(data("keys/RPM-GPG-KEY-test1"))
+        .arg(data("signed-key2-1.0-1.noarch.rpm"))
+        .assert()
+        .code(4)
+        .stdout("");
+}
+
+#[test]
+fn test_rpm_info_check_payload() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("corrupted.rpm");
+    let mut bytes = fs::read(data("hello-1.0-1.noarch.rpm")).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    fs::write(&path, bytes).unwrap();
+
+    let assert = cmd("rpm-info")
+        .arg("--check-payload")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.ends_with(": Payload digest OK\n"), "{}", stdout);
+
+    let assert = cmd("rpm-info")
+        .arg("--check-payload")
+        .arg(&path)
+        .assert()
+        .code(4);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains(": Payload digest BAD"), "{}", stdout);
+}
+
+#[test]
+fn test_rpm2cpio_and_cpio_extract_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let cpio = dir.path().join("hello.cpio");
+    let target = dir.path().join("root");
+
+    cmd("rpm2cpio")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .arg("--output")
+        .arg(&cpio)
+        .assert()
+        .success();
+    cmd("rpm2cpio")
+        .arg(corrupt(dir.path()))
+        .arg("--output")
+        .arg(dir.path().join("corrupt.cpio"))
+        .assert()
+        .code(3);
+    cmd("rpm2cpio")
+        .arg(dir.path().join("missing.rpm"))
+        .arg("--output")
+        .arg(dir.path().join("missing.cpio"))
+        .assert()
+        .code(1);
+
+    let output = cmd("rpm2cpio")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(output, fs::read(&cpio).unwrap());
+
+    let output = cmd("rpm2cpio")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .arg("--list")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let names = String::from_utf8(output).unwrap();
+    assert_eq!(names.lines().count(), 6);
+    assert!(names.lines().any(|name| name == "./usr/bin/hello"));
+    let truncated = dir.path().join("truncated.rpm");
+    let bytes = fs::read(data("hello-1.0-1.noarch.rpm")).unwrap();
+    fs::write(&truncated, &bytes[..bytes.len() - 40]).unwrap();
+    cmd("rpm2cpio")
+        .arg(&truncated)
+        .arg("--list")
+        .arg("-v")
+        .assert()
+        .code(3);
+
+    cmd("cpio-extract")
+        .arg("--quiet")
+        .arg(&cpio)
+        .arg("-e")
+        .arg(&target)
+        .assert()
+        .success()
+        .stdout("");
+    assert!(target.join("usr/bin/hello").is_file());
+    cmd("cpio-extract")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .arg("-e")
+        .arg(&target)
+        .assert()
+        .code(3);
+    cmd("cpio-extract")
+        .arg(dir.path().join("missing.cpio"))
+        .arg("-e")
+        .arg(&target)
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn test_rpm_extract_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("root");
+
+    cmd("rpm-extract")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .arg("-e")
+        .arg(&target)
+        .arg("--quiet")
+        .assert()
+        .success()
+        .stdout("");
+    assert!(target.join("usr/bin/hello").is_file());
+    assert!(target.join("usr/bin/hi").symlink_metadata().is_ok());
+    cmd("rpm-extract")
+        .arg(corrupt(dir.path()))
+        .arg("-e")
+        .arg(&target)
+        .assert()
+        .code(3);
+    cmd("rpm-extract")
+        .arg(dir.path().join("missing.rpm"))
+        .arg("-e")
+        .arg(&target)
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn test_rpm_verify_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("root");
+    cmd("rpm-extract")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .arg("-e")
+        .arg(&target)
+        .arg("--quiet")
+        .assert()
+        .success();
+    fs::remove_file(target.join("usr/share/doc/hello/README")).unwrap();
+
+    let assert = cmd("rpm-verify")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .arg("--root")
+        .arg(&target)
+        .assert()
+        .code(4);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(
+        stdout.contains("missing   d /usr/share/doc/hello/README\n"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("/var/log/hello.log"), "{}", stdout);
+
+    let assert = cmd("rpm-verify")
+        .arg(data("hello-1.0-1.noarch.rpm"))
+        .arg("--root")
+        .arg(&target)
+        .arg("-v")
+        .assert()
+        .code(4);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert_eq!(stdout.lines().count(), 7);
+    assert!(
+        stdout.contains(".........  g /var/log/hello.log\n"),
+        "{}",
+        stdout
+    );
+    cmd("rpm-verify")
+        .arg(dir.path().join("missing.rpm"))
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn test_cpio_create_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file.txt");
+    fs::write(&file, "data").unwrap();
+
+    cmd("cpio-create")
+        .arg("-f")
+        .arg(dir.path().join("out.cpio"))
+        .arg(&file)
+        .assert()
+        .success();
+    cmd("cpio-create")
+        .args(&[
+            "--owner",
+            "root:root",
+            "--mode",
+            "755",
+            "--mtime",
+            "0",
+            "-f",
+        ])
+        .arg(dir.path().join("fixed.cpio"))
+        .arg(&file)
+        .assert()
+        .success();
+    cmd("cpio-create")
+        .args(&["--mode", "rw", "-f"])
+        .arg(dir.path().join("out.cpio"))
+        .arg(&file)
+        .assert()
+        .code(2);
+    cmd("cpio-create")
+        .arg("-f")
+        .arg(dir.path().join("missing.cpio"))
+        .arg(dir.path().join("missing.txt"))
+        .assert()
+        .code(1);
+    cmd("cpio-create").assert().code(2);
+}
+
+#[test]
+fn test_rpm_closure_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    cmd("rpm-closure").arg(dir.path()).assert().success();
+
+    fs::copy(
+        data("hello-1.0-1.noarch.rpm"),
+        dir.path().join("hello-1.0-1.noarch.rpm"),
+    )
+    .unwrap();
+    let assert = cmd("rpm-closure").arg(dir.path()).assert().code(4);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(
+        stdout.contains("hello-1.0-1.noarch requires libc.so.6"),
+        "{}",
+        stdout
+    );
+}
+
+/// Packages written by the builder are read by rpm, skipped if rpm is not installed
+#[test]
+fn test_rpm_queryformat() {
+    let rpm = |args: &[&str]| std::process::Command::new("rpm").args(args).output();
+    if rpm(&["--version"]).is_err() {
+        return;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    fs::create_dir_all(root.join("usr/share/demo")).unwrap();
+    fs::write(root.join("usr/share/demo/readme"), "hello\n").unwrap();
+    let path = dir.path().join("demo-1.0-1.noarch.rpm");
+    rpm_utils::RPMBuilder::new()
+        .package_name("demo")
+        .version("1.0")
+        .summary("Demo package")
+        .vendor("Demo Vendor")
+        .url("https://example.com/demo")
+        .packager("Demo Packager")
+        .build_time(1_700_000_000)
+        .build_root(&root)
+        .add_directory("/usr/share/demo")
+        .add_file("/usr/share/demo/readme")
+        .filename(&path)
+        .build_package()
+        .unwrap();
+
+    let path = path.to_str().unwrap();
+    let output = rpm(&[
+        "-qp",
+        "--queryformat",
+        "%{NAME}-%{VERSION}-%{RELEASE} %{SIZE} %{BUILDTIME} %{SUMMARY}\n",
+        path,
+    ])
+    .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "demo-1.0-1 6 1700000000 Demo package\n"
+    );
+
+    let output = rpm(&["-qpl", path]).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "/usr/share/demo\n/usr/share/demo/readme\n"
+    );
+    let output = rpm(&["-qpi", path]).unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let field = |name: &str| {
+        stdout
+            .lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.split_once(": "))
+            .map(|(_, value)| value.to_owned())
+    };
+    assert_eq!(
+        field("Vendor").as_deref(),
+        Some("Demo Vendor"),
+        "{}",
+        stdout
+    );
+    assert_eq!(field("URL").as_deref(), Some("https://example.com/demo"));
+    assert_eq!(field("Packager").as_deref(), Some("Demo Packager"));
+
+    let output = rpm(&["-Kv", "--nosignature", path]).unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+}
+
+#[test]
+fn test_rpm_install_dependencies() {
+    use rpm_utils::VersionConstraint;
+
+    let rpm = |args: &[&str]| std::process::Command::new("rpm").args(args).output();
+    if rpm(&["--version"]).is_err() {
+        return;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let dbpath = dir.path().join("db");
+    let dbpath = dbpath.to_str().unwrap();
+    let output = rpm(&["--initdb", "--dbpath", dbpath]).unwrap();
+    assert!(output.status.success());
+
+    let demo = dir.path().join("demo-1.0-1.noarch.rpm");
+    rpm_utils::RPMBuilder::new()
+        .package_name("demo")
+        .version("1.0")
+        .add_directory("/opt/demo")
+        .provides("demo-api", VersionConstraint::Equal("3".to_owned()))
+        .obsoletes("old-demo", VersionConstraint::Any)
+        .filename(&demo)
+        .build_package()
+        .unwrap();
+    let app = dir.path().join("app-2.0-1.noarch.rpm");
+    rpm_utils::RPMBuilder::new()
+        .package_name("app")
+        .version("2.0")
+        .add_directory("/opt/app")
+        .requires("demo", VersionConstraint::GreaterOrEqual("1.0".to_owned()))
+        .requires(
+            "demo-api",
+            VersionConstraint::GreaterOrEqual("3".to_owned()),
+        )
+        .conflicts("demo", VersionConstraint::Less("1.0".to_owned()))
+        .recommends("demo-doc", VersionConstraint::Any)
+        .filename(&app)
+        .build_package()
+        .unwrap();
+    let (demo, app) = (demo.to_str().unwrap(), app.to_str().unwrap());
+
+    let output = rpm(&["-i", "--test", "--dbpath", dbpath, app]).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("demo >= 1.0 is needed by app-2.0-1.noarch"),
+        "{}",
+        stderr
+    );
+
+    let output = rpm(&["-i", "--test", "--dbpath", dbpath, demo, app]).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+}
+
+#[test]
+fn test_rpm_verify_file_digests() {
+    let rpm = |args: &[&str]| std::process::Command::new("rpm").args(args).output();
+    if rpm(&["--version"]).is_err() {
+        return;
+    }
+
+    // files are added by their absolute paths, so rpm -Vp checks them in place
+    let dir = tempfile::tempdir().unwrap();
+    let data = dir.path().join("data");
+    fs::write(&data, "payload\n").unwrap();
+    let data = data.to_str().unwrap();
+    for algo in [
+        rpm_utils::digest::DigestAlgo::Sha256,
+        rpm_utils::digest::DigestAlgo::Md5,
+    ]
+    .iter()
+    {
+        let path = dir.path().join(format!("demo-{}.rpm", algo));
+        rpm_utils::RPMBuilder::new()
+            .package_name("demo")
+            .version("1.0")
+            .file_digest(*algo)
+            .add_file(data)
+            .filename(&path)
+            .build_package()
+            .unwrap();
+
+        let output = rpm(&["-Vp", "--nouser", "--nogroup", path.to_str().unwrap()]).unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert_eq!(stdout, "");
+    }
+}
+
+#[test]
+fn test_rpm_query_source_package() {
+    let rpm = |args: &[&str]| std::process::Command::new("rpm").args(args).output();
+    if rpm(&["--version"]).is_err() {
+        return;
+    }
+
+    let source = |name: &str| data("source").join(name).to_string_lossy().into_owned();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hello-1.0-1.src.rpm");
+    rpm_utils::RPMBuilder::new()
+        .package_name("hello")
+        .version("1.0")
+        .source_package()
+        .add_spec(&source("hello.spec"))
+        .add_source(&source("hello-1.0.tar.gz"))
+        .add_patch(&source("hello-greeting.patch"))
+        .filename(&path)
+        .build_package()
+        .unwrap();
+
+    let path = path.to_str().unwrap();
+    let output = rpm(&[
+        "-qp",
+        "--qf",
+        "%{SOURCEPACKAGE} [%{SOURCE} ][%{PATCH}]\n",
+        path,
+    ])
+    .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "1 hello-1.0.tar.gz hello-greeting.patch\n"
+    );
+    let output = rpm(&["-qpl", path]).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "hello-1.0.tar.gz\nhello-greeting.patch\nhello.spec\n"
+    );
+}
@@ -0,0 +1,20 @@
+Name:           hello
+Version:        1.0
+Release:        1
+Summary:        Hello world script
+License:        MIT
+BuildArch:      noarch
+Source0:        hello-1.0.tar.gz
+Patch0:         hello-greeting.patch
+
+%description
+Prints a greeting.
+
+%prep
+%autosetup -p1
+
+%install
+install -Dm755 hello %{buildroot}%{_bindir}/hello
+
+%files
+%{_bindir}/hello